@@ -0,0 +1,91 @@
+//! Compare `Message::decode` with the old way of decoding through `serde_json::Value`.
+//!
+//! Run by `cargo bench --bench decode`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use burz::ws::{event::EventData, Message};
+use bytes::Bytes;
+use serde_json::json;
+
+const ITERATIONS: u32 = 5000;
+
+/// A channel message event with given content size and mention count
+fn event(content_size: usize, mention_count: usize) -> Bytes {
+    let mention: Vec<_> = (0..mention_count).map(|i| i.to_string()).collect();
+    let d = json!({
+        "channel_type": "GROUP",
+        "type": 9,
+        "target_id": "1234567890",
+        "author_id": "987654321",
+        "content": "x".repeat(content_size),
+        "msg_id": "67b5a1e4-2c1d-4f7a-9d3c-3e2e1f0a9b8c",
+        "msg_timestamp": 1607679616431u64,
+        "nonce": "",
+        "extra": {
+            "type": 9,
+            "guild_id": "1111",
+            "channel_name": "general",
+            "mention": mention,
+            "mention_all": false,
+            "mention_roles": [],
+            "mention_here": false,
+            "author": {
+                "id": "987654321",
+                "username": "user",
+                "identify_num": "1234",
+                "online": true,
+                "avatar": "https://example.com/avatar.png",
+                "bot": false,
+            },
+        },
+    });
+    // same field order as gateway sends
+    format!(r#"{{"s":0,"sn":42,"d":{d}}}"#).into()
+}
+
+/// Old implement: parse to value, check type, then deserialize typed data from the value
+fn decode_by_value(data: &Bytes) -> EventData {
+    let value: serde_json::Value = serde_json::from_slice(data).unwrap();
+    assert_eq!(value["s"].as_i64(), Some(0));
+    serde_json::from_value(value).unwrap()
+}
+
+fn bench<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+
+    println!("{name:<16} {per_iter:>12?}/iter");
+    per_iter
+}
+
+fn main() {
+    let payloads = [
+        ("typical", event(64, 2)),
+        ("many mentions", event(64, 1000)),
+        ("large content", event(64 * 1024, 2)),
+    ];
+
+    for (name, data) in payloads {
+        println!("{name}, payload size: {} bytes", data.len());
+
+        let old = bench("value", || {
+            black_box(decode_by_value(black_box(&data)));
+        });
+        let new = bench("decode", || {
+            black_box(Message::decode(black_box(data.clone()), false).unwrap());
+        });
+
+        println!("speedup: {:.2}x\n", old.as_secs_f64() / new.as_secs_f64());
+    }
+}
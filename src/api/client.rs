@@ -1,13 +1,22 @@
-use std::borrow::Borrow;
+use std::{borrow::Borrow, sync::Arc, time::Instant};
 
-use reqwest::{Method, StatusCode};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::IgnoredAny;
 use snafu::prelude::*;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use super::error::variant::*;
+use super::rate_limit::{Limiter, SendRateLimit};
 use super::types::*;
-use super::Result;
+use super::{Error, Result};
+use crate::audit::{ApiCallRecord, AuditRecord, AuditSink};
+use crate::models::{Channel, Emoji, Guild, Role, User};
+
+const PAGE_SIZE: &str = "100";
 
 static BASE_URL: &str = "https://www.kaiheila.cn/api/v3";
+#[cfg(any(test, feature = "testing"))]
+static BASE_PATH: &str = "/api/v3";
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
@@ -15,10 +24,19 @@ static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_P
 #[derive(Debug, Clone)]
 pub struct Client {
     client: reqwest::Client,
+    assets: reqwest::Client,
+    limiter: Option<Arc<Limiter>>,
+    audit: Option<Arc<dyn AuditSink + 'static>>,
+    #[cfg(any(test, feature = "testing"))]
+    mock: Option<Arc<crate::testing::MockApi>>,
 }
 
 impl Client {
-    fn new<S: AsRef<str> + ?Sized>(auth_type: &'static str, token: &S) -> Result<Self> {
+    fn new<S: AsRef<str> + ?Sized>(
+        auth_type: &'static str,
+        token: &S,
+        proxy: Option<reqwest::Proxy>,
+    ) -> Result<Self> {
         let token = token.as_ref();
         let auth_header_value = format!("{} {}", auth_type, token).parse().map_err(|_| {
             TokenInvalid {
@@ -30,25 +48,91 @@ impl Client {
         let mut headers = reqwest::header::HeaderMap::new();
         headers.insert(reqwest::header::AUTHORIZATION, auth_header_value);
 
-        let client = reqwest::Client::builder()
-            .gzip(true)
-            .deflate(true)
-            .user_agent(APP_USER_AGENT)
+        let builder = || {
+            let builder = reqwest::Client::builder()
+                .gzip(true)
+                .deflate(true)
+                .user_agent(APP_USER_AGENT);
+            match proxy.clone() {
+                Some(proxy) => builder.proxy(proxy),
+                None => builder,
+            }
+        };
+
+        let client = builder()
             .default_headers(headers)
             .build()
             .context(ClientCreateFailed)?;
+        // assets are hosted by cdn, so they are downloaded without the token
+        let assets = builder().build().context(ClientCreateFailed)?;
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            assets,
+            limiter: None,
+            audit: None,
+            #[cfg(any(test, feature = "testing"))]
+            mock: None,
+        })
     }
 
     /// create a new api client using bot token
     pub fn new_from_bot_token<S: AsRef<str> + ?Sized>(token: &S) -> Result<Self> {
-        Self::new("Bot", token)
+        Self::new("Bot", token, None)
+    }
+
+    /// create a new api client using bot token, send requests through the proxy
+    pub fn new_from_bot_token_with_proxy<S: AsRef<str> + ?Sized>(
+        token: &S,
+        proxy: reqwest::Proxy,
+    ) -> Result<Self> {
+        Self::new("Bot", token, Some(proxy))
     }
 
     /// create a new api client using oauth2 token
     pub fn new_from_oauth2_token<S: AsRef<str> + ?Sized>(token: &S) -> Result<Self> {
-        Self::new("Bearer", token)
+        Self::new("Bearer", token, None)
+    }
+
+    /// create a api client which sends nothing but records calls to the mock api
+    #[cfg(any(test, feature = "testing"))]
+    pub fn new_mock(mock: Arc<crate::testing::MockApi>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            assets: reqwest::Client::new(),
+            limiter: None,
+            audit: None,
+            mock: Some(mock),
+        }
+    }
+
+    /// Limit the rate of message send apis, see [SendRateLimit]
+    ///
+    /// Clones of this client created after this call share the limit.
+    pub fn send_rate_limit(mut self, limit: SendRateLimit) -> Self {
+        self.limiter.replace(Arc::new(Limiter::new(limit)));
+        self
+    }
+
+    /// Send a [AuditRecord::ApiCall] to the sink after every api call
+    pub fn audit(mut self, sink: Arc<dyn AuditSink + 'static>) -> Self {
+        self.audit.replace(sink);
+        self
+    }
+
+    /// Replace the limit of clients sharing it, does nothing if no limit is set.
+    pub(crate) fn update_send_rate_limit(&self, limit: SendRateLimit) {
+        match &self.limiter {
+            Some(limiter) => limiter.set_config(limit),
+            None => warn!("Send rate limit is not enabled, new limit is ignored"),
+        }
+    }
+
+    async fn acquire_send(&self, target_id: Option<&str>) -> Result<()> {
+        match &self.limiter {
+            Some(limiter) => limiter.acquire(target_id).await,
+            None => Ok(()),
+        }
     }
 
     async fn request<R, P, Q, K, V>(&self, path: &P, query: Q) -> Result<R>
@@ -68,30 +152,112 @@ impl Client {
             req = req.query(&[(k.as_ref(), v.as_ref())]);
         }
 
+        self.execute(Method::GET, url, req).await
+    }
+
+    /// Request all pages of a list api
+    async fn request_all<R, P>(&self, path: &P, query: &[(&str, &str)]) -> Result<Vec<R>>
+    where
+        P: AsRef<str> + ?Sized,
+        R: serde::de::DeserializeOwned,
+    {
+        let mut items = vec![];
+        let mut page = 1u64;
+
+        loop {
+            let page_str = page.to_string();
+            let mut q = query.to_vec();
+            q.push(("page", &page_str));
+            q.push(("page_size", PAGE_SIZE));
+
+            let data: Page<R> = self.request(path, &q).await?;
+            items.extend(data.items);
+
+            if page >= data.meta.page_total {
+                return Ok(items);
+            }
+            page += 1;
+        }
+    }
+
+    async fn post<R, P, B>(&self, path: &P, body: &B) -> Result<R>
+    where
+        P: AsRef<str> + ?Sized,
+        B: serde::Serialize + ?Sized,
+        R: serde::de::DeserializeOwned,
+    {
+        let url = format!("{}{}", BASE_URL, path.as_ref());
+        let req = self.client.post(&url).json(body);
+
+        self.execute(Method::POST, url, req).await
+    }
+
+    async fn execute<R>(&self, method: Method, url: String, req: RequestBuilder) -> Result<R>
+    where
+        R: serde::de::DeserializeOwned,
+    {
+        let Some(audit) = self.audit.as_ref() else {
+            return self.send(method, url, req).await;
+        };
+
+        let endpoint = url.strip_prefix(BASE_URL).unwrap_or(&url).to_string();
+        let start = Instant::now();
+        let result = self.send(method.clone(), url, req).await;
+
+        let status = match &result {
+            Err(Error::HTTPStatusNotOK { status_code, .. }) => Some(status_code.as_u16()),
+            Err(Error::RequestFailed { .. } | Error::BuildRequestFailed { .. }) => None,
+            _ => Some(StatusCode::OK.as_u16()),
+        };
+        audit.record(&AuditRecord::ApiCall(ApiCallRecord::new(
+            method.to_string(),
+            endpoint,
+            status,
+            result.as_ref().err().map(ToString::to_string),
+            start.elapsed(),
+        )));
+
+        result
+    }
+
+    async fn send<R>(&self, method: Method, url: String, req: RequestBuilder) -> Result<R>
+    where
+        R: serde::de::DeserializeOwned,
+    {
         let req = req.build().context(BuildRequestFailed)?;
 
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(mock) = self.mock.as_ref() {
+            let data = match mock.call(BASE_PATH, &req) {
+                Ok(data) => data,
+                Err((code, message)) => return CodeNotZero { code, message }.fail(),
+            };
+            let body = bytes::Bytes::from(data.to_string());
+            return serde_json::from_slice(&body).with_context(|_| ParseBodyFailed { body });
+        }
+
         let resp = self
             .client
             .execute(req)
             .await
             .with_context(|_| RequestFailed {
-                method: Method::GET,
+                method: method.clone(),
                 url: &url,
             })?;
 
         ensure!(
             resp.status() == StatusCode::OK,
             HTTPStatusNotOK {
-                method: Method::GET,
+                method: method.clone(),
                 url: &url,
                 status_code: resp.status()
             }
         );
 
-        let body = resp.bytes().await.with_context(|_| RequestFailed {
-            method: Method::GET,
-            url: &url,
-        })?;
+        let body = resp
+            .bytes()
+            .await
+            .with_context(|_| RequestFailed { method, url: &url })?;
 
         let result: Response<R> =
             serde_json::from_slice(&body).with_context(|_| ParseBodyFailed { body })?;
@@ -107,9 +273,214 @@ impl Client {
         Ok(result.data)
     }
 
-    /// Call /gateway/index, get gateway url
+    /// Download a asset, like a [Attachment](crate::models::Attachment) or image uploaded to
+    /// kaiheila
+    ///
+    /// The proxy of this client is used, but the token is not sent because assets are not
+    /// hosted by the api server.
+    pub async fn download_asset(&self, url: &str) -> Result<bytes::Bytes> {
+        let mut buffer = vec![];
+        self.download_asset_to(url, &mut buffer).await?;
+        Ok(buffer.into())
+    }
+
+    /// Download a asset and write it to `writer` chunk by chunk, returns how many bytes are
+    /// written, see [download_asset](Self::download_asset)
+    pub async fn download_asset_to<W>(&self, url: &str, writer: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let req = self.assets.get(url).build().context(BuildRequestFailed)?;
+
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(mock) = self.mock.as_ref() {
+            let body = mock.asset(&req).context(HTTPStatusNotOK {
+                method: Method::GET,
+                url,
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+            writer
+                .write_all(&body)
+                .await
+                .context(WriteAssetFailed { url })?;
+            return Ok(body.len() as u64);
+        }
+
+        let mut resp = self.assets.execute(req).await.context(RequestFailed {
+            method: Method::GET,
+            url,
+        })?;
+
+        ensure!(
+            resp.status() == StatusCode::OK,
+            HTTPStatusNotOK {
+                method: Method::GET,
+                url,
+                status_code: resp.status()
+            }
+        );
+
+        let mut written = 0;
+        while let Some(chunk) = resp.chunk().await.context(RequestFailed {
+            method: Method::GET,
+            url,
+        })? {
+            writer
+                .write_all(&chunk)
+                .await
+                .context(WriteAssetFailed { url })?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await.context(WriteAssetFailed { url })?;
+
+        Ok(written)
+    }
+
+    /// Call /gateway/index, get gateway url with server->client message compress enabled
     pub async fn gateway_url(&self) -> Result<String> {
-        let data: GatewayIndexData = self.request("/gateway/index", &[("compress", "1")]).await?;
+        self.gateway_url_with_compress(true).await
+    }
+
+    /// Call /gateway/index, get gateway url, `compress` controls server->client message compress
+    pub async fn gateway_url_with_compress(&self, compress: bool) -> Result<String> {
+        let compress = if compress { "1" } else { "0" };
+        let data: GatewayIndexData = self
+            .request("/gateway/index", &[("compress", compress)])
+            .await?;
         Ok(data.url)
     }
+
+    /// Call /user/me, get current user(the bot itself) info
+    pub async fn me(&self) -> Result<User> {
+        self.request("/user/me", &[] as &[(&str, &str)]).await
+    }
+
+    /// Call /guild/view, get guild detail
+    pub async fn guild_view(&self, guild_id: &str) -> Result<Guild> {
+        self.request("/guild/view", &[("guild_id", guild_id)]).await
+    }
+
+    /// Call /guild/list, get all guilds the bot joined
+    pub async fn guild_list(&self) -> Result<Vec<Guild>> {
+        self.request_all("/guild/list", &[]).await
+    }
+
+    /// Call /channel/list, get all channels of a guild
+    pub async fn channel_list(&self, guild_id: &str) -> Result<Vec<Channel>> {
+        self.request_all("/channel/list", &[("guild_id", guild_id)])
+            .await
+    }
+
+    /// Call /guild-role/list, get all roles of a guild
+    pub async fn guild_role_list(&self, guild_id: &str) -> Result<Vec<Role>> {
+        self.request_all("/guild-role/list", &[("guild_id", guild_id)])
+            .await
+    }
+
+    /// Call /channel/view, get channel detail
+    pub async fn channel_view(&self, target_id: &str) -> Result<Channel> {
+        self.request("/channel/view", &[("target_id", target_id)])
+            .await
+    }
+
+    /// Call /message/view, get a channel message detail
+    pub async fn message_view(&self, msg_id: &str) -> Result<MessageDetail> {
+        self.request("/message/view", &[("msg_id", msg_id)]).await
+    }
+
+    /// Call /message/create, send a channel message
+    pub async fn message_create(&self, message: &MessageCreate) -> Result<MessageCreateData> {
+        self.acquire_send(Some(&message.target_id)).await?;
+        self.post("/message/create", message).await
+    }
+
+    /// Call /message/update, update content of a channel message
+    pub async fn message_update(&self, message: &MessageUpdate) -> Result<()> {
+        let _: IgnoredAny = self.post("/message/update", message).await?;
+        Ok(())
+    }
+
+    /// Call /message/add-reaction, add a reaction to a channel message
+    pub async fn message_add_reaction(&self, msg_id: &str, emoji: &Emoji) -> Result<()> {
+        let _: IgnoredAny = self
+            .post(
+                "/message/add-reaction",
+                &Reaction {
+                    msg_id,
+                    emoji: &emoji.id,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Call /direct-message/create, send a private message
+    ///
+    /// Per channel send rate limit is counted by `target_id`, or `chat_code` if no `target_id`,
+    /// so messages to the same user by the two ways are limited separately.
+    pub async fn direct_message_create(
+        &self,
+        message: &DirectMessageCreate,
+    ) -> Result<MessageCreateData> {
+        let target_id = message
+            .target_id
+            .as_deref()
+            .or(message.chat_code.as_deref());
+        self.acquire_send(target_id).await?;
+        self.post("/direct-message/create", message).await
+    }
+
+    /// Call /direct-message/update, update content of a private message
+    pub async fn direct_message_update(&self, message: &MessageUpdate) -> Result<()> {
+        let _: IgnoredAny = self.post("/direct-message/update", message).await?;
+        Ok(())
+    }
+
+    /// Call /direct-message/add-reaction, add a reaction to a private message
+    pub async fn direct_message_add_reaction(&self, msg_id: &str, emoji: &Emoji) -> Result<()> {
+        let _: IgnoredAny = self
+            .post(
+                "/direct-message/add-reaction",
+                &Reaction {
+                    msg_id,
+                    emoji: &emoji.id,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Call /voice/join, join a voice channel, see [VoiceConnection](crate::voice::VoiceConnection)
+    /// for keeping it alive
+    pub async fn voice_join(&self, join: &VoiceJoin) -> Result<VoiceJoinData> {
+        self.post("/voice/join", join).await
+    }
+
+    /// Call /voice/keep-alive, should be called every 45 seconds after joined a voice channel
+    pub async fn voice_keep_alive(&self, channel_id: &str) -> Result<()> {
+        let _: IgnoredAny = self
+            .post("/voice/keep-alive", &VoiceChannel { channel_id })
+            .await?;
+        Ok(())
+    }
+
+    /// Call /voice/leave, leave a voice channel
+    pub async fn voice_leave(&self, channel_id: &str) -> Result<()> {
+        let _: IgnoredAny = self
+            .post("/voice/leave", &VoiceChannel { channel_id })
+            .await?;
+        Ok(())
+    }
+
+    /// Call /user-chat/create, get the private chat with the user, it is created if not exists
+    pub async fn user_chat_create(&self, target_id: &str) -> Result<UserChat> {
+        self.post("/user-chat/create", &UserChatCreate { target_id })
+            .await
+    }
+
+    /// Call /user/offline, make the bot offline
+    pub async fn user_offline(&self) -> Result<()> {
+        let _: IgnoredAny = self.post("/user/offline", &serde_json::Map::new()).await?;
+        Ok(())
+    }
 }
@@ -56,6 +56,15 @@ pub enum Error {
         source: serde_json::Error,
     },
 
+    /// write downloaded asset to the writer failed
+    #[snafu(display("write asset {url} failed: {source}"))]
+    WriteAssetFailed {
+        /// asset url
+        url: String,
+        /// source io error
+        source: std::io::Error,
+    },
+
     /// api response code is not zero
     #[snafu(display("api return error code {code}, {message}"))]
     CodeNotZero {
@@ -64,4 +73,13 @@ pub enum Error {
         /// received message
         message: String,
     },
+
+    /// message send is rejected by the send rate limit
+    #[snafu(display("send to {target_id} is rate limited, retry after {retry_after:?}"))]
+    RateLimited {
+        /// target channel or user id, empty if the message has no target
+        target_id: String,
+        /// how long to wait before the send can be accepted
+        retry_after: std::time::Duration,
+    },
 }
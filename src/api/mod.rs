@@ -2,10 +2,13 @@
 
 mod client;
 mod error;
+mod rate_limit;
 pub mod types;
 
 pub use client::Client;
 pub use error::Error;
+pub use rate_limit::{RateLimitOverflow, SendRateLimit};
+pub use reqwest::Proxy;
 
 /// Result type for api module
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,271 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use super::error::variant::*;
+use super::Result;
+
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// What to do when a message send exceeds the rate limit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RateLimitOverflow {
+    /// Wait until the message can be sent, this is the default.
+    Wait,
+    /// Return [Error::RateLimited](super::Error::RateLimited) immediately.
+    Reject,
+}
+
+/// Rate limit of message send apis, with a global bucket and a bucket per channel.
+///
+/// Set it to a api client by [Client::send_rate_limit](super::Client::send_rate_limit).
+#[derive(Debug, Clone)]
+pub struct SendRateLimit {
+    global: Option<(usize, Duration)>,
+    per_channel: Option<(usize, Duration)>,
+    overflow: RateLimitOverflow,
+}
+
+impl Default for SendRateLimit {
+    fn default() -> Self {
+        Self {
+            global: None,
+            per_channel: None,
+            overflow: RateLimitOverflow::Wait,
+        }
+    }
+}
+
+impl SendRateLimit {
+    /// Send at most `n` messages in every `per` duration across all channels, default is no limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn global(mut self, n: usize, per: Duration) -> Self {
+        assert!(n > 0, "rate limit must allow at least one message");
+        self.global.replace((n, per));
+        self
+    }
+
+    /// Send at most `n` messages in every `per` duration to each channel or user, default is no limit.
+    ///
+    /// Private messages are limited by the `target_id` or `chat_code` they are sent with, so a
+    /// user has two buckets if messages are sent to it in both ways. Messages without either of
+    /// them are only limited by the global limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn per_channel(mut self, n: usize, per: Duration) -> Self {
+        assert!(n > 0, "rate limit must allow at least one message");
+        self.per_channel.replace((n, per));
+        self
+    }
+
+    /// Set what to do when the limit is exceeded, default is [RateLimitOverflow::Wait].
+    pub fn overflow(mut self, overflow: RateLimitOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Get global limit
+    pub fn get_global(&self) -> Option<(usize, Duration)> {
+        self.global
+    }
+
+    /// Get per channel limit
+    pub fn get_per_channel(&self) -> Option<(usize, Duration)> {
+        self.per_channel
+    }
+
+    /// Get overflow behavior
+    pub fn get_overflow(&self) -> RateLimitOverflow {
+        self.overflow
+    }
+}
+
+#[derive(Debug, Default)]
+struct History {
+    global: VecDeque<Instant>,
+    channels: HashMap<String, VecDeque<Instant>>,
+}
+
+/// Returns how long to wait before `times` has room for another entry.
+fn wait_for(times: &mut VecDeque<Instant>, (n, per): (usize, Duration), now: Instant) -> Duration {
+    while times.front().is_some_and(|t| now - *t >= per) {
+        times.pop_front();
+    }
+
+    if times.len() < n {
+        Duration::ZERO
+    } else {
+        times
+            .get(times.len() - n)
+            .map_or(per, |t| per.saturating_sub(now - *t))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    config: RwLock<SendRateLimit>,
+    history: Mutex<History>,
+}
+
+impl Limiter {
+    pub(crate) fn new(config: SendRateLimit) -> Self {
+        Self {
+            config: RwLock::new(config),
+            history: Mutex::default(),
+        }
+    }
+
+    /// Replace the limit, history of sends is kept.
+    pub(crate) fn set_config(&self, config: SendRateLimit) {
+        *self.config.write().unwrap() = config;
+    }
+
+    /// Count a send to `target_id` at `now`, or return how long to wait if it is limited.
+    ///
+    /// Per channel limit is skipped if `target_id` is None.
+    fn try_acquire_at(
+        &self,
+        target_id: Option<&str>,
+        now: Instant,
+    ) -> std::result::Result<(), Duration> {
+        let config = self.config.read().unwrap().clone();
+        let mut history = self.history.lock().unwrap();
+        let History { global, channels } = &mut *history;
+
+        let mut wait = config
+            .global
+            .map_or(Duration::ZERO, |limit| wait_for(global, limit, now));
+
+        let per_channel = config.per_channel.zip(target_id);
+        if let Some((limit, target_id)) = per_channel {
+            if channels.len() > PRUNE_THRESHOLD {
+                channels.retain(|_, times| times.back().is_some_and(|t| now - *t < limit.1));
+            }
+            let times = channels.entry(target_id.to_owned()).or_default();
+            wait = wait.max(wait_for(times, limit, now));
+        }
+
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        if config.global.is_some() {
+            global.push_back(now);
+        }
+        if let Some((_, target_id)) = per_channel {
+            channels
+                .entry(target_id.to_owned())
+                .or_default()
+                .push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// Wait until a message can be sent to `target_id`, or fail if overflow is [RateLimitOverflow::Reject].
+    pub(crate) async fn acquire(&self, target_id: Option<&str>) -> Result<()> {
+        let target = target_id.unwrap_or_default();
+        loop {
+            let retry_after = match self.try_acquire_at(target_id, Instant::now()) {
+                Ok(()) => return Ok(()),
+                Err(retry_after) => retry_after,
+            };
+
+            let overflow = self.config.read().unwrap().overflow;
+            match overflow {
+                RateLimitOverflow::Wait => {
+                    debug!("Send to {target} is rate limited, wait {retry_after:?}");
+                    tokio::time::sleep(retry_after).await
+                }
+                RateLimitOverflow::Reject => {
+                    return RateLimited {
+                        target_id: target,
+                        retry_after,
+                    }
+                    .fail()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn test_send_rate_limit_zero_global() {
+        SendRateLimit::default().global(0, Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_send_rate_limit_zero_per_channel() {
+        SendRateLimit::default().per_channel(0, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_send_rate_limit_buckets() {
+        let limiter = Limiter::new(
+            SendRateLimit::default()
+                .global(3, Duration::from_secs(10))
+                .per_channel(2, Duration::from_secs(5)),
+        );
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(limiter.try_acquire_at(Some("a"), at(0)), Ok(()));
+        assert_eq!(limiter.try_acquire_at(Some("a"), at(1)), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(Some("a"), at(2)),
+            Err(Duration::from_secs(3))
+        );
+        assert_eq!(limiter.try_acquire_at(Some("b"), at(2)), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(Some("c"), at(3)),
+            Err(Duration::from_secs(7))
+        );
+        assert_eq!(limiter.try_acquire_at(Some("a"), at(10)), Ok(()));
+    }
+
+    #[test]
+    fn test_send_rate_limit_without_target() {
+        let limiter = Limiter::new(
+            SendRateLimit::default()
+                .global(2, Duration::from_secs(10))
+                .per_channel(1, Duration::from_secs(10)),
+        );
+        let now = Instant::now();
+
+        assert_eq!(limiter.try_acquire_at(None, now), Ok(()));
+        assert_eq!(limiter.try_acquire_at(None, now), Ok(()));
+        assert_eq!(
+            limiter.try_acquire_at(None, now),
+            Err(Duration::from_secs(10))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_rate_limit_reject() {
+        let limiter = Limiter::new(
+            SendRateLimit::default()
+                .per_channel(1, Duration::from_secs(60))
+                .overflow(RateLimitOverflow::Reject),
+        );
+
+        assert!(limiter.acquire(Some("a")).await.is_ok());
+        assert!(limiter.acquire(Some("b")).await.is_ok());
+        assert!(matches!(
+            limiter.acquire(Some("a")).await,
+            Err(super::super::Error::RateLimited { .. })
+        ));
+    }
+}
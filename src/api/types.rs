@@ -2,10 +2,13 @@
 
 use std::{collections::HashMap, fmt::Display, str::FromStr};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 
-use crate::ws::message::{Message, SN};
+use crate::{
+    models::{Attachment, Embed, MessageType, Quote, Timestamp, User},
+    ws::message::{Message, SN},
+};
 
 /// Response is common response structure with a code and message, and a data field.
 #[derive(Debug, Deserialize)]
@@ -25,6 +28,334 @@ pub struct GatewayIndexData {
     pub url: String,
 }
 
+/// data type for api /message/view
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessageDetail {
+    /// message id
+    pub id: String,
+    /// message type
+    pub r#type: MessageType,
+    /// message author
+    pub author: User,
+    /// message content
+    pub content: String,
+    /// mentioned user id list
+    #[serde(default)]
+    pub mention: Vec<String>,
+    /// is mention all users
+    #[serde(default)]
+    pub mention_all: bool,
+    /// mentioned role id list
+    #[serde(default)]
+    pub mention_roles: Vec<u64>,
+    /// is mention online users
+    #[serde(default)]
+    pub mention_here: bool,
+    /// attachment of image/video/file/audio message
+    #[serde(default)]
+    pub attachments: Option<Attachment>,
+    /// embed contents, like link preview
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    /// quoted message
+    #[serde(default)]
+    pub quote: Option<Quote>,
+    /// create time in milliseconds timestamp
+    pub create_at: Timestamp,
+    /// update time in milliseconds timestamp, zero if never updated
+    #[serde(default)]
+    pub updated_at: Timestamp,
+}
+
+/// request body for api /message/create
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MessageCreate {
+    /// message type, default is text
+    pub r#type: MessageType,
+    /// target channel id
+    pub target_id: String,
+    /// message content
+    pub content: String,
+    /// quoted message id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// random string to identify the message in the message event, for deduplication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// if set, the message is temporary and only visible to this user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_target_id: Option<String>,
+    /// message template id, content is json data rendered by the template if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+}
+
+impl MessageCreate {
+    /// Create a message with type and content which will be sent to target channel
+    pub fn new<T, C>(r#type: MessageType, target_id: T, content: C) -> Self
+    where
+        T: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            r#type,
+            target_id: target_id.into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a message rendered by server side template with json data,
+    /// `type` should be the type of template
+    pub fn template<T, I>(
+        r#type: MessageType,
+        target_id: T,
+        template_id: I,
+        data: &serde_json::Value,
+    ) -> Self
+    where
+        T: Into<String>,
+        I: Into<String>,
+    {
+        Self {
+            template_id: Some(template_id.into()),
+            ..Self::new(r#type, target_id, data.to_string())
+        }
+    }
+}
+
+/// request body for api /direct-message/create
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectMessageCreate {
+    /// message type, default is text
+    pub r#type: MessageType,
+    /// target user id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+    /// chat code, can be used instead of target user id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_code: Option<String>,
+    /// message content
+    pub content: String,
+    /// quoted message id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// random string to identify the message in the message event, for deduplication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// message template id, content is json data rendered by the template if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+}
+
+impl DirectMessageCreate {
+    /// Create a message with type and content which will be sent to target user
+    pub fn new<T, C>(r#type: MessageType, target_id: T, content: C) -> Self
+    where
+        T: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            r#type,
+            target_id: Some(target_id.into()),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a message rendered by server side template with json data,
+    /// `type` should be the type of template
+    pub fn template<T, I>(
+        r#type: MessageType,
+        target_id: T,
+        template_id: I,
+        data: &serde_json::Value,
+    ) -> Self
+    where
+        T: Into<String>,
+        I: Into<String>,
+    {
+        Self {
+            template_id: Some(template_id.into()),
+            ..Self::new(r#type, target_id, data.to_string())
+        }
+    }
+}
+
+/// request body for api /message/update and /direct-message/update
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MessageUpdate {
+    /// id of message to update, only kmarkdown and card messages can be updated
+    pub msg_id: String,
+    /// new content
+    pub content: String,
+    /// new quoted message id, empty string removes the quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// update a temporary message only visible to this user, channel message only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_target_id: Option<String>,
+    /// message template id, content is json data rendered by the template if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+}
+
+impl MessageUpdate {
+    /// Replace content of the message
+    pub fn new<M, C>(msg_id: M, content: C) -> Self
+    where
+        M: Into<String>,
+        C: Into<String>,
+    {
+        Self {
+            msg_id: msg_id.into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Replace content of the message by server side template rendered with json data
+    pub fn template<M, I>(msg_id: M, template_id: I, data: &serde_json::Value) -> Self
+    where
+        M: Into<String>,
+        I: Into<String>,
+    {
+        Self {
+            template_id: Some(template_id.into()),
+            ..Self::new(msg_id, data.to_string())
+        }
+    }
+}
+
+/// data type for api /message/create and /direct-message/create
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessageCreateData {
+    /// created message id
+    pub msg_id: String,
+    /// message create time
+    pub msg_timestamp: Timestamp,
+    /// nonce in the request
+    #[serde(default)]
+    pub nonce: String,
+}
+
+/// pagination info of list api
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PageMeta {
+    /// current page, start from 1
+    pub page: u64,
+    /// total page count
+    pub page_total: u64,
+    /// item count per page
+    pub page_size: u64,
+    /// total item count
+    pub total: u64,
+}
+
+/// data type for list api, like /guild/list
+#[derive(Debug, Clone, Deserialize)]
+pub struct Page<T> {
+    /// items in this page
+    #[serde(default = "Vec::new")]
+    pub items: Vec<T>,
+    /// pagination info
+    #[serde(default)]
+    pub meta: PageMeta,
+}
+
+/// data type for api /user-chat/create
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UserChat {
+    /// chat code, used to send private messages by [DirectMessageCreate::chat_code]
+    pub code: String,
+    /// last time the chat is read
+    pub last_read_time: Timestamp,
+    /// time of latest message
+    pub latest_msg_time: Timestamp,
+    /// unread message count
+    pub unread_count: u64,
+    /// the other user in the chat
+    pub target_info: User,
+}
+
+/// request body for api /user-chat/create
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UserChatCreate<'a> {
+    pub(crate) target_id: &'a str,
+}
+
+/// request body for api /message/add-reaction and /direct-message/add-reaction
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Reaction<'a> {
+    pub(crate) msg_id: &'a str,
+    pub(crate) emoji: &'a str,
+}
+
+/// request body for api /voice/join
+#[derive(Debug, Clone, Serialize)]
+pub struct VoiceJoin {
+    /// voice channel id
+    pub channel_id: String,
+    /// ssrc of pushed audio, server default is 1111
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_ssrc: Option<String>,
+    /// rtp payload type of pushed audio, server default is 111
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_pt: Option<String>,
+    /// whether rtcp uses the same port as rtp, server default is true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtcp_mux: Option<bool>,
+    /// channel password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl VoiceJoin {
+    /// Join the voice channel with server default audio parameters
+    pub fn new<S: Into<String>>(channel_id: S) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            audio_ssrc: None,
+            audio_pt: None,
+            rtcp_mux: None,
+            password: None,
+        }
+    }
+}
+
+/// data type for api /voice/join, where to push rtp audio stream
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct VoiceJoinData {
+    /// rtp server ip
+    pub ip: String,
+    /// rtp server port
+    #[serde(deserialize_with = "crate::models::number_or_string")]
+    pub port: u16,
+    /// whether rtcp uses the same port as rtp
+    pub rtcp_mux: bool,
+    /// rtcp server port, used if `rtcp_mux` is false
+    #[serde(deserialize_with = "crate::models::number_or_string")]
+    pub rtcp_port: u16,
+    /// audio bitrate in bps
+    #[serde(deserialize_with = "crate::models::number_or_string")]
+    pub bitrate: u32,
+    /// ssrc of pushed audio
+    #[serde(deserialize_with = "crate::models::number_or_string")]
+    pub audio_ssrc: u32,
+    /// rtp payload type of pushed audio
+    #[serde(deserialize_with = "crate::models::number_or_string")]
+    pub audio_pt: u8,
+}
+
+/// request body for api /voice/leave and /voice/keep-alive
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct VoiceChannel<'a> {
+    pub(crate) channel_id: &'a str,
+}
+
 /// Parse string as gateway url error
 #[derive(Debug, Snafu)]
 #[snafu(
@@ -90,7 +421,7 @@ pub enum ParseGatewayURLError {
 }
 
 /// needed arguments when reconnect to a gateway
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GatewayResumeArguments {
     /// last message id
     pub sn: u64,
@@ -214,3 +545,102 @@ impl Display for GatewayURLInfo {
         self.url().fmt(f)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message_create_serialize() {
+        let mut message = MessageCreate::new(MessageType::KMarkdown, "channel-id", "**hi**");
+        message.quote = Some("msg-id".to_string());
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "type": 9,
+                "target_id": "channel-id",
+                "content": "**hi**",
+                "quote": "msg-id",
+            })
+        );
+    }
+
+    #[test]
+    fn test_template_message_serialize() {
+        let data = serde_json::json!({ "name": "burz" });
+
+        let message = MessageCreate::template(MessageType::Card, "channel-id", "tpl-id", &data);
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "type": 10,
+                "target_id": "channel-id",
+                "content": r#"{"name":"burz"}"#,
+                "template_id": "tpl-id",
+            })
+        );
+
+        let message = DirectMessageCreate::template(MessageType::KMarkdown, "user", "tpl", &data);
+        assert_eq!(message.template_id.as_deref(), Some("tpl"));
+        assert_eq!(message.content, r#"{"name":"burz"}"#);
+
+        let update = MessageUpdate::template("msg-id", "tpl-id", &data);
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            serde_json::json!({
+                "msg_id": "msg-id",
+                "content": r#"{"name":"burz"}"#,
+                "template_id": "tpl-id",
+            })
+        );
+    }
+
+    #[test]
+    fn test_voice_join_data_deserialize() {
+        let data: VoiceJoinData = serde_json::from_str(
+            r#"{
+                "ip": "1.2.3.4",
+                "port": "30000",
+                "rtcp_mux": false,
+                "rtcp_port": 30001,
+                "bitrate": 48000,
+                "audio_ssrc": "1111",
+                "audio_pt": "111"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.port, 30000);
+        assert_eq!(data.rtcp_port, 30001);
+        assert_eq!(data.audio_ssrc, 1111);
+        assert_eq!(data.audio_pt, 111);
+        assert!(serde_json::from_str::<VoiceJoinData>(r#"{"port": "x"}"#).is_err());
+    }
+
+    #[test]
+    fn test_page_deserialize() {
+        let page: Page<crate::models::Role> = serde_json::from_value(serde_json::json!({
+            "items": [{"role_id": 1, "name": "admin", "color": 0, "position": 1, "hoist": 0, "mentionable": 0, "permissions": 1}],
+            "meta": {"page": 1, "page_total": 2, "page_size": 1, "total": 2},
+        }))
+        .unwrap();
+
+        assert_eq!(page.items[0].name, "admin");
+        assert_eq!(page.meta.page_total, 2);
+    }
+
+    #[test]
+    fn test_user_chat_deserialize() {
+        let chat: UserChat = serde_json::from_value(serde_json::json!({
+            "code": "chat-code",
+            "last_read_time": 1612345678901_i64,
+            "latest_msg_time": 1612345679000_i64,
+            "unread_count": 1,
+        }))
+        .unwrap();
+
+        assert_eq!(chat.last_read_time, Timestamp::from_millis(1612345678901));
+        assert_eq!(chat.latest_msg_time.as_millis(), 1612345679000);
+    }
+}
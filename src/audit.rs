@@ -0,0 +1,318 @@
+//! Structured audit records of received events and api calls.
+//!
+//! Set a [AuditSink] by [BotBuilder::audit](crate::BotBuilder::audit) to receive them.
+
+use std::{
+    fmt::Debug,
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ws::{event::EventData, Event};
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// A event received by bot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// milliseconds since unix epoch when the event is received
+    pub time: u64,
+    /// event serial number
+    pub sn: u64,
+    /// event type, `channel_message`, `private_message`, `unknown`,
+    /// or type of system event, like `added_reaction`
+    pub kind: String,
+    /// message id
+    pub msg_id: String,
+    /// event author id
+    pub author_id: String,
+    /// guild id, if any
+    pub guild_id: Option<String>,
+    /// channel id, if any
+    pub channel_id: Option<String>,
+}
+
+impl EventRecord {
+    pub(crate) fn new(data: &EventData) -> Self {
+        let event = data.event.as_ref();
+        let kind = match event {
+            Event::ChannelMessage(_) => "channel_message".to_string(),
+            Event::PrivateMessage(_) => "private_message".to_string(),
+            Event::SystemEvent(b) => serde_json::to_value(&b.extra)
+                .ok()
+                .and_then(|v| v["type"].as_str().map(ToOwned::to_owned))
+                .unwrap_or_else(|| "system_event".to_string()),
+            Event::Unknown(_) => "unknown".to_string(),
+        };
+
+        Self {
+            time: now_millis(),
+            sn: data.sn,
+            kind,
+            msg_id: event.msg_id().to_string(),
+            author_id: event.author_id().to_string(),
+            guild_id: event.guild_id().map(ToOwned::to_owned),
+            channel_id: event.channel_id().map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// A api call made by bot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiCallRecord {
+    /// milliseconds since unix epoch when the call finished
+    pub time: u64,
+    /// http method
+    pub method: String,
+    /// api path, like `/message/create`
+    pub endpoint: String,
+    /// http status code, `None` if no response is received
+    pub status: Option<u16>,
+    /// error message if call failed
+    pub error: Option<String>,
+    /// milliseconds the call takes
+    pub latency: u64,
+}
+
+impl ApiCallRecord {
+    pub(crate) fn new(
+        method: String,
+        endpoint: String,
+        status: Option<u16>,
+        error: Option<String>,
+        latency: Duration,
+    ) -> Self {
+        Self {
+            time: now_millis(),
+            method,
+            endpoint,
+            status,
+            error,
+            latency: latency.as_millis() as u64,
+        }
+    }
+}
+
+/// Audit record, serialized with a `record` field of `event` or `api_call`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum AuditRecord {
+    /// a event is received
+    Event(EventRecord),
+    /// a api call is finished
+    ApiCall(ApiCallRecord),
+}
+
+/// Receiver of audit records.
+///
+/// It is called in the event loop and api calls, so it should not block for long.
+pub trait AuditSink: Send + Sync + Debug {
+    /// record a audit record
+    fn record(&self, record: &AuditRecord);
+}
+
+/// Max count of records waiting to be written, newer records are dropped when exceeded
+const JSONL_QUEUE_CAPACITY: usize = 1024;
+
+/// Write every audit record as a json line.
+///
+/// Records are written by a background thread, so recording never blocks on io. The writer
+/// is flushed when no record is waiting, and when the sink is dropped.
+pub struct JsonlAuditSink {
+    sender: Option<SyncSender<AuditRecord>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Debug for JsonlAuditSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonlAuditSink").finish_non_exhaustive()
+    }
+}
+
+impl Drop for JsonlAuditSink {
+    fn drop(&mut self) {
+        // writer thread exits after writing all queued records when sender dropped
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl JsonlAuditSink {
+    /// Create a sink writes to the writer
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(JSONL_QUEUE_CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("burz-audit".to_string())
+            .spawn(move || write_jsonl(writer, receiver))
+            .expect("spawn audit writer thread");
+
+        Self {
+            sender: Some(sender),
+            writer: Some(writer),
+        }
+    }
+
+    /// Create a sink appends to the file, the file is created if not exists
+    pub fn append<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+fn write_jsonl<W: Write>(mut writer: W, receiver: Receiver<AuditRecord>) {
+    while let Ok(mut record) = receiver.recv() {
+        loop {
+            let result = serde_json::to_writer(&mut writer, &record)
+                .map_err(std::io::Error::from)
+                .and_then(|_| writer.write_all(b"\n"));
+            if let Err(err) = result {
+                warn!("Write audit record failed: {}", err);
+            }
+
+            match receiver.try_recv() {
+                Ok(next) => record = next,
+                Err(_) => break,
+            }
+        }
+
+        if let Err(err) = writer.flush() {
+            warn!("Flush audit records failed: {}", err);
+        }
+    }
+}
+
+impl AuditSink for JsonlAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        match sender.try_send(record.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => warn!("Too many audit records waiting, record dropped"),
+            Err(TrySendError::Disconnected(_)) => warn!("Audit writer stopped, record dropped"),
+        }
+    }
+}
+
+/// Emit every audit record as a `tracing` event with target `burz::audit`.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingAuditSink;
+
+#[cfg(feature = "tracing")]
+impl AuditSink for TracingAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        match record {
+            AuditRecord::Event(r) => tracing::info!(
+                target: "burz::audit",
+                sn = r.sn,
+                kind = %r.kind,
+                msg_id = %r.msg_id,
+                author_id = %r.author_id,
+                guild_id = ?r.guild_id,
+                channel_id = ?r.channel_id,
+                "event received"
+            ),
+            AuditRecord::ApiCall(r) => tracing::info!(
+                target: "burz::audit",
+                method = %r.method,
+                endpoint = %r.endpoint,
+                status = ?r.status,
+                error = ?r.error,
+                latency_ms = r.latency,
+                "api called"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::ws::event::{EventBody, MessageExtra, ReactionBody, SystemEvent};
+
+    #[derive(Debug, Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_event_record_kind() {
+        let message = EventData {
+            sn: 1,
+            event: Box::new(Event::ChannelMessage(EventBody {
+                target_id: "channel".to_string(),
+                author_id: "user".to_string(),
+                extra: MessageExtra {
+                    guild_id: "guild".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })),
+        };
+        let record = EventRecord::new(&message);
+        assert_eq!(record.kind, "channel_message");
+        assert_eq!(record.guild_id.as_deref(), Some("guild"));
+        assert_eq!(record.channel_id.as_deref(), Some("channel"));
+
+        let reaction = EventData {
+            sn: 2,
+            event: Box::new(Event::SystemEvent(
+                EventBody::<()>::default()
+                    .with_extra(SystemEvent::AddedReaction(ReactionBody::default())),
+            )),
+        };
+        assert_eq!(EventRecord::new(&reaction).kind, "added_reaction");
+    }
+
+    #[test]
+    fn test_jsonl_audit_sink() {
+        let buffer = Buffer::default();
+        let sink = JsonlAuditSink::new(buffer.clone());
+
+        let record = AuditRecord::ApiCall(ApiCallRecord::new(
+            "POST".to_string(),
+            "/message/create".to_string(),
+            Some(200),
+            None,
+            Duration::from_millis(12),
+        ));
+        sink.record(&record);
+        sink.record(&record);
+        drop(sink);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(value["record"], "api_call");
+        assert_eq!(value["latency"], 12);
+        assert_eq!(
+            serde_json::from_value::<AuditRecord>(value).unwrap(),
+            record
+        );
+    }
+}
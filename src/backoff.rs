@@ -0,0 +1,134 @@
+//! Delay strategies between retries.
+//!
+//! Used by [RetryPolicy](crate::RetryPolicy) for gateway refetch and reconnect, and by
+//! [ws::ClientConfig](crate::ws::ClientConfig) for pings in timeout state. Implement
+//! [BackoffStrategy] for a custom strategy.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Debug,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// Strategy to decide how long to wait before a retry.
+pub trait BackoffStrategy: Send + Sync + Debug {
+    /// Get delay before the nth(0-based) retry
+    fn delay(&self, attempt: usize) -> Duration;
+}
+
+/// Delay doubles after each retry, from initial delay to max delay, optionally with jitter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+    jitter: f64,
+}
+
+impl ExponentialBackoff {
+    /// Create a strategy without jitter
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            jitter: 0.0,
+        }
+    }
+
+    /// Reduce every delay by a random amount, up to `ratio` of the delay
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` is not in 0 to 1.
+    pub fn jitter(mut self, ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "jitter ratio must be in 0..=1"
+        );
+        self.jitter = ratio;
+        self
+    }
+
+    /// Get initial delay
+    pub fn get_initial(&self) -> Duration {
+        self.initial
+    }
+
+    /// Get max delay
+    pub fn get_max(&self) -> Duration {
+        self.max
+    }
+
+    /// Get jitter ratio
+    pub fn get_jitter(&self) -> f64 {
+        self.jitter
+    }
+}
+
+/// A random number in 0 to 1, std hasher keys are randomly seeded for every [RandomState].
+fn random() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl BackoffStrategy for ExponentialBackoff {
+    fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        let delay = self
+            .initial
+            .saturating_mul(factor)
+            .min(self.max.max(self.initial));
+
+        if self.jitter > 0.0 {
+            delay.mul_f64(1.0 - self.jitter * random())
+        } else {
+            delay
+        }
+    }
+}
+
+/// Wait the same delay before every retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedBackoff(pub Duration);
+
+impl BackoffStrategy for FixedBackoff {
+    fn delay(&self, _attempt: usize) -> Duration {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let backoff = ExponentialBackoff::new(Duration::from_secs(2), Duration::from_secs(10));
+        let delays: Vec<_> = (0..5).map(|i| backoff.delay(i).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 10, 10]);
+        assert_eq!(backoff.delay(usize::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_exponential_backoff_jitter() {
+        let exact = ExponentialBackoff::new(Duration::from_secs(8), Duration::from_secs(60));
+        let backoff = exact.jitter(0.5);
+        for attempt in 0..10 {
+            let delay = backoff.delay(attempt);
+            let full = exact.delay(attempt);
+            assert!(delay <= full && delay >= full / 2, "{delay:?} {full:?}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_exponential_backoff_invalid_jitter() {
+        let _ = ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(2)).jitter(1.5);
+    }
+
+    #[test]
+    fn test_fixed_backoff() {
+        let backoff = FixedBackoff(Duration::from_secs(3));
+        assert_eq!(backoff.delay(0), backoff.delay(100));
+    }
+}
@@ -0,0 +1,638 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use futures_util::{stream::BoxStream, Stream, StreamExt};
+use snafu::prelude::*;
+
+use super::Bot;
+use crate::{
+    api::{self, types::GatewayURLInfo},
+    audit::AuditSink,
+    backoff::{BackoffStrategy, ExponentialBackoff, FixedBackoff},
+    config::{Config, LiveConfig},
+    dedup::Dedup,
+    error, filter,
+    session::SessionStore,
+    ws,
+    ws::Event,
+    Filter, Result,
+};
+
+const RETRY_DELAY_INITIAL: Duration = Duration::from_secs(1);
+const RETRY_DELAY_MAX: Duration = Duration::from_secs(60);
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+const DEDUP_TTL: Duration = Duration::from_secs(600);
+
+/// Policy of retrying when fetch gateway url or connect to websocket gateway failed, and of
+/// reconnecting when event stream is broken.
+///
+/// By default, delay of retry doubles after each failed try, from initial delay to max delay,
+/// and reconnect happens immediately. Both can be replaced by a [BackoffStrategy].
+///
+/// Both retry and reconnect are unlimited by default. When a limit is reached, bot notifies
+/// [Lifecycle::GaveUp](crate::Lifecycle::GaveUp) and stops running with error.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    max_retries: Option<usize>,
+    max_reconnects: Option<usize>,
+    backoff: Option<Arc<dyn BackoffStrategy>>,
+    reconnect_backoff: Arc<dyn BackoffStrategy>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: RETRY_DELAY_INITIAL,
+            max_delay: RETRY_DELAY_MAX,
+            jitter: 0.0,
+            max_retries: None,
+            max_reconnects: None,
+            backoff: None,
+            reconnect_backoff: Arc::new(FixedBackoff(Duration::ZERO)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create default policy, retry forever with delay from 1 second to 60 seconds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set delay before first retry
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set max delay between retries
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Reduce every retry delay by a random amount, up to `ratio` of the delay, so bots
+    /// restarted together do not retry at the same time. Default is 0, no jitter
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` is not in 0 to 1.
+    pub fn jitter(mut self, ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "jitter ratio must be in 0..=1"
+        );
+        self.jitter = ratio;
+        self
+    }
+
+    /// Set max retry count, bot stops running with error when exceeded
+    pub fn max_retries(mut self, retries: usize) -> Self {
+        self.max_retries.replace(retries);
+        self
+    }
+
+    /// Set max count of event stream broken or failed to establish in a row without receiving
+    /// any event, bot stops running with error when exceeded
+    pub fn max_reconnects(mut self, reconnects: usize) -> Self {
+        self.max_reconnects.replace(reconnects);
+        self
+    }
+
+    /// Set strategy of retry delay, initial delay and max delay are ignored when it's set
+    pub fn backoff<B: BackoffStrategy + 'static>(mut self, backoff: B) -> Self {
+        self.backoff.replace(Arc::new(backoff));
+        self
+    }
+
+    /// Set strategy of delay before reconnecting a broken event stream, the attempt count is
+    /// how many streams broken in a row without receiving any event
+    pub fn reconnect_backoff<B: BackoffStrategy + 'static>(mut self, backoff: B) -> Self {
+        self.reconnect_backoff = Arc::new(backoff);
+        self
+    }
+
+    /// Get jitter ratio of retry delay
+    pub fn get_jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Get max retry count, None means retry forever
+    pub fn get_max_retries(&self) -> Option<usize> {
+        self.max_retries
+    }
+
+    /// Get max reconnect count, None means reconnect forever
+    pub fn get_max_reconnects(&self) -> Option<usize> {
+        self.max_reconnects
+    }
+
+    /// Get delay before the nth(0-based) retry
+    pub fn delay(&self, retry: usize) -> Duration {
+        match &self.backoff {
+            Some(backoff) => backoff.delay(retry),
+            None => ExponentialBackoff::new(self.initial_delay, self.max_delay)
+                .jitter(self.jitter)
+                .delay(retry),
+        }
+    }
+
+    /// Get delay before the nth(0-based) reconnect
+    pub fn reconnect_delay(&self, attempt: usize) -> Duration {
+        self.reconnect_backoff.delay(attempt)
+    }
+}
+
+/// What to do when the event queue of bot is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Stop receiving new events until queue has space, this is the default.
+    #[default]
+    Block,
+    /// Drop the oldest waiting event.
+    DropOldest,
+    /// Reject the new event and keep running, rejected events are logged and reported as
+    /// [Lifecycle::EventRejected](crate::Lifecycle::EventRejected).
+    Reject,
+}
+
+/// Limit of events dispatched by bot at the same time.
+///
+/// Events exceed the limit wait in a queue, [Backpressure] decides what to do when it's full.
+/// Capacity of the channel between websocket client and bot is set by
+/// [ws::ClientConfig::event_capacity].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventQueue {
+    max_in_flight: Option<usize>,
+    capacity: usize,
+    backpressure: Backpressure,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self {
+            max_in_flight: None,
+            capacity: EVENT_QUEUE_CAPACITY,
+            backpressure: Backpressure::default(),
+        }
+    }
+}
+
+impl EventQueue {
+    /// Create default config, events are dispatched without limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set max number of events dispatched at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_in_flight` is zero.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "max_in_flight must be positive");
+        self.max_in_flight.replace(max_in_flight);
+        self
+    }
+
+    /// Set max number of events waiting to be dispatched, default is 1024.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set what to do when queue is full, default is [Backpressure::Block].
+    pub fn backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Get max number of events dispatched at the same time, None means no limit
+    pub fn get_max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    /// Get max number of events waiting to be dispatched
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get backpressure policy
+    pub fn get_backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+}
+
+/// Builder of [Bot], created by [Bot::builder].
+pub struct BotBuilder {
+    token: String,
+    name: Option<String>,
+    compress: bool,
+    gateway: Option<GatewayURLInfo>,
+    gateway_url: Option<String>,
+    ws_config: ws::ClientConfig,
+    retry: RetryPolicy,
+    event_queue: EventQueue,
+    filters: Vec<Box<dyn Filter + 'static>>,
+    event_log_level: log::LevelFilter,
+    proxy: Option<api::Proxy>,
+    send_rate_limit: Option<api::SendRateLimit>,
+    session_store: Option<Arc<dyn SessionStore + 'static>>,
+    config: Option<Config>,
+    audit: Option<Arc<dyn AuditSink + 'static>>,
+    dedup_capacity: usize,
+    dedup_ttl: Duration,
+    #[cfg(feature = "webhook")]
+    webhook: Option<crate::webhook::WebhookConfig>,
+    #[cfg(any(test, feature = "testing"))]
+    recorder: Option<crate::testing::Recorder>,
+    #[cfg(any(test, feature = "testing"))]
+    replayer: Option<crate::testing::Replayer>,
+    #[cfg(any(test, feature = "testing"))]
+    mock_api: Option<Arc<crate::testing::MockApi>>,
+    event_source: Option<BoxStream<'static, Event>>,
+}
+
+impl Debug for BotBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BotBuilder")
+            .field("name", &self.name)
+            .field("compress", &self.compress)
+            .field("gateway", &self.gateway)
+            .field("gateway_url", &self.gateway_url)
+            .field("ws_config", &self.ws_config)
+            .field("retry", &self.retry)
+            .field("event_queue", &self.event_queue)
+            .field("filters", &self.filters.len())
+            .field("event_log_level", &self.event_log_level)
+            .field("proxy", &self.proxy)
+            .field("send_rate_limit", &self.send_rate_limit)
+            .field("session_store", &self.session_store)
+            .field("config", &self.config)
+            .field("audit", &self.audit)
+            .field("dedup_capacity", &self.dedup_capacity)
+            .field("dedup_ttl", &self.dedup_ttl)
+            .field("event_source", &self.event_source.is_some())
+            .finish()
+    }
+}
+
+impl BotBuilder {
+    /// Create a builder with bot token and default configs
+    pub fn new<S: Into<String>>(token: S) -> Self {
+        Self {
+            token: token.into(),
+            name: None,
+            compress: true,
+            gateway: None,
+            gateway_url: None,
+            ws_config: ws::ClientConfig::default(),
+            retry: RetryPolicy::default(),
+            event_queue: EventQueue::default(),
+            filters: vec![],
+            event_log_level: log::LevelFilter::Info,
+            proxy: None,
+            send_rate_limit: None,
+            session_store: None,
+            config: None,
+            audit: None,
+            dedup_capacity: 0,
+            dedup_ttl: DEDUP_TTL,
+            #[cfg(feature = "webhook")]
+            webhook: None,
+            #[cfg(any(test, feature = "testing"))]
+            recorder: None,
+            #[cfg(any(test, feature = "testing"))]
+            replayer: None,
+            #[cfg(any(test, feature = "testing"))]
+            mock_api: None,
+            event_source: None,
+        }
+    }
+
+    /// Set name of the bot, see [Bot::name]
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name.replace(name.into());
+        self
+    }
+
+    /// Set if gateway compresses websocket messages sent to bot, default is true
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Connect to the gateway directly instead of getting one from `/gateway/index` api,
+    /// like a local test server or `testing::MockGateway`.
+    ///
+    /// The gateway is used for every connect and reconnect, its compress flag is used instead of
+    /// [BotBuilder::compress].
+    pub fn gateway(mut self, gateway: GatewayURLInfo) -> Self {
+        self.gateway.replace(gateway);
+        self.gateway_url = None;
+        self
+    }
+
+    /// Connect to the gateway url directly, see [BotBuilder::gateway].
+    ///
+    /// The url is parsed when building the bot, an invalid one fails [BotBuilder::build].
+    pub fn gateway_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.gateway_url.replace(url.into());
+        self.gateway = None;
+        self
+    }
+
+    /// Set websocket client config, like ping interval and event channel capacity
+    pub fn ws_config(mut self, config: ws::ClientConfig) -> Self {
+        self.ws_config = config;
+        self
+    }
+
+    /// Connect to websocket gateway through the proxy, see [ws::ClientConfig::proxy]
+    pub fn ws_proxy(mut self, proxy: ws::Proxy) -> Self {
+        self.ws_config = self.ws_config.proxy(proxy);
+        self
+    }
+
+    /// Set policy of retrying when connect to websocket gateway failed
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Set limit of events dispatched at the same time, and backpressure policy when exceeded
+    pub fn event_queue(mut self, queue: EventQueue) -> Self {
+        self.event_queue = queue;
+        self
+    }
+
+    /// Add a filter applied to all events, events rejected by it will not be dispatched
+    pub fn filter<F: Filter + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Ignore messages sent by bots, including this bot itself
+    pub fn ignore_bots(self) -> Self {
+        self.filter(filter::not_bot())
+    }
+
+    /// Set log level of received events, default is `Info`, `Off` to disable
+    pub fn event_log_level(mut self, level: log::LevelFilter) -> Self {
+        self.event_log_level = level;
+        self
+    }
+
+    /// Send api requests through the proxy.
+    ///
+    /// Websocket connection does not use the proxy, set it by [BotBuilder::ws_proxy].
+    pub fn proxy(mut self, proxy: api::Proxy) -> Self {
+        self.proxy.replace(proxy);
+        self
+    }
+
+    /// Limit the rate of messages sent by the bot, see [api::SendRateLimit]
+    pub fn send_rate_limit(mut self, limit: api::SendRateLimit) -> Self {
+        self.send_rate_limit.replace(limit);
+        self
+    }
+
+    /// Send audit records of received events and api calls to the sink, see [crate::audit]
+    pub fn audit<S: AuditSink + 'static>(mut self, sink: S) -> Self {
+        self.audit.replace(Arc::new(sink));
+        self
+    }
+
+    /// Persist websocket session to the store, and resume from it after restart
+    pub fn session_store<S: SessionStore + 'static>(mut self, store: S) -> Self {
+        self.session_store.replace(Arc::new(store));
+        self
+    }
+
+    /// Set how many recent msg ids are remembered to drop duplicate events,
+    /// default is 0 which disables deduplication
+    ///
+    /// The ids are persisted by [session store](Self::session_store) if set,
+    /// so events delivered again after restart are dropped too.
+    pub fn dedup_capacity(mut self, capacity: usize) -> Self {
+        self.dedup_capacity = capacity;
+        self
+    }
+
+    /// Apply config which can be reloaded while running, see [Config].
+    ///
+    /// Send rate limit is always enabled, [BotBuilder::send_rate_limit] called after this
+    /// overrides the config until it is reloaded.
+    pub fn config(mut self, config: Config) -> Self {
+        self.send_rate_limit.replace((&config.rate_limit).into());
+        self.config.replace(config);
+        self
+    }
+
+    /// Set how long a msg id is remembered, default is 10 minutes
+    pub fn dedup_ttl(mut self, ttl: Duration) -> Self {
+        self.dedup_ttl = ttl;
+        self
+    }
+
+    /// Receive events by webhook server instead of websocket
+    #[cfg(feature = "webhook")]
+    pub fn webhook(mut self, config: crate::webhook::WebhookConfig) -> Self {
+        self.webhook.replace(config);
+        self
+    }
+
+    /// Record all received events by the recorder
+    #[cfg(any(test, feature = "testing"))]
+    pub fn recorder(mut self, recorder: crate::testing::Recorder) -> Self {
+        self.recorder.replace(recorder);
+        self
+    }
+
+    /// Receive events from the replayer instead of websocket
+    #[cfg(any(test, feature = "testing"))]
+    pub fn replay(mut self, replayer: crate::testing::Replayer) -> Self {
+        self.replayer.replace(replayer);
+        self
+    }
+
+    /// Send all api calls to the mock api instead of network
+    #[cfg(any(test, feature = "testing"))]
+    pub fn mock_api(mut self, mock: Arc<crate::testing::MockApi>) -> Self {
+        self.mock_api.replace(mock);
+        self
+    }
+
+    /// Receive events from the stream instead of websocket, bot stops when the stream ends.
+    ///
+    /// Events get sn in order, starting from 1.
+    pub fn event_source<S>(mut self, source: S) -> Self
+    where
+        S: Stream<Item = Event> + Send + 'static,
+    {
+        self.event_source.replace(source.boxed());
+        self
+    }
+
+    /// Build the bot
+    pub fn build(self) -> Result<Bot> {
+        let gateway = match self.gateway_url {
+            Some(url) => Some(
+                url.parse()
+                    .with_context(|_| error::InvalidGatewayURL { url })?,
+            ),
+            None => self.gateway,
+        };
+
+        let mut api_client = match self.proxy {
+            Some(proxy) => api::Client::new_from_bot_token_with_proxy(&self.token, proxy),
+            None => api::Client::new_from_bot_token(&self.token),
+        }
+        .context(error::CallAPIFailed)?;
+
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(mock) = self.mock_api {
+            api_client = api::Client::new_mock(mock);
+        }
+
+        if let Some(limit) = self.send_rate_limit {
+            api_client = api_client.send_rate_limit(limit);
+        }
+
+        if let Some(sink) = self.audit.as_ref() {
+            api_client = api_client.audit(Arc::clone(sink));
+        }
+
+        info!("Crate api and websocket client success");
+
+        let live_config = self
+            .config
+            .map(|config| Arc::new(LiveConfig::new(config, api_client.clone())));
+
+        let mut bot = Bot::with_api_client(api_client);
+        if let Some(config) = live_config {
+            bot.config = config;
+        }
+        bot.name = self.name;
+        bot.compress = self.compress;
+        bot.gateway = gateway;
+        bot.ws_config = self.ws_config;
+        bot.retry = self.retry;
+        bot.event_queue = self.event_queue;
+        bot.session_store = self.session_store;
+        bot.audit = self.audit;
+        if self.dedup_capacity > 0 {
+            bot.dedup = Some(Arc::new(std::sync::Mutex::new(Dedup::new(
+                self.dedup_capacity,
+                self.dedup_ttl,
+            ))));
+        }
+        bot.filters = self.filters;
+        bot.event_log_level = self.event_log_level;
+        bot.event_source = self.event_source.map(std::sync::Mutex::new);
+        #[cfg(feature = "webhook")]
+        {
+            bot.webhook = self.webhook;
+        }
+        #[cfg(any(test, feature = "testing"))]
+        {
+            bot.recorder = self.recorder;
+            bot.replayer = self.replayer;
+        }
+
+        Ok(bot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::new();
+        let delays: Vec<_> = (0..8).map(|n| policy.delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(policy.delay(100), RETRY_DELAY_MAX);
+
+        let policy = RetryPolicy::new()
+            .initial_delay(Duration::from_secs(5))
+            .max_delay(Duration::from_secs(1));
+        assert_eq!(policy.delay(0), Duration::from_secs(5));
+        assert_eq!(policy.delay(3), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy::new()
+            .backoff(FixedBackoff(Duration::from_secs(7)))
+            .reconnect_backoff(ExponentialBackoff::new(
+                Duration::from_secs(1),
+                Duration::from_secs(4),
+            ));
+        assert_eq!(policy.delay(0), Duration::from_secs(7));
+        assert_eq!(policy.delay(10), Duration::from_secs(7));
+        assert_eq!(policy.reconnect_delay(1), Duration::from_secs(2));
+        assert_eq!(policy.reconnect_delay(5), Duration::from_secs(4));
+
+        assert_eq!(RetryPolicy::new().reconnect_delay(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_policy_jitter() {
+        let policy = RetryPolicy::new().jitter(0.5).max_reconnects(2);
+        assert_eq!(policy.get_jitter(), 0.5);
+        assert_eq!(policy.get_max_reconnects(), Some(2));
+        for retry in 0..8 {
+            let delay = policy.delay(retry);
+            let max = RetryPolicy::new().delay(retry);
+            assert!(delay <= max && delay >= max / 2);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_retry_policy_jitter_out_of_range() {
+        let _ = RetryPolicy::new().jitter(1.5);
+    }
+
+    #[test]
+    fn test_builder() {
+        let bot = BotBuilder::new("token")
+            .compress(false)
+            .ignore_bots()
+            .event_log_level(log::LevelFilter::Off)
+            .retry(RetryPolicy::new().max_retries(3))
+            .build()
+            .unwrap();
+
+        assert!(!bot.compress);
+        assert_eq!(bot.filters.len(), 1);
+        assert_eq!(bot.event_log_level, log::LevelFilter::Off);
+        assert_eq!(bot.retry.get_max_retries(), Some(3));
+    }
+
+    #[test]
+    fn test_builder_gateway_url() {
+        let bot = BotBuilder::new("token")
+            .gateway_url("ws://127.0.0.1:7777/gateway?token=x&compress=0")
+            .build()
+            .unwrap();
+        let gateway = bot.gateway.unwrap();
+        assert_eq!(gateway.port, Some(7777));
+        assert!(!gateway.compress);
+
+        let result = BotBuilder::new("token").gateway_url("not a url").build();
+        assert!(matches!(
+            result,
+            Err(crate::Error::InvalidGatewayURL { url, .. }) if url == "not a url"
+        ));
+    }
+}
@@ -0,0 +1,665 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use tokio::sync::{mpsc, Mutex, Notify};
+
+use super::builder::{Backpressure, EventQueue};
+use crate::{
+    context::{BotContext, EventContext},
+    filter::{AsyncFilter, Extract, Extracted},
+    logging::{self, Instrument, Span},
+    subscriber::{Overflow, SubscribeOptions, Subscriber, SubscriberErrorHandler},
+    ws::Event,
+};
+
+/// A registered subscriber, with its filter and options.
+#[derive(Clone)]
+pub(super) struct Subscription {
+    pub(super) filter: Arc<dyn AsyncFilter + 'static>,
+    pub(super) subscriber: Arc<dyn Subscriber + 'static>,
+    /// extracts data for subscriber after filter passed, event is rejected if nothing extracted
+    pub(super) extract: Option<Arc<dyn Extract + 'static>>,
+    pub(super) options: SubscribeOptions,
+    /// event queue to workers, only exists if concurrency is limited and workers started
+    pub(super) queue: Option<mpsc::Sender<Queued>>,
+}
+
+/// Event queued to workers, with its span and in flight guard, so queued events are in flight.
+pub(super) type Queued = (EventContext, Span, InFlightGuard);
+
+impl Subscription {
+    /// Check the event by filter, then extract data from it.
+    ///
+    /// Returns None if event is rejected, otherwise the extracted data if subscription has an
+    /// extractor.
+    pub(super) async fn accept(
+        &self,
+        ctx: &BotContext,
+        event: Arc<Event>,
+    ) -> Option<Option<Extracted>> {
+        if !self.filter.check(ctx, Arc::clone(&event)).await {
+            return None;
+        }
+        match &self.extract {
+            Some(extract) => extract.extract(&event).map(Some),
+            None => Some(None),
+        }
+    }
+}
+
+/// Registered subscriptions, sorted by priority.
+#[derive(Default)]
+pub(super) struct Subscriptions {
+    inner: RwLock<Vec<Subscription>>,
+}
+
+impl Subscriptions {
+    /// Insert after all subscriptions with higher or same priority.
+    pub(super) fn insert(&self, subscription: Subscription) {
+        let mut inner = self.inner.write().unwrap();
+        let priority = subscription.options.get_priority();
+        let index = inner.partition_point(|s| s.options.get_priority() >= priority);
+        inner.insert(index, subscription);
+    }
+
+    pub(super) fn snapshot(&self) -> Vec<Subscription> {
+        self.inner.read().unwrap().clone()
+    }
+
+    pub(super) fn take(&self) -> Vec<Subscription> {
+        std::mem::take(&mut *self.inner.write().unwrap())
+    }
+
+    /// Remove all subscriptions whose subscriber has specified name, return removed count.
+    pub(super) fn remove(&self, name: &str) -> usize {
+        let mut inner = self.inner.write().unwrap();
+        let before = inner.len();
+        inner.retain(|s| s.subscriber.name() != name);
+        before - inner.len()
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.inner.read().unwrap().len()
+    }
+}
+
+/// Counter of running dispatch and subscriber tasks.
+#[derive(Default)]
+pub(super) struct InFlight {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl InFlight {
+    fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(Arc::clone(self))
+    }
+
+    /// Wait until no task is running.
+    pub(super) async fn wait_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+pub(super) struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Subscriptions and the event to dispatch, with the span of the event.
+type Job = (Vec<Subscription>, Arc<Event>, Span);
+
+#[derive(Default)]
+struct QueueState {
+    running: usize,
+    pending: VecDeque<Job>,
+}
+
+/// Queue of events waiting to be dispatched, when too many events are dispatching.
+#[derive(Default)]
+pub(super) struct DispatchQueue {
+    config: EventQueue,
+    state: std::sync::Mutex<QueueState>,
+    space: Notify,
+}
+
+impl DispatchQueue {
+    pub(super) fn new(config: EventQueue) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Number of events waiting to be dispatched.
+    pub(super) fn len(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    /// Returns the job back if it should be dispatched now, or when the queue is full.
+    fn push(&self, job: Job) -> std::result::Result<Option<Job>, Job> {
+        let mut state = self.state.lock().unwrap();
+
+        if self
+            .config
+            .get_max_in_flight()
+            .is_none_or(|max| state.running < max)
+        {
+            state.running += 1;
+            return Ok(Some(job));
+        }
+
+        if state.pending.len() >= self.config.get_capacity() {
+            if self.config.get_backpressure() != Backpressure::DropOldest {
+                return Err(job);
+            }
+            state.pending.pop_front();
+            warn!("Event queue is full, oldest event dropped");
+        }
+
+        state.pending.push_back(job);
+        debug!("Event queued, queue depth {}", state.pending.len());
+
+        Ok(None)
+    }
+
+    /// Take next waiting job for a finished dispatch task, or mark the task as stopped.
+    fn next(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.pending.pop_front();
+        match job {
+            Some(_) => self.space.notify_one(),
+            None => state.running -= 1,
+        }
+        job
+    }
+}
+
+/// Shared settings used when dispatching events.
+#[derive(Clone)]
+pub(super) struct Dispatcher {
+    pub(super) ctx: BotContext,
+    pub(super) filter_timeout: Duration,
+    pub(super) error_handler: Option<Arc<dyn SubscriberErrorHandler + 'static>>,
+    pub(super) in_flight: Arc<InFlight>,
+    pub(super) queue: Arc<DispatchQueue>,
+}
+
+impl Dispatcher {
+    /// Spawn a task to dispatch the event, which is tracked as in flight.
+    ///
+    /// If too many events are dispatching, the event is queued, and the [Backpressure] policy
+    /// applies when the queue is full. Returns the event back if it's rejected.
+    pub(super) async fn spawn_dispatch(
+        &self,
+        subscriptions: Vec<Subscription>,
+        event: Arc<Event>,
+    ) -> std::result::Result<(), Arc<Event>> {
+        let mut job = (subscriptions, event, Span::current());
+        let job = loop {
+            match self.queue.push(job) {
+                Ok(Some(job)) => break job,
+                Ok(None) => return Ok(()),
+                Err(rejected) => match self.queue.config.get_backpressure() {
+                    Backpressure::Reject => return Err(rejected.1),
+                    _ => {
+                        job = rejected;
+                        self.queue.space.notified().await;
+                    }
+                },
+            }
+        };
+
+        let guard = self.in_flight.enter();
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            let mut job = Some(job);
+            while let Some((subscriptions, event, span)) = job {
+                dispatcher
+                    .clone()
+                    .dispatch(subscriptions, event)
+                    .instrument(span)
+                    .await;
+                job = dispatcher.queue.next();
+            }
+            drop(guard);
+        });
+
+        Ok(())
+    }
+
+    /// Check filters of all subscriptions concurrently, then start accepted subscribers in order,
+    /// until a subscriber which consumes events accepted it. Ordered subscribers are run to
+    /// finish before starting next one.
+    ///
+    /// `subscriptions` should be sorted by priority already.
+    pub(super) async fn dispatch(self, subscriptions: Vec<Subscription>, event: Arc<Event>) {
+        let checks = subscriptions.iter().map(|s| {
+            tokio::time::timeout(self.filter_timeout, s.accept(&self.ctx, Arc::clone(&event)))
+        });
+        let results = futures_util::future::join_all(checks).await;
+
+        for (subscription, result) in subscriptions.into_iter().zip(results) {
+            let name = subscription.subscriber.name();
+            match result {
+                Ok(Some(extracted)) => {
+                    debug!("New event is accepted by subscriber {}", name);
+
+                    let consume = subscription.options.is_consume();
+                    let ctx = EventContext::new(self.ctx.clone(), Arc::clone(&event))
+                        .with_extracted(extracted);
+                    self.enqueue(subscription, ctx).await;
+
+                    if consume {
+                        debug!("Event is consumed by subscriber {}", name);
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => warn!("Filter of subscriber {} timeout", name),
+            }
+        }
+    }
+
+    /// Load a new subscription and start its workers.
+    ///
+    /// Filter and subscriber of the subscription must not be shared yet.
+    pub(super) async fn load(&self, subscription: &mut Subscription) {
+        Arc::get_mut(&mut subscription.filter)
+            .expect("filter is not shared before loaded")
+            .on_loaded(self.ctx.me());
+        Arc::get_mut(&mut subscription.subscriber)
+            .expect("subscriber is not shared before loaded")
+            .on_loaded(self.ctx.clone())
+            .await;
+        self.start_workers(subscription);
+
+        info!("Subscriber {} loaded", subscription.subscriber.name());
+    }
+
+    /// Start workers for subscription whose concurrency is limited.
+    pub(super) fn start_workers(&self, subscription: &mut Subscription) {
+        let concurrency = match subscription.options.get_concurrency() {
+            Some(c) => *c,
+            None => return,
+        };
+
+        let (sender, receiver) = mpsc::channel(concurrency.get_queue_size());
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..concurrency.get_max_in_flight() {
+            let receiver = Arc::clone(&receiver);
+            let dispatcher = self.clone();
+            let subscription = subscription.clone();
+
+            tokio::spawn(async move {
+                loop {
+                    let event = receiver.lock().await.recv().await;
+                    match event {
+                        Some((ctx, span, _guard)) => {
+                            dispatcher
+                                .clone()
+                                .run(subscription.clone(), ctx)
+                                .instrument(span)
+                                .await
+                        }
+                        None => break,
+                    }
+                }
+            });
+        }
+
+        subscription.queue.replace(sender);
+    }
+
+    async fn enqueue(&self, subscription: Subscription, ctx: EventContext) {
+        let queue = match &subscription.queue {
+            Some(queue) => queue,
+            None if subscription.options.is_ordered() => {
+                self.clone().run(subscription, ctx).await;
+                return;
+            }
+            None => {
+                let guard = self.in_flight.enter();
+                let dispatcher = self.clone();
+                tokio::spawn(
+                    async move {
+                        dispatcher.run(subscription, ctx).await;
+                        drop(guard);
+                    }
+                    .in_current_span(),
+                );
+                return;
+            }
+        };
+
+        let overflow = subscription
+            .options
+            .get_concurrency()
+            .map(|c| c.get_overflow())
+            .unwrap_or_default();
+
+        let queued = (ctx, Span::current(), self.in_flight.enter());
+        let full = match overflow {
+            Overflow::DropNewest => queue.try_send(queued).is_err(),
+            Overflow::Wait => queue.send(queued).await.is_err(),
+        };
+
+        if full {
+            warn!(
+                "Event queue of subscriber {} is full, event dropped",
+                subscription.subscriber.name()
+            );
+        }
+    }
+
+    async fn run(self, subscription: Subscription, ctx: EventContext) {
+        let span = logging::subscriber_span(&subscription.subscriber.name());
+        self.run_subscriber(subscription, ctx)
+            .instrument(span)
+            .await
+    }
+
+    async fn run_subscriber(self, subscription: Subscription, ctx: EventContext) {
+        let subscriber = subscription.subscriber;
+
+        if let Err(err) = Arc::clone(&subscriber).on_event(ctx.clone()).await {
+            warn!("Subscriber {} failed: {}", subscriber.name(), err);
+            if let Some(handler) = self.error_handler {
+                handler.on_error(subscriber.name(), ctx, err).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filter;
+
+    fn dispatcher() -> Dispatcher {
+        Dispatcher {
+            ctx: BotContext::mock(),
+            filter_timeout: Duration::from_secs(1),
+            error_handler: None,
+            in_flight: Arc::default(),
+            queue: Arc::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_consume() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let subscription = |id: u32, filter: Arc<dyn AsyncFilter>, consume: bool| {
+            let tx = tx.clone();
+            let options = SubscribeOptions::new();
+            Subscription {
+                filter,
+                subscriber: Arc::new(move |_: EventContext| {
+                    let tx = tx.clone();
+                    async move { tx.send(id).unwrap() }
+                }),
+                options: if consume { options.consume() } else { options },
+                extract: None,
+                queue: None,
+            }
+        };
+
+        let subscriptions = vec![
+            subscription(1, Arc::new(filter::all()), false),
+            subscription(2, Arc::new(filter::none()), true),
+            subscription(3, Arc::new(filter::all()), true),
+            subscription(4, Arc::new(filter::all()), false),
+        ];
+        drop(tx);
+
+        dispatcher()
+            .dispatch(
+                subscriptions,
+                Arc::new(Event::ChannelMessage(Default::default())),
+            )
+            .await;
+
+        let mut received = vec![];
+        while let Some(id) = rx.recv().await {
+            received.push(id);
+        }
+        received.sort_unstable();
+
+        assert_eq!(received, [1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_ordered() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let subscription = |id: u32, options: SubscribeOptions| {
+            let tx = tx.clone();
+            Subscription {
+                filter: Arc::new(filter::all()),
+                subscriber: Arc::new(move |_: EventContext| {
+                    let tx = tx.clone();
+                    async move {
+                        if id == 1 {
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                        }
+                        tx.send(id).unwrap()
+                    }
+                }),
+                options,
+                extract: None,
+                queue: None,
+            }
+        };
+
+        for (options, expected) in [
+            (SubscribeOptions::new(), [2, 1]),
+            (SubscribeOptions::new().ordered(), [1, 2]),
+        ] {
+            let subscriptions = vec![
+                subscription(1, options),
+                subscription(2, SubscribeOptions::new()),
+            ];
+            dispatcher()
+                .dispatch(
+                    subscriptions,
+                    Arc::new(Event::ChannelMessage(Default::default())),
+                )
+                .await;
+
+            let received = [rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_serialize() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (gate_tx, gate_rx) = tokio::sync::watch::channel(false);
+
+        let mut subscription = Subscription {
+            filter: Arc::new(filter::all()),
+            subscriber: Arc::new(move |ctx: EventContext| {
+                let tx = tx.clone();
+                let mut gate = gate_rx.clone();
+                async move {
+                    gate.wait_for(|open| *open).await.unwrap();
+                    tx.send(ctx.content().to_string()).unwrap();
+                }
+            }),
+            options: SubscribeOptions::new()
+                .concurrency(crate::Concurrency::serialize().queue_size(2)),
+            extract: None,
+            queue: None,
+        };
+
+        let dispatcher = dispatcher();
+        dispatcher.start_workers(&mut subscription);
+
+        for content in ["1", "2", "3", "4", "5"] {
+            let event = Event::ChannelMessage(crate::ws::event::EventBody {
+                content: content.to_string(),
+                ..Default::default()
+            });
+            dispatcher
+                .clone()
+                .dispatch(vec![subscription.clone()], Arc::new(event))
+                .await;
+            tokio::task::yield_now().await;
+        }
+
+        gate_tx.send(true).unwrap();
+        drop(subscription);
+
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(rx.recv().await.unwrap());
+        }
+
+        assert_eq!(received, ["1", "2", "3"]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_in_flight() {
+        let (gate_tx, gate_rx) = tokio::sync::watch::channel(false);
+
+        let subscription = Subscription {
+            filter: Arc::new(filter::all()),
+            subscriber: Arc::new(move |_: EventContext| {
+                let mut gate = gate_rx.clone();
+                async move {
+                    gate.wait_for(|open| *open).await.unwrap();
+                }
+            }),
+            options: SubscribeOptions::new(),
+            extract: None,
+            queue: None,
+        };
+
+        let dispatcher = dispatcher();
+        dispatcher.in_flight.wait_idle().await;
+
+        dispatcher
+            .spawn_dispatch(
+                vec![subscription],
+                Arc::new(Event::ChannelMessage(Default::default())),
+            )
+            .await
+            .unwrap();
+
+        let wait =
+            tokio::time::timeout(Duration::from_millis(50), dispatcher.in_flight.wait_idle());
+        assert!(wait.await.is_err());
+
+        gate_tx.send(true).unwrap();
+        dispatcher.in_flight.wait_idle().await;
+    }
+
+    #[tokio::test]
+    async fn test_queued_events_in_flight() {
+        let (gate_tx, gate_rx) = tokio::sync::watch::channel(false);
+
+        let mut subscription = Subscription {
+            filter: Arc::new(filter::all()),
+            subscriber: Arc::new(move |_: EventContext| {
+                let mut gate = gate_rx.clone();
+                async move {
+                    gate.wait_for(|open| *open).await.unwrap();
+                }
+            }),
+            options: SubscribeOptions::new().concurrency(crate::Concurrency::serialize()),
+            extract: None,
+            queue: None,
+        };
+
+        let dispatcher = dispatcher();
+        dispatcher.start_workers(&mut subscription);
+
+        for _ in 0..2 {
+            dispatcher
+                .clone()
+                .dispatch(
+                    vec![subscription.clone()],
+                    Arc::new(Event::ChannelMessage(Default::default())),
+                )
+                .await;
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(dispatcher.in_flight.count.load(Ordering::SeqCst), 2);
+
+        gate_tx.send(true).unwrap();
+        dispatcher.in_flight.wait_idle().await;
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_queue_backpressure() {
+        let job = || {
+            let event = Arc::new(Event::ChannelMessage(Default::default()));
+            (vec![], event, Span::current())
+        };
+        let config = EventQueue::new().max_in_flight(1).capacity(1);
+
+        let queue = DispatchQueue::new(config.backpressure(Backpressure::DropOldest));
+        assert!(matches!(queue.push(job()), Ok(Some(_))));
+        assert!(matches!(queue.push(job()), Ok(None)));
+        assert!(matches!(queue.push(job()), Ok(None)));
+        assert_eq!(queue.len(), 1);
+        assert!(queue.next().is_some());
+        assert!(queue.next().is_none());
+        assert!(matches!(queue.push(job()), Ok(Some(_))));
+
+        let mut dispatcher = dispatcher();
+        dispatcher.queue = Arc::new(DispatchQueue::new(
+            config.backpressure(Backpressure::Reject),
+        ));
+        let (gate_tx, gate_rx) = tokio::sync::watch::channel(false);
+        let subscription = Subscription {
+            filter: Arc::new(filter::all()),
+            subscriber: Arc::new(move |_: EventContext| {
+                let mut gate = gate_rx.clone();
+                async move {
+                    gate.wait_for(|open| *open).await.unwrap();
+                }
+            }),
+            options: SubscribeOptions::new(),
+            extract: None,
+            queue: None,
+        };
+
+        for _ in 0..2 {
+            let (_, event, _) = job();
+            let result = dispatcher
+                .spawn_dispatch(vec![subscription.clone()], event)
+                .await;
+            assert!(result.is_ok());
+        }
+        let (subscriptions, event, _) = job();
+        let result = dispatcher.spawn_dispatch(subscriptions, event).await;
+        assert!(result.is_err());
+        assert_eq!(dispatcher.queue.len(), 1);
+
+        gate_tx.send(true).unwrap();
+        dispatcher.in_flight.wait_idle().await;
+        assert_eq!(dispatcher.queue.len(), 0);
+    }
+}
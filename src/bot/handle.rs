@@ -0,0 +1,262 @@
+use std::{
+    any::Any,
+    borrow::Cow,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use tokio::{
+    sync::{watch, Notify},
+    task::JoinHandle,
+};
+
+use super::dispatch::{Dispatcher, Subscription, Subscriptions};
+use crate::{
+    api::types::GatewayResumeArguments,
+    config::{Config, LiveConfig},
+    error::{self, Error},
+    filter::AsyncFilter,
+    subscriber::{SubscribeOptions, Subscriber},
+    ws::client::{EventStream, Heartbeat, Latency},
+    Result,
+};
+
+/// Connection status of a bot, shared with its handles.
+#[derive(Debug, Default)]
+pub(super) struct Status {
+    connected: AtomicBool,
+    session: Mutex<Option<watch::Receiver<GatewayResumeArguments>>>,
+    /// session of all connections, forwarded from the current one
+    resume: watch::Sender<GatewayResumeArguments>,
+    forwarder: Mutex<Option<JoinHandle<()>>>,
+    heartbeat: Mutex<Option<Arc<Heartbeat>>>,
+    pub(super) stop: Notify,
+    /// result of the bot started by [Bot::start](crate::Bot::start), None before it stops
+    done: OnceLock<watch::Receiver<Option<Joined>>>,
+}
+
+impl Status {
+    /// Bot is receiving events, from the websocket stream if any
+    pub(super) fn connected(&self, stream: Option<&EventStream>) {
+        *self.session.lock().unwrap() = stream.map(EventStream::resume_watcher);
+        let forwarder = stream.map(|stream| self.forward(stream.resume_watcher()));
+        if let Some(old) = std::mem::replace(&mut *self.forwarder.lock().unwrap(), forwarder) {
+            old.abort();
+        }
+        *self.heartbeat.lock().unwrap() = stream.map(EventStream::heartbeat);
+        self.connected.store(true, Ordering::Release);
+    }
+
+    pub(super) fn disconnected(&self) {
+        self.connected.store(false, Ordering::Release);
+    }
+
+    pub(super) fn resume_watcher(&self) -> watch::Receiver<GatewayResumeArguments> {
+        self.resume.subscribe()
+    }
+
+    /// Spawn a task which copies every change of a connection's session to the bot's
+    fn forward(&self, mut session: watch::Receiver<GatewayResumeArguments>) -> JoinHandle<()> {
+        let resume = self.resume.clone();
+        tokio::spawn(async move {
+            loop {
+                resume.send_replace(session.borrow_and_update().clone());
+                if session.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+/// Handle of a bot, can be used to manage subscribers, query status and stop the bot
+/// after it start running.
+///
+/// Get it by [Bot::handle](crate::Bot::handle) before calling [Bot::run](crate::Bot::run),
+/// or run the bot in background by [Bot::start](crate::Bot::start).
+#[derive(Clone)]
+pub struct BotHandle {
+    subscribers: Arc<Subscriptions>,
+    dispatcher: Arc<OnceLock<Dispatcher>>,
+    status: Arc<Status>,
+    config: Arc<LiveConfig>,
+}
+
+/// Result of a stopped bot, shared by all handles.
+type Joined = std::result::Result<(), Arc<Error>>;
+
+impl Debug for BotHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BotHandle")
+            .field("subscribers", &self.subscribers.len())
+            .field("loaded", &self.dispatcher.get().is_some())
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl BotHandle {
+    pub(super) fn new(
+        subscribers: Arc<Subscriptions>,
+        dispatcher: Arc<OnceLock<Dispatcher>>,
+        status: Arc<Status>,
+        config: Arc<LiveConfig>,
+    ) -> Self {
+        Self {
+            subscribers,
+            dispatcher,
+            status,
+            config,
+        }
+    }
+
+    /// Track the task running the bot, its result is shared to all [BotHandle::join] callers.
+    pub(super) fn set_task(&self, task: JoinHandle<Result<()>>) {
+        let (sender, receiver) = watch::channel(None);
+        tokio::spawn(async move {
+            let result = match task.await {
+                Ok(result) => result,
+                Err(err) if err.is_panic() => error::BotPanicked {
+                    message: panic_message(err.into_panic()),
+                }
+                .fail(),
+                Err(_) => error::BotCancelled.fail(),
+            };
+            sender.send_replace(Some(result.map_err(Arc::new)));
+        });
+        self.status
+            .done
+            .set(receiver)
+            .expect("bot of a handle is started only once");
+    }
+
+    /// Add new subscriber with a event filter and options.
+    ///
+    /// If bot is already running, the subscriber is loaded before receiving events.
+    pub async fn add_subscriber<F, S>(&self, filter: F, subscriber: S, options: SubscribeOptions)
+    where
+        F: AsyncFilter + 'static,
+        S: Subscriber + 'static,
+    {
+        let mut subscription = Subscription {
+            filter: Arc::new(filter),
+            subscriber: Arc::new(subscriber),
+            options,
+            extract: None,
+            queue: None,
+        };
+
+        if let Some(dispatcher) = self.dispatcher.get() {
+            dispatcher.load(&mut subscription).await;
+        }
+
+        self.subscribers.insert(subscription);
+    }
+
+    /// Remove all subscribers with specified name, return removed count.
+    ///
+    /// Events already accepted by them will still be processed.
+    pub fn remove_subscriber(&self, name: &str) -> usize {
+        let count = self.subscribers.remove(name);
+        info!("{} subscriber(s) named {} removed", count, name);
+        count
+    }
+
+    /// Names of all subscribers, in dispatch order.
+    pub fn subscriber_names(&self) -> Vec<Cow<'static, str>> {
+        self.subscribers
+            .snapshot()
+            .iter()
+            .map(|s| s.subscriber.name())
+            .collect()
+    }
+
+    /// Check if bot is connected and receiving events.
+    pub fn is_connected(&self) -> bool {
+        self.status.connected.load(Ordering::Acquire)
+    }
+
+    /// Current websocket session id and sn of last received event.
+    ///
+    /// Returns `None` if bot never connected by websocket.
+    pub fn resume_arguments(&self) -> Option<GatewayResumeArguments> {
+        self.status
+            .session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|session| session.borrow().clone())
+    }
+
+    /// Watch websocket session id and sn of last received event, across reconnects.
+    ///
+    /// Notified whenever any of them changes, so progress can be persisted or monitored
+    /// without waiting for the connection to break. Holds default value before connected.
+    pub fn resume_watcher(&self) -> watch::Receiver<GatewayResumeArguments> {
+        self.status.resume_watcher()
+    }
+
+    /// Heartbeat latency to websocket gateway.
+    ///
+    /// Returns `None` if bot is not connected by websocket, or no pong received yet.
+    pub fn latency(&self) -> Option<Latency> {
+        self.status
+            .heartbeat
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|heartbeat| heartbeat.latency())
+    }
+
+    /// Number of events waiting to be dispatched, see [EventQueue](crate::EventQueue).
+    pub fn queue_depth(&self) -> usize {
+        self.dispatcher.get().map_or(0, |d| d.queue.len())
+    }
+
+    /// Current config of the bot
+    pub fn config(&self) -> Arc<Config> {
+        self.config.get()
+    }
+
+    /// Apply new config without reconnecting, see [Config] for what can be changed.
+    pub fn reload_config(&self, config: Config) {
+        self.config.reload(config);
+    }
+
+    /// Request the bot to shutdown gracefully, see [Bot::run_until](crate::Bot::run_until).
+    pub fn stop(&self) {
+        self.status.stop.notify_one();
+    }
+
+    /// Wait the bot started by [Bot::start](crate::Bot::start) to stop, and get its result.
+    ///
+    /// Can be called by many handles, or many times, every caller gets the same result.
+    /// If the bot panicked or its task is cancelled, [Error::BotPanicked] or
+    /// [Error::BotCancelled] is returned.
+    ///
+    /// Returns `Ok(())` immediately if the bot is not started by [Bot::start](crate::Bot::start).
+    pub async fn join(&self) -> std::result::Result<(), Arc<Error>> {
+        let mut done = match self.status.done.get() {
+            Some(done) => done.clone(),
+            None => return Ok(()),
+        };
+        let result = match done.wait_for(Option::is_some).await {
+            Ok(result) => result.clone(),
+            Err(_) => None,
+        };
+        result.unwrap_or_else(|| Err(Arc::new(Error::BotCancelled)))
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    }
+}
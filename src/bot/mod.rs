@@ -0,0 +1,1629 @@
+mod builder;
+mod dispatch;
+mod handle;
+mod set;
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use futures_util::{stream::BoxStream, StreamExt};
+use snafu::prelude::*;
+
+use crate::{
+    api::{
+        self,
+        types::{GatewayResumeArguments, GatewayURLInfo},
+    },
+    audit::{AuditRecord, AuditSink, EventRecord},
+    button::ButtonRegistry,
+    command::{self, CommandRegistry},
+    config::{Config, LiveConfig},
+    context::{BotContext, EventContext, TypeMap},
+    dedup::Dedup,
+    error,
+    filter::{self, AsyncFilter, Filter, FilterMap},
+    lifecycle::{Lifecycle, LifecycleHandler},
+    logging::{self, Instrument},
+    plugin::Plugin,
+    schedule::{self, Schedule, Task},
+    session::{self, SessionStore},
+    subscriber::{Mapped, SubscribeOptions, Subscriber, SubscriberErrorHandler},
+    ws::{
+        self,
+        event::{
+            EventBody, EventData, ExitedGuildBody, JoinedGuildBody, MessageExtra, ReactionBody,
+            SystemEvent,
+        },
+        Event,
+    },
+    Result,
+};
+
+#[cfg(feature = "webhook")]
+use crate::webhook;
+
+use dispatch::{DispatchQueue, Dispatcher, Subscription, Subscriptions};
+
+pub use builder::{Backpressure, BotBuilder, EventQueue, RetryPolicy};
+pub use handle::BotHandle;
+pub use set::BotSet;
+
+const DEFAULT_FILTER_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the websocket connection closing gracefully when shutdown
+const STREAM_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Burz instance
+pub struct Bot {
+    name: Option<String>,
+    #[allow(dead_code)]
+    api_client: api::Client,
+    compress: bool,
+    /// gateway used instead of getting from api
+    gateway: Option<GatewayURLInfo>,
+    ws_config: ws::ClientConfig,
+    retry: RetryPolicy,
+    event_queue: EventQueue,
+    filters: Vec<Box<dyn Filter + 'static>>,
+    event_log_level: log::LevelFilter,
+    session_store: Option<Arc<dyn SessionStore + 'static>>,
+    session_saver: Option<tokio::task::JoinHandle<()>>,
+    dedup: Option<Arc<std::sync::Mutex<Dedup>>>,
+    audit: Option<Arc<dyn AuditSink + 'static>>,
+    #[cfg(feature = "webhook")]
+    webhook: Option<webhook::WebhookConfig>,
+    #[cfg(any(test, feature = "testing"))]
+    recorder: Option<crate::testing::Recorder>,
+    #[cfg(any(test, feature = "testing"))]
+    replayer: Option<crate::testing::Replayer>,
+    // Mutex makes Bot Sync, it is never contended
+    event_source: Option<std::sync::Mutex<BoxStream<'static, Event>>>,
+    dispatcher: Arc<OnceLock<Dispatcher>>,
+    status: Arc<handle::Status>,
+    config: Arc<LiveConfig>,
+    filter_timeout: Duration,
+    shutdown_timeout: Duration,
+    error_handler: Option<Arc<dyn SubscriberErrorHandler + 'static>>,
+    lifecycle_handlers: Vec<Arc<dyn LifecycleHandler + 'static>>,
+    data: TypeMap,
+    plugins: Vec<Box<dyn Plugin + 'static>>,
+    subscribers: Arc<Subscriptions>,
+    schedules: Vec<(Box<dyn Schedule + 'static>, Arc<dyn Task + 'static>)>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl Debug for Bot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bot")
+            .field("name", &self.name)
+            .field("api_client", &self.api_client)
+            .field("ctx", &self.ctx())
+            .field("compress", &self.compress)
+            .field("gateway", &self.gateway)
+            .field("ws_config", &self.ws_config)
+            .field("retry", &self.retry)
+            .field("event_queue", &self.event_queue)
+            .field("filters", &self.filters.len())
+            .field("event_log_level", &self.event_log_level)
+            .field("session_store", &self.session_store)
+            .field("dedup", &self.dedup.is_some())
+            .field("audit", &self.audit)
+            .field("config", &self.config.get())
+            .field("event_source", &self.event_source.is_some())
+            .field("filter_timeout", &self.filter_timeout)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("error_handler", &self.error_handler.is_some())
+            .field("lifecycle_handlers", &self.lifecycle_handlers.len())
+            .field("data", &self.data)
+            .field(
+                "plugins",
+                &self.plugins.iter().map(|p| p.name()).collect::<Vec<_>>(),
+            )
+            .field("subscribers", &self.subscribers.len())
+            .field("schedules", &(self.schedules.len() + self.tasks.len()))
+            .finish()
+    }
+}
+
+impl Bot {
+    /// Create new framework instance using bot token, with default configs
+    pub fn new<S: AsRef<str> + ?Sized>(token: &S) -> Result<Self> {
+        Self::builder(token.as_ref()).build()
+    }
+
+    /// Create new framework instance using token and other settings in config
+    pub fn from_config(config: Config) -> Result<Self> {
+        Self::builder(config.token.clone()).config(config).build()
+    }
+
+    /// Create a builder to config the framework instance
+    pub fn builder<S: Into<String>>(token: S) -> BotBuilder {
+        BotBuilder::new(token)
+    }
+
+    fn with_api_client(api_client: api::Client) -> Self {
+        Self {
+            name: None,
+            api_client,
+            compress: true,
+            gateway: None,
+            ws_config: ws::ClientConfig::default(),
+            retry: RetryPolicy::default(),
+            event_queue: EventQueue::default(),
+            filters: vec![],
+            event_log_level: log::LevelFilter::Info,
+            session_store: None,
+            session_saver: None,
+            dedup: None,
+            audit: None,
+            #[cfg(feature = "webhook")]
+            webhook: None,
+            #[cfg(any(test, feature = "testing"))]
+            recorder: None,
+            #[cfg(any(test, feature = "testing"))]
+            replayer: None,
+            event_source: None,
+            dispatcher: Arc::default(),
+            status: Arc::default(),
+            config: Arc::default(),
+            filter_timeout: DEFAULT_FILTER_TIMEOUT,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            error_handler: None,
+            lifecycle_handlers: vec![],
+            data: TypeMap::default(),
+            plugins: vec![],
+            subscribers: Arc::default(),
+            schedules: vec![],
+            tasks: vec![],
+        }
+    }
+
+    async fn fetch_new_gateway(&self) -> Result<GatewayURLInfo> {
+        if let Some(gateway) = self.gateway.as_ref() {
+            return Ok(gateway.clone());
+        }
+
+        let gateway_str = self
+            .api_client
+            .gateway_url_with_compress(self.compress)
+            .await
+            .context(error::CallAPIFailed)?;
+
+        let mut gateway: GatewayURLInfo = gateway_str
+            .parse()
+            .with_context(|_| error::InvalidGatewayURL { url: gateway_str })?;
+        gateway.compress = self.compress;
+        Ok(gateway)
+    }
+
+    /// Set name of the bot, which can be get by [BotContext::name]
+    pub fn name<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.name.replace(name.into());
+        self
+    }
+
+    /// Add new subscriber with a event filter, sync [Filter](crate::Filter)s can be used directly
+    pub fn subscribe<F, S>(&mut self, filter: F, subscriber: S) -> &mut Self
+    where
+        F: AsyncFilter + 'static,
+        S: Subscriber + 'static,
+    {
+        self.subscribe_with(filter, subscriber, SubscribeOptions::default())
+    }
+
+    /// Add new subscriber with a event filter and options
+    pub fn subscribe_with<F, S>(
+        &mut self,
+        filter: F,
+        subscriber: S,
+        options: SubscribeOptions,
+    ) -> &mut Self
+    where
+        F: AsyncFilter + 'static,
+        S: Subscriber + 'static,
+    {
+        self.subscribers.insert(Subscription {
+            filter: Arc::new(filter),
+            subscriber: Arc::new(subscriber),
+            options,
+            extract: None,
+            queue: None,
+        });
+        self
+    }
+
+    /// Add new handler with a [FilterMap], the handler receives the event and extracted data
+    pub fn subscribe_map<M, H, Fut>(&mut self, filter: M, handler: H) -> &mut Self
+    where
+        M: FilterMap + 'static,
+        H: Fn(EventContext, M::Output) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribers.insert(Subscription {
+            filter: Arc::new(filter::all()),
+            subscriber: Arc::new(Mapped::<M, H>::new(handler)),
+            options: SubscribeOptions::default(),
+            extract: Some(Arc::new(filter)),
+            queue: None,
+        });
+        self
+    }
+
+    /// Add new handler for channel and private messages
+    pub fn on_message<H, Fut>(&mut self, handler: H) -> &mut Self
+    where
+        H: Fn(EventContext, EventBody<MessageExtra>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_map(|event: &Event| event.as_message().cloned(), handler)
+    }
+
+    /// Add new handler for users joining a guild
+    pub fn on_member_join<H, Fut>(&mut self, handler: H) -> &mut Self
+    where
+        H: Fn(EventContext, JoinedGuildBody) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_map(
+            |event: &Event| match event.as_system()? {
+                SystemEvent::JoinedGuild(body) => Some(body.clone()),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add new handler for users exiting a guild
+    pub fn on_member_exit<H, Fut>(&mut self, handler: H) -> &mut Self
+    where
+        H: Fn(EventContext, ExitedGuildBody) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_map(
+            |event: &Event| match event.as_system()? {
+                SystemEvent::ExitedGuild(body) => Some(body.clone()),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add new handler for reactions added to channel messages
+    pub fn on_reaction_add<H, Fut>(&mut self, handler: H) -> &mut Self
+    where
+        H: Fn(EventContext, ReactionBody) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_map(
+            |event: &Event| match event.as_system()? {
+                SystemEvent::AddedReaction(body) => Some(body.clone()),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add new handler for reactions removed from channel messages
+    pub fn on_reaction_remove<H, Fut>(&mut self, handler: H) -> &mut Self
+    where
+        H: Fn(EventContext, ReactionBody) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.subscribe_map(
+            |event: &Event| match event.as_system()? {
+                SystemEvent::DeletedReaction(body) => Some(body.clone()),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add a task which runs by the schedule after bot loaded, and stops when bot shutdown
+    pub fn schedule<S, T>(&mut self, schedule: S, task: T) -> &mut Self
+    where
+        S: Schedule + 'static,
+        T: Task + 'static,
+    {
+        self.schedules.push((Box::new(schedule), Arc::new(task)));
+        self
+    }
+
+    /// Set max time a filter can spend on checking a event, default is 5 seconds.
+    ///
+    /// Event will be treated as rejected by the filter when timeout.
+    pub fn filter_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.filter_timeout = timeout;
+        self
+    }
+
+    /// Set max time to wait running subscribers when shutdown, default is 10 seconds.
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Add a button registry to dispatch card button clicks
+    pub fn buttons(&mut self, registry: ButtonRegistry) -> &mut Self {
+        self.subscribe(ButtonRegistry::is_button_click, registry)
+    }
+
+    /// Add a command registry to dispatch commands
+    ///
+    /// If prefixes are set in [Config], they are used instead of prefixes of the registry.
+    pub fn commands(&mut self, mut registry: CommandRegistry) -> &mut Self {
+        if let Some(prefixes) = self.config.prefixes() {
+            registry.share_prefixes(prefixes);
+        }
+        let mounted = command::Mounted::new(registry);
+        self.subscribe(mounted.clone(), mounted)
+    }
+
+    /// Set handler of errors returned by subscribers, errors are only logged if not set
+    pub fn on_subscriber_error<H>(&mut self, handler: H) -> &mut Self
+    where
+        H: SubscriberErrorHandler + 'static,
+    {
+        self.error_handler.replace(Arc::new(handler));
+        self
+    }
+
+    /// Add handler of connection lifecycle notifications, like [Lifecycle::Ready].
+    ///
+    /// Handlers are spawned, so they do not block receiving events.
+    pub fn on_lifecycle<H>(&mut self, handler: H) -> &mut Self
+    where
+        H: LifecycleHandler + 'static,
+    {
+        self.lifecycle_handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Add shared data, which can be get from [BotContext::data] by its type.
+    ///
+    /// Data with same type will be replaced.
+    pub fn data<T: Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.data.insert(value);
+        self
+    }
+
+    /// Add a plugin, its subscribers, commands and data are registered immediately
+    ///
+    /// Plugins not enabled in [Config] are ignored.
+    pub fn plugin<P: Plugin + 'static>(&mut self, mut plugin: P) -> &mut Self {
+        if !self.config.get().is_plugin_enabled(&plugin.name()) {
+            info!("Plugin {} is disabled by config", plugin.name());
+            return self;
+        }
+
+        plugin.register(self);
+        info!("Plugin {} registered", plugin.name());
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Get a handle to manage subscribers, can be used after bot start running
+    pub fn handle(&self) -> BotHandle {
+        BotHandle::new(
+            Arc::clone(&self.subscribers),
+            Arc::clone(&self.dispatcher),
+            Arc::clone(&self.status),
+            Arc::clone(&self.config),
+        )
+    }
+
+    /// Watch websocket session id and sn of last received event, see [BotHandle::resume_watcher].
+    pub fn resume_watcher(&self) -> tokio::sync::watch::Receiver<GatewayResumeArguments> {
+        self.status.resume_watcher()
+    }
+
+    /// Apply new config without reconnecting, see [Config] for what can be changed.
+    pub fn reload_config(&self, config: Config) {
+        self.config.reload(config);
+    }
+
+    fn ctx(&self) -> Option<&BotContext> {
+        self.dispatcher.get().map(|d| &d.ctx)
+    }
+
+    fn notify_lifecycle(&self, lifecycle: Lifecycle) {
+        debug!("Bot lifecycle changed: {:?}", lifecycle);
+
+        let Some(ctx) = self.ctx() else {
+            return;
+        };
+
+        for handler in self.lifecycle_handlers.iter() {
+            tokio::spawn(Arc::clone(handler).on_lifecycle(ctx.clone(), lifecycle.clone()));
+        }
+    }
+
+    async fn init_subscribers(&mut self) -> Result<()> {
+        let me = self.api_client.me().await.context(error::CallAPIFailed)?;
+
+        info!("Bot user is {}#{}", me.username, me.identify_num);
+
+        let name = self.name.clone().unwrap_or_else(|| me.username.clone());
+        let ctx = BotContext::new(
+            name,
+            self.api_client.clone(),
+            me,
+            std::mem::take(&mut self.data),
+        );
+
+        for filter in self.filters.iter_mut() {
+            filter.on_loaded(ctx.me());
+        }
+
+        #[cfg(feature = "cache")]
+        ctx.cache().load(ctx.api(), ctx.me().clone()).await;
+
+        let dispatcher = self.dispatcher(ctx.clone());
+
+        // publish dispatcher before taking subscribers out to load them, so subscribers added by
+        // handle meanwhile are loaded by themselves instead of inserted as not loaded
+        if self.dispatcher.set(dispatcher.clone()).is_err() {
+            warn!("Bot is already loaded");
+        }
+
+        for mut subscription in self.subscribers.take() {
+            dispatcher.load(&mut subscription).await;
+            self.subscribers.insert(subscription);
+        }
+
+        for plugin in self.plugins.iter() {
+            plugin.setup(ctx.clone()).await;
+            info!("Plugin {} loaded", plugin.name());
+        }
+
+        for (schedule, task) in self.schedules.drain(..) {
+            self.tasks
+                .push(schedule::spawn(schedule, task, ctx.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn run_lifecycle_callbacks(&self, event: &Event) {
+        match event.as_system() {
+            Some(SystemEvent::SelfJoinedGuild(body)) => {
+                info!("Bot joined guild {}", body.guild_id);
+
+                #[cfg(feature = "cache")]
+                if let Some(ctx) = self.ctx() {
+                    let ctx = ctx.clone();
+                    let guild_id = body.guild_id.clone();
+                    tokio::spawn(async move {
+                        ctx.cache().load_guild_by_id(ctx.api(), &guild_id).await;
+                    });
+                }
+
+                for Subscription { subscriber, .. } in self.subscribers.snapshot() {
+                    tokio::spawn(subscriber.on_self_joined_guild(body.guild_id.clone()));
+                }
+            }
+            Some(SystemEvent::SelfExitedGuild(body)) => {
+                info!("Bot exited guild {}", body.guild_id);
+                for Subscription { subscriber, .. } in self.subscribers.snapshot() {
+                    tokio::spawn(subscriber.on_self_exited_guild(body.guild_id.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn run_subscribers(&self, data: EventData) -> Result<()> {
+        let span = logging::event_span(data.sn);
+        self.handle_event(data).instrument(span).await
+    }
+
+    async fn handle_event(&self, data: EventData) -> Result<()> {
+        if let Some(level) = self.event_log_level.to_level() {
+            log_at!(level, "Received event {}: {:?}", data.sn, data.event);
+        }
+
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.record(&data);
+        }
+
+        if let Some(audit) = self.audit.as_ref() {
+            audit.record(&AuditRecord::Event(EventRecord::new(&data)));
+        }
+
+        let (sn, event) = (data.sn, data.event);
+
+        if let Some(dedup) = self.dedup.as_ref() {
+            if !dedup.lock().unwrap().check(event.msg_id(), Instant::now()) {
+                debug!("Duplicate event {} dropped", event.msg_id());
+                return Ok(());
+            }
+        }
+
+        let event = Arc::from(event);
+        let dispatcher = self.dispatcher.get().expect("bot is loaded");
+
+        // cache follows every event, even it's rejected by filters below
+        #[cfg(feature = "cache")]
+        dispatcher.ctx.cache().update(&event);
+
+        self.run_lifecycle_callbacks(&event);
+
+        if !self.filters.iter().all(|f| f.filter_event(&event)) {
+            debug!("Event is rejected by bot filters");
+            return Ok(());
+        }
+
+        if !self.config.accept(&event) {
+            debug!("Event is rejected by config filters");
+            return Ok(());
+        }
+
+        dispatcher.ctx.publish(Arc::clone(&event));
+        let dispatched = dispatcher
+            .spawn_dispatch(self.subscribers.snapshot(), event)
+            .await;
+        if let Err(event) = dispatched {
+            warn!("Event queue is full, event {} rejected", event.msg_id());
+            self.notify_lifecycle(Lifecycle::EventRejected { sn });
+        }
+
+        Ok(())
+    }
+
+    fn dispatcher(&self, ctx: BotContext) -> Dispatcher {
+        Dispatcher {
+            ctx,
+            filter_timeout: self.filter_timeout,
+            error_handler: self.error_handler.clone(),
+            in_flight: Arc::default(),
+            queue: Arc::new(DispatchQueue::new(self.event_queue)),
+        }
+    }
+
+    async fn shutdown(&mut self) {
+        info!("Bot shutting down");
+
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+
+        if let Some(dispatcher) = self.dispatcher.get() {
+            let wait = dispatcher.in_flight.wait_idle();
+            if tokio::time::timeout(self.shutdown_timeout, wait)
+                .await
+                .is_err()
+            {
+                warn!("Wait running subscribers timeout, stop waiting");
+            }
+        }
+
+        let callbacks = self
+            .subscribers
+            .snapshot()
+            .into_iter()
+            .map(|s| s.subscriber.on_shutdown());
+        futures_util::future::join_all(callbacks).await;
+
+        self.save_session().await;
+
+        if let Some(ctx) = self.ctx() {
+            for plugin in self.plugins.iter() {
+                plugin.teardown(ctx.clone()).await;
+                info!("Plugin {} teardown", plugin.name());
+            }
+
+            if let Err(err) = ctx.api().user_offline().await {
+                warn!("Make bot offline failed: {}", err);
+            }
+        }
+    }
+
+    async fn load_session(&self) -> Option<GatewayResumeArguments> {
+        let store = self.session_store.as_ref()?;
+
+        if let Some(dedup) = self.dedup.as_ref() {
+            match store.load_msg_ids().await {
+                Ok(ids) => {
+                    let mut dedup = dedup.lock().unwrap();
+                    let now = Instant::now();
+                    for id in ids.iter() {
+                        dedup.check(id, now);
+                    }
+                    info!("Loaded {} recent msg ids", ids.len());
+                }
+                Err(err) => warn!("Load recent msg ids failed: {}", err),
+            }
+        }
+
+        match store.load().await {
+            Ok(resume) => {
+                info!("Loaded saved session: {:?}", resume);
+                resume
+            }
+            Err(err) => {
+                warn!("Load saved session failed: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn save_session(&mut self) {
+        if let Some(saver) = self.session_saver.take() {
+            saver.abort();
+        }
+
+        let Some(store) = self.session_store.as_ref() else {
+            return;
+        };
+
+        if let Some(resume) = self.handle().resume_arguments() {
+            session::save(store.as_ref(), &resume, self.dedup.as_deref()).await;
+        }
+    }
+
+    /// Run until event stream broken and can't recover
+    pub async fn run(self) -> Result<()> {
+        self.run_until(futures_util::future::pending()).await
+    }
+
+    /// Run the bot in background, returns a handle to query its status, stop and join it.
+    pub fn start(self) -> BotHandle {
+        let handle = self.handle();
+        handle.set_task(tokio::spawn(self.run()));
+        handle
+    }
+
+    /// Run until the `shutdown` future completes or [BotHandle::stop] is called, then shutdown gracefully:
+    /// close the websocket connection and handle events already received (at most 5 seconds),
+    /// wait running subscribers (see [Bot::shutdown_timeout]),
+    /// call [Subscriber::on_shutdown] and [Plugin::teardown], and make the bot offline.
+    pub async fn run_until<F>(mut self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        self.init_subscribers().await?;
+
+        let status = Arc::clone(&self.status);
+        let stop_requested = async {
+            tokio::select! {
+                _ = shutdown => info!("Shutdown requested"),
+                _ = status.stop.notified() => info!("Stop requested by handle"),
+            }
+        };
+
+        let (stop, stopping) = tokio::sync::watch::channel(false);
+        let result = {
+            let events = self.run_events(stopping);
+            tokio::pin!(events);
+
+            let finished = tokio::select! {
+                result = &mut events => Some(result),
+                _ = stop_requested => None,
+            };
+
+            match finished {
+                Some(result) => result,
+                None => {
+                    // let the event loop close the connection and handle received events
+                    stop.send_replace(true);
+                    match tokio::time::timeout(STREAM_CLOSE_TIMEOUT, events).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            warn!("Wait event stream closing timeout, stop waiting");
+                            Ok(())
+                        }
+                    }
+                }
+            }
+        };
+
+        self.status.disconnected();
+        self.shutdown().await;
+
+        result
+    }
+
+    /// Run until SIGINT(Ctrl-C) or SIGTERM received, then shutdown gracefully.
+    ///
+    /// See [Bot::run_until] for details.
+    pub async fn run_until_signal(self) -> Result<()> {
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Receive and dispatch events, returns soon after `stopping` becomes true
+    async fn run_events(&mut self, mut stopping: tokio::sync::watch::Receiver<bool>) -> Result<()> {
+        #[cfg(feature = "webhook")]
+        if let Some(config) = self.webhook.take() {
+            return until_stopping(&mut stopping, self.run_webhook_loop(config))
+                .await
+                .unwrap_or(Ok(()));
+        }
+
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(replayer) = self.replayer.take() {
+            return until_stopping(&mut stopping, self.run_replay_loop(replayer))
+                .await
+                .unwrap_or(Ok(()));
+        }
+
+        if let Some(source) = self.event_source.take() {
+            let source = source.into_inner().unwrap();
+            return until_stopping(&mut stopping, self.run_source_loop(source))
+                .await
+                .unwrap_or(Ok(()));
+        }
+
+        self.run_event_loop(stopping).await
+    }
+
+    async fn run_source_loop(&mut self, mut source: BoxStream<'static, Event>) -> Result<()> {
+        self.status.connected(None);
+        self.notify_lifecycle(Lifecycle::Ready);
+
+        let mut sn = 0;
+        while let Some(event) = source.next().await {
+            sn += 1;
+            self.run_subscribers(EventData {
+                sn,
+                event: Box::new(event),
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    async fn run_replay_loop(&mut self, replayer: crate::testing::Replayer) -> Result<()> {
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::channel(self.ws_config.get_event_capacity());
+
+        tokio::spawn(replayer.replay(sender));
+
+        self.status.connected(None);
+        self.notify_lifecycle(Lifecycle::Ready);
+
+        while let Some(event) = receiver.recv().await {
+            self.run_subscribers(event).await?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "webhook")]
+    async fn run_webhook_loop(&mut self, config: webhook::WebhookConfig) -> Result<()> {
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::channel(self.ws_config.get_event_capacity());
+
+        let server = webhook::serve(config, sender);
+        tokio::pin!(server);
+
+        self.status.connected(None);
+        self.notify_lifecycle(Lifecycle::Ready);
+
+        loop {
+            tokio::select! {
+                result = &mut server => return result.context(error::RunWebhookServerFailed),
+                Some(event) = receiver.recv() => self.run_subscribers(event).await?,
+            }
+        }
+    }
+
+    /// Fetch gateway url and establish event stream on it
+    async fn connect(
+        &self,
+        resume: Option<GatewayResumeArguments>,
+    ) -> Result<ws::client::EventStream> {
+        let gateway_info = self.fetch_new_gateway().await?;
+
+        debug!("Got gateway url: {}", gateway_info.url());
+
+        let ws_client = if let Some(r) = resume {
+            debug!("Resume conversion using argument: {:?}", r);
+            ws::Client::resume(r)
+        } else {
+            ws::Client::new()
+        }
+        .config(self.ws_config.clone());
+
+        ws_client
+            .run(gateway_info)
+            .await
+            .context(error::RunWebsocketClientFailed)
+    }
+
+    async fn run_event_loop(
+        &mut self,
+        mut stopping: tokio::sync::watch::Receiver<bool>,
+    ) -> Result<()> {
+        let mut resume = self.load_session().await;
+        let mut retries = 0;
+        let mut reconnects = 0;
+
+        loop {
+            info!("Getting gateway url ...");
+
+            let resuming = resume.is_some();
+            let Some(connected) = until_stopping(&mut stopping, self.connect(resume.clone())).await
+            else {
+                return Ok(());
+            };
+            let mut stream = match connected {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("Can't establish event stream: {}", err);
+
+                    let out_of_retries = self
+                        .retry
+                        .get_max_retries()
+                        .is_some_and(|max| retries >= max);
+                    let out_of_reconnects = self
+                        .retry
+                        .get_max_reconnects()
+                        .is_some_and(|max| reconnects >= max);
+                    if out_of_retries || out_of_reconnects {
+                        error!(
+                            "Reached max retry count {} or reconnect count {}, stop",
+                            retries, reconnects
+                        );
+                        self.notify_lifecycle(Lifecycle::GaveUp {
+                            reason: err.to_string(),
+                        });
+                        return Err(err);
+                    }
+
+                    let delay = self.retry.delay(retries);
+                    warn!("Retry fetch new gateway url after {:?} ...", delay);
+
+                    let slept = until_stopping(&mut stopping, tokio::time::sleep(delay)).await;
+                    if slept.is_none() {
+                        return Ok(());
+                    }
+                    retries += 1;
+                    reconnects += 1;
+
+                    self.notify_lifecycle(Lifecycle::Reconnecting { attempt: retries });
+
+                    continue;
+                }
+            };
+
+            resume = None;
+            retries = 0;
+
+            info!("Event stream established, start receiving events");
+
+            self.status.connected(Some(&stream));
+            if let Some(store) = self.session_store.as_ref() {
+                let saver = session::spawn_saver(
+                    Arc::clone(store),
+                    stream.resume_watcher(),
+                    self.dedup.clone(),
+                );
+                if let Some(old) = self.session_saver.replace(saver) {
+                    old.abort();
+                }
+            }
+            self.notify_lifecycle(if resuming {
+                Lifecycle::Resumed
+            } else {
+                Lifecycle::Ready
+            });
+
+            let mut gaps = stream.gap_watcher();
+            let mut closing = false;
+            loop {
+                let item = tokio::select! {
+                    biased;
+
+                    _ = stopping.wait_for(|stop| *stop), if !closing => {
+                        info!("Closing event stream");
+                        stream.close();
+                        closing = true;
+                        continue;
+                    }
+
+                    Ok(()) = gaps.changed() => {
+                        let gap = *gaps.borrow_and_update();
+                        if let Some(gap) = gap {
+                            self.notify_lifecycle(Lifecycle::EventGap {
+                                from: gap.from,
+                                to: gap.to,
+                                resync: gap.policy == ws::GapPolicy::Resume,
+                            });
+                        }
+                        continue;
+                    }
+                    item = stream.next_data() => match item {
+                        Some(item) => item,
+                        None if closing => {
+                            info!("Event stream closed");
+                            return Ok(());
+                        }
+                        // background task of client stopped without telling why
+                        None => Err(ws::client::EventStreamError {
+                            resume: stream.resume_arguments(),
+                            source: ws::client::EventStreamErrorKind::Closed { frame: None },
+                        }),
+                    },
+                };
+                match item {
+                    Ok(event) => {
+                        reconnects = 0;
+                        self.run_subscribers(event).await?
+                    }
+                    Err(err) if closing => {
+                        info!("Event stream ended while closing: {}", err.source);
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        warn!("EventStream broken, reason: {}", err.source);
+                        debug!("Resume argument: {:?}", err.resume);
+
+                        self.status.disconnected();
+
+                        self.notify_lifecycle(Lifecycle::Disconnected {
+                            reason: err.source.to_string(),
+                        });
+
+                        if self
+                            .retry
+                            .get_max_reconnects()
+                            .is_some_and(|max| reconnects >= max)
+                        {
+                            error!("Reached max reconnect count {}, stop", reconnects);
+                            self.notify_lifecycle(Lifecycle::GaveUp {
+                                reason: err.source.to_string(),
+                            });
+                            return Err(err).context(error::TooManyReconnects {
+                                count: reconnects + 1,
+                            });
+                        }
+
+                        resume.replace(err.resume);
+
+                        let delay = self.retry.reconnect_delay(reconnects);
+                        reconnects += 1;
+                        if !delay.is_zero() {
+                            info!("Reconnect after {:?} ...", delay);
+                        }
+                        let slept = until_stopping(&mut stopping, tokio::time::sleep(delay)).await;
+                        if slept.is_none() {
+                            return Ok(());
+                        }
+
+                        info!("Bot Restart");
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Run `fut` until it completes, None if `stopping` becomes true before that
+async fn until_stopping<F: Future>(
+    stopping: &mut tokio::sync::watch::Receiver<bool>,
+    fut: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        output = fut => Some(output),
+        _ = stopping.wait_for(|stop| *stop) => None,
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(err) => warn!("Listen SIGTERM failed: {}", err),
+        }
+    }
+
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        warn!("Listen Ctrl-C failed, never shutdown by signal: {}", err);
+        futures_util::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_subscribe_priority_order() {
+        let mut bot = Bot::new("token").unwrap();
+
+        for priority in [0, 10, -1, 10, 0] {
+            bot.subscribe_with(
+                filter::all(),
+                |_: EventContext| async {},
+                SubscribeOptions::new().priority(priority),
+            );
+        }
+
+        let priorities: Vec<_> = bot
+            .subscribers
+            .snapshot()
+            .iter()
+            .map(|s| s.options.get_priority())
+            .collect();
+        assert_eq!(priorities, [10, 10, 0, 0, -1]);
+
+        let first = Arc::clone(&bot.subscribers.snapshot()[0].subscriber);
+        bot.subscribe_with(
+            filter::all(),
+            |_: EventContext| async {},
+            SubscribeOptions::new().priority(10),
+        );
+        assert!(Arc::ptr_eq(
+            &bot.subscribers.snapshot()[0].subscriber,
+            &first
+        ));
+    }
+
+    #[test]
+    fn test_plugin_register() {
+        struct Greeting;
+
+        impl Plugin for Greeting {
+            fn name(&self) -> std::borrow::Cow<'static, str> {
+                "greeting".into()
+            }
+
+            fn register(&mut self, bot: &mut Bot) {
+                bot.data("hello")
+                    .subscribe(filter::all(), |_: EventContext| async {})
+                    .subscribe(filter::none(), |_: EventContext| async {});
+            }
+        }
+
+        let mut bot = Bot::new("token").unwrap();
+        bot.plugin(Greeting);
+
+        assert_eq!(bot.plugins.len(), 1);
+        assert_eq!(bot.subscribers.len(), 2);
+        assert_eq!(bot.data.get::<&str>(), Some(&"hello"));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_filters_of_map_and_commands() {
+        let mut registry = CommandRegistry::new();
+        registry.register(crate::command::Command::new("roll", |_, _| async {}));
+
+        let mut bot = Bot::new("token").unwrap();
+        bot.on_message(|_, _| async {}).commands(registry);
+
+        let message = |content: &str| {
+            Arc::new(Event::ChannelMessage(EventBody {
+                content: content.to_string(),
+                ..Default::default()
+            }))
+        };
+        let unknown = Arc::new(Event::Unknown(Default::default()));
+
+        let ctx = BotContext::mock();
+        let mut accepted = vec![];
+        for subscription in bot.subscribers.snapshot().iter() {
+            let mut result = vec![];
+            for event in [message("/roll"), message("hello"), Arc::clone(&unknown)] {
+                result.push(subscription.accept(&ctx, event).await.is_some());
+            }
+            accepted.push(result);
+        }
+
+        assert_eq!(accepted, [[true, true, false], [true, false, false]]);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_map_extracts_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counting(Arc<AtomicUsize>);
+
+        impl FilterMap for Counting {
+            type Output = String;
+
+            fn filter_map(&self, event: &Event) -> Option<String> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                event.as_message().map(|m| m.content.clone())
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut bot = Bot::new("token").unwrap();
+        bot.subscribe_map(Counting(Arc::clone(&calls)), move |_, content| {
+            let tx = tx.clone();
+            async move { tx.send(content).unwrap() }
+        });
+
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        let events = [
+            Event::ChannelMessage(EventBody {
+                content: "hello".to_string(),
+                ..Default::default()
+            }),
+            Event::Unknown(Default::default()),
+            Event::ChannelMessage(EventBody {
+                content: "world".to_string(),
+                ..Default::default()
+            }),
+        ];
+        for event in events {
+            dispatcher
+                .clone()
+                .dispatch(bot.subscribers.snapshot(), Arc::new(event))
+                .await;
+            dispatcher.in_flight.wait_idle().await;
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(rx.recv().await.unwrap(), "hello");
+        assert_eq!(rx.recv().await.unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn test_command_args_in_handler() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut bot = Bot::new("token").unwrap();
+        bot.subscribe_map(filter::command("roll").prefix("/"), move |_, args| {
+            let tx = tx.clone();
+            async move { tx.send(args).unwrap() }
+        });
+
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        for content in ["/roll 1d6 +2", "/rolling", "(met)bot(met) /roll"] {
+            let event = Event::ChannelMessage(EventBody {
+                content: content.to_string(),
+                ..Default::default()
+            });
+            dispatcher
+                .clone()
+                .dispatch(bot.subscribers.snapshot(), Arc::new(event))
+                .await;
+            dispatcher.in_flight.wait_idle().await;
+        }
+        drop(bot);
+
+        assert_eq!(rx.recv().await.unwrap(), "1d6 +2");
+        assert_eq!(rx.recv().await.unwrap(), "");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_callbacks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counter(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl Subscriber for Counter {
+            fn name(&self) -> std::borrow::Cow<'static, str> {
+                "counter".into()
+            }
+
+            async fn on_loaded(&mut self, _ctx: BotContext) {}
+
+            async fn on_event(
+                self: Arc<Self>,
+                _ctx: EventContext,
+            ) -> std::result::Result<(), crate::BoxError> {
+                Ok(())
+            }
+
+            async fn on_shutdown(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut bot = Bot::new("token").unwrap();
+        bot.subscribe(filter::all(), Counter(Arc::clone(&count)))
+            .subscribe(filter::none(), Counter(Arc::clone(&count)));
+
+        bot.shutdown().await;
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_handle_add_remove_subscriber() {
+        let mut bot = Bot::new("token").unwrap();
+        bot.subscribe(filter::all(), |_: EventContext| async {});
+
+        let handle = bot.handle();
+        handle
+            .add_subscriber(
+                filter::all(),
+                crate::button::ButtonRegistry::new(),
+                SubscribeOptions::new().priority(1),
+            )
+            .await;
+
+        assert_eq!(
+            handle.subscriber_names(),
+            ["Button Registry", "Anonymous FnMut Subscriber"]
+        );
+
+        assert_eq!(handle.remove_subscriber("Button Registry"), 1);
+        assert_eq!(handle.remove_subscriber("Button Registry"), 0);
+        assert_eq!(bot.subscribers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_add_subscriber_while_loading() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        struct Loading {
+            gate: tokio::sync::watch::Receiver<bool>,
+            started: Arc<tokio::sync::Notify>,
+            loaded: Arc<AtomicBool>,
+        }
+
+        #[async_trait::async_trait]
+        impl Subscriber for Loading {
+            fn name(&self) -> std::borrow::Cow<'static, str> {
+                "loading".into()
+            }
+
+            async fn on_loaded(&mut self, _ctx: BotContext) {
+                self.started.notify_one();
+                self.gate.wait_for(|open| *open).await.unwrap();
+                self.loaded.store(true, Ordering::SeqCst);
+            }
+
+            async fn on_event(
+                self: Arc<Self>,
+                _ctx: EventContext,
+            ) -> std::result::Result<(), crate::subscriber::BoxError> {
+                Ok(())
+            }
+        }
+
+        let (gate_tx, gate) = tokio::sync::watch::channel(false);
+        let started = Arc::new(tokio::sync::Notify::new());
+        let first = Arc::new(AtomicBool::new(false));
+        let second = Arc::new(AtomicBool::new(false));
+
+        let mut bot = Bot::builder("token")
+            .mock_api(crate::testing::MockApi::new())
+            .build()
+            .unwrap();
+        bot.subscribe(
+            filter::all(),
+            Loading {
+                gate: gate.clone(),
+                started: Arc::clone(&started),
+                loaded: Arc::clone(&first),
+            },
+        );
+        let handle = bot.start();
+
+        started.notified().await;
+        gate_tx.send(true).unwrap();
+        handle
+            .add_subscriber(
+                filter::all(),
+                Loading {
+                    gate,
+                    started: Arc::default(),
+                    loaded: Arc::clone(&second),
+                },
+                SubscribeOptions::new(),
+            )
+            .await;
+        assert!(second.load(Ordering::SeqCst));
+
+        handle.stop();
+        let _ = handle.join().await;
+        assert!(first.load(Ordering::SeqCst));
+        assert_eq!(handle.subscriber_names().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_typed_handlers() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let sender = |name: &'static str| {
+            let tx = tx.clone();
+            move |_: EventContext, data: String| {
+                let tx = tx.clone();
+                async move { tx.send((name, data)).unwrap() }
+            }
+        };
+
+        let mut bot = Bot::new("token").unwrap();
+        let (message, join, exit, add, remove) = (
+            sender("message"),
+            sender("join"),
+            sender("exit"),
+            sender("add"),
+            sender("remove"),
+        );
+        bot.on_message(move |ctx, body| message(ctx, body.content))
+            .on_member_join(move |ctx, body| join(ctx, body.user_id))
+            .on_member_exit(move |ctx, body| exit(ctx, body.user_id))
+            .on_reaction_add(move |ctx, body| add(ctx, body.emoji.id))
+            .on_reaction_remove(move |ctx, body| remove(ctx, body.msg_id));
+        drop(tx);
+
+        let system = |extra| Event::SystemEvent(EventBody::<()>::default().with_extra(extra));
+        let reaction = ReactionBody {
+            msg_id: "msg".to_string(),
+            emoji: crate::models::Emoji::unicode("👍"),
+            ..Default::default()
+        };
+        let events = [
+            Event::PrivateMessage(EventBody {
+                content: "hello".to_string(),
+                ..Default::default()
+            }),
+            system(SystemEvent::JoinedGuild(JoinedGuildBody {
+                user_id: "joined".to_string(),
+                ..Default::default()
+            })),
+            system(SystemEvent::ExitedGuild(ExitedGuildBody {
+                user_id: "exited".to_string(),
+                ..Default::default()
+            })),
+            system(SystemEvent::AddedReaction(reaction.clone())),
+            system(SystemEvent::DeletedReaction(reaction)),
+            Event::Unknown(Default::default()),
+        ];
+
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        for event in events {
+            dispatcher
+                .clone()
+                .dispatch(bot.subscribers.snapshot(), Arc::new(event))
+                .await;
+        }
+        drop(bot);
+
+        let mut received = vec![];
+        while let Some((name, data)) = rx.recv().await {
+            received.push((name, data));
+        }
+        received.sort();
+
+        let expected = [
+            ("add", "👍"),
+            ("exit", "exited"),
+            ("join", "joined"),
+            ("message", "hello"),
+            ("remove", "msg"),
+        ];
+        assert_eq!(
+            received,
+            expected.map(|(name, data)| (name, data.to_string()))
+        );
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_cache_updated_by_rejected_event() {
+        let bot = Bot::builder("token")
+            .filter(filter::none())
+            .build()
+            .unwrap();
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        assert!(bot.dispatcher.set(dispatcher).is_ok());
+
+        let mut body = EventBody::<MessageExtra>::default();
+        body.extra.guild_id = "guild".to_string();
+        body.extra.author.id = "user".to_string();
+        let data = EventData {
+            sn: 1,
+            event: Box::new(Event::ChannelMessage(body)),
+        };
+        bot.handle_event(data).await.unwrap();
+
+        let cache = bot.dispatcher.get().unwrap().ctx.cache();
+        assert!(cache.member("guild", "user").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_event_reported() {
+        let queue = EventQueue::new()
+            .max_in_flight(1)
+            .capacity(1)
+            .backpressure(Backpressure::Reject);
+        let mut bot = Bot::builder("token").event_queue(queue).build().unwrap();
+
+        let (gate_tx, gate_rx) = tokio::sync::watch::channel(false);
+        bot.subscribe(filter::all(), move |_: EventContext| {
+            let mut gate = gate_rx.clone();
+            async move {
+                gate.wait_for(|open| *open).await.unwrap();
+            }
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.on_lifecycle(move |_, lifecycle| {
+            let tx = tx.clone();
+            async move {
+                tx.send(lifecycle).unwrap();
+            }
+        });
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        assert!(bot.dispatcher.set(dispatcher).is_ok());
+
+        for sn in 1..=3 {
+            let data = EventData {
+                sn,
+                event: Box::new(Event::ChannelMessage(Default::default())),
+            };
+            assert!(bot.handle_event(data).await.is_ok());
+        }
+
+        assert_eq!(rx.recv().await, Some(Lifecycle::EventRejected { sn: 3 }));
+        gate_tx.send(true).unwrap();
+        bot.dispatcher.get().unwrap().in_flight.wait_idle().await;
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_handlers() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut bot = Bot::new("token").unwrap();
+        bot.on_lifecycle(move |_, lifecycle| {
+            let tx = tx.clone();
+            async move {
+                tx.send(lifecycle).unwrap();
+            }
+        });
+
+        bot.notify_lifecycle(Lifecycle::Ready);
+
+        let dispatcher = bot.dispatcher(BotContext::mock());
+        assert!(bot.dispatcher.set(dispatcher).is_ok());
+        bot.notify_lifecycle(Lifecycle::Reconnecting { attempt: 1 });
+
+        assert_eq!(
+            rx.recv().await,
+            Some(Lifecycle::Reconnecting { attempt: 1 })
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handle_status_and_stop() {
+        let bot = Bot::new("token").unwrap();
+        let handle = bot.handle();
+
+        assert!(!handle.is_connected());
+        assert!(handle.resume_arguments().is_none());
+
+        let (tx, status) = tokio::sync::watch::channel(api::types::GatewayResumeArguments {
+            sn: 1,
+            session_id: "session".to_string(),
+        });
+        let heartbeat = Arc::<ws::client::Heartbeat>::default();
+        let stream = ws::client::EventStream {
+            rx: tokio::sync::mpsc::channel(1).1,
+            status,
+            gaps: tokio::sync::watch::channel(None).1,
+            state: tokio::sync::watch::channel(ws::ConnectionState::Streaming).1,
+            shutdown: tokio::sync::watch::channel(false).0,
+            heartbeat: Arc::clone(&heartbeat),
+        };
+        bot.status.connected(Some(&stream));
+        tx.send_modify(|resume| resume.sn = 2);
+
+        assert!(handle.is_connected());
+        assert_eq!(handle.resume_arguments().map(|r| r.sn), Some(2));
+        assert!(handle.latency().is_none());
+
+        let now = tokio::time::Instant::now();
+        heartbeat.ping_sent(now);
+        heartbeat.pong_received(now + Duration::from_millis(20));
+        assert_eq!(
+            handle.latency().map(|l| l.latest),
+            Some(Duration::from_millis(20))
+        );
+
+        handle.stop();
+        tokio::time::timeout(Duration::from_secs(1), bot.status.stop.notified())
+            .await
+            .unwrap();
+
+        bot.status.disconnected();
+        assert!(!handle.is_connected());
+        assert!(handle.join().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_join_shares_result() {
+        let bot = Bot::new("token").unwrap();
+        let other = bot.handle();
+        let handle = bot.handle();
+        handle.set_task(tokio::spawn(async { error::NoReplyTarget.fail() }));
+
+        let (first, second) = tokio::join!(handle.join(), other.join());
+        assert!(matches!(*first.unwrap_err(), crate::Error::NoReplyTarget));
+        assert!(matches!(*second.unwrap_err(), crate::Error::NoReplyTarget));
+        assert!(matches!(
+            *handle.join().await.unwrap_err(),
+            crate::Error::NoReplyTarget
+        ));
+
+        let handle = Bot::new("token").unwrap().handle();
+        handle.set_task(tokio::spawn(async { panic!("boom") }));
+        assert!(matches!(
+            &*handle.join().await.unwrap_err(),
+            crate::Error::BotPanicked { message } if message == "boom"
+        ));
+
+        let handle = Bot::new("token").unwrap().handle();
+        let task = tokio::spawn(futures_util::future::pending());
+        task.abort();
+        handle.set_task(task);
+        assert!(matches!(
+            *handle.join().await.unwrap_err(),
+            crate::Error::BotCancelled
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resume_watcher_across_connections() {
+        let bot = Bot::new("token").unwrap();
+        let mut watcher = bot.resume_watcher();
+        assert_eq!(watcher.borrow_and_update().sn, 0);
+
+        let stream = |sn: u64| {
+            let (tx, status) = tokio::sync::watch::channel(api::types::GatewayResumeArguments {
+                sn,
+                session_id: "session".to_string(),
+            });
+            let stream = ws::client::EventStream {
+                rx: tokio::sync::mpsc::channel(1).1,
+                status,
+                gaps: tokio::sync::watch::channel(None).1,
+                state: tokio::sync::watch::channel(ws::ConnectionState::Streaming).1,
+                shutdown: tokio::sync::watch::channel(false).0,
+                heartbeat: Arc::default(),
+            };
+            (tx, stream)
+        };
+        async fn next_sn(
+            watcher: &mut tokio::sync::watch::Receiver<api::types::GatewayResumeArguments>,
+        ) -> u64 {
+            tokio::time::timeout(Duration::from_secs(1), watcher.changed())
+                .await
+                .unwrap()
+                .unwrap();
+            watcher.borrow_and_update().sn
+        }
+
+        let (first, first_stream) = stream(1);
+        bot.status.connected(Some(&first_stream));
+        assert_eq!(next_sn(&mut watcher).await, 1);
+
+        first.send_modify(|resume| resume.sn = 2);
+        assert_eq!(next_sn(&mut watcher).await, 2);
+
+        let (_second, second_stream) = stream(5);
+        bot.status.connected(Some(&second_stream));
+        assert_eq!(next_sn(&mut watcher).await, 5);
+
+        // old connection no longer updates the bot
+        first.send_modify(|resume| resume.sn = 3);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!watcher.has_changed().unwrap());
+        assert_eq!(bot.handle().resume_watcher().borrow().sn, 5);
+    }
+}
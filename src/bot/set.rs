@@ -0,0 +1,158 @@
+use std::{fmt::Debug, future::Future};
+
+use futures_util::FutureExt;
+
+use super::{shutdown_signal, Bot, BotHandle};
+use crate::{
+    filter::AsyncFilter,
+    subscriber::{SubscribeOptions, Subscriber},
+    Result,
+};
+
+type Setup = Box<dyn Fn(&mut Bot) + Send + Sync + 'static>;
+
+/// Run multiple bots on one runtime, with shared subscribers and data.
+///
+/// Use [BotContext::name](crate::BotContext::name) in subscribers to know which bot received the event.
+#[derive(Default)]
+pub struct BotSet {
+    bots: Vec<Bot>,
+    setups: Vec<Setup>,
+}
+
+impl Debug for BotSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BotSet")
+            .field("bots", &self.bots)
+            .field("setups", &self.setups.len())
+            .finish()
+    }
+}
+
+impl BotSet {
+    /// Create a empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a bot into the set
+    pub fn add(&mut self, bot: Bot) -> &mut Self {
+        self.bots.push(bot);
+        self
+    }
+
+    /// Bot count in the set
+    pub fn len(&self) -> usize {
+        self.bots.len()
+    }
+
+    /// Check if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.bots.is_empty()
+    }
+
+    /// Handles of all bots, see [Bot::handle]
+    pub fn handles(&self) -> Vec<BotHandle> {
+        self.bots.iter().map(Bot::handle).collect()
+    }
+
+    /// Add a subscriber to all bots, each bot gets a clone of the filter and subscriber.
+    ///
+    /// Share states between clones by `Arc`.
+    pub fn subscribe<F, S>(&mut self, filter: F, subscriber: S) -> &mut Self
+    where
+        F: AsyncFilter + Clone + 'static,
+        S: Subscriber + Clone + 'static,
+    {
+        self.subscribe_with(filter, subscriber, SubscribeOptions::default())
+    }
+
+    /// Add a subscriber with options to all bots, see [BotSet::subscribe]
+    pub fn subscribe_with<F, S>(
+        &mut self,
+        filter: F,
+        subscriber: S,
+        options: SubscribeOptions,
+    ) -> &mut Self
+    where
+        F: AsyncFilter + Clone + 'static,
+        S: Subscriber + Clone + 'static,
+    {
+        self.setups.push(Box::new(move |bot| {
+            bot.subscribe_with(filter.clone(), subscriber.clone(), options.clone());
+        }));
+        self
+    }
+
+    /// Add shared data to all bots, each bot gets a clone of the data.
+    ///
+    /// Use `Arc<T>` to share the same data between bots.
+    pub fn data<T: Clone + Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.setups.push(Box::new(move |bot| {
+            bot.data(value.clone());
+        }));
+        self
+    }
+
+    fn setup_bots(&mut self) {
+        for setup in self.setups.drain(..) {
+            for bot in self.bots.iter_mut() {
+                setup(bot);
+            }
+        }
+    }
+
+    /// Run all bots until the `shutdown` future completes, see [Bot::run_until].
+    ///
+    /// Returns the first error of bots after all of them stopped.
+    pub async fn run_until<F>(mut self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        self.setup_bots();
+
+        let shutdown = Box::pin(shutdown).shared();
+        let runs = self
+            .bots
+            .into_iter()
+            .map(|bot| bot.run_until(shutdown.clone()));
+
+        futures_util::future::join_all(runs)
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Run all bots until SIGINT(Ctrl-C) or SIGTERM received, see [Bot::run_until_signal].
+    pub async fn run_until_signal(self) -> Result<()> {
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Run all bots, see [Bot::run]
+    pub async fn run(self) -> Result<()> {
+        self.run_until(futures_util::future::pending()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{filter, EventContext};
+
+    #[test]
+    fn test_bot_set_setup() {
+        let mut set = BotSet::new();
+        set.subscribe(filter::all(), |_: EventContext| async {})
+            .data(1u32)
+            .add(Bot::new("token-1").unwrap())
+            .add(Bot::new("token-2").unwrap());
+
+        set.setup_bots();
+
+        assert_eq!(set.len(), 2);
+        for bot in set.bots.iter() {
+            assert_eq!(bot.subscribers.len(), 1);
+            assert_eq!(bot.data.get::<u32>(), Some(&1));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Card button click routing.
+
+use std::{borrow::Cow, fmt::Debug, future::Future, sync::Arc};
+
+use crate::{
+    context::{BotContext, EventContext},
+    subscriber::{BoxError, Subscriber},
+    ws::{
+        event::{ButtonClickBody, SystemEvent},
+        Event,
+    },
+};
+
+/// Handler can be register to [ButtonRegistry] and process button clicks.
+#[async_trait::async_trait]
+pub trait ButtonHandler: Send + Sync {
+    /// callback will be execute when a button with matched value is clicked
+    async fn on_click(self: Arc<Self>, ctx: EventContext, click: ButtonClickBody);
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> ButtonHandler for F
+where
+    F: Fn(EventContext, ButtonClickBody) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_click(self: Arc<Self>, ctx: EventContext, click: ButtonClickBody) {
+        self(ctx, click).await
+    }
+}
+
+/// Dispatch button clicks to registered handlers by button value prefix.
+///
+/// When multiple prefixes match a value, the longest one wins.
+#[derive(Default)]
+pub struct ButtonRegistry {
+    handlers: Vec<(String, Arc<dyn ButtonHandler + 'static>)>,
+}
+
+impl Debug for ButtonRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ButtonRegistry")
+            .field(
+                "prefixes",
+                &self.handlers.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl ButtonRegistry {
+    /// Create a empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for buttons whose value starts with `prefix`
+    pub fn register<P, H>(&mut self, prefix: P, handler: H) -> &mut Self
+    where
+        P: Into<String>,
+        H: ButtonHandler + 'static,
+    {
+        self.handlers.push((prefix.into(), Arc::new(handler)));
+        self
+    }
+
+    pub(crate) fn find(&self, value: &str) -> Option<&Arc<dyn ButtonHandler + 'static>> {
+        self.handlers
+            .iter()
+            .filter(|(prefix, _)| value.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, handler)| handler)
+    }
+
+    /// Check if a event is a button click event
+    pub fn is_button_click(event: &Event) -> bool {
+        matches!(event.as_system(), Some(SystemEvent::MessageBtnClick(_)))
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscriber for ButtonRegistry {
+    fn name(&self) -> Cow<'static, str> {
+        "Button Registry".into()
+    }
+
+    async fn on_loaded(&mut self, _ctx: BotContext) {}
+
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError> {
+        if let Some(SystemEvent::MessageBtnClick(click)) = ctx.as_system() {
+            match self.find(&click.value) {
+                Some(handler) => {
+                    Arc::clone(handler)
+                        .on_click(ctx.clone(), click.clone())
+                        .await
+                }
+                None => debug!("No handler for button value {}", click.value),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_button_registry_longest_prefix() {
+        let mut registry = ButtonRegistry::new();
+        registry
+            .register("vote:", |_, _| async {})
+            .register("vote:admin:", |_, _| async {});
+
+        let handler = registry.find("vote:admin:yes").unwrap();
+        assert!(Arc::ptr_eq(handler, &registry.handlers[1].1));
+
+        let handler = registry.find("vote:yes").unwrap();
+        assert!(Arc::ptr_eq(handler, &registry.handlers[0].1));
+
+        assert!(registry.find("other").is_none());
+    }
+}
@@ -0,0 +1,310 @@
+//! In-memory cache of guilds, channels, roles and members.
+//!
+//! The cache is seeded from api when bot loaded, and kept current by system events.
+//! Get it from [BotContext::cache](crate::BotContext::cache) in subscribers.
+
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::{
+    api,
+    models::{Channel, Guild, Role, User},
+    permission::{self, Permissions},
+    ws::{event::SystemEvent, Event},
+};
+
+/// In-memory cache updated by events
+#[derive(Debug, Default)]
+pub struct Cache {
+    me: RwLock<Option<User>>,
+    guilds: RwLock<HashMap<String, Guild>>,
+    channels: RwLock<HashMap<String, Channel>>,
+    /// guild id -> role id -> role
+    roles: RwLock<HashMap<String, HashMap<u64, Role>>>,
+    /// guild id -> user id -> member
+    members: RwLock<HashMap<String, HashMap<String, User>>>,
+}
+
+impl Cache {
+    /// User info of the bot itself
+    pub fn me(&self) -> Option<User> {
+        self.me.read().unwrap().clone()
+    }
+
+    /// Get a guild by id
+    pub fn guild(&self, id: &str) -> Option<Guild> {
+        self.guilds.read().unwrap().get(id).cloned()
+    }
+
+    /// All guilds the bot joined
+    pub fn guilds(&self) -> Vec<Guild> {
+        self.guilds.read().unwrap().values().cloned().collect()
+    }
+
+    /// Get a channel by id
+    pub fn channel(&self, id: &str) -> Option<Channel> {
+        self.channels.read().unwrap().get(id).cloned()
+    }
+
+    /// All channels of a guild
+    pub fn channels(&self, guild_id: &str) -> Vec<Channel> {
+        self.channels
+            .read()
+            .unwrap()
+            .values()
+            .filter(|c| c.guild_id == guild_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Get a role of a guild
+    pub fn role(&self, guild_id: &str, role_id: u64) -> Option<Role> {
+        self.roles
+            .read()
+            .unwrap()
+            .get(guild_id)?
+            .get(&role_id)
+            .cloned()
+    }
+
+    /// All roles of a guild
+    pub fn roles(&self, guild_id: &str) -> Vec<Role> {
+        self.roles
+            .read()
+            .unwrap()
+            .get(guild_id)
+            .map(|roles| roles.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get a member of a guild, members are cached when they send messages
+    pub fn member(&self, guild_id: &str, user_id: &str) -> Option<User> {
+        self.members
+            .read()
+            .unwrap()
+            .get(guild_id)?
+            .get(user_id)
+            .cloned()
+    }
+
+    /// Compute effective permissions of a guild member in a channel,
+    /// None if the channel, its guild or the member is not cached.
+    ///
+    /// See [permission::permissions_for] for details.
+    pub fn permissions_for(&self, user_id: &str, channel_id: &str) -> Option<Permissions> {
+        let channel = self.channel(channel_id)?;
+        let guild = self.guild(&channel.guild_id)?;
+        let member = self.member(&channel.guild_id, user_id)?;
+        let roles = self.roles(&channel.guild_id);
+
+        Some(permission::permissions_for(
+            &guild, &roles, &member, &channel,
+        ))
+    }
+
+    /// Seed the cache from api, errors are logged and ignored
+    pub(crate) async fn load(&self, api: &api::Client, me: User) {
+        self.me.write().unwrap().replace(me);
+
+        let guilds = match api.guild_list().await {
+            Ok(guilds) => guilds,
+            Err(err) => {
+                warn!("Load guild list into cache failed: {}", err);
+                return;
+            }
+        };
+
+        for guild in guilds {
+            self.load_guild(api, guild).await;
+        }
+
+        info!("Cache loaded, {} guilds", self.guilds.read().unwrap().len());
+    }
+
+    /// Load channels and roles of a guild
+    pub(crate) async fn load_guild(&self, api: &api::Client, guild: Guild) {
+        match api.channel_list(&guild.id).await {
+            Ok(channels) => {
+                let mut cache = self.channels.write().unwrap();
+                for channel in channels {
+                    cache.insert(channel.id.clone(), channel);
+                }
+            }
+            Err(err) => warn!("Load channels of guild {} failed: {}", guild.id, err),
+        }
+
+        match api.guild_role_list(&guild.id).await {
+            Ok(roles) => {
+                let roles = roles.into_iter().map(|r| (r.role_id, r)).collect();
+                self.roles.write().unwrap().insert(guild.id.clone(), roles);
+            }
+            Err(err) => warn!("Load roles of guild {} failed: {}", guild.id, err),
+        }
+
+        self.guilds.write().unwrap().insert(guild.id.clone(), guild);
+    }
+
+    /// Load a new joined guild
+    pub(crate) async fn load_guild_by_id(&self, api: &api::Client, guild_id: &str) {
+        match api.guild_view(guild_id).await {
+            Ok(guild) => self.load_guild(api, guild).await,
+            Err(err) => warn!("Load guild {} failed: {}", guild_id, err),
+        }
+    }
+
+    fn remove_guild(&self, guild_id: &str) {
+        self.guilds.write().unwrap().remove(guild_id);
+        self.channels
+            .write()
+            .unwrap()
+            .retain(|_, c| c.guild_id != guild_id);
+        self.roles.write().unwrap().remove(guild_id);
+        self.members.write().unwrap().remove(guild_id);
+    }
+
+    /// Update cache by a event
+    pub(crate) fn update(&self, event: &Event) {
+        if let Event::ChannelMessage(body) = event {
+            self.members
+                .write()
+                .unwrap()
+                .entry(body.extra.guild_id.clone())
+                .or_default()
+                .insert(body.extra.author.id.clone(), body.extra.author.clone());
+            return;
+        }
+
+        let (guild_id, system) = match (event.guild_id(), event.as_system()) {
+            (guild_id, Some(system)) => (guild_id.unwrap_or_default(), system),
+            _ => return,
+        };
+
+        match system {
+            SystemEvent::UpdatedGuild(guild) => {
+                self.guilds
+                    .write()
+                    .unwrap()
+                    .insert(guild.id.clone(), guild.clone());
+            }
+            SystemEvent::DeletedGuild(guild) => self.remove_guild(&guild.id),
+            SystemEvent::SelfExitedGuild(body) => self.remove_guild(&body.guild_id),
+            SystemEvent::AddedChannel(channel) | SystemEvent::UpdatedChannel(channel) => {
+                self.channels
+                    .write()
+                    .unwrap()
+                    .insert(channel.id.clone(), channel.clone());
+            }
+            SystemEvent::DeletedChannel(body) => {
+                self.channels.write().unwrap().remove(&body.id);
+            }
+            SystemEvent::AddedRole(role) | SystemEvent::UpdatedRole(role) => {
+                self.roles
+                    .write()
+                    .unwrap()
+                    .entry(guild_id.to_string())
+                    .or_default()
+                    .insert(role.role_id, role.clone());
+            }
+            SystemEvent::DeletedRole(role) => {
+                if let Some(roles) = self.roles.write().unwrap().get_mut(guild_id) {
+                    roles.remove(&role.role_id);
+                }
+            }
+            SystemEvent::ExitedGuild(body) => {
+                if let Some(members) = self.members.write().unwrap().get_mut(guild_id) {
+                    members.remove(&body.user_id);
+                }
+            }
+            SystemEvent::UpdatedGuildMember(body) => {
+                let mut members = self.members.write().unwrap();
+                if let Some(member) = members
+                    .get_mut(guild_id)
+                    .and_then(|m| m.get_mut(&body.user_id))
+                {
+                    member.nickname = body.nickname.clone();
+                }
+            }
+            SystemEvent::UserUpdated(body) => {
+                for member in self.members.write().unwrap().values_mut() {
+                    if let Some(user) = member.get_mut(&body.user_id) {
+                        user.username = body.username.clone();
+                        user.avatar = body.avatar.clone();
+                    }
+                }
+                if let Some(me) = self.me.write().unwrap().as_mut() {
+                    if me.id == body.user_id {
+                        me.username = body.username.clone();
+                        me.avatar = body.avatar.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{models::ChannelType, ws::event::EventBody};
+
+    fn system(guild_id: &str, extra: SystemEvent) -> Event {
+        Event::SystemEvent(
+            EventBody::<()> {
+                channel_type: ChannelType::Group,
+                target_id: guild_id.to_string(),
+                ..Default::default()
+            }
+            .with_extra(extra),
+        )
+    }
+
+    #[test]
+    fn test_cache_update() {
+        let cache = Cache::default();
+
+        let role = Role {
+            role_id: 1,
+            name: "admin".to_string(),
+            ..Default::default()
+        };
+        cache.update(&system("guild", SystemEvent::AddedRole(role.clone())));
+        assert_eq!(cache.role("guild", 1), Some(role.clone()));
+        assert_eq!(cache.roles("guild").len(), 1);
+
+        let channel = Channel {
+            id: "channel".to_string(),
+            guild_id: "guild".to_string(),
+            ..Default::default()
+        };
+        cache.update(&system("guild", SystemEvent::AddedChannel(channel.clone())));
+        assert_eq!(cache.channel("channel"), Some(channel));
+        assert_eq!(cache.channels("guild").len(), 1);
+
+        cache.update(&system("guild", SystemEvent::DeletedRole(role)));
+        assert!(cache.role("guild", 1).is_none());
+
+        let message = Event::ChannelMessage(EventBody {
+            extra: crate::ws::event::MessageExtra {
+                guild_id: "guild".to_string(),
+                author: User {
+                    id: "user".to_string(),
+                    username: "someone".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        cache.update(&message);
+        assert_eq!(cache.member("guild", "user").unwrap().username, "someone");
+
+        cache.update(&system(
+            "guild",
+            SystemEvent::SelfExitedGuild(crate::ws::event::SelfGuildBody {
+                guild_id: "guild".to_string(),
+            }),
+        ));
+        assert!(cache.channel("channel").is_none());
+        assert!(cache.member("guild", "user").is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Size, Theme};
+use crate::{kmarkdown::KMarkdown, models::Emoji};
+
+/// Element used in card modules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Element {
+    /// plain text
+    PlainText {
+        /// text content
+        content: String,
+        /// whether to convert emoji short names like `:smile:`, server default is true
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        emoji: Option<bool>,
+    },
+    /// kmarkdown text
+    #[serde(rename = "kmarkdown")]
+    KMarkdown {
+        /// kmarkdown content
+        content: String,
+    },
+    /// text fields in columns
+    Paragraph {
+        /// column count, 1 to 3
+        cols: u8,
+        /// text fields, plain text or kmarkdown
+        fields: Vec<Element>,
+    },
+    /// image
+    Image(Image),
+    /// button
+    Button(Button),
+}
+
+impl Element {
+    /// Create a plain text element
+    pub fn plain<S: Into<String>>(content: S) -> Self {
+        Self::PlainText {
+            content: content.into(),
+            emoji: None,
+        }
+    }
+
+    /// Create a kmarkdown element, see [KMarkdown] for building the content
+    pub fn kmarkdown<S: Into<String>>(content: S) -> Self {
+        Self::KMarkdown {
+            content: content.into(),
+        }
+    }
+
+    /// Create a paragraph with text fields in columns
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cols` is not in 1 to 3, or there are more than 50 fields.
+    pub fn paragraph<I: IntoIterator<Item = Element>>(cols: u8, fields: I) -> Self {
+        assert!((1..=3).contains(&cols), "paragraph cols must be in 1..=3");
+        let fields: Vec<_> = fields.into_iter().collect();
+        assert!(fields.len() <= 50, "paragraph has at most 50 fields");
+        Self::Paragraph { cols, fields }
+    }
+
+    /// Get text content if it's a plain text or kmarkdown element
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::PlainText { content, .. } | Self::KMarkdown { content } => Some(content),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Element {
+    fn from(content: &str) -> Self {
+        Self::plain(content)
+    }
+}
+
+impl From<String> for Element {
+    fn from(content: String) -> Self {
+        Self::plain(content)
+    }
+}
+
+impl From<KMarkdown> for Element {
+    fn from(content: KMarkdown) -> Self {
+        Self::kmarkdown(content)
+    }
+}
+
+impl From<&Emoji> for Element {
+    fn from(emoji: &Emoji) -> Self {
+        Self::kmarkdown(emoji.to_kmarkdown())
+    }
+}
+
+impl From<Image> for Element {
+    fn from(image: Image) -> Self {
+        Self::Image(image)
+    }
+}
+
+impl From<Button> for Element {
+    fn from(button: Button) -> Self {
+        Self::Button(button)
+    }
+}
+
+/// Image element
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Image {
+    /// image url, should be uploaded to kaiheila first
+    pub src: String,
+    /// alternative text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    /// image size, only used in section accessory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Size>,
+    /// whether to show as circle, only used in context and section accessory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circle: Option<bool>,
+}
+
+impl Image {
+    /// Create a image with url
+    pub fn new<S: Into<String>>(src: S) -> Self {
+        Self {
+            src: src.into(),
+            alt: None,
+            size: None,
+            circle: None,
+        }
+    }
+
+    /// Set alternative text
+    pub fn alt<S: Into<String>>(mut self, alt: S) -> Self {
+        self.alt.replace(alt.into());
+        self
+    }
+
+    /// Set image size
+    pub fn size(mut self, size: Size) -> Self {
+        self.size.replace(size);
+        self
+    }
+
+    /// Show image as circle
+    pub fn circle(mut self) -> Self {
+        self.circle.replace(true);
+        self
+    }
+}
+
+/// What happens when a button is clicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Click {
+    /// open the url in button value
+    Link,
+    /// send a [button click event](crate::ws::event::SystemEvent::MessageBtnClick) with button
+    /// value to bot
+    ReturnVal,
+}
+
+/// Button element
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Button {
+    /// button color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    /// url or value returned to bot, by click action
+    #[serde(default)]
+    pub value: String,
+    /// click action, server default is doing nothing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click: Option<Click>,
+    /// button text, plain text or kmarkdown
+    pub text: Box<Element>,
+}
+
+impl Button {
+    /// Create a button which sends `value` to bot when clicked,
+    /// see [ButtonRegistry](crate::button::ButtonRegistry)
+    pub fn new<S: Into<String>, V: Into<String>>(text: S, value: V) -> Self {
+        Self {
+            theme: None,
+            value: value.into(),
+            click: Some(Click::ReturnVal),
+            text: Box::new(Element::plain(text)),
+        }
+    }
+
+    /// Create a button which opens `url` when clicked
+    pub fn link<S: Into<String>, U: Into<String>>(text: S, url: U) -> Self {
+        Self {
+            click: Some(Click::Link),
+            ..Self::new(text, url)
+        }
+    }
+
+    /// Set button color
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme.replace(theme);
+        self
+    }
+
+    /// Use a plain text or kmarkdown element as button text
+    pub fn text(mut self, text: Element) -> Self {
+        self.text = Box::new(text);
+        self
+    }
+}
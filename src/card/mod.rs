@@ -0,0 +1,451 @@
+//! Card message builder.
+//!
+//! Content of a card message is a list of [Card]s in json, see
+//! <https://developer.kookapp.cn/doc/cardmessage> for how they look.
+//!
+//! ```
+//! use burz::card::{Button, Card, Module, Theme};
+//!
+//! let card = Card::new()
+//!     .theme(Theme::Info)
+//!     .module(Module::header("Vote"))
+//!     .module(Module::section("Do you like it?"))
+//!     .module(Module::action_group([
+//!         Button::new("Yes", "vote:yes").theme(Theme::Success),
+//!         Button::new("No", "vote:no").theme(Theme::Danger),
+//!     ]));
+//!
+//! // send by `ctx.reply_with(MessageType::Card, card)`
+//! let content = String::from(card);
+//! assert!(content.starts_with(r#"[{"type":"card","theme":"info""#));
+//! ```
+
+mod element;
+mod module;
+
+pub use element::{Button, Click, Element, Image};
+pub use module::{Countdown, CountdownMode, Media, Module, Section, SectionMode};
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Color theme of card and button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// blue
+    Primary,
+    /// green
+    Success,
+    /// red
+    Danger,
+    /// yellow
+    Warning,
+    /// light blue
+    Info,
+    /// grey
+    Secondary,
+    /// no color, for card only
+    None,
+    /// no border and background, for card only
+    Invisible,
+}
+
+/// Size of card and image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Size {
+    /// small
+    Sm,
+    /// large
+    Lg,
+}
+
+/// A card
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "card")]
+pub struct Card {
+    /// color theme, server default is primary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    /// color of left border like `#aaaaaa`, overrides theme
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// card size, server default is large
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<Size>,
+    /// modules, at most 50
+    #[serde(default)]
+    pub modules: Vec<Module>,
+}
+
+impl Card {
+    /// Create a empty card
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set color theme
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme.replace(theme);
+        self
+    }
+
+    /// Set color of left border, like `#aaaaaa`
+    pub fn color<S: Into<String>>(mut self, color: S) -> Self {
+        self.color.replace(color.into());
+        self
+    }
+
+    /// Set card size
+    pub fn size(mut self, size: Size) -> Self {
+        self.size.replace(size);
+        self
+    }
+
+    /// Add a module
+    ///
+    /// # Panics
+    ///
+    /// Panics if the card already has 50 modules.
+    pub fn module<M: Into<Module>>(mut self, module: M) -> Self {
+        assert!(self.modules.len() < 50, "card has at most 50 modules");
+        self.modules.push(module.into());
+        self
+    }
+
+    /// Add a divider module
+    pub fn divider(self) -> Self {
+        self.module(Module::Divider)
+    }
+
+    /// Buttons in action groups and section accessories
+    pub fn buttons(&self) -> impl Iterator<Item = &Button> {
+        self.modules
+            .iter()
+            .flat_map(Module::elements)
+            .filter_map(|element| match element {
+                Element::Button(button) => Some(button),
+                _ => None,
+            })
+    }
+
+    /// Countdown modules
+    pub fn countdowns(&self) -> impl Iterator<Item = &Countdown> {
+        self.modules.iter().filter_map(|module| match module {
+            Module::Countdown(countdown) => Some(countdown),
+            _ => None,
+        })
+    }
+}
+
+/// Content of a card message, at most 5 cards.
+///
+/// Parse content of received card messages by [str::parse], or [Event::cards].
+/// Modules which can't be recognized are kept as [Module::Unknown].
+///
+/// [Event::cards]: crate::ws::Event::cards
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardMessage(pub Vec<Card>);
+
+impl CardMessage {
+    /// Create a empty message
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a card
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message already has 5 cards.
+    pub fn card(mut self, card: Card) -> Self {
+        assert!(self.0.len() < 5, "card message has at most 5 cards");
+        self.0.push(card);
+        self
+    }
+
+    /// Serialize to message content
+    pub fn to_content(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl FromStr for CardMessage {
+    type Err = serde_json::Error;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(content)
+    }
+}
+
+impl From<Card> for CardMessage {
+    fn from(card: Card) -> Self {
+        Self(vec![card])
+    }
+}
+
+impl From<CardMessage> for String {
+    fn from(message: CardMessage) -> Self {
+        message.to_content()
+    }
+}
+
+impl From<Card> for String {
+    fn from(card: Card) -> Self {
+        CardMessage::from(card).to_content()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{kmarkdown::KMarkdown, models::Timestamp};
+
+    fn round_trip(card: &Card, expected: serde_json::Value) {
+        assert_eq!(serde_json::to_value(card).unwrap(), expected);
+        assert_eq!(&serde_json::from_value::<Card>(expected).unwrap(), card);
+    }
+
+    #[test]
+    fn test_card_empty() {
+        round_trip(&Card::new(), json!({ "type": "card", "modules": [] }));
+        round_trip(
+            &Card::new()
+                .theme(Theme::Warning)
+                .size(Size::Sm)
+                .color("#aaaaaa"),
+            json!({
+                "type": "card",
+                "theme": "warning",
+                "color": "#aaaaaa",
+                "size": "sm",
+                "modules": [],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_card_text_modules() {
+        let card = Card::new()
+            .module(Module::header("title"))
+            .module(
+                Section::new(KMarkdown::new().bold("hi"))
+                    .mode(SectionMode::Left)
+                    .accessory(Image::new("https://img/a.png").size(Size::Sm).circle()),
+            )
+            .module(Module::section(Element::paragraph(
+                2,
+                [Element::plain("a"), Element::kmarkdown("**b**")],
+            )))
+            .module(Section::new(Element::plain("link")).accessory(Button::link("go", "https://a")))
+            .divider()
+            .module(Module::context([
+                Element::plain("note"),
+                Image::new("https://img/b.png").alt("b").into(),
+            ]));
+
+        round_trip(
+            &card,
+            json!({
+                "type": "card",
+                "modules": [
+                    { "type": "header", "text": { "type": "plain-text", "content": "title" } },
+                    {
+                        "type": "section",
+                        "mode": "left",
+                        "text": { "type": "kmarkdown", "content": "**hi**" },
+                        "accessory": {
+                            "type": "image",
+                            "src": "https://img/a.png",
+                            "size": "sm",
+                            "circle": true,
+                        },
+                    },
+                    {
+                        "type": "section",
+                        "text": {
+                            "type": "paragraph",
+                            "cols": 2,
+                            "fields": [
+                                { "type": "plain-text", "content": "a" },
+                                { "type": "kmarkdown", "content": "**b**" },
+                            ],
+                        },
+                    },
+                    {
+                        "type": "section",
+                        "text": { "type": "plain-text", "content": "link" },
+                        "accessory": {
+                            "type": "button",
+                            "value": "https://a",
+                            "click": "link",
+                            "text": { "type": "plain-text", "content": "go" },
+                        },
+                    },
+                    { "type": "divider" },
+                    {
+                        "type": "context",
+                        "elements": [
+                            { "type": "plain-text", "content": "note" },
+                            { "type": "image", "src": "https://img/b.png", "alt": "b" },
+                        ],
+                    },
+                ],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_card_media_modules() {
+        let card = Card::new()
+            .module(Module::image_group([Image::new("a"), Image::new("b")]))
+            .module(Module::container([Image::new("c")]))
+            .module(Module::action_group([
+                Button::new("ok", "confirm").theme(Theme::Primary)
+            ]))
+            .module(Module::file("f", "file.txt"))
+            .module(Module::Audio(Media::new("m").title("song").cover("c")))
+            .module(Module::video("v", "clip"))
+            .module(
+                Countdown::new(CountdownMode::Second, Timestamp::from_millis(1608891600000))
+                    .start_time(Timestamp::from_millis(1608805200000)),
+            )
+            .module(Module::countdown(
+                CountdownMode::Day,
+                Timestamp::from_millis(1608891600000),
+            ))
+            .module(Module::invite("abcd"));
+
+        round_trip(
+            &card,
+            json!({
+                "type": "card",
+                "modules": [
+                    {
+                        "type": "image-group",
+                        "elements": [
+                            { "type": "image", "src": "a" },
+                            { "type": "image", "src": "b" },
+                        ],
+                    },
+                    { "type": "container", "elements": [{ "type": "image", "src": "c" }] },
+                    {
+                        "type": "action-group",
+                        "elements": [{
+                            "type": "button",
+                            "theme": "primary",
+                            "value": "confirm",
+                            "click": "return-val",
+                            "text": { "type": "plain-text", "content": "ok" },
+                        }],
+                    },
+                    { "type": "file", "src": "f", "title": "file.txt" },
+                    { "type": "audio", "src": "m", "title": "song", "cover": "c" },
+                    { "type": "video", "src": "v", "title": "clip" },
+                    {
+                        "type": "countdown",
+                        "mode": "second",
+                        "endTime": 1608891600000i64,
+                        "startTime": 1608805200000i64,
+                    },
+                    { "type": "countdown", "mode": "day", "endTime": 1608891600000i64 },
+                    { "type": "invite", "code": "abcd" },
+                ],
+            }),
+        );
+    }
+
+    #[test]
+    fn test_card_message_content() {
+        let message = CardMessage::new()
+            .card(Card::new().module(Module::header("a")))
+            .card(Card::new().theme(Theme::None));
+        let content = String::from(message.clone());
+        assert_eq!(
+            serde_json::from_str::<CardMessage>(&content).unwrap(),
+            message
+        );
+        assert_eq!(
+            String::from(Card::new()),
+            r#"[{"type":"card","modules":[]}]"#
+        );
+    }
+
+    #[test]
+    fn test_card_message_parse() {
+        let content = json!([{
+            "type": "card",
+            "theme": "secondary",
+            "modules": [
+                { "type": "countdown", "mode": "hour", "endTime": 1608891600000i64 },
+                { "type": "some-new-module", "data": 1 },
+                {
+                    "type": "section",
+                    "text": { "type": "plain-text", "content": "join?" },
+                    "accessory": {
+                        "type": "button",
+                        "value": "join",
+                        "click": "return-val",
+                        "text": { "type": "plain-text", "content": "join" },
+                    },
+                },
+                {
+                    "type": "action-group",
+                    "elements": [{
+                        "type": "button",
+                        "value": "https://a",
+                        "click": "link",
+                        "text": { "type": "kmarkdown", "content": "**open**" },
+                    }],
+                },
+            ],
+        }])
+        .to_string();
+
+        let message: CardMessage = content.parse().unwrap();
+        let card = &message.0[0];
+
+        assert_eq!(card.theme, Some(Theme::Secondary));
+        assert!(matches!(
+            &card.modules[1],
+            Module::Unknown(v) if v["type"] == "some-new-module"
+        ));
+
+        let countdowns: Vec<_> = card.countdowns().collect();
+        assert_eq!(countdowns.len(), 1);
+        assert_eq!(countdowns[0].mode, CountdownMode::Hour);
+        assert_eq!(
+            countdowns[0].end_time,
+            Timestamp::from_millis(1608891600000)
+        );
+
+        let buttons: Vec<_> = card.buttons().collect();
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0].value, "join");
+        assert_eq!(buttons[0].click, Some(Click::ReturnVal));
+        assert_eq!(buttons[1].text.as_text(), Some("**open**"));
+
+        // unknown modules are kept as is
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&content).unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_action_group_limit() {
+        Module::action_group((0..5).map(|i| Button::new("b", i.to_string())));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_section_accessory_type() {
+        let _ = Section::new(Element::plain("a")).accessory(Element::plain("b"));
+    }
+}
@@ -0,0 +1,335 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Button, Element, Image};
+use crate::models::Timestamp;
+
+/// Module of a card, shown from top to bottom
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", rename_all = "kebab-case")]
+pub enum Module {
+    /// title of card
+    Header {
+        /// plain text
+        text: Element,
+    },
+    /// text with an optional image or button beside it
+    Section(Section),
+    /// images in grid
+    ImageGroup {
+        /// 1 to 9 images
+        elements: Vec<Element>,
+    },
+    /// images in original size
+    Container {
+        /// 1 to 9 images
+        elements: Vec<Element>,
+    },
+    /// a row of buttons
+    ActionGroup {
+        /// at most 4 buttons
+        elements: Vec<Element>,
+    },
+    /// small text and images for remarks
+    Context {
+        /// at most 10 plain text, kmarkdown and image elements
+        elements: Vec<Element>,
+    },
+    /// a divider line
+    Divider,
+    /// a file
+    File(Media),
+    /// a audio
+    Audio(Media),
+    /// a video
+    Video(Media),
+    /// countdown to a time
+    Countdown(Countdown),
+    /// a guild invite
+    Invite {
+        /// invite code or invite url
+        code: String,
+    },
+    /// module can't be recognized, keep it as raw json
+    #[serde(skip)]
+    Unknown(serde_json::Value),
+}
+
+impl Serialize for Module {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Unknown(v) => v.serialize(serializer),
+            module => Module::serialize(module, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Module {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Ok(Module::deserialize(&value).unwrap_or(Self::Unknown(value)))
+    }
+}
+
+impl Module {
+    /// Create a header with plain text
+    pub fn header<S: Into<String>>(text: S) -> Self {
+        Self::Header {
+            text: Element::plain(text),
+        }
+    }
+
+    /// Create a section with text, use [Section] for accessory and mode
+    pub fn section<E: Into<Element>>(text: E) -> Self {
+        Self::Section(Section::new(text))
+    }
+
+    /// Create a image group
+    ///
+    /// # Panics
+    ///
+    /// Panics if image count is not in 1 to 9.
+    pub fn image_group<I: IntoIterator<Item = Image>>(images: I) -> Self {
+        Self::ImageGroup {
+            elements: images_elements(images),
+        }
+    }
+
+    /// Create a container of images
+    ///
+    /// # Panics
+    ///
+    /// Panics if image count is not in 1 to 9.
+    pub fn container<I: IntoIterator<Item = Image>>(images: I) -> Self {
+        Self::Container {
+            elements: images_elements(images),
+        }
+    }
+
+    /// Create a row of buttons
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 4 buttons.
+    pub fn action_group<I: IntoIterator<Item = Button>>(buttons: I) -> Self {
+        let elements: Vec<_> = buttons.into_iter().map(Element::Button).collect();
+        assert!(elements.len() <= 4, "action group has at most 4 buttons");
+        Self::ActionGroup { elements }
+    }
+
+    /// Create a context module
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 10 elements, or any of them is not plain text, kmarkdown or
+    /// image.
+    pub fn context<I, E>(elements: I) -> Self
+    where
+        I: IntoIterator<Item = E>,
+        E: Into<Element>,
+    {
+        let elements: Vec<_> = elements.into_iter().map(Into::into).collect();
+        assert!(elements.len() <= 10, "context has at most 10 elements");
+        assert!(
+            elements.iter().all(|e| matches!(
+                e,
+                Element::PlainText { .. } | Element::KMarkdown { .. } | Element::Image(_)
+            )),
+            "context only contains plain text, kmarkdown and image"
+        );
+        Self::Context { elements }
+    }
+
+    /// Create a file module
+    pub fn file<S: Into<String>, T: Into<String>>(src: S, title: T) -> Self {
+        Self::File(Media::new(src).title(title))
+    }
+
+    /// Create a audio module, use [Media] for cover
+    pub fn audio<S: Into<String>, T: Into<String>>(src: S, title: T) -> Self {
+        Self::Audio(Media::new(src).title(title))
+    }
+
+    /// Create a video module
+    pub fn video<S: Into<String>, T: Into<String>>(src: S, title: T) -> Self {
+        Self::Video(Media::new(src).title(title))
+    }
+
+    /// Create a countdown to `end_time`, use [Countdown] for start time
+    pub fn countdown(mode: CountdownMode, end_time: Timestamp) -> Self {
+        Self::Countdown(Countdown::new(mode, end_time))
+    }
+
+    /// Create a guild invite
+    pub fn invite<S: Into<String>>(code: S) -> Self {
+        Self::Invite { code: code.into() }
+    }
+
+    /// Elements directly in the module, paragraph fields and button texts are not included
+    pub fn elements(&self) -> Vec<&Element> {
+        match self {
+            Self::Header { text } => vec![text],
+            Self::Section(section) => std::iter::once(&section.text)
+                .chain(section.accessory.as_ref())
+                .collect(),
+            Self::ImageGroup { elements }
+            | Self::Container { elements }
+            | Self::ActionGroup { elements }
+            | Self::Context { elements } => elements.iter().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn images_elements<I: IntoIterator<Item = Image>>(images: I) -> Vec<Element> {
+    let elements: Vec<_> = images.into_iter().map(Element::Image).collect();
+    assert!(
+        (1..=9).contains(&elements.len()),
+        "image count must be in 1..=9"
+    );
+    elements
+}
+
+/// Where the accessory of section is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionMode {
+    /// accessory on the left
+    Left,
+    /// accessory on the right
+    Right,
+}
+
+/// Section module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Section {
+    /// accessory position, server default is right
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<SectionMode>,
+    /// plain text, kmarkdown or paragraph
+    pub text: Element,
+    /// image or button
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessory: Option<Element>,
+}
+
+impl Section {
+    /// Create a section with text
+    pub fn new<E: Into<Element>>(text: E) -> Self {
+        Self {
+            mode: None,
+            text: text.into(),
+            accessory: None,
+        }
+    }
+
+    /// Set accessory position
+    pub fn mode(mut self, mode: SectionMode) -> Self {
+        self.mode.replace(mode);
+        self
+    }
+
+    /// Add a image or button beside the text
+    ///
+    /// # Panics
+    ///
+    /// Panics if accessory is not a image or button.
+    pub fn accessory<E: Into<Element>>(mut self, accessory: E) -> Self {
+        let accessory = accessory.into();
+        assert!(
+            matches!(accessory, Element::Image(_) | Element::Button(_)),
+            "section accessory must be image or button"
+        );
+        self.accessory.replace(accessory);
+        self
+    }
+}
+
+impl From<Section> for Module {
+    fn from(section: Section) -> Self {
+        Self::Section(section)
+    }
+}
+
+/// File, audio or video module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Media {
+    /// url, should be uploaded to kaiheila first
+    pub src: String,
+    /// title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// cover image url, only used by audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover: Option<String>,
+}
+
+impl Media {
+    /// Create with url
+    pub fn new<S: Into<String>>(src: S) -> Self {
+        Self {
+            src: src.into(),
+            title: None,
+            cover: None,
+        }
+    }
+
+    /// Set title
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title.replace(title.into());
+        self
+    }
+
+    /// Set cover image url
+    pub fn cover<S: Into<String>>(mut self, cover: S) -> Self {
+        self.cover.replace(cover.into());
+        self
+    }
+}
+
+/// Display style of countdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountdownMode {
+    /// days, hours, minutes and seconds
+    Day,
+    /// hours, minutes and seconds
+    Hour,
+    /// seconds, with a progress bar from start time
+    Second,
+}
+
+/// Countdown module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Countdown {
+    /// display style
+    pub mode: CountdownMode,
+    /// time to count down to
+    pub end_time: Timestamp,
+    /// start time of progress bar, only used by [CountdownMode::Second]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<Timestamp>,
+}
+
+impl Countdown {
+    /// Create a countdown to `end_time`
+    pub fn new(mode: CountdownMode, end_time: Timestamp) -> Self {
+        Self {
+            mode,
+            end_time,
+            start_time: None,
+        }
+    }
+
+    /// Set start time of progress bar
+    pub fn start_time(mut self, start_time: Timestamp) -> Self {
+        self.start_time.replace(start_time);
+        self
+    }
+}
+
+impl From<Countdown> for Module {
+    fn from(countdown: Countdown) -> Self {
+        Self::Countdown(countdown)
+    }
+}
@@ -0,0 +1,405 @@
+use std::{collections::VecDeque, fmt::Display, str::FromStr};
+
+use snafu::prelude::*;
+
+use crate::kmarkdown::Mention;
+
+/// Error when parse command arguments.
+#[derive(Debug, Snafu, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub(crate)), context(suffix(false)))]
+pub enum ArgsError {
+    /// A quote is not closed
+    #[snafu(display("unclosed quote in arguments"))]
+    UnclosedQuote,
+
+    /// A required argument is not given
+    #[snafu(display("missing argument <{name}>"))]
+    MissingArgument {
+        /// argument name
+        name: &'static str,
+    },
+
+    /// A argument can't be parsed as wanted type
+    #[snafu(display("invalid argument <{name}> {value:?}: {reason}"))]
+    InvalidArgument {
+        /// argument name
+        name: &'static str,
+        /// given value
+        value: String,
+        /// parse error message
+        reason: String,
+    },
+
+    /// More arguments than needed are given
+    #[snafu(display("too many arguments: {extra:?}"))]
+    TooManyArguments {
+        /// extra arguments
+        extra: Vec<String>,
+    },
+}
+
+/// Error when parse a mention argument.
+#[derive(Debug, Snafu, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub(crate)), context(suffix(false)))]
+pub enum ParseMentionError {
+    /// Not a `(met)user_id(met)` mention
+    #[snafu(display("not a user mention"))]
+    NotUserMention,
+
+    /// Not a `(rol)role_id(rol)` mention
+    #[snafu(display("not a role mention"))]
+    NotRoleMention,
+
+    /// Not a `(chn)channel_id(chn)` mention
+    #[snafu(display("not a channel mention"))]
+    NotChannelMention,
+}
+
+/// Argument string of a invoked command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    raw: String,
+}
+
+impl Args {
+    pub(crate) fn new<S: Into<String>>(raw: S) -> Self {
+        Self { raw: raw.into() }
+    }
+
+    /// The whole argument string, trimmed.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// true if no argument is given.
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Iterate over whitespace separated arguments.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.raw.split_whitespace()
+    }
+
+    /// Get the nth whitespace separated argument.
+    pub fn get(&self, n: usize) -> Option<&str> {
+        self.iter().nth(n)
+    }
+
+    /// Parse the nth whitespace separated argument, None if it doesn't exist or can't be parsed.
+    pub fn parse<T: FromStr>(&self, n: usize) -> Option<T> {
+        self.get(n)?.parse().ok()
+    }
+
+    /// Split arguments like a shell, `"..."` and `'...'` can be used to include whitespaces,
+    /// and `\` escapes next char outside single quotes.
+    pub fn tokens(&self) -> Result<Vec<String>, ArgsError> {
+        let mut tokens = vec![];
+        let mut current: Option<String> = None;
+        let mut quote = None;
+        let mut chars = self.raw.chars();
+
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+                (_, '\\') => {
+                    let escaped = chars.next().unwrap_or('\\');
+                    current.get_or_insert_with(String::new).push(escaped);
+                }
+                (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    current.get_or_insert_with(String::new);
+                }
+                (None, c) if c.is_whitespace() => tokens.extend(current.take()),
+                (None, c) => current.get_or_insert_with(String::new).push(c),
+            }
+        }
+
+        ensure!(quote.is_none(), UnclosedQuote);
+        tokens.extend(current);
+
+        Ok(tokens)
+    }
+
+    /// Create a parser to parse arguments one by one.
+    pub fn parser(&self) -> Result<ArgsParser, ArgsError> {
+        Ok(ArgsParser {
+            tokens: self.tokens()?.into(),
+        })
+    }
+
+    /// Parse arguments into type `T`, all arguments must be consumed.
+    pub fn extract<T: FromArgs>(&self) -> Result<T, ArgsError> {
+        let mut parser = self.parser()?;
+        let value = T::from_args(&mut parser)?;
+        parser.finish()?;
+        Ok(value)
+    }
+}
+
+/// Parse tokenized arguments one by one.
+#[derive(Debug, Clone)]
+pub struct ArgsParser {
+    tokens: VecDeque<String>,
+}
+
+impl ArgsParser {
+    fn parse<T>(name: &'static str, value: String) -> Result<T, ArgsError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        value.parse().map_err(|e: T::Err| {
+            InvalidArgument {
+                name,
+                reason: e.to_string(),
+                value,
+            }
+            .build()
+        })
+    }
+
+    /// Parse next argument as type `T`, error if no more arguments.
+    pub fn required<T>(&mut self, name: &'static str) -> Result<T, ArgsError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.tokens.pop_front().context(MissingArgument { name })?;
+        Self::parse(name, value)
+    }
+
+    /// Parse next argument as type `T` if there is one.
+    pub fn optional<T>(&mut self, name: &'static str) -> Result<Option<T>, ArgsError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.tokens
+            .pop_front()
+            .map(|value| Self::parse(name, value))
+            .transpose()
+    }
+
+    /// Take all remaining arguments.
+    pub fn rest(&mut self) -> Vec<String> {
+        self.tokens.drain(..).collect()
+    }
+
+    /// Make sure all arguments are consumed.
+    pub fn finish(self) -> Result<(), ArgsError> {
+        ensure!(
+            self.tokens.is_empty(),
+            TooManyArguments {
+                extra: Vec::from(self.tokens)
+            }
+        );
+        Ok(())
+    }
+}
+
+/// Type can be parsed from command arguments, used by [Command::typed](super::Command::typed).
+///
+/// ```
+/// use burz::command::{ArgsError, ArgsParser, FromArgs, UserMention};
+///
+/// struct Ban {
+///     user: UserMention,
+///     days: Option<u32>,
+///     reason: String,
+/// }
+///
+/// impl FromArgs for Ban {
+///     fn from_args(p: &mut ArgsParser) -> Result<Self, ArgsError> {
+///         Ok(Self {
+///             user: p.required("user")?,
+///             days: p.optional("days")?,
+///             reason: p.rest().join(" "),
+///         })
+///     }
+/// }
+/// ```
+pub trait FromArgs: Sized {
+    /// Parse self from arguments.
+    fn from_args(parser: &mut ArgsParser) -> Result<Self, ArgsError>;
+}
+
+impl FromArgs for () {
+    fn from_args(_parser: &mut ArgsParser) -> Result<Self, ArgsError> {
+        Ok(())
+    }
+}
+
+impl FromArgs for Vec<String> {
+    fn from_args(parser: &mut ArgsParser) -> Result<Self, ArgsError> {
+        Ok(parser.rest())
+    }
+}
+
+/// A user mention argument, like `(met)user_id(met)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserMention {
+    /// mentioned user id
+    pub id: String,
+}
+
+impl FromStr for UserMention {
+    type Err = ParseMentionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(Mention::User(id)) => Ok(Self { id }),
+            _ => NotUserMention.fail(),
+        }
+    }
+}
+
+/// A role mention argument, like `(rol)role_id(rol)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleMention {
+    /// mentioned role id
+    pub id: String,
+}
+
+impl FromStr for RoleMention {
+    type Err = ParseMentionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(Mention::Role(id)) => Ok(Self { id }),
+            _ => NotRoleMention.fail(),
+        }
+    }
+}
+
+/// A channel mention argument, like `(chn)channel_id(chn)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMention {
+    /// mentioned channel id
+    pub id: String,
+}
+
+impl FromStr for ChannelMention {
+    type Err = ParseMentionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(Mention::Channel(id)) => Ok(Self { id }),
+            _ => NotChannelMention.fail(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command_args() {
+        let args = Args::new("3  dice\tfoo");
+
+        assert_eq!(args.iter().collect::<Vec<_>>(), ["3", "dice", "foo"]);
+        assert_eq!(args.get(1), Some("dice"));
+        assert_eq!(args.parse::<u32>(0), Some(3));
+        assert_eq!(args.parse::<u32>(1), None);
+        assert_eq!(args.get(3), None);
+        assert!(Args::new("").is_empty());
+    }
+
+    #[test]
+    fn test_command_args_tokens() {
+        let args = Args::new(r#"a "b c" 'd \ e' f\ g "" h"i""#);
+        assert_eq!(
+            args.tokens().unwrap(),
+            ["a", "b c", r"d \ e", "f g", "", "hi"]
+        );
+
+        assert_eq!(Args::new(r#"a "b"#).tokens(), Err(ArgsError::UnclosedQuote));
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Give {
+        user: UserMention,
+        amount: i64,
+        note: Option<String>,
+    }
+
+    impl FromArgs for Give {
+        fn from_args(p: &mut ArgsParser) -> Result<Self, ArgsError> {
+            Ok(Self {
+                user: p.required("user")?,
+                amount: p.required("amount")?,
+                note: p.optional("note")?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_command_args_extract() {
+        assert_eq!(
+            Args::new(r#"(met)123(met) -5 "for lunch""#).extract::<Give>(),
+            Ok(Give {
+                user: UserMention {
+                    id: "123".to_string()
+                },
+                amount: -5,
+                note: Some("for lunch".to_string()),
+            })
+        );
+        assert!(Args::new("(met)123(met) 5")
+            .extract::<Give>()
+            .unwrap()
+            .note
+            .is_none());
+
+        assert_eq!(
+            Args::new("(met)123(met)").extract::<Give>(),
+            Err(ArgsError::MissingArgument { name: "amount" })
+        );
+        assert!(matches!(
+            Args::new("someone 5").extract::<Give>(),
+            Err(ArgsError::InvalidArgument { name: "user", .. })
+        ));
+        assert!(matches!(
+            Args::new("(met)123(met) 5 a b").extract::<Give>(),
+            Err(ArgsError::TooManyArguments { .. })
+        ));
+    }
+
+    #[test]
+    fn test_command_args_mentions() {
+        let args = Args::new("(met)1(met) (rol)2(rol) (chn)3(chn) (met)all(met)");
+
+        assert_eq!(
+            args.parse(0),
+            Some(UserMention {
+                id: "1".to_string()
+            })
+        );
+        assert_eq!(
+            args.parse(1),
+            Some(RoleMention {
+                id: "2".to_string()
+            })
+        );
+        assert_eq!(
+            args.parse(2),
+            Some(ChannelMention {
+                id: "3".to_string()
+            })
+        );
+        assert_eq!(args.parse::<UserMention>(1), None);
+        assert_eq!(args.parse::<UserMention>(3), None);
+        assert_eq!(args.parse(3), Some(Mention::All));
+        assert_eq!(
+            "(rol)2(rol)".parse::<UserMention>(),
+            Err(ParseMentionError::NotUserMention)
+        );
+        assert_eq!(
+            "(met)1(met)".parse::<ChannelMention>(),
+            Err(ParseMentionError::NotChannelMention)
+        );
+    }
+}
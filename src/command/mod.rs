@@ -0,0 +1,493 @@
+//! Command framework.
+//!
+//! Define [Command]s with names, aliases, descriptions and filters,
+//! collect them into a [CommandRegistry], then mount it by [Bot::commands](crate::Bot::commands).
+
+mod args;
+
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
+
+pub use args::{
+    Args, ArgsError, ArgsParser, ChannelMention, FromArgs, ParseMentionError, RoleMention,
+    UserMention,
+};
+
+use crate::{
+    context::{BotContext, EventContext},
+    filter::{strip_leading_mentions, Filter},
+    models::User,
+    subscriber::{BoxError, Subscriber},
+    ws::Event,
+};
+
+const DEFAULT_PREFIX: &str = "/";
+
+/// Handler of a command.
+#[async_trait::async_trait]
+pub trait CommandHandler: Send + Sync {
+    /// callback will be execute when the command is invoked,
+    /// returned error will be passed to error handler of the registry
+    async fn on_command(self: Arc<Self>, ctx: EventContext, args: Args) -> Result<(), ArgsError>;
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> CommandHandler for F
+where
+    F: Fn(EventContext, Args) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_command(self: Arc<Self>, ctx: EventContext, args: Args) -> Result<(), ArgsError> {
+        self(ctx, args).await;
+        Ok(())
+    }
+}
+
+/// Command handler which receives arguments parsed as type `A`.
+struct Typed<A, H> {
+    handler: H,
+    _args: PhantomData<fn() -> A>,
+}
+
+#[async_trait::async_trait]
+impl<A, H, Fut> CommandHandler for Typed<A, H>
+where
+    A: FromArgs + Send + 'static,
+    H: Fn(EventContext, A) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_command(self: Arc<Self>, ctx: EventContext, args: Args) -> Result<(), ArgsError> {
+        let args = args.extract()?;
+        (self.handler)(ctx, args).await;
+        Ok(())
+    }
+}
+
+/// Handler of command argument errors.
+#[async_trait::async_trait]
+pub trait CommandErrorHandler: Send + Sync {
+    /// callback will be execute when arguments of a invoked command is invalid
+    async fn on_error(self: Arc<Self>, ctx: EventContext, command: String, error: ArgsError);
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> CommandErrorHandler for F
+where
+    F: Fn(EventContext, String, ArgsError) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_error(self: Arc<Self>, ctx: EventContext, command: String, error: ArgsError) {
+        self(ctx, command, error).await
+    }
+}
+
+/// A command, with its name, aliases, description, filter and handler.
+pub struct Command {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    filter: Option<Box<dyn Filter + 'static>>,
+    handler: Arc<dyn CommandHandler + 'static>,
+}
+
+impl Debug for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Command")
+            .field("name", &self.name)
+            .field("aliases", &self.aliases)
+            .field("description", &self.description)
+            .field("filter", &self.filter.is_some())
+            .finish()
+    }
+}
+
+impl Command {
+    /// Create a command with name and handler
+    pub fn new<S, H>(name: S, handler: H) -> Self
+    where
+        S: Into<String>,
+        H: CommandHandler + 'static,
+    {
+        Self {
+            name: name.into(),
+            aliases: vec![],
+            description: String::new(),
+            filter: None,
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Create a command whose handler receives arguments parsed as type `A`
+    pub fn typed<S, A, H, Fut>(name: S, handler: H) -> Self
+    where
+        S: Into<String>,
+        A: FromArgs + Send + 'static,
+        H: Fn(EventContext, A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::new(
+            name,
+            Typed {
+                handler,
+                _args: PhantomData,
+            },
+        )
+    }
+
+    /// Add a alias name
+    pub fn alias<S: Into<String>>(mut self, alias: S) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    /// Set description, which is used in help text
+    pub fn description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Set a filter, the command only be invoked by events pass it
+    pub fn filter<F: Filter + 'static>(mut self, filter: F) -> Self {
+        self.filter.replace(Box::new(filter));
+        self
+    }
+
+    /// Command name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Command aliases
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+}
+
+/// Dispatch command messages to registered commands.
+pub struct CommandRegistry {
+    prefixes: Arc<RwLock<Vec<String>>>,
+    commands: Vec<Command>,
+    error_handler: Option<Arc<dyn CommandErrorHandler + 'static>>,
+}
+
+impl Debug for CommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandRegistry")
+            .field("prefixes", &self.prefixes)
+            .field("commands", &self.commands)
+            .field("error_handler", &self.error_handler.is_some())
+            .finish()
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self {
+            prefixes: Arc::new(RwLock::new(vec![DEFAULT_PREFIX.to_string()])),
+            commands: vec![],
+            error_handler: None,
+        }
+    }
+}
+
+impl CommandRegistry {
+    /// Create a empty registry, with default prefix `/`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set command prefix
+    pub fn prefix<S: Into<String>>(&mut self, prefix: S) -> &mut Self {
+        self.prefixes(Some(prefix))
+    }
+
+    /// Set multiple command prefixes, longer ones are tried first
+    pub fn prefixes<I>(&mut self, prefixes: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut prefixes: Vec<String> = prefixes.into_iter().map(Into::into).collect();
+        prefixes.sort_by_key(|p| std::cmp::Reverse(p.len()));
+        self.prefixes = Arc::new(RwLock::new(prefixes));
+        self
+    }
+
+    /// Use prefixes from bot config, which can be changed by reloading config
+    pub(crate) fn share_prefixes(&mut self, prefixes: Arc<RwLock<Vec<String>>>) {
+        self.prefixes = prefixes;
+    }
+
+    /// Register a command
+    pub fn register(&mut self, command: Command) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Set handler of command argument errors, errors are only logged if not set
+    pub fn on_error<H: CommandErrorHandler + 'static>(&mut self, handler: H) -> &mut Self {
+        self.error_handler.replace(Arc::new(handler));
+        self
+    }
+
+    /// Registered commands
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Generate help text of all registered commands, one line per command
+    pub fn help(&self) -> String {
+        let prefixes = self.prefixes.read().unwrap();
+        let prefix = prefixes.last().map(String::as_str).unwrap_or_default();
+
+        self.commands
+            .iter()
+            .map(|c| {
+                let mut line = format!("{}{}", prefix, c.name);
+                if !c.aliases.is_empty() {
+                    line.push_str(&format!(" ({})", c.aliases.join(", ")));
+                }
+                if !c.description.is_empty() {
+                    line.push_str(&format!(": {}", c.description));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub(crate) fn find(&self, event: &Event) -> Option<(&Command, Args)> {
+        let content = strip_leading_mentions(&event.as_message()?.content);
+        let content = self
+            .prefixes
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|p| content.strip_prefix(p.as_str()))?;
+
+        let (name, rest) = content
+            .split_once(char::is_whitespace)
+            .unwrap_or((content, ""));
+
+        let command = self.commands.iter().find(|c| c.is_called(name))?;
+
+        if command
+            .filter
+            .as_ref()
+            .is_some_and(|f| !f.filter_event(event))
+        {
+            return None;
+        }
+
+        Some((command, Args::new(rest.trim())))
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscriber for CommandRegistry {
+    fn name(&self) -> Cow<'static, str> {
+        "Command Registry".into()
+    }
+
+    async fn on_loaded(&mut self, ctx: BotContext) {
+        for filter in self.commands.iter_mut().filter_map(|c| c.filter.as_mut()) {
+            filter.on_loaded(ctx.me());
+        }
+    }
+
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError> {
+        if let Some(invocation) = self.find(&ctx).map(|(c, args)| self.invocation(c, args)) {
+            invocation.run(ctx).await;
+        }
+        Ok(())
+    }
+}
+
+impl CommandRegistry {
+    fn invocation(&self, command: &Command, args: Args) -> Invocation {
+        Invocation {
+            name: command.name.clone(),
+            handler: Arc::clone(&command.handler),
+            args,
+            error_handler: self.error_handler.clone(),
+        }
+    }
+}
+
+/// A found command with its arguments, can be run without borrowing the registry.
+struct Invocation {
+    name: String,
+    handler: Arc<dyn CommandHandler + 'static>,
+    args: Args,
+    error_handler: Option<Arc<dyn CommandErrorHandler + 'static>>,
+}
+
+impl Invocation {
+    async fn run(self, ctx: EventContext) {
+        debug!(
+            "Command {} invoked with args {:?}",
+            self.name,
+            self.args.raw()
+        );
+        let result = self.handler.on_command(ctx.clone(), self.args).await;
+
+        if let Err(err) = result {
+            debug!("Command {} arguments invalid: {}", self.name, err);
+            if let Some(handler) = self.error_handler {
+                handler.on_error(ctx, self.name, err).await
+            }
+        }
+    }
+}
+
+/// A registry mounted by [Bot::commands](crate::Bot::commands), used as both filter and
+/// subscriber, so only events invoking a command are accepted.
+#[derive(Debug, Clone)]
+pub(crate) struct Mounted(Arc<RwLock<CommandRegistry>>);
+
+impl Mounted {
+    pub(crate) fn new(registry: CommandRegistry) -> Self {
+        Self(Arc::new(RwLock::new(registry)))
+    }
+}
+
+impl Filter for Mounted {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.0.read().unwrap().find(event).is_some()
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        let mut registry = self.0.write().unwrap();
+        for filter in registry
+            .commands
+            .iter_mut()
+            .filter_map(|c| c.filter.as_mut())
+        {
+            filter.on_loaded(me);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscriber for Mounted {
+    fn name(&self) -> Cow<'static, str> {
+        "Command Registry".into()
+    }
+
+    // command filters are loaded as filter of the subscription
+    async fn on_loaded(&mut self, _ctx: BotContext) {}
+
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError> {
+        let invocation = {
+            let registry = self.0.read().unwrap();
+            registry
+                .find(&ctx)
+                .map(|(c, args)| registry.invocation(c, args))
+        };
+        if let Some(invocation) = invocation {
+            invocation.run(ctx).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{filter, ws::event::EventBody};
+
+    fn message(author_id: &str, content: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            author_id: author_id.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_command_registry_find() {
+        let mut registry = CommandRegistry::new();
+        registry
+            .prefixes(["!", "!!"])
+            .register(
+                Command::new("roll", |_, _| async {})
+                    .alias("r")
+                    .description("roll a dice"),
+            )
+            .register(Command::new("ban", |_, _| async {}).filter(filter::author("admin")));
+
+        let (c, args) = registry.find(&message("u", "!roll 1d6")).unwrap();
+        assert_eq!(c.name(), "roll");
+        assert_eq!(args.raw(), "1d6");
+
+        let (c, args) = registry.find(&message("u", "(met)bot(met) !!r")).unwrap();
+        assert_eq!(c.name(), "roll");
+        assert!(args.is_empty());
+
+        assert!(registry.find(&message("u", "!rolls")).is_none());
+        assert!(registry.find(&message("u", "/roll")).is_none());
+        assert!(registry.find(&message("u", "!ban someone")).is_none());
+        assert!(registry.find(&message("admin", "!ban someone")).is_some());
+
+        assert_eq!(registry.help(), "!roll (r): roll a dice\n!ban");
+    }
+
+    #[tokio::test]
+    async fn test_command_registry_loads_filters() {
+        let mut registry = CommandRegistry::new();
+        registry.register(Command::new("roll", |_, _| async {}).filter(filter::not_self()));
+
+        registry.on_loaded(BotContext::mock()).await;
+
+        assert!(registry.find(&message("bot-user-id", "/roll")).is_none());
+        assert!(registry.find(&message("u", "/roll")).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_command_registry_typed_error() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut registry = CommandRegistry::new();
+        registry
+            .register(Command::typed("add", |_, Pair(a, b)| async move {
+                assert_eq!(a + b, 3);
+            }))
+            .on_error(move |_, command: String, error: ArgsError| {
+                let tx = tx.clone();
+                async move { tx.send((command, error)).unwrap() }
+            });
+        let registry = Arc::new(registry);
+
+        Arc::clone(&registry)
+            .on_event(EventContext::mock(message("u", "/add 1 2")))
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        Arc::clone(&registry)
+            .on_event(EventContext::mock(message("u", "/add 1")))
+            .await
+            .unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ("add".to_string(), ArgsError::MissingArgument { name: "b" })
+        );
+    }
+
+    struct Pair(i32, i32);
+
+    impl FromArgs for Pair {
+        fn from_args(p: &mut ArgsParser) -> Result<Self, ArgsError> {
+            Ok(Self(p.required("a")?, p.required("b")?))
+        }
+    }
+}
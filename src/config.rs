@@ -0,0 +1,285 @@
+//! Bot configuration which can be loaded from file and reloaded while running.
+
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+
+use crate::{
+    api::{self, RateLimitOverflow, SendRateLimit},
+    error,
+    ws::Event,
+    Result,
+};
+
+/// Bot configuration, can be deserialized from json.
+///
+/// Apply it by [BotBuilder::config](crate::BotBuilder::config), and reload it while bot running
+/// by [BotHandle::reload_config](crate::BotHandle::reload_config). Prefixes, filter lists and
+/// rate limits take effect immediately, token and enabled plugins need a restart.
+///
+/// ```json
+/// {
+///     "token": "...",
+///     "prefixes": ["!", "/"],
+///     "plugins": ["ping"],
+///     "filters": { "block_users": ["1234"] },
+///     "rate_limit": { "per_channel": { "count": 5, "secs": 5 } }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// bot token
+    pub token: String,
+    /// command prefixes of all [CommandRegistry](crate::command::CommandRegistry)s,
+    /// empty means registries use their own prefixes
+    pub prefixes: Vec<String>,
+    /// names of enabled plugins, `None` means all plugins are enabled
+    pub plugins: Option<Vec<String>>,
+    /// guild, channel and user lists to filter events
+    pub filters: FilterConfig,
+    /// rate limit of message send apis
+    pub rate_limit: RateLimitConfig,
+}
+
+impl Config {
+    /// Parse config from json string
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context(error::ParseConfigFailed)
+    }
+
+    /// Read and parse config from a json file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).context(error::ReadConfigFailed { path })?;
+        Self::from_json(&json)
+    }
+
+    /// Check if the plugin is enabled
+    pub fn is_plugin_enabled(&self, name: &str) -> bool {
+        self.plugins
+            .as_ref()
+            .is_none_or(|plugins| plugins.iter().any(|p| p == name))
+    }
+}
+
+/// Guild, channel and user lists to filter events, events rejected by them are ignored by bot.
+///
+/// Empty allow list means allow all.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// only accept events from these guilds
+    pub allow_guilds: Vec<String>,
+    /// ignore events from these guilds
+    pub block_guilds: Vec<String>,
+    /// only accept events from these channels
+    pub allow_channels: Vec<String>,
+    /// ignore events from these channels
+    pub block_channels: Vec<String>,
+    /// ignore events from these users
+    pub block_users: Vec<String>,
+}
+
+fn allowed(allow: &[String], block: &[String], id: Option<&str>) -> bool {
+    match id {
+        Some(id) => {
+            (allow.is_empty() || allow.iter().any(|a| a == id)) && !block.iter().any(|b| b == id)
+        }
+        None => true,
+    }
+}
+
+impl FilterConfig {
+    /// Check if the event is accepted
+    pub fn accept(&self, event: &Event) -> bool {
+        allowed(&self.allow_guilds, &self.block_guilds, event.guild_id())
+            && allowed(
+                &self.allow_channels,
+                &self.block_channels,
+                event.channel_id(),
+            )
+            && allowed(
+                &[],
+                &self.block_users,
+                event.as_message().map(|b| b.author_id.as_str()),
+            )
+    }
+}
+
+/// Message count limit in a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimitConfig {
+    /// max message count
+    pub count: usize,
+    /// period in seconds
+    pub secs: u64,
+}
+
+/// Rate limit of message send apis, see [SendRateLimit].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// limit across all channels
+    pub global: Option<LimitConfig>,
+    /// limit of each channel or user
+    pub per_channel: Option<LimitConfig>,
+    /// fail instead of waiting when limit is exceeded
+    pub reject: bool,
+}
+
+impl From<&RateLimitConfig> for SendRateLimit {
+    fn from(config: &RateLimitConfig) -> Self {
+        let mut limit = SendRateLimit::default();
+        if let Some(LimitConfig { count, secs }) = config.global {
+            limit = limit.global(count, Duration::from_secs(secs));
+        }
+        if let Some(LimitConfig { count, secs }) = config.per_channel {
+            limit = limit.per_channel(count, Duration::from_secs(secs));
+        }
+        if config.reject {
+            limit = limit.overflow(RateLimitOverflow::Reject);
+        }
+        limit
+    }
+}
+
+/// Longer prefixes are tried first.
+fn sorted(mut prefixes: Vec<String>) -> Vec<String> {
+    prefixes.sort_by_key(|p| std::cmp::Reverse(p.len()));
+    prefixes
+}
+
+/// Config in use by a running bot, shared with its handles.
+#[derive(Debug, Default)]
+pub(crate) struct LiveConfig {
+    current: RwLock<Arc<Config>>,
+    prefixes: Arc<RwLock<Vec<String>>>,
+    api: Option<api::Client>,
+}
+
+impl LiveConfig {
+    pub(crate) fn new(config: Config, api: api::Client) -> Self {
+        Self {
+            prefixes: Arc::new(RwLock::new(sorted(config.prefixes.clone()))),
+            current: RwLock::new(Arc::new(config)),
+            api: Some(api),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Arc<Config> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Shared command prefixes, `None` if config does not set prefixes.
+    pub(crate) fn prefixes(&self) -> Option<Arc<RwLock<Vec<String>>>> {
+        (!self.get().prefixes.is_empty()).then(|| Arc::clone(&self.prefixes))
+    }
+
+    pub(crate) fn accept(&self, event: &Event) -> bool {
+        self.current.read().unwrap().filters.accept(event)
+    }
+
+    pub(crate) fn reload(&self, config: Config) {
+        let old = self.get();
+
+        if old.token != config.token {
+            warn!("Token change in config will take effect after restart");
+        }
+        if old.plugins != config.plugins {
+            warn!("Plugin change in config will take effect after restart");
+        }
+
+        if !config.prefixes.is_empty() {
+            *self.prefixes.write().unwrap() = sorted(config.prefixes.clone());
+        }
+
+        if old.rate_limit != config.rate_limit {
+            if let Some(api) = &self.api {
+                api.update_send_rate_limit((&config.rate_limit).into());
+            }
+        }
+
+        *self.current.write().unwrap() = Arc::new(config);
+        info!("Config reloaded");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::{EventBody, MessageExtra};
+
+    fn message(guild: &str, channel: &str, author: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            target_id: channel.to_string(),
+            author_id: author.to_string(),
+            extra: MessageExtra {
+                guild_id: guild.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let config = Config::from_json(
+            r#"{
+                "token": "token",
+                "prefixes": ["!"],
+                "filters": { "block_users": ["bad"] },
+                "rate_limit": { "per_channel": { "count": 5, "secs": 10 }, "reject": true }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.token, "token");
+        assert_eq!(config.prefixes, ["!"]);
+        assert!(config.is_plugin_enabled("any"));
+
+        let limit = SendRateLimit::from(&config.rate_limit);
+        assert_eq!(limit.get_global(), None);
+        assert_eq!(limit.get_per_channel(), Some((5, Duration::from_secs(10))));
+        assert_eq!(limit.get_overflow(), RateLimitOverflow::Reject);
+
+        assert!(Config::from_json("{\"prefixes\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_filter_config_accept() {
+        let filters = FilterConfig {
+            allow_guilds: vec!["g1".to_string()],
+            block_channels: vec!["c2".to_string()],
+            block_users: vec!["bad".to_string()],
+            ..Default::default()
+        };
+
+        assert!(filters.accept(&message("g1", "c1", "good")));
+        assert!(!filters.accept(&message("g2", "c1", "good")));
+        assert!(!filters.accept(&message("g1", "c2", "good")));
+        assert!(!filters.accept(&message("g1", "c1", "bad")));
+    }
+
+    #[test]
+    fn test_live_config_reload_prefixes() {
+        let live = LiveConfig::default();
+        assert!(live.prefixes().is_none());
+
+        live.reload(Config {
+            prefixes: vec!["!".to_string(), "!!".to_string()],
+            ..Default::default()
+        });
+        let prefixes = live.prefixes().unwrap();
+        assert_eq!(*prefixes.read().unwrap(), ["!!", "!"]);
+
+        live.reload(Config::default());
+        assert_eq!(*prefixes.read().unwrap(), ["!!", "!"]);
+        assert!(live.prefixes().is_none());
+    }
+}
@@ -0,0 +1,480 @@
+//! Shared bot context.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use snafu::prelude::*;
+
+use crate::{
+    api::{
+        self,
+        types::{DirectMessageCreate, MessageCreate, MessageCreateData},
+    },
+    error,
+    filter::{AsyncFilter, Extracted, FilterExt},
+    models::{Emoji, MessageType, User},
+    waiter::Waiter,
+    ws::Event,
+    Result,
+};
+
+#[cfg(feature = "cache")]
+use crate::cache::Cache;
+
+/// A map which stores at most one value per type.
+#[derive(Default)]
+pub struct TypeMap {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Debug for TypeMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypeMap")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+impl TypeMap {
+    /// Create a empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a value, return the old value of same type if exists
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    /// Get value of type `T`
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Get mutable reference of value of type `T`
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// true if a value of type `T` exists
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove value of type `T`
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+}
+
+/// Bot level context, available after bot loaded.
+///
+/// It's cheap to clone.
+#[derive(Debug, Clone)]
+pub struct BotContext {
+    name: Arc<str>,
+    api_client: api::Client,
+    me: Arc<User>,
+    data: Arc<TypeMap>,
+    waiter: Waiter,
+    chat_codes: Arc<Mutex<HashMap<String, String>>>,
+    #[cfg(feature = "cache")]
+    cache: Arc<Cache>,
+}
+
+impl BotContext {
+    pub(crate) fn new(name: String, api_client: api::Client, me: User, data: TypeMap) -> Self {
+        Self {
+            name: name.into(),
+            api_client,
+            me: Arc::new(me),
+            data: Arc::new(data),
+            waiter: Waiter::default(),
+            chat_codes: Arc::default(),
+            #[cfg(feature = "cache")]
+            cache: Arc::default(),
+        }
+    }
+
+    pub(crate) fn publish(&self, event: Arc<Event>) {
+        self.waiter.publish(event)
+    }
+
+    /// Name of the bot, set by [Bot::name](crate::Bot::name), default is username of the bot.
+    ///
+    /// Can be used to identify which bot received the event when running a [BotSet](crate::BotSet).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Api client of the bot
+    pub fn api(&self) -> &api::Client {
+        &self.api_client
+    }
+
+    /// User info of the bot itself
+    pub fn me(&self) -> &User {
+        &self.me
+    }
+
+    /// In-memory cache of guilds, channels, roles and members
+    #[cfg(feature = "cache")]
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// Shared data added by [Bot::data](crate::Bot::data)
+    ///
+    /// Use interior mutability types like `Mutex` if the data need to be modified.
+    pub fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.data.get()
+    }
+
+    /// Wait for next event which pass the filter, None if timeout.
+    ///
+    /// Only events received after calling this method are checked.
+    pub async fn wait_for<F: AsyncFilter>(
+        &self,
+        filter: F,
+        timeout: Duration,
+    ) -> Option<Arc<Event>> {
+        self.waiter.wait_for(self, filter, timeout).await
+    }
+
+    /// Send a text message to the user privately, the private chat is created if needed
+    pub async fn dm<S: Into<String>>(
+        &self,
+        user_id: &str,
+        content: S,
+    ) -> Result<MessageCreateData> {
+        let code = self.chat_code(user_id).await?;
+        let message = DirectMessageCreate {
+            chat_code: Some(code),
+            content: content.into(),
+            ..Default::default()
+        };
+        self.api_client
+            .direct_message_create(&message)
+            .await
+            .context(error::CallAPIFailed)
+    }
+
+    async fn chat_code(&self, user_id: &str) -> Result<String> {
+        if let Some(code) = self.chat_codes.lock().unwrap().get(user_id) {
+            return Ok(code.clone());
+        }
+
+        let chat = self
+            .api_client
+            .user_chat_create(user_id)
+            .await
+            .context(error::CallAPIFailed)?;
+
+        self.chat_codes
+            .lock()
+            .unwrap()
+            .insert(user_id.to_string(), chat.code.clone());
+
+        Ok(chat.code)
+    }
+}
+
+/// Context of a event, passed to subscribers.
+///
+/// It derefs to [Event], and provides helpers to reply the event.
+#[derive(Debug, Clone)]
+pub struct EventContext {
+    bot: BotContext,
+    event: Arc<Event>,
+    /// data extracted by the [FilterMap](crate::filter::FilterMap) of subscription, if any
+    extracted: Option<Arc<Mutex<Option<Extracted>>>>,
+}
+
+impl Deref for EventContext {
+    type Target = Event;
+
+    fn deref(&self) -> &Self::Target {
+        &self.event
+    }
+}
+
+impl EventContext {
+    pub(crate) fn new(bot: BotContext, event: Arc<Event>) -> Self {
+        Self {
+            bot,
+            event,
+            extracted: None,
+        }
+    }
+
+    pub(crate) fn with_extracted(mut self, extracted: Option<Extracted>) -> Self {
+        self.extracted = extracted.map(|data| Arc::new(Mutex::new(Some(data))));
+        self
+    }
+
+    /// Take data extracted by the subscription, it can be taken only once.
+    pub(crate) fn take_extracted<T: 'static>(&self) -> Option<T> {
+        let data = self.extracted.as_ref()?.lock().unwrap().take()?;
+        data.downcast().ok().map(|data| *data)
+    }
+
+    /// The event
+    pub fn event(&self) -> &Arc<Event> {
+        &self.event
+    }
+
+    /// Bot level context
+    pub fn bot(&self) -> &BotContext {
+        &self.bot
+    }
+
+    /// Api client of the bot
+    pub fn api(&self) -> &api::Client {
+        self.bot.api()
+    }
+
+    /// User info of the bot itself
+    pub fn me(&self) -> &User {
+        self.bot.me()
+    }
+
+    /// Shared data added by [Bot::data](crate::Bot::data)
+    pub fn data<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.bot.data()
+    }
+
+    /// Send a text message to the user privately, see [BotContext::dm]
+    pub async fn dm<S: Into<String>>(
+        &self,
+        user_id: &str,
+        content: S,
+    ) -> Result<MessageCreateData> {
+        self.bot.dm(user_id, content).await
+    }
+
+    /// In-memory cache of guilds, channels, roles and members
+    #[cfg(feature = "cache")]
+    pub fn cache(&self) -> &Cache {
+        self.bot.cache()
+    }
+
+    /// Wait for next event which pass the filter, None if timeout.
+    ///
+    /// Only events received after calling this method are checked.
+    pub async fn wait_for<F: AsyncFilter>(
+        &self,
+        filter: F,
+        timeout: Duration,
+    ) -> Option<Arc<Event>> {
+        self.bot.wait_for(filter, timeout).await
+    }
+
+    /// Wait for next message from the author of this event, in the same channel, None if timeout.
+    ///
+    /// Useful for questions like "reply yes/no within 30s".
+    pub async fn wait_for_reply(&self, timeout: Duration) -> Option<Arc<Event>> {
+        let author_id = self.event.author_id().to_string();
+        let channel_id = self.event.channel_id().map(ToOwned::to_owned);
+        let is_private = matches!(self.event.as_ref(), Event::PrivateMessage(_));
+
+        let same_author = move |e: &Event| e.author_id() == author_id;
+        let same_channel = move |e: &Event| match e {
+            Event::ChannelMessage(_) => e.channel_id() == channel_id.as_deref(),
+            Event::PrivateMessage(_) => is_private,
+            _ => false,
+        };
+
+        self.wait_for(same_author.and(same_channel), timeout).await
+    }
+
+    /// Send a text message to where the event comes from, private messages are replied privately
+    pub async fn reply<S: Into<String>>(&self, content: S) -> Result<MessageCreateData> {
+        self.send(MessageType::Text, content.into(), false, false)
+            .await
+    }
+
+    /// Send a message with specified type to where the event comes from
+    pub async fn reply_with<S: Into<String>>(
+        &self,
+        r#type: MessageType,
+        content: S,
+    ) -> Result<MessageCreateData> {
+        self.send(r#type, content.into(), false, false).await
+    }
+
+    /// Send a text message which quotes the event message
+    pub async fn reply_quote<S: Into<String>>(&self, content: S) -> Result<MessageCreateData> {
+        self.send(MessageType::Text, content.into(), true, false)
+            .await
+    }
+
+    /// Send a temporary text message which only visible to the event author,
+    /// private messages are replied normally
+    pub async fn reply_temp<S: Into<String>>(&self, content: S) -> Result<MessageCreateData> {
+        self.send(MessageType::Text, content.into(), false, true)
+            .await
+    }
+
+    /// Add a reaction to the event message, `emoji` can be a unicode emoji like `"👍"` or a
+    /// guild emoji
+    pub async fn react<E: Into<Emoji>>(&self, emoji: E) -> Result<()> {
+        let emoji = emoji.into();
+        let msg_id = &self
+            .event
+            .as_message()
+            .context(error::NoReplyTarget)?
+            .msg_id;
+
+        match self.event.as_ref() {
+            Event::PrivateMessage(_) => {
+                self.api().direct_message_add_reaction(msg_id, &emoji).await
+            }
+            _ => self.api().message_add_reaction(msg_id, &emoji).await,
+        }
+        .context(error::CallAPIFailed)
+    }
+
+    async fn send(
+        &self,
+        r#type: MessageType,
+        content: String,
+        quote: bool,
+        temp: bool,
+    ) -> Result<MessageCreateData> {
+        let quote = quote
+            .then(|| self.event.as_message().map(|b| b.msg_id.clone()))
+            .flatten();
+
+        let result = match self.event.as_ref() {
+            Event::PrivateMessage(b) => {
+                let mut message = DirectMessageCreate::new(r#type, &b.author_id, content);
+                message.quote = quote;
+                self.api().direct_message_create(&message).await
+            }
+            event => {
+                let channel_id = event.channel_id().context(error::NoReplyTarget)?;
+                let mut message = MessageCreate::new(r#type, channel_id, content);
+                message.quote = quote;
+                message.temp_target_id = temp.then(|| event.author_id().to_string());
+                self.api().message_create(&message).await
+            }
+        };
+
+        result.context(error::CallAPIFailed)
+    }
+}
+
+#[cfg(test)]
+impl BotContext {
+    pub(crate) fn mock() -> Self {
+        Self::new(
+            "bot".to_string(),
+            api::Client::new_from_bot_token("token").unwrap(),
+            User {
+                id: "bot-user-id".to_string(),
+                ..Default::default()
+            },
+            TypeMap::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl EventContext {
+    pub(crate) fn mock(event: Event) -> Self {
+        Self::new(BotContext::mock(), Arc::new(event))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_reply() {
+        let message = |author_id: &str, channel_id: &str, content: &str| {
+            Arc::new(Event::ChannelMessage(crate::ws::event::EventBody {
+                author_id: author_id.to_string(),
+                target_id: channel_id.to_string(),
+                content: content.to_string(),
+                ..Default::default()
+            }))
+        };
+
+        let ctx = EventContext::mock(Event::clone(&message("u", "c", "ban?")));
+        let bot = ctx.bot().clone();
+
+        let waiting = tokio::spawn({
+            let ctx = ctx.clone();
+            async move { ctx.wait_for_reply(Duration::from_secs(1)).await }
+        });
+        while bot.waiter.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        bot.publish(message("other", "c", "no"));
+        bot.publish(message("u", "other", "no"));
+        bot.publish(message("u", "c", "yes"));
+
+        assert_eq!(waiting.await.unwrap().unwrap().content(), "yes");
+
+        assert!(ctx
+            .wait_for_reply(Duration::from_millis(10))
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_type_map() {
+        let mut map = TypeMap::new();
+
+        assert_eq!(map.insert(1u32), None);
+        assert_eq!(map.insert("config"), None);
+        assert_eq!(map.insert(2u32), Some(1));
+
+        *map.get_mut::<u32>().unwrap() += 1;
+        assert_eq!(map.get::<u32>(), Some(&3));
+        assert_eq!(map.get::<&str>(), Some(&"config"));
+        assert!(!map.contains::<i32>());
+
+        assert_eq!(map.remove::<&str>(), Some("config"));
+        assert_eq!(map.get::<&str>(), None);
+    }
+
+    #[tokio::test]
+    async fn test_dm_creates_chat_once() {
+        let mock = crate::testing::MockApi::new();
+        mock.respond(
+            "/user-chat/create",
+            serde_json::json!({ "code": "chat-code" }),
+        );
+        let ctx = BotContext::new(
+            "bot".to_string(),
+            api::Client::new_mock(Arc::clone(&mock)),
+            User::default(),
+            TypeMap::default(),
+        );
+
+        ctx.dm("user", "hello").await.unwrap();
+        ctx.dm("user", "again").await.unwrap();
+
+        assert_eq!(mock.calls_to("/user-chat/create").len(), 1);
+        let messages = mock.calls_to("/direct-message/create");
+        assert_eq!(messages.len(), 2);
+        let body = messages[1].body.as_ref().unwrap();
+        assert_eq!(body["chat_code"], "chat-code");
+        assert_eq!(body["content"], "again");
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Remember recently seen msg ids, to drop re-delivered events.
+///
+/// Oldest ids are forgot when exceeding capacity or expired.
+#[derive(Debug)]
+pub(crate) struct Dedup {
+    capacity: usize,
+    ttl: Duration,
+    seen: HashSet<String>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl Dedup {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Record the id, return false if it's already seen.
+    pub fn check(&mut self, id: &str, now: Instant) -> bool {
+        if self.capacity == 0 || id.is_empty() {
+            return true;
+        }
+
+        while let Some((time, _)) = self.order.front() {
+            if now.duration_since(*time) < self.ttl {
+                break;
+            }
+            self.forget_oldest();
+        }
+
+        if self.seen.contains(id) {
+            return false;
+        }
+
+        while self.order.len() >= self.capacity {
+            self.forget_oldest();
+        }
+
+        self.seen.insert(id.to_string());
+        self.order.push_back((now, id.to_string()));
+        true
+    }
+
+    /// Forget the id, so it's not treated as seen anymore.
+    #[cfg(feature = "webhook")]
+    pub fn forget(&mut self, id: &str) {
+        if self.seen.remove(id) {
+            self.order.retain(|(_, seen)| seen != id);
+        }
+    }
+
+    /// Remembered ids, from oldest to newest.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(|(_, id)| id.as_str())
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((_, id)) = self.order.pop_front() {
+            self.seen.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dedup() {
+        let start = Instant::now();
+        let mut dedup = Dedup::new(2, Duration::from_secs(10));
+
+        assert!(dedup.check("a", start));
+        assert!(!dedup.check("a", start));
+        assert!(dedup.check("b", start));
+        assert!(dedup.check("", start));
+        assert!(dedup.check("", start));
+
+        // "a" is forgot because of capacity
+        assert!(dedup.check("c", start));
+        assert!(dedup.check("a", start));
+        assert!(!dedup.check("c", start));
+
+        // all expired
+        let later = start + Duration::from_secs(10);
+        assert!(dedup.check("c", later));
+        assert!(!dedup.check("c", later));
+
+        assert_eq!(dedup.ids().collect::<Vec<_>>(), ["c"]);
+
+        let mut disabled = Dedup::new(0, Duration::from_secs(10));
+        assert!(disabled.check("a", start));
+        assert!(disabled.check("a", start));
+    }
+
+    #[test]
+    #[cfg(feature = "webhook")]
+    fn test_dedup_forget() {
+        let start = Instant::now();
+        let mut dedup = Dedup::new(2, Duration::from_secs(10));
+
+        assert!(dedup.check("a", start));
+        assert!(dedup.check("b", start));
+        dedup.forget("a");
+        assert_eq!(dedup.ids().collect::<Vec<_>>(), ["b"]);
+        assert!(dedup.check("a", start));
+    }
+}
@@ -3,7 +3,7 @@
 use snafu::prelude::*;
 
 use super::api::Error as APIError;
-use super::ws::client::RunError;
+use super::ws::client::{EventStreamError, RunError};
 
 /// framework result type
 pub type Result<T> = std::result::Result<T, Error>;
@@ -32,6 +32,58 @@ pub enum Error {
     #[snafu(display("run inner websocket client failed: {source}"))]
     RunWebsocketClientFailed {
         /// source error
-        source: RunError,
+        #[snafu(source(from(RunError, Box::new)))]
+        source: Box<RunError>,
     },
+
+    /// Event stream broken too many times in a row, see
+    /// [RetryPolicy::max_reconnects](crate::RetryPolicy::max_reconnects)
+    #[snafu(display("event stream broken {count} times in a row, last: {source}"))]
+    TooManyReconnects {
+        /// how many times event stream broken in a row
+        count: usize,
+        /// the last error
+        #[snafu(source(from(EventStreamError, Box::new)))]
+        source: Box<EventStreamError>,
+    },
+
+    /// Run webhook server failed
+    #[cfg(feature = "webhook")]
+    #[snafu(display("run webhook server failed: {source}"))]
+    RunWebhookServerFailed {
+        /// source error
+        source: hyper::Error,
+    },
+
+    /// Read config file failed
+    #[snafu(display("read config file {} failed: {source}", path.display()))]
+    ReadConfigFailed {
+        /// config file path
+        path: std::path::PathBuf,
+        /// source error
+        source: std::io::Error,
+    },
+
+    /// Parse config failed
+    #[snafu(display("parse config failed: {source}"))]
+    ParseConfigFailed {
+        /// source error
+        source: serde_json::Error,
+    },
+
+    /// Event has no channel or user to reply
+    #[snafu(display("event has no channel or user to reply"))]
+    NoReplyTarget,
+
+    /// Background task of bot started by [Bot::start](crate::Bot::start) panicked
+    #[snafu(display("bot task panicked: {message}"))]
+    BotPanicked {
+        /// panic message
+        message: String,
+    },
+
+    /// Background task of bot started by [Bot::start](crate::Bot::start) is cancelled,
+    /// usually because the runtime is shutting down
+    #[snafu(display("bot task is cancelled"))]
+    BotCancelled,
 }
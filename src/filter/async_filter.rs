@@ -0,0 +1,132 @@
+use std::{fmt::Debug, future::Future, sync::Arc};
+
+use super::Filter;
+use crate::{context::BotContext, models::User, ws::Event};
+
+/// Type implements this trait can check if a event is wanted, asynchronously.
+///
+/// Useful when the check needs I/O or bot context, like cache lookup or permission check.
+/// All sync [Filter]s are also async filters.
+#[async_trait::async_trait]
+pub trait AsyncFilter: Send + Sync {
+    /// true if event is wanted, otherwise false.
+    async fn check(&self, ctx: &BotContext, event: Arc<Event>) -> bool;
+
+    /// callback will be execute when a bot load this filter, with the bot user info
+    fn on_loaded(&mut self, _me: &User) {}
+}
+
+#[async_trait::async_trait]
+impl<F> AsyncFilter for F
+where
+    F: Filter,
+{
+    async fn check(&self, _ctx: &BotContext, event: Arc<Event>) -> bool {
+        self.filter_event(&event)
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        Filter::on_loaded(self, me)
+    }
+}
+
+/// Async filter created from a async function or closure.
+#[derive(Clone)]
+pub struct AsyncFn<F> {
+    f: F,
+}
+
+impl<F> Debug for AsyncFn<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncFn").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> AsyncFilter for AsyncFn<F>
+where
+    F: Fn(Arc<Event>) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    async fn check(&self, _ctx: &BotContext, event: Arc<Event>) -> bool {
+        (self.f)(event).await
+    }
+}
+
+/// Create a async filter from a async function or closure.
+pub fn async_fn<F, Fut>(f: F) -> AsyncFn<F>
+where
+    F: Fn(Arc<Event>) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    AsyncFn { f }
+}
+
+/// Async filter created from a async function or closure which also needs bot context.
+#[derive(Clone)]
+pub struct ContextFn<F> {
+    f: F,
+}
+
+impl<F> Debug for ContextFn<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextFn").finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> AsyncFilter for ContextFn<F>
+where
+    F: Fn(BotContext, Arc<Event>) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    async fn check(&self, ctx: &BotContext, event: Arc<Event>) -> bool {
+        (self.f)(ctx.clone(), event).await
+    }
+}
+
+/// Create a async filter from a async function or closure which accepts bot context and event.
+///
+/// Can be used to create filter needs api access, like permission check.
+pub fn context_fn<F, Fut>(f: F) -> ContextFn<F>
+where
+    F: Fn(BotContext, Arc<Event>) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    ContextFn { f }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{filter::text, ws::event::EventBody};
+
+    #[tokio::test]
+    async fn test_async_filter() {
+        let ctx = BotContext::mock();
+        let event = Arc::new(Event::ChannelMessage(EventBody {
+            author_id: "bot-user-id".to_string(),
+            content: "hello".to_string(),
+            ..Default::default()
+        }));
+
+        let filters: Vec<Box<dyn AsyncFilter>> = vec![
+            Box::new(text()),
+            Box::new(async_fn(|e: Arc<Event>| async move {
+                tokio::task::yield_now().await;
+                e.content() == "hello"
+            })),
+            Box::new(async_fn(|_| async { false })),
+            Box::new(context_fn(|ctx: BotContext, e: Arc<Event>| async move {
+                ctx.me().id == e.author_id()
+            })),
+        ];
+
+        let mut results = vec![];
+        for f in filters.iter() {
+            results.push(f.check(&ctx, Arc::clone(&event)).await);
+        }
+
+        assert_eq!(results, [true, true, false, true]);
+    }
+}
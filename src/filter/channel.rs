@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use super::Filter;
+use crate::{models::ChannelType, ws::Event};
+
+/// Filter that pass events with specified channel type.
+#[derive(Debug, Clone)]
+pub struct ChannelTypeIs {
+    channel_type: ChannelType,
+}
+
+impl Filter for ChannelTypeIs {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.channel_type() == &self.channel_type
+    }
+}
+
+/// Create a filter that pass events with specified channel type.
+pub fn channel_type(channel_type: ChannelType) -> ChannelTypeIs {
+    ChannelTypeIs { channel_type }
+}
+
+/// Create a filter that pass guild channel events.
+pub fn group() -> ChannelTypeIs {
+    channel_type(ChannelType::Group)
+}
+
+/// Create a filter that pass private chat events.
+pub fn person() -> ChannelTypeIs {
+    channel_type(ChannelType::Person)
+}
+
+/// Create a filter that pass broadcast events.
+pub fn broadcast() -> ChannelTypeIs {
+    channel_type(ChannelType::Broadcast)
+}
+
+/// Filter that pass events happened in specified channel.
+#[derive(Debug, Clone)]
+pub struct ChannelIs {
+    id: String,
+}
+
+impl Filter for ChannelIs {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.channel_id() == Some(self.id.as_str())
+    }
+}
+
+/// Create a filter that pass events happened in specified channel.
+pub fn channel<S: Into<String>>(id: S) -> ChannelIs {
+    ChannelIs { id: id.into() }
+}
+
+/// Filter that pass events happened in any of specified channels.
+#[derive(Debug, Clone)]
+pub struct ChannelIn {
+    ids: HashSet<String>,
+}
+
+impl Filter for ChannelIn {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.channel_id().is_some_and(|id| self.ids.contains(id))
+    }
+}
+
+/// Create a filter that pass events happened in any of specified channels.
+pub fn channels<I>(ids: I) -> ChannelIn
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    ChannelIn {
+        ids: ids.into_iter().map(Into::into).collect(),
+    }
+}
+
+/// Filter that pass events happened in specified guild.
+#[derive(Debug, Clone)]
+pub struct GuildIs {
+    id: String,
+}
+
+impl Filter for GuildIs {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.guild_id() == Some(self.id.as_str())
+    }
+}
+
+/// Create a filter that pass events happened in specified guild.
+pub fn guild<S: Into<String>>(id: S) -> GuildIs {
+    GuildIs { id: id.into() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::{EventBody, MessageExtra};
+
+    #[test]
+    fn test_filter_channel_type() {
+        let channel = Event::ChannelMessage(EventBody::default());
+        let private = Event::PrivateMessage(EventBody {
+            channel_type: ChannelType::Person,
+            ..Default::default()
+        });
+
+        assert!(group().filter_event(&channel));
+        assert!(!group().filter_event(&private));
+        assert!(person().filter_event(&private));
+        assert!(!broadcast().filter_event(&channel));
+    }
+
+    #[test]
+    fn test_filter_channel_and_guild() {
+        let event = Event::ChannelMessage(EventBody {
+            target_id: "some-channel-id".to_string(),
+            extra: MessageExtra {
+                guild_id: "some-guild-id".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert!(channel("some-channel-id").filter_event(&event));
+        assert!(!channel("other-channel-id").filter_event(&event));
+        assert!(channels(["other-channel-id", "some-channel-id"]).filter_event(&event));
+        assert!(guild("some-guild-id").filter_event(&event));
+        assert!(!guild("other-guild-id").filter_event(&event));
+    }
+}
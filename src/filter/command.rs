@@ -0,0 +1,124 @@
+use super::{Filter, FilterMap};
+use crate::ws::Event;
+
+const MENTION_TAGS: [&str; 2] = ["(met)", "(rol)"];
+
+/// Filter that pass messages which invoke a command, like `!ping some args`.
+///
+/// Leading mentions(like `(met)bot_id(met)`) and whitespaces before the command are ignored.
+///
+/// It's also a [FilterMap] which extracts the remaining argument string, so subscribe it by
+/// [Bot::subscribe_map](crate::Bot::subscribe_map) to receive the arguments in handler:
+///
+/// ```no_run
+/// # use burz::{filter::command, Bot};
+/// # let mut bot = Bot::new("token").unwrap();
+/// bot.subscribe_map(command("!echo"), |ctx, args: String| async move {
+///     let _ = ctx.reply(args).await;
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Command {
+    prefix: String,
+    name: String,
+}
+
+impl Command {
+    /// Set command prefix, like `!` or `/`, default is empty.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Get argument string of the command if the event invokes it.
+    ///
+    /// The argument string is trimmed, and is empty if no argument is given.
+    pub fn args<'a>(&self, event: &'a Event) -> Option<&'a str> {
+        let content = strip_leading_mentions(event.as_message()?.content.as_str());
+        let rest = content
+            .strip_prefix(self.prefix.as_str())?
+            .strip_prefix(self.name.as_str())?;
+
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            Some(rest.trim())
+        } else {
+            None
+        }
+    }
+}
+
+impl Filter for Command {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.args(event).is_some()
+    }
+}
+
+impl FilterMap for Command {
+    type Output = String;
+
+    fn filter_map(&self, event: &Event) -> Option<String> {
+        self.args(event).map(ToOwned::to_owned)
+    }
+}
+
+/// Create a filter that pass messages which invoke specified command.
+///
+/// The prefix can be included in `name` directly, like `command("!ping")`,
+/// or be set by [Command::prefix], like `command("ping").prefix("!")`.
+pub fn command<S: Into<String>>(name: S) -> Command {
+    Command {
+        prefix: String::new(),
+        name: name.into(),
+    }
+}
+
+pub(crate) fn strip_leading_mentions(mut s: &str) -> &str {
+    loop {
+        s = s.trim_start();
+
+        let stripped = MENTION_TAGS.iter().find_map(|tag| {
+            let rest = s.strip_prefix(tag)?;
+            let end = rest.find(tag)?;
+            Some(&rest[end + tag.len()..])
+        });
+
+        match stripped {
+            Some(rest) => s = rest,
+            None => return s,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::EventBody;
+
+    fn message(content: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            content: content.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter_command() {
+        let ping = command("ping").prefix("!");
+
+        assert_eq!(ping.args(&message("!ping")), Some(""));
+        assert_eq!(ping.args(&message("  !ping  a b ")), Some("a b"));
+        assert_eq!(
+            ping.args(&message("(met)bot-id(met) (rol)123(rol)!ping\ta")),
+            Some("a")
+        );
+        assert_eq!(ping.args(&message("!pingpong")), None);
+        assert_eq!(ping.args(&message("ping")), None);
+        assert_eq!(ping.args(&message("say !ping")), None);
+
+        assert!(command("/help").filter_event(&message("/help me")));
+        assert_eq!(
+            command("/help").filter_map(&message("/help me")),
+            Some("me".to_string())
+        );
+    }
+}
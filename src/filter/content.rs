@@ -0,0 +1,105 @@
+use super::Filter;
+use crate::ws::Event;
+
+/// Filter that pass messages whose content contains specified text.
+#[derive(Debug, Clone)]
+pub struct ContentContains {
+    text: String,
+    ignore_case: bool,
+}
+
+impl Filter for ContentContains {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.as_message().is_some_and(|b| {
+            if self.ignore_case {
+                b.content.to_lowercase().contains(&self.text)
+            } else {
+                b.content.contains(&self.text)
+            }
+        })
+    }
+}
+
+/// Create a filter that pass messages whose content contains `text`.
+pub fn content_contains<S: Into<String>>(text: S) -> ContentContains {
+    ContentContains {
+        text: text.into(),
+        ignore_case: false,
+    }
+}
+
+/// Create a filter that pass messages whose content contains `text`, ignoring case.
+pub fn content_contains_ignore_case<S: AsRef<str>>(text: S) -> ContentContains {
+    ContentContains {
+        text: text.as_ref().to_lowercase(),
+        ignore_case: true,
+    }
+}
+
+/// Filter that pass messages whose content starts with specified text.
+#[derive(Debug, Clone)]
+pub struct ContentStartsWith {
+    text: String,
+    ignore_case: bool,
+}
+
+impl Filter for ContentStartsWith {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.as_message().is_some_and(|b| {
+            if self.ignore_case {
+                b.content.to_lowercase().starts_with(&self.text)
+            } else {
+                b.content.starts_with(&self.text)
+            }
+        })
+    }
+}
+
+/// Create a filter that pass messages whose content starts with `text`.
+pub fn content_starts_with<S: Into<String>>(text: S) -> ContentStartsWith {
+    ContentStartsWith {
+        text: text.into(),
+        ignore_case: false,
+    }
+}
+
+/// Create a filter that pass messages whose content starts with `text`, ignoring case.
+pub fn content_starts_with_ignore_case<S: AsRef<str>>(text: S) -> ContentStartsWith {
+    ContentStartsWith {
+        text: text.as_ref().to_lowercase(),
+        ignore_case: true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::EventBody;
+
+    fn message(content: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            content: content.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter_content_contains() {
+        let event = message("Hello World");
+
+        assert!(content_contains("World").filter_event(&event));
+        assert!(!content_contains("world").filter_event(&event));
+        assert!(content_contains_ignore_case("WORLD").filter_event(&event));
+        assert!(!content_contains_ignore_case("earth").filter_event(&event));
+    }
+
+    #[test]
+    fn test_filter_content_starts_with() {
+        let event = message("Hello World");
+
+        assert!(content_starts_with("Hello").filter_event(&event));
+        assert!(!content_starts_with("hello").filter_event(&event));
+        assert!(!content_starts_with("World").filter_event(&event));
+        assert!(content_starts_with_ignore_case("hELLO").filter_event(&event));
+    }
+}
@@ -0,0 +1,42 @@
+use std::any::Any;
+
+use crate::ws::Event;
+
+/// Type implements this trait can check if a event is wanted, and extract data from it.
+///
+/// The extracted data is handed to the handler registered by [Bot::subscribe_map](crate::Bot::subscribe_map),
+/// so it's no need to parse the event again in handler.
+pub trait FilterMap: Send + Sync {
+    /// Type of extracted data.
+    type Output: Send + 'static;
+
+    /// Some(data) if event is wanted, otherwise None.
+    fn filter_map(&self, event: &Event) -> Option<Self::Output>;
+}
+
+impl<F, T> FilterMap for F
+where
+    F: Fn(&Event) -> Option<T> + Send + Sync,
+    T: Send + 'static,
+{
+    type Output = T;
+
+    fn filter_map(&self, event: &Event) -> Option<T> {
+        self(event)
+    }
+}
+
+/// Data extracted by a [FilterMap], with its type erased.
+pub(crate) type Extracted = Box<dyn Any + Send>;
+
+/// Object safe version of [FilterMap], used by subscriptions to extract data once per event.
+pub(crate) trait Extract: Send + Sync {
+    fn extract(&self, event: &Event) -> Option<Extracted>;
+}
+
+impl<M: FilterMap> Extract for M {
+    fn extract(&self, event: &Event) -> Option<Extracted> {
+        self.filter_map(event)
+            .map(|output| Box::new(output) as Extracted)
+    }
+}
@@ -0,0 +1,125 @@
+use super::{Filter, FilterMap};
+use crate::{models::User, ws::Event};
+
+/// Filter that pass messages which mention the bot itself.
+///
+/// The bot user id is injected by [Bot](crate::Bot) when loading, before that it will reject all events.
+#[derive(Debug, Clone, Default)]
+pub struct MentionsMe {
+    me: Option<String>,
+}
+
+impl Filter for MentionsMe {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.me.as_deref().is_some_and(|me| event.is_mention_me(me))
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.me.replace(me.id.clone());
+    }
+}
+
+/// Create a filter that pass messages which mention the bot itself.
+pub fn mentions_me() -> MentionsMe {
+    MentionsMe::default()
+}
+
+/// Filter that pass messages which mention specified user.
+#[derive(Debug, Clone)]
+pub struct Mentions {
+    id: String,
+}
+
+impl Filter for Mentions {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.is_mentioned(&self.id)
+    }
+}
+
+/// Create a filter that pass messages which mention specified user.
+pub fn mentions<S: Into<String>>(user_id: S) -> Mentions {
+    Mentions { id: user_id.into() }
+}
+
+/// Filter that pass messages which mention all users.
+#[derive(Debug, Copy, Clone)]
+pub struct MentionAll;
+
+impl Filter for MentionAll {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.is_mention_all()
+    }
+}
+
+/// Create a filter that pass messages which mention all users.
+pub fn mention_all() -> MentionAll {
+    MentionAll
+}
+
+/// Filter that pass messages which mention online users.
+#[derive(Debug, Copy, Clone)]
+pub struct MentionHere;
+
+impl Filter for MentionHere {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.is_mention_here()
+    }
+}
+
+/// Create a filter that pass messages which mention online users.
+pub fn mention_here() -> MentionHere {
+    MentionHere
+}
+
+/// Filter that pass messages which mention at least one user, and extract the first one.
+#[derive(Debug, Copy, Clone)]
+pub struct MentionedUser;
+
+impl FilterMap for MentionedUser {
+    type Output = String;
+
+    fn filter_map(&self, event: &Event) -> Option<String> {
+        event.mentioned_users().first().cloned()
+    }
+}
+
+/// Create a filter that pass messages which mention at least one user, and extract the first one.
+pub fn mentioned_user() -> MentionedUser {
+    MentionedUser
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::{EventBody, MessageExtra};
+
+    #[test]
+    fn test_filter_mentions() {
+        let event = Event::ChannelMessage(EventBody {
+            extra: MessageExtra {
+                mention: vec!["bot-user-id".to_string()],
+                mention_here: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut me = mentions_me();
+        assert!(!me.filter_event(&event));
+
+        me.on_loaded(&User {
+            id: "bot-user-id".to_string(),
+            ..Default::default()
+        });
+        assert!(me.filter_event(&event));
+
+        assert!(mentions("bot-user-id").filter_event(&event));
+        assert!(!mentions("other-user-id").filter_event(&event));
+        assert!(!mention_all().filter_event(&event));
+        assert!(mention_here().filter_event(&event));
+        assert_eq!(
+            mentioned_user().filter_map(&event),
+            Some("bot-user-id".to_string())
+        );
+    }
+}
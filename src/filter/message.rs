@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use super::Filter;
+use crate::{
+    models::{MessageType, User},
+    ws::Event,
+};
+
+const SYSTEM_AUTHOR_ID: &str = "1";
+
+/// Filter that pass events with specified message type.
+#[derive(Debug, Copy, Clone)]
+pub struct MessageTypeIs {
+    message_type: MessageType,
+}
+
+impl Filter for MessageTypeIs {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.message_type() == self.message_type
+    }
+}
+
+/// Create a filter that pass events with specified message type.
+pub fn message_type(message_type: MessageType) -> MessageTypeIs {
+    MessageTypeIs { message_type }
+}
+
+/// Create a filter that pass plain text messages.
+pub fn text() -> MessageTypeIs {
+    message_type(MessageType::Text)
+}
+
+/// Create a filter that pass image messages.
+pub fn image() -> MessageTypeIs {
+    message_type(MessageType::Image)
+}
+
+/// Create a filter that pass video messages.
+pub fn video() -> MessageTypeIs {
+    message_type(MessageType::Video)
+}
+
+/// Create a filter that pass file messages.
+pub fn file() -> MessageTypeIs {
+    message_type(MessageType::File)
+}
+
+/// Create a filter that pass audio messages.
+pub fn audio() -> MessageTypeIs {
+    message_type(MessageType::Audio)
+}
+
+/// Create a filter that pass kmarkdown messages.
+pub fn kmarkdown() -> MessageTypeIs {
+    message_type(MessageType::KMarkdown)
+}
+
+/// Create a filter that pass card messages.
+pub fn card() -> MessageTypeIs {
+    message_type(MessageType::Card)
+}
+
+/// Create a filter that pass system events.
+pub fn system() -> MessageTypeIs {
+    message_type(MessageType::System)
+}
+
+/// Filter that pass events sent by specified user.
+#[derive(Debug, Clone)]
+pub struct AuthorIs {
+    id: String,
+}
+
+impl Filter for AuthorIs {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.author_id() == self.id
+    }
+}
+
+/// Create a filter that pass events sent by specified user.
+pub fn author<S: Into<String>>(id: S) -> AuthorIs {
+    AuthorIs { id: id.into() }
+}
+
+/// Filter that pass events sent by any of specified users.
+#[derive(Debug, Clone)]
+pub struct AuthorIn {
+    ids: HashSet<String>,
+}
+
+impl Filter for AuthorIn {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.ids.contains(event.author_id())
+    }
+}
+
+/// Create a filter that pass events sent by any of specified users.
+pub fn authors<I>(ids: I) -> AuthorIn
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    AuthorIn {
+        ids: ids.into_iter().map(Into::into).collect(),
+    }
+}
+
+/// Filter that reject events sent by system.
+#[derive(Debug, Copy, Clone)]
+pub struct NotSystem;
+
+impl Filter for NotSystem {
+    fn filter_event(&self, event: &Event) -> bool {
+        event.author_id() != SYSTEM_AUTHOR_ID
+    }
+}
+
+/// Create a filter that reject events sent by system(author id is "1").
+pub fn not_system() -> NotSystem {
+    NotSystem
+}
+
+/// Filter that reject events sent by the bot itself.
+///
+/// The bot user id is injected by [Bot](crate::Bot) when loading, before that it will pass all events.
+#[derive(Debug, Clone, Default)]
+pub struct NotSelf {
+    me: Option<String>,
+}
+
+impl Filter for NotSelf {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.me.as_deref() != Some(event.author_id())
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.me.replace(me.id.clone());
+    }
+}
+
+/// Create a filter that reject events sent by the bot itself.
+pub fn not_self() -> NotSelf {
+    NotSelf::default()
+}
+
+/// Filter that reject messages sent by bot users.
+#[derive(Debug, Copy, Clone)]
+pub struct NotBot;
+
+impl Filter for NotBot {
+    fn filter_event(&self, event: &Event) -> bool {
+        !event.author().is_some_and(|u| u.bot)
+    }
+}
+
+/// Create a filter that reject messages sent by bot users, including the bot itself.
+pub fn not_bot() -> NotBot {
+    NotBot
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::{EventBody, MessageExtra};
+
+    #[test]
+    fn test_filter_message_type() {
+        let kmd = Event::ChannelMessage(EventBody {
+            r#type: MessageType::KMarkdown,
+            ..Default::default()
+        });
+
+        assert!(kmarkdown().filter_event(&kmd));
+        assert!(!text().filter_event(&kmd));
+        assert!(!system().filter_event(&kmd));
+    }
+
+    #[test]
+    fn test_filter_author() {
+        let user = Event::ChannelMessage(EventBody {
+            author_id: "some-user-id".to_string(),
+            ..Default::default()
+        });
+        let sys = Event::Unknown(EventBody {
+            author_id: "1".to_string(),
+            ..Default::default()
+        });
+
+        assert!(author("some-user-id").filter_event(&user));
+        assert!(!author("other-user-id").filter_event(&user));
+        assert!(authors(["other-user-id", "some-user-id"]).filter_event(&user));
+        assert!(!authors(Vec::<String>::new()).filter_event(&user));
+        assert!(not_system().filter_event(&user));
+        assert!(!not_system().filter_event(&sys));
+    }
+
+    #[test]
+    fn test_filter_not_self_and_bot() {
+        let from_bot = Event::ChannelMessage(EventBody {
+            author_id: "bot-user-id".to_string(),
+            extra: MessageExtra {
+                author: User {
+                    id: "bot-user-id".to_string(),
+                    bot: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let from_user = Event::ChannelMessage(EventBody {
+            author_id: "some-user-id".to_string(),
+            ..Default::default()
+        });
+
+        let mut f = not_self();
+        assert!(f.filter_event(&from_bot));
+
+        f.on_loaded(&User {
+            id: "bot-user-id".to_string(),
+            ..Default::default()
+        });
+        assert!(!f.filter_event(&from_bot));
+        assert!(f.filter_event(&from_user));
+
+        assert!(!not_bot().filter_event(&from_bot));
+        assert!(not_bot().filter_event(&from_user));
+    }
+}
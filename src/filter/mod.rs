@@ -0,0 +1,343 @@
+//! Event filter for subscribers.
+
+mod async_filter;
+mod channel;
+mod command;
+mod content;
+mod map;
+mod mention;
+mod message;
+mod rate_limit;
+#[cfg(feature = "regex")]
+mod regex;
+
+#[cfg(feature = "regex")]
+pub use self::regex::{content_regex, ContentRegex};
+pub use async_filter::{async_fn, context_fn, AsyncFilter, AsyncFn, ContextFn};
+pub use channel::{
+    broadcast, channel, channel_type, channels, group, guild, person, ChannelIn, ChannelIs,
+    ChannelTypeIs, GuildIs,
+};
+pub(crate) use command::strip_leading_mentions;
+pub use command::{command, Command};
+pub use content::{
+    content_contains, content_contains_ignore_case, content_starts_with,
+    content_starts_with_ignore_case, ContentContains, ContentStartsWith,
+};
+pub use map::FilterMap;
+pub(crate) use map::{Extract, Extracted};
+pub use mention::{
+    mention_all, mention_here, mentioned_user, mentions, mentions_me, MentionAll, MentionHere,
+    MentionedUser, Mentions, MentionsMe,
+};
+pub use message::{
+    audio, author, authors, card, file, image, kmarkdown, message_type, not_bot, not_self,
+    not_system, system, text, video, AuthorIn, AuthorIs, MessageTypeIs, NotBot, NotSelf, NotSystem,
+};
+pub use rate_limit::{cooldown, rate_limit, Bucket, RateLimit};
+
+use std::fmt::Debug;
+
+use crate::{models::User, ws::Event};
+
+/// Type implements this trait can check if a event is wanted.
+pub trait Filter: Send + Sync {
+    /// true if event is wanted, otherwise false.
+    fn filter_event(&self, event: &Event) -> bool;
+
+    /// callback will be execute when a bot load this filter, with the bot user info
+    fn on_loaded(&mut self, _me: &User) {}
+}
+
+impl<F> Filter for F
+where
+    F: Fn(&Event) -> bool + Send + Sync,
+{
+    fn filter_event(&self, event: &Event) -> bool {
+        self(event)
+    }
+}
+
+/// Negative wrapper of a filter.
+#[derive(Debug, Copy, Clone)]
+pub struct Not<F> {
+    filter: F,
+}
+
+impl<F> Filter for Not<F>
+where
+    F: Filter,
+{
+    fn filter_event(&self, event: &Event) -> bool {
+        !self.filter.filter_event(event)
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.filter.on_loaded(me)
+    }
+}
+
+/// If and only if a and b both pass, this filter will pass.
+#[derive(Debug, Copy, Clone)]
+pub struct And<FA, FB> {
+    a: FA,
+    b: FB,
+}
+
+impl<FA, FB> Filter for And<FA, FB>
+where
+    FA: Filter,
+    FB: Filter,
+{
+    fn filter_event(&self, event: &Event) -> bool {
+        self.a.filter_event(event) && self.b.filter_event(event)
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.a.on_loaded(me);
+        self.b.on_loaded(me);
+    }
+}
+
+/// If a or b pass, this filter will pass.
+#[derive(Debug, Copy, Clone)]
+pub struct Or<FA, FB> {
+    a: FA,
+    b: FB,
+}
+
+impl<FA, FB> Filter for Or<FA, FB>
+where
+    FA: Filter,
+    FB: Filter,
+{
+    fn filter_event(&self, event: &Event) -> bool {
+        self.a.filter_event(event) || self.b.filter_event(event)
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.a.on_loaded(me);
+        self.b.on_loaded(me);
+    }
+}
+
+/// If and only if exactly one of a and b pass, this filter will pass.
+#[derive(Debug, Copy, Clone)]
+pub struct Xor<FA, FB> {
+    a: FA,
+    b: FB,
+}
+
+impl<FA, FB> Filter for Xor<FA, FB>
+where
+    FA: Filter,
+    FB: Filter,
+{
+    fn filter_event(&self, event: &Event) -> bool {
+        self.a.filter_event(event) != self.b.filter_event(event)
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.a.on_loaded(me);
+        self.b.on_loaded(me);
+    }
+}
+
+/// Pass a event if any of the filters pass it, reject all events if there is no filter.
+pub struct AnyOf {
+    filters: Vec<Box<dyn Filter + 'static>>,
+}
+
+impl Debug for AnyOf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyOf")
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl Filter for AnyOf {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.filters.iter().any(|f| f.filter_event(event))
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.filters.iter_mut().for_each(|f| f.on_loaded(me));
+    }
+}
+
+/// Create a filter that pass a event if any of `filters` pass it.
+///
+/// Useful when the filter set is built at runtime, like from a config file.
+pub fn any_of<I>(filters: I) -> AnyOf
+where
+    I: IntoIterator<Item = Box<dyn Filter + 'static>>,
+{
+    AnyOf {
+        filters: filters.into_iter().collect(),
+    }
+}
+
+/// Pass a event only if all of the filters pass it, pass all events if there is no filter.
+pub struct AllOf {
+    filters: Vec<Box<dyn Filter + 'static>>,
+}
+
+impl Debug for AllOf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AllOf")
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl Filter for AllOf {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.filters.iter().all(|f| f.filter_event(event))
+    }
+
+    fn on_loaded(&mut self, me: &User) {
+        self.filters.iter_mut().for_each(|f| f.on_loaded(me));
+    }
+}
+
+/// Create a filter that pass a event only if all of `filters` pass it.
+///
+/// Useful when the filter set is built at runtime, like from a config file.
+pub fn all_of<I>(filters: I) -> AllOf
+where
+    I: IntoIterator<Item = Box<dyn Filter + 'static>>,
+{
+    AllOf {
+        filters: filters.into_iter().collect(),
+    }
+}
+
+/// Filter combinator.
+pub trait FilterExt
+where
+    Self: Sized,
+{
+    /// Invert a filter.
+    fn not(self) -> Not<Self> {
+        Not { filter: self }
+    }
+
+    /// Return a new filter that pass a event only if self and other both pass it.
+    fn and<F>(self, other: F) -> And<Self, F> {
+        And { a: self, b: other }
+    }
+
+    /// Return a new filter that pass a event if self or other pass it.
+    fn or<F>(self, other: F) -> Or<Self, F> {
+        Or { a: self, b: other }
+    }
+
+    /// Return a new filter that pass a event only if exactly one of self and other pass it.
+    fn xor<F>(self, other: F) -> Xor<Self, F> {
+        Xor { a: self, b: other }
+    }
+}
+
+impl<T> FilterExt for T where T: Filter {}
+
+/// Filter that will pass all events.
+#[derive(Debug, Copy, Clone)]
+pub struct All;
+
+impl Filter for All {
+    fn filter_event(&self, _event: &Event) -> bool {
+        true
+    }
+}
+
+/// Create a filter that pass all events.
+pub fn all() -> All {
+    All
+}
+
+/// Filter that will reject all events.
+#[derive(Debug, Copy, Clone)]
+pub struct None;
+
+impl Filter for None {
+    fn filter_event(&self, _event: &Event) -> bool {
+        false
+    }
+}
+
+/// Create a filter that will reject all events.
+pub fn none() -> None {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::EventBody;
+
+    fn message(content: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            content: content.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter_combinators() {
+        let event = message("");
+        let t = || all();
+        let f = || none();
+
+        assert!(!t().not().filter_event(&event));
+        assert!(f().not().filter_event(&event));
+
+        assert!(t().and(t()).filter_event(&event));
+        assert!(!t().and(f()).filter_event(&event));
+        assert!(!f().and(t()).filter_event(&event));
+        assert!(!f().and(f()).filter_event(&event));
+
+        assert!(t().or(t()).filter_event(&event));
+        assert!(t().or(f()).filter_event(&event));
+        assert!(f().or(t()).filter_event(&event));
+        assert!(!f().or(f()).filter_event(&event));
+
+        assert!(!t().xor(t()).filter_event(&event));
+        assert!(t().xor(f()).filter_event(&event));
+        assert!(f().xor(t()).filter_event(&event));
+        assert!(!f().xor(f()).filter_event(&event));
+
+        // De Morgan's laws
+        for (a, b) in [(true, true), (true, false), (false, true), (false, false)] {
+            let fa = move |_: &Event| a;
+            let fb = move |_: &Event| b;
+            assert_eq!(
+                fa.and(fb).not().filter_event(&event),
+                fa.not().or(fb.not()).filter_event(&event)
+            );
+            assert_eq!(
+                fa.or(fb).not().filter_event(&event),
+                fa.not().and(fb.not()).filter_event(&event)
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_any_all_of() {
+        let event = message("hello world");
+        let build = |words: &[&str]| -> Vec<Box<dyn Filter>> {
+            words
+                .iter()
+                .map(|w| Box::new(content_contains(*w)) as Box<dyn Filter>)
+                .collect()
+        };
+
+        assert!(any_of(build(&["foo", "world"])).filter_event(&event));
+        assert!(!any_of(build(&["foo", "bar"])).filter_event(&event));
+        assert!(!any_of(build(&[])).filter_event(&event));
+
+        assert!(all_of(build(&["hello", "world"])).filter_event(&event));
+        assert!(!all_of(build(&["hello", "bar"])).filter_event(&event));
+        assert!(all_of(build(&[])).filter_event(&event));
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::Filter;
+use crate::ws::Event;
+
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// How [RateLimit] group events into independent buckets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bucket {
+    /// One bucket per event author.
+    User,
+    /// One bucket per channel, private messages use the author as channel.
+    Channel,
+    /// One bucket shared by all events.
+    Global,
+}
+
+impl Bucket {
+    fn key<'a>(&self, event: &'a Event) -> &'a str {
+        match self {
+            Self::User => event.author_id(),
+            Self::Channel => event.channel_id().unwrap_or_else(|| event.author_id()),
+            Self::Global => "",
+        }
+    }
+}
+
+/// Filter that pass at most `n` events in every `per` duration, rejects others.
+///
+/// Every passed event is counted, so this filter should usually be the last one in a `and` chain,
+/// like `command("!roll").and(rate_limit(3, Duration::from_secs(60)))`.
+#[derive(Debug)]
+pub struct RateLimit {
+    n: usize,
+    per: Duration,
+    bucket: Bucket,
+    history: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimit {
+    /// Set how events are grouped, default is [Bucket::User].
+    pub fn bucket(mut self, bucket: Bucket) -> Self {
+        self.bucket = bucket;
+        self
+    }
+
+    /// Limit per user, this is the default.
+    pub fn per_user(self) -> Self {
+        self.bucket(Bucket::User)
+    }
+
+    /// Limit per channel.
+    pub fn per_channel(self) -> Self {
+        self.bucket(Bucket::Channel)
+    }
+
+    /// Limit all events together.
+    pub fn global(self) -> Self {
+        self.bucket(Bucket::Global)
+    }
+
+    fn check_at(&self, event: &Event, now: Instant) -> bool {
+        let mut history = self.history.lock().unwrap();
+
+        if history.len() > PRUNE_THRESHOLD {
+            history.retain(|_, times| times.back().is_some_and(|t| now - *t < self.per));
+        }
+
+        let times = history
+            .entry(self.bucket.key(event).to_owned())
+            .or_default();
+        while times.front().is_some_and(|t| now - *t >= self.per) {
+            times.pop_front();
+        }
+
+        if times.len() < self.n {
+            times.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Filter for RateLimit {
+    fn filter_event(&self, event: &Event) -> bool {
+        self.check_at(event, Instant::now())
+    }
+}
+
+/// Create a filter that pass at most `n` events of a user in every `per` duration.
+pub fn rate_limit(n: usize, per: Duration) -> RateLimit {
+    RateLimit {
+        n,
+        per,
+        bucket: Bucket::User,
+        history: Mutex::default(),
+    }
+}
+
+/// Create a filter that pass at most one event of a user in every `duration`.
+pub fn cooldown(duration: Duration) -> RateLimit {
+    rate_limit(1, duration)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::EventBody;
+
+    fn message(author_id: &str, channel_id: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            author_id: author_id.to_string(),
+            target_id: channel_id.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter_rate_limit() {
+        let f = rate_limit(2, Duration::from_secs(10));
+        let start = Instant::now();
+        let a = message("a", "c");
+        let b = message("b", "c");
+
+        assert!(f.check_at(&a, start));
+        assert!(f.check_at(&a, start + Duration::from_secs(1)));
+        assert!(!f.check_at(&a, start + Duration::from_secs(2)));
+        assert!(f.check_at(&b, start + Duration::from_secs(2)));
+        assert!(f.check_at(&a, start + Duration::from_secs(10)));
+        assert!(!f.check_at(&a, start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_filter_cooldown_per_channel() {
+        let f = cooldown(Duration::from_secs(5)).per_channel();
+        let start = Instant::now();
+
+        assert!(f.check_at(&message("a", "c1"), start));
+        assert!(!f.check_at(&message("b", "c1"), start));
+        assert!(f.check_at(&message("b", "c2"), start));
+        assert!(f.check_at(&message("b", "c1"), start + Duration::from_secs(5)));
+    }
+}
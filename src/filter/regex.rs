@@ -0,0 +1,49 @@
+use super::Filter;
+use crate::ws::Event;
+
+/// Filter that pass messages whose content matches a regex.
+#[derive(Debug, Clone)]
+pub struct ContentRegex {
+    regex: regex::Regex,
+}
+
+impl Filter for ContentRegex {
+    fn filter_event(&self, event: &Event) -> bool {
+        event
+            .as_message()
+            .is_some_and(|b| self.regex.is_match(&b.content))
+    }
+}
+
+impl From<regex::Regex> for ContentRegex {
+    fn from(regex: regex::Regex) -> Self {
+        Self { regex }
+    }
+}
+
+/// Create a filter that pass messages whose content matches the regex pattern.
+pub fn content_regex(pattern: &str) -> Result<ContentRegex, regex::Error> {
+    regex::Regex::new(pattern).map(ContentRegex::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::event::EventBody;
+
+    fn message(content: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            content: content.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_filter_content_regex() {
+        let f = content_regex(r"(?i)\bbad\s*word\b").unwrap();
+
+        assert!(f.filter_event(&message("this is a BAD word")));
+        assert!(!f.filter_event(&message("badwords are fine")));
+        assert!(content_regex("(").is_err());
+    }
+}
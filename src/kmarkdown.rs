@@ -0,0 +1,419 @@
+//! KMarkdown message content builder.
+//!
+//! See <https://developer.kookapp.cn/doc/kmarkdown> for the syntax. Text passed to the builder is
+//! escaped, so user input can not break the format, use [KMarkdown::raw] for content which is
+//! already KMarkdown.
+//!
+//! ```
+//! use burz::kmarkdown::KMarkdown;
+//!
+//! let content = KMarkdown::new()
+//!     .mention("1234")
+//!     .text(" rolled ")
+//!     .bold("6")
+//!     .text("!")
+//!     .build();
+//! assert_eq!(content, "(met)1234(met) rolled **6**!");
+//! ```
+//!
+//! Mentions in received content can be extracted by [mentions] and removed by [strip_mentions].
+
+use std::{fmt, ops::Range, str::FromStr};
+
+/// Characters with special meaning in KMarkdown
+const SPECIAL_CHARS: &[char] = &['\\', '*', '~', '_', '[', ']', '(', ')', '`', '>', '-', ':'];
+
+/// Escape special characters in text by backslash, so it's shown as is
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if SPECIAL_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Emoji names and ids are inserted as is, `_` and `-` in them must not be escaped
+fn is_emoji_token(s: &str) -> bool {
+    !s.is_empty()
+        && !s.contains(|c: char| c.is_whitespace() || matches!(c, ':' | '(' | ')' | '[' | ']'))
+}
+
+/// A mention of user, role or channel in KMarkdown content
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Mention {
+    /// `(met)user_id(met)`
+    User(String),
+    /// `(met)all(met)`
+    All,
+    /// `(met)here(met)`
+    Here,
+    /// `(rol)role_id(rol)`
+    Role(String),
+    /// `(chn)channel_id(chn)`
+    Channel(String),
+}
+
+const MENTION_TAGS: [&str; 3] = ["(met)", "(rol)", "(chn)"];
+
+impl Mention {
+    fn new(tag: &str, id: &str) -> Option<Self> {
+        if id.is_empty() || id.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+            return None;
+        }
+
+        Some(match (tag, id) {
+            ("(met)", "all") => Self::All,
+            ("(met)", "here") => Self::Here,
+            ("(met)", _) => Self::User(id.to_string()),
+            ("(rol)", _) => Self::Role(id.to_string()),
+            ("(chn)", _) => Self::Channel(id.to_string()),
+            _ => return None,
+        })
+    }
+
+    /// Parse a mention at the start of content, returns the mention and its length
+    fn parse_prefix(content: &str) -> Option<(Self, usize)> {
+        MENTION_TAGS.iter().find_map(|tag| {
+            let rest = content.strip_prefix(tag)?;
+            let end = rest.find(tag)?;
+            Some((Self::new(tag, &rest[..end])?, end + tag.len() * 2))
+        })
+    }
+}
+
+impl FromStr for Mention {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::parse_prefix(s) {
+            Some((mention, len)) if len == s.len() => Ok(mention),
+            _ => Err("not a mention"),
+        }
+    }
+}
+
+impl fmt::Display for Mention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(id) => write!(f, "(met){id}(met)"),
+            Self::All => f.write_str("(met)all(met)"),
+            Self::Here => f.write_str("(met)here(met)"),
+            Self::Role(id) => write!(f, "(rol){id}(rol)"),
+            Self::Channel(id) => write!(f, "(chn){id}(chn)"),
+        }
+    }
+}
+
+/// Find mentions in content with their byte ranges
+fn find_mentions(content: &str) -> impl Iterator<Item = (Range<usize>, Mention)> + '_ {
+    let mut from = 0;
+    std::iter::from_fn(move || {
+        while let Some(pos) = content[from..].find('(') {
+            let start = from + pos;
+            if let Some((mention, len)) = Mention::parse_prefix(&content[start..]) {
+                from = start + len;
+                return Some((start..from, mention));
+            }
+            from = start + 1;
+        }
+        None
+    })
+}
+
+/// Extract all mentions in content, in order of appearance
+pub fn mentions(content: &str) -> impl Iterator<Item = Mention> + '_ {
+    find_mentions(content).map(|(_, mention)| mention)
+}
+
+/// Remove all mentions in content, leading and trailing whitespaces of result are trimmed
+pub fn strip_mentions(content: &str) -> String {
+    let mut stripped = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, _) in find_mentions(content) {
+        stripped.push_str(&content[last..range.start]);
+        last = range.end;
+    }
+    stripped.push_str(&content[last..]);
+    stripped.trim().to_string()
+}
+
+/// Builder of KMarkdown content, can be used as message content by `String::from` or `to_string`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KMarkdown {
+    content: String,
+}
+
+impl KMarkdown {
+    /// Create a empty content
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append KMarkdown content as is, without escaping
+    pub fn raw(mut self, content: &str) -> Self {
+        self.content.push_str(content);
+        self
+    }
+
+    fn wrap(self, mark: &str, text: &str) -> Self {
+        self.raw(mark).raw(&escape(text)).raw(mark)
+    }
+
+    /// Append plain text
+    pub fn text(self, text: &str) -> Self {
+        self.raw(&escape(text))
+    }
+
+    /// Append bold text
+    pub fn bold(self, text: &str) -> Self {
+        self.wrap("**", text)
+    }
+
+    /// Append italic text
+    pub fn italic(self, text: &str) -> Self {
+        self.wrap("*", text)
+    }
+
+    /// Append strikethrough text
+    pub fn strikethrough(self, text: &str) -> Self {
+        self.wrap("~~", text)
+    }
+
+    /// Append underlined text
+    pub fn underline(self, text: &str) -> Self {
+        self.wrap("(ins)", text)
+    }
+
+    /// Append spoiler text, which is hidden until clicked
+    pub fn spoiler(self, text: &str) -> Self {
+        self.wrap("(spl)", text)
+    }
+
+    /// Append a link
+    pub fn link(self, text: &str, url: &str) -> Self {
+        self.raw("[")
+            .text(text)
+            .raw("](")
+            .raw(&url.replace(')', "%29"))
+            .raw(")")
+    }
+
+    /// Append inline code, backticks in it are escaped
+    pub fn code(self, code: &str) -> Self {
+        self.raw("`").raw(&code.replace('`', "\\`")).raw("`")
+    }
+
+    /// Append a code block on its own lines, with an optional language for highlight
+    pub fn code_block(self, language: Option<&str>, code: &str) -> Self {
+        self.line_start()
+            .raw("```")
+            .raw(language.unwrap_or_default())
+            .raw("\n")
+            .raw(&code.replace("```", "\\`\\`\\`"))
+            .line_start()
+            .raw("```\n")
+    }
+
+    /// Append a quote on its own lines, it ends at the following empty line
+    pub fn quote(self, text: &str) -> Self {
+        self.line_start().raw("> ").text(text).raw("\n\n")
+    }
+
+    /// Append a divider line
+    pub fn divider(self) -> Self {
+        self.line_start().raw("---\n")
+    }
+
+    /// Append a line break
+    pub fn newline(self) -> Self {
+        self.raw("\n")
+    }
+
+    /// Mention a user by id
+    pub fn mention(self, user_id: &str) -> Self {
+        self.wrap("(met)", user_id)
+    }
+
+    /// Mention all users in the channel
+    pub fn mention_all(self) -> Self {
+        self.raw("(met)all(met)")
+    }
+
+    /// Mention online users in the channel
+    pub fn mention_here(self) -> Self {
+        self.raw("(met)here(met)")
+    }
+
+    /// Mention a role by id
+    pub fn role(self, role_id: &str) -> Self {
+        self.wrap("(rol)", role_id)
+    }
+
+    /// Link to a channel by id
+    pub fn channel(self, channel_id: &str) -> Self {
+        self.wrap("(chn)", channel_id)
+    }
+
+    /// Append a standard emoji by its short name, like `smile` or `heart_eyes`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is empty or contains whitespace or emoji delimiters like `:`.
+    pub fn emoji(self, name: &str) -> Self {
+        assert!(is_emoji_token(name), "invalid emoji name {:?}", name);
+        self.raw(":").raw(name).raw(":")
+    }
+
+    /// Append a server emoji by its name and id, `emoji_id` is in `guild_id/emoji_id` form
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` or `emoji_id` contains whitespace or emoji delimiters like `:`, or
+    /// `emoji_id` is empty.
+    pub fn server_emoji(self, name: &str, emoji_id: &str) -> Self {
+        assert!(
+            name.is_empty() || is_emoji_token(name),
+            "invalid emoji name {:?}",
+            name
+        );
+        assert!(is_emoji_token(emoji_id), "invalid emoji id {:?}", emoji_id);
+        self.raw("(emj)")
+            .raw(name)
+            .raw("(emj)[")
+            .raw(emoji_id)
+            .raw("]")
+    }
+
+    /// Start a new line if current line is not empty
+    fn line_start(self) -> Self {
+        if self.content.is_empty() || self.content.ends_with('\n') {
+            self
+        } else {
+            self.newline()
+        }
+    }
+
+    /// Get the built content
+    pub fn build(self) -> String {
+        self.content
+    }
+
+    /// Get the content built so far
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+}
+
+impl fmt::Display for KMarkdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.content)
+    }
+}
+
+impl From<KMarkdown> for String {
+    fn from(value: KMarkdown) -> Self {
+        value.build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain text"), "plain text");
+        assert_eq!(escape("**not bold**"), "\\*\\*not bold\\*\\*");
+        assert_eq!(escape("(met)all(met)"), "\\(met\\)all\\(met\\)");
+        assert_eq!(escape("a\\b"), "a\\\\b");
+    }
+
+    #[test]
+    fn test_inline_format() {
+        let content = KMarkdown::new()
+            .bold("b*")
+            .italic("i")
+            .strikethrough("s")
+            .underline("u")
+            .spoiler("(spl)")
+            .code("let `x`")
+            .link("[docs]", "https://example.com/a_(b)")
+            .build();
+        assert_eq!(
+            content,
+            "**b\\***\
+             *i*\
+             ~~s~~\
+             (ins)u(ins)\
+             (spl)\\(spl\\)(spl)\
+             `let \\`x\\``\
+             [\\[docs\\]](https://example.com/a_(b%29)"
+        );
+    }
+
+    #[test]
+    fn test_mentions_and_emoji() {
+        let content = KMarkdown::new()
+            .mention("1")
+            .mention_all()
+            .mention_here()
+            .role("2")
+            .channel("3")
+            .emoji("heart_eyes")
+            .server_emoji("party_parrot", "4/5-a")
+            .to_string();
+        assert_eq!(
+            content,
+            "(met)1(met)(met)all(met)(met)here(met)(rol)2(rol)(chn)3(chn)\
+             :heart_eyes:(emj)party_parrot(emj)[4/5-a]"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_emoji_invalid_name() {
+        KMarkdown::new().emoji("heart eyes");
+    }
+
+    #[test]
+    fn test_mentions() {
+        let content = "(met)all(met) ask (rol)2(rol) in (chn)3(chn), \\(met\\)4(met) (met)5(met)";
+        assert_eq!(
+            mentions(content).collect::<Vec<_>>(),
+            [
+                Mention::All,
+                Mention::Role("2".to_string()),
+                Mention::Channel("3".to_string()),
+                Mention::User("5".to_string()),
+            ]
+        );
+        assert_eq!(strip_mentions(content), "ask  in , \\(met\\)4(met)");
+        assert_eq!(strip_mentions(" (met)1(met) ban (met)2(met)"), "ban");
+
+        assert_eq!("(met)here(met)".parse(), Ok(Mention::Here));
+        assert!("(met)1(met) ".parse::<Mention>().is_err());
+        assert!("(met)a b(met)".parse::<Mention>().is_err());
+        assert!("(rol)(rol)".parse::<Mention>().is_err());
+        assert_eq!(Mention::Channel("3".to_string()).to_string(), "(chn)3(chn)");
+    }
+
+    #[test]
+    fn test_block_format() {
+        let content = KMarkdown::new()
+            .text("intro")
+            .code_block(Some("rust"), "fn main() {}")
+            .quote("> quoted")
+            .divider()
+            .text("end");
+        assert_eq!(
+            String::from(content),
+            "intro\n```rust\nfn main() {}\n```\n> \\> quoted\n\n---\nend"
+        );
+
+        let content = KMarkdown::new().code_block(None, "a\n").build();
+        assert_eq!(content, "```\na\n```\n");
+    }
+}
@@ -7,15 +7,48 @@
 #![deny(missing_debug_implementations, missing_docs)]
 #![forbid(unsafe_code)]
 
+#[macro_use]
+mod logging;
+
 pub mod api;
+pub mod audit;
+pub mod backoff;
+pub mod button;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod card;
+pub mod command;
+pub mod config;
+pub mod context;
 pub mod filter;
+pub mod kmarkdown;
+pub mod models;
+pub mod permission;
+pub mod schedule;
+pub mod session;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod voice;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 pub mod ws;
 
 mod bot;
+mod dedup;
 mod error;
+mod lifecycle;
+mod plugin;
 mod subscriber;
+mod waiter;
 
-pub use bot::Bot;
+pub use bot::{Backpressure, Bot, BotBuilder, BotHandle, BotSet, EventQueue, RetryPolicy};
+pub use config::Config;
+pub use context::{BotContext, EventContext, TypeMap};
 pub use error::{Error, Result};
 pub use filter::{Filter, FilterExt};
-pub use subscriber::Subscriber;
+pub use lifecycle::{Lifecycle, LifecycleHandler};
+pub use plugin::Plugin;
+pub use subscriber::{
+    BoxError, Concurrency, IntoSubscriberResult, Overflow, SubscribeOptions, Subscriber,
+    SubscriberErrorHandler,
+};
@@ -0,0 +1,62 @@
+use std::{future::Future, sync::Arc};
+
+use crate::context::BotContext;
+
+/// Connection lifecycle notification of a bot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lifecycle {
+    /// Event stream is established with a new session
+    Ready,
+    /// Event stream is established by resuming the last session
+    Resumed,
+    /// Event stream is broken, bot will reconnect soon
+    Disconnected {
+        /// reason of the disconnection
+        reason: String,
+    },
+    /// Bot is trying to connect again after failures
+    Reconnecting {
+        /// how many connect attempts failed in a row
+        attempt: usize,
+    },
+    /// Events are missing for too long, see [GapPolicy](crate::ws::GapPolicy)
+    EventGap {
+        /// sn of first missing event
+        from: u64,
+        /// sn of last missing event
+        to: u64,
+        /// true if bot reconnects to resume from the last continuous event, false if they are
+        /// skipped
+        resync: bool,
+    },
+    /// Event is rejected because the event queue is full, see
+    /// [Backpressure::Reject](crate::Backpressure::Reject)
+    EventRejected {
+        /// sn of the rejected event
+        sn: u64,
+    },
+    /// Bot stops reconnecting because limits of [RetryPolicy](crate::RetryPolicy) are reached,
+    /// run returns with error after this
+    GaveUp {
+        /// reason of the last failure
+        reason: String,
+    },
+}
+
+/// Handler of bot connection lifecycle notifications.
+#[async_trait::async_trait]
+pub trait LifecycleHandler: Send + Sync {
+    /// callback will be execute when the connection state of bot changes
+    async fn on_lifecycle(self: Arc<Self>, ctx: BotContext, lifecycle: Lifecycle);
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> LifecycleHandler for F
+where
+    F: Fn(BotContext, Lifecycle) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_lifecycle(self: Arc<Self>, ctx: BotContext, lifecycle: Lifecycle) {
+        self(ctx, lifecycle).await
+    }
+}
@@ -0,0 +1,141 @@
+//! Internal logging, by `tracing` with structured spans if the feature is enabled, otherwise by
+//! `log`.
+//!
+//! With `tracing` feature, events are still forwarded to `log` when no tracing subscriber is set.
+
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        ::log::trace!($($arg)+);
+    }};
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        ::log::debug!($($arg)+);
+    }};
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::info!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        ::log::info!($($arg)+);
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        ::log::warn!($($arg)+);
+    }};
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::error!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        ::log::error!($($arg)+);
+    }};
+}
+
+/// Log at a [log::Level] decided at runtime
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        match $level {
+            ::log::Level::Error => ::tracing::error!($($arg)+),
+            ::log::Level::Warn => ::tracing::warn!($($arg)+),
+            ::log::Level::Info => ::tracing::info!($($arg)+),
+            ::log::Level::Debug => ::tracing::debug!($($arg)+),
+            ::log::Level::Trace => ::tracing::trace!($($arg)+),
+        }
+        #[cfg(not(feature = "tracing"))]
+        ::log::log!($level, $($arg)+);
+    }};
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) use spans::*;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use noop::*;
+
+#[cfg(feature = "tracing")]
+mod spans {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    pub(crate) use tracing::{Instrument, Span};
+
+    static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+    /// Span of a websocket connection, with an unique id and the session id once known
+    pub(crate) fn connection_span() -> Span {
+        let id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        tracing::info_span!("connection", id, session_id = tracing::field::Empty)
+    }
+
+    /// Record session id into current connection span
+    pub(crate) fn record_session_id(session_id: &str) {
+        Span::current().record("session_id", session_id);
+    }
+
+    /// Span of dispatching an event
+    pub(crate) fn event_span(sn: u64) -> Span {
+        tracing::info_span!("event", sn)
+    }
+
+    /// Span of a subscriber handling an event
+    pub(crate) fn subscriber_span(name: &str) -> Span {
+        tracing::info_span!("subscriber", name)
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod noop {
+    /// Placeholder of `tracing::Span`
+    #[derive(Debug, Clone)]
+    pub(crate) struct Span;
+
+    impl Span {
+        pub(crate) fn current() -> Self {
+            Self
+        }
+    }
+
+    /// Placeholder of `tracing::Instrument`, returns the future itself
+    pub(crate) trait Instrument: Sized {
+        fn instrument(self, _span: Span) -> Self {
+            self
+        }
+
+        fn in_current_span(self) -> Self {
+            self
+        }
+    }
+
+    impl<T> Instrument for T {}
+
+    pub(crate) fn connection_span() -> Span {
+        Span
+    }
+
+    pub(crate) fn record_session_id(_session_id: &str) {}
+
+    pub(crate) fn event_span(_sn: u64) -> Span {
+        Span
+    }
+
+    pub(crate) fn subscriber_span(_name: &str) -> Span {
+        Span
+    }
+}
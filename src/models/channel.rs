@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use super::{bool_or_int, User};
+
+/// Channel object
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Channel {
+    /// channel id
+    pub id: String,
+    /// channel name
+    pub name: String,
+    /// channel creator id
+    pub user_id: String,
+    /// guild id the channel belongs to
+    pub guild_id: String,
+    /// channel topic
+    pub topic: String,
+    /// is a category
+    #[serde(deserialize_with = "bool_or_int")]
+    pub is_category: bool,
+    /// parent category id
+    pub parent_id: String,
+    /// sort level
+    pub level: i64,
+    /// slow mode interval in milliseconds
+    pub slow_mode: i64,
+    /// channel type, 1 for text, 2 for voice
+    pub r#type: i64,
+    /// role permission overwrites
+    pub permission_overwrites: Vec<PermissionOverwrite>,
+    /// user permission overwrites
+    pub permission_users: Vec<PermissionUser>,
+    /// 1 if permissions are synced with parent category
+    pub permission_sync: i64,
+}
+
+/// Role permission overwrite of a channel
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionOverwrite {
+    /// role id
+    pub role_id: u64,
+    /// allowed permission bits
+    pub allow: u64,
+    /// denied permission bits
+    pub deny: u64,
+}
+
+/// User permission overwrite of a channel
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionUser {
+    /// user
+    pub user: User,
+    /// allowed permission bits
+    pub allow: u64,
+    /// denied permission bits
+    pub deny: u64,
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Embed content of message, like link preview or bilibili video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Embed {
+    /// link preview
+    Link(LinkEmbed),
+    /// bilibili video
+    BiliVideo(BiliVideoEmbed),
+    /// embed can't be recognized, keep it as raw json
+    Unknown(serde_json::Value),
+}
+
+/// Link preview embed
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkEmbed {
+    /// link url
+    pub url: String,
+    /// page title
+    pub title: String,
+    /// page description
+    pub description: String,
+    /// site name
+    pub site_name: String,
+    /// theme color, like `#FF0000`
+    pub theme_color: String,
+    /// preview image url
+    pub image: String,
+}
+
+/// Bilibili video embed
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiliVideoEmbed {
+    /// video url
+    pub url: String,
+    /// origin url in message
+    pub origin_url: String,
+    /// av number
+    pub av_no: String,
+    /// iframe path for embedding player
+    pub iframe_path: String,
+    /// video duration in seconds
+    pub duration: i64,
+    /// video title
+    pub title: String,
+    /// cover image url
+    pub pic: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum TaggedEmbed<'a> {
+    Link(&'a LinkEmbed),
+    BiliVideo(&'a BiliVideoEmbed),
+}
+
+impl Serialize for Embed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Link(e) => TaggedEmbed::Link(e).serialize(serializer),
+            Self::BiliVideo(e) => TaggedEmbed::BiliVideo(e).serialize(serializer),
+            Self::Unknown(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Embed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        let result = match value.get("type").and_then(|t| t.as_str()) {
+            Some("link") => LinkEmbed::deserialize(&value).map(Self::Link),
+            Some("bili-video") => BiliVideoEmbed::deserialize(&value).map(Self::BiliVideo),
+            _ => return Ok(Self::Unknown(value)),
+        };
+
+        Ok(result.unwrap_or(Self::Unknown(value)))
+    }
+}
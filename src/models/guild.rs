@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Guild object
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Guild {
+    /// guild id
+    pub id: String,
+    /// guild name
+    pub name: String,
+    /// guild owner id
+    pub user_id: String,
+    /// guild icon url
+    pub icon: String,
+    /// default notify type, 0 for guild default, 1 for all, 2 for mention only, 3 for none
+    pub notify_type: i64,
+    /// guild voice region
+    pub region: String,
+    /// is public guild
+    pub enable_open: bool,
+    /// public guild id
+    pub open_id: serde_json::Value,
+    /// default channel id
+    pub default_channel_id: String,
+    /// welcome channel id
+    pub welcome_channel_id: String,
+}
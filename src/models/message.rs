@@ -0,0 +1,277 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+
+use super::{Timestamp, User};
+use crate::kmarkdown::KMarkdown;
+
+/// Common quoted message
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quote {
+    /// quoted message id
+    pub id: String,
+    /// quoted message id in rong cloud
+    pub rong_id: String,
+    /// quoted message type
+    pub r#type: MessageType,
+    /// quoted message content
+    pub content: String,
+    /// quoted message create time in milliseconds timestamp
+    pub create_at: Timestamp,
+    /// quoted message author
+    pub author: User,
+}
+
+/// Attachment of image/video/file/audio message
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attachment {
+    /// attachment type, image/video/file/audio
+    pub r#type: String,
+    /// file url
+    pub url: String,
+    /// file name
+    pub name: String,
+    /// file size in bytes
+    pub size: u64,
+    /// media duration in seconds, only for video and audio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<serde_json::Number>,
+    /// width in pixels, only for image and video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u64>,
+    /// height in pixels, only for image and video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+    /// file mime type
+    pub file_type: String,
+}
+
+impl Attachment {
+    /// Download the file, see [Client::download_asset](crate::api::Client::download_asset)
+    pub async fn download(&self, client: &crate::api::Client) -> crate::api::Result<bytes::Bytes> {
+        client.download_asset(&self.url).await
+    }
+}
+
+/// Emoji used in reactions, a unicode emoji or a guild emoji
+///
+/// Can be parsed from the reaction api form (`😘` or `guild_id/emoji_id`) and the kmarkdown form
+/// (`(emj)name(emj)[guild_id/emoji_id]`).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Emoji {
+    /// emoji id, for unicode emoji it's the emoji itself, for guild emoji it's `guild_id/emoji_id`
+    pub id: String,
+    /// emoji name
+    pub name: String,
+}
+
+impl Emoji {
+    /// Create a unicode emoji
+    pub fn unicode<S: Into<String>>(emoji: S) -> Self {
+        let emoji = emoji.into();
+        Self {
+            id: emoji.clone(),
+            name: emoji,
+        }
+    }
+
+    /// Create a guild emoji, `id` is in `guild_id/emoji_id` form
+    pub fn guild<I: Into<String>, N: Into<String>>(id: I, name: N) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+
+    /// Whether it's a guild emoji
+    pub fn is_guild(&self) -> bool {
+        self.guild_id().is_some()
+    }
+
+    /// Id of guild which the emoji belongs to, `None` for unicode emoji
+    pub fn guild_id(&self) -> Option<&str> {
+        self.id
+            .split_once('/')
+            .map(|(guild, _)| guild)
+            .filter(|guild| !guild.is_empty())
+    }
+
+    /// Format as kmarkdown content
+    pub fn to_kmarkdown(&self) -> String {
+        if self.is_guild() {
+            KMarkdown::new().server_emoji(&self.name, &self.id).build()
+        } else if self.name.is_empty() {
+            self.id.clone()
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Error when parse [Emoji] from string
+#[derive(Debug, Snafu, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub(crate)), module(error), context(suffix(false)))]
+pub enum ParseEmojiError {
+    /// starts with `(emj)` but not like `(emj)name(emj)[guild_id/emoji_id]`
+    #[snafu(display("not a kmarkdown emoji"))]
+    InvalidKMarkdown,
+
+    /// contains `/` but guild id or emoji id part is empty
+    #[snafu(display("not a guild emoji id"))]
+    InvalidGuildEmojiId,
+
+    /// empty or contains whitespaces
+    #[snafu(display("not an emoji"))]
+    InvalidEmoji,
+}
+
+impl FromStr for Emoji {
+    type Err = ParseEmojiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("(emj)") {
+            return rest
+                .split_once("(emj)[")
+                .and_then(|(name, id)| Some((name, id.strip_suffix(']')?)))
+                .map(|(name, id)| Self::guild(id, name))
+                .filter(Self::is_guild)
+                .context(error::InvalidKMarkdown);
+        }
+
+        match s.split_once('/') {
+            Some((guild, emoji)) if guild.is_empty() || emoji.is_empty() => {
+                error::InvalidGuildEmojiId.fail()
+            }
+            Some(_) => Ok(Self::guild(s, "")),
+            None if s.is_empty() || s.contains(char::is_whitespace) => error::InvalidEmoji.fail(),
+            None => Ok(Self::unicode(s)),
+        }
+    }
+}
+
+impl From<&str> for Emoji {
+    /// Parse from wire formats, fallback to unicode emoji
+    fn from(s: &str) -> Self {
+        s.parse().unwrap_or_else(|_| Self::unicode(s))
+    }
+}
+
+/// Message channel type
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ChannelType {
+    /// guild channel message
+    #[default]
+    Group,
+    /// private chat message
+    Person,
+    /// broadcast message
+    Broadcast,
+    /// type can't be recognized
+    Unknown(String),
+}
+
+impl ChannelType {
+    /// get type string used in kaiheila protocol
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Group => "GROUP",
+            Self::Person => "PERSON",
+            Self::Broadcast => "BROADCAST",
+            Self::Unknown(s) => s.as_str(),
+        }
+    }
+}
+
+impl From<String> for ChannelType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "GROUP" => Self::Group,
+            "PERSON" => Self::Person,
+            "BROADCAST" => Self::Broadcast,
+            _ => Self::Unknown(s),
+        }
+    }
+}
+
+impl From<ChannelType> for String {
+    fn from(t: ChannelType) -> Self {
+        match t {
+            ChannelType::Unknown(s) => s,
+            t => t.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Message type
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "i64", into = "i64")]
+pub enum MessageType {
+    /// plain text message
+    #[default]
+    Text,
+    /// image message
+    Image,
+    /// video message
+    Video,
+    /// file message
+    File,
+    /// audio message
+    Audio,
+    /// kmarkdown message
+    KMarkdown,
+    /// card message
+    Card,
+    /// system message
+    System,
+    /// type can't be recognized
+    Unknown(i64),
+}
+
+impl MessageType {
+    /// get type number used in kaiheila protocol
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            Self::Text => 1,
+            Self::Image => 2,
+            Self::Video => 3,
+            Self::File => 4,
+            Self::Audio => 8,
+            Self::KMarkdown => 9,
+            Self::Card => 10,
+            Self::System => 255,
+            Self::Unknown(t) => *t,
+        }
+    }
+}
+
+impl From<i64> for MessageType {
+    fn from(t: i64) -> Self {
+        match t {
+            1 => Self::Text,
+            2 => Self::Image,
+            3 => Self::Video,
+            4 => Self::File,
+            8 => Self::Audio,
+            9 => Self::KMarkdown,
+            10 => Self::Card,
+            255 => Self::System,
+            t => Self::Unknown(t),
+        }
+    }
+}
+
+impl From<MessageType> for i64 {
+    fn from(t: MessageType) -> Self {
+        t.as_i64()
+    }
+}
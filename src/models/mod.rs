@@ -0,0 +1,245 @@
+//! Kaiheila data models shared by HTTP API and websocket events.
+
+mod channel;
+mod embed;
+mod guild;
+mod message;
+mod role;
+mod timestamp;
+mod user;
+
+pub use channel::{Channel, PermissionOverwrite, PermissionUser};
+pub use embed::{BiliVideoEmbed, Embed, LinkEmbed};
+pub use guild::Guild;
+pub use message::{Attachment, ChannelType, Emoji, MessageType, ParseEmojiError, Quote};
+pub use role::Role;
+pub use timestamp::Timestamp;
+pub use user::User;
+
+use serde::Deserialize;
+
+// some api and events use 0/1 for bool field
+pub(crate) fn bool_or_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrInt {
+        Bool(bool),
+        Int(i64),
+    }
+
+    Ok(match BoolOrInt::deserialize(deserializer)? {
+        BoolOrInt::Bool(b) => b,
+        BoolOrInt::Int(i) => i != 0,
+    })
+}
+
+// some api return numbers as string
+pub(crate) fn number_or_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString<T> {
+        Number(T),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_model_user_fixture() {
+        let user: User = serde_json::from_str(include_str!("fixtures/user.json")).unwrap();
+
+        assert_eq!(user.id, "2418200000");
+        assert_eq!(user.username, "tz-un");
+        assert_eq!(user.identify_num, "5618");
+        assert!(user.online);
+        assert!(!user.bot);
+        assert_eq!(user.roles, vec![11111, 22222]);
+    }
+
+    #[test]
+    fn test_model_guild_fixture() {
+        let guild: Guild = serde_json::from_str(include_str!("fixtures/guild.json")).unwrap();
+
+        assert_eq!(guild.id, "91686000000");
+        assert_eq!(guild.name, "Hello");
+        assert_eq!(guild.region, "beijing");
+        assert!(!guild.enable_open);
+        assert_eq!(guild.default_channel_id, "2710000000");
+    }
+
+    #[test]
+    fn test_model_channel_fixture() {
+        let channel: Channel = serde_json::from_str(include_str!("fixtures/channel.json")).unwrap();
+
+        assert_eq!(channel.id, "4334000000");
+        assert!(!channel.is_category);
+        assert_eq!(channel.r#type, 1);
+        assert_eq!(channel.permission_overwrites[0].allow, 2048);
+        assert_eq!(channel.permission_users[0].user.username, "tz-un");
+    }
+
+    #[test]
+    fn test_model_role_fixture() {
+        let role: Role = serde_json::from_str(include_str!("fixtures/role.json")).unwrap();
+
+        assert_eq!(role.role_id, 11111);
+        assert_eq!(role.color, 0);
+        assert_eq!(role.permissions, 142924296);
+    }
+
+    #[test]
+    fn test_model_channel_type() {
+        let types: Vec<ChannelType> =
+            serde_json::from_str(r#"["GROUP", "PERSON", "BROADCAST", "NEW"]"#).unwrap();
+
+        assert_eq!(
+            types,
+            vec![
+                ChannelType::Group,
+                ChannelType::Person,
+                ChannelType::Broadcast,
+                ChannelType::Unknown("NEW".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            serde_json::to_string(&types).unwrap(),
+            r#"["GROUP","PERSON","BROADCAST","NEW"]"#
+        );
+    }
+
+    #[test]
+    fn test_model_message_type() {
+        let types: Vec<MessageType> = serde_json::from_str("[1, 9, 10, 255, 42]").unwrap();
+
+        assert_eq!(
+            types,
+            vec![
+                MessageType::Text,
+                MessageType::KMarkdown,
+                MessageType::Card,
+                MessageType::System,
+                MessageType::Unknown(42),
+            ]
+        );
+
+        assert_eq!(serde_json::to_string(&types).unwrap(), "[1,9,10,255,42]");
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_model_chrono_timestamp() {
+        let quote: Quote = serde_json::from_str(r#"{"create_at": 1612703779612}"#).unwrap();
+
+        assert_eq!(
+            quote.create_at.to_datetime().unwrap().to_rfc3339(),
+            "2021-02-07T13:16:19.612+00:00"
+        );
+        assert_eq!(
+            serde_json::to_value(&quote).unwrap()["create_at"],
+            1612703779612_i64
+        );
+    }
+
+    #[test]
+    fn test_model_embeds() {
+        let embeds: Vec<Embed> = serde_json::from_str(
+            r##"[
+                {
+                    "type": "link",
+                    "url": "https://www.kaiheila.cn",
+                    "title": "KOOK",
+                    "description": "voice chat",
+                    "site_name": "kaiheila",
+                    "theme_color": "#FFFFFF",
+                    "image": "https://img.kaiheila.cn/some.png"
+                },
+                {
+                    "type": "bili-video",
+                    "url": "https://www.bilibili.com/video/BV1xx411c7mD",
+                    "origin_url": "https://b23.tv/xxx",
+                    "av_no": "BV1xx411c7mD",
+                    "iframe_path": "https://player.bilibili.com/player.html?bvid=BV1xx411c7mD",
+                    "duration": 300,
+                    "title": "video",
+                    "pic": "https://i0.hdslb.com/some.jpg"
+                },
+                {
+                    "type": "new-embed",
+                    "foo": "bar"
+                }
+            ]"##,
+        )
+        .unwrap();
+
+        assert!(matches!(&embeds[0], Embed::Link(e) if e.site_name == "kaiheila"));
+        assert!(matches!(&embeds[1], Embed::BiliVideo(e) if e.duration == 300));
+        assert!(matches!(&embeds[2], Embed::Unknown(v) if v["foo"] == "bar"));
+
+        let json = serde_json::to_value(&embeds).unwrap();
+        assert_eq!(json[0]["type"], "link");
+        assert_eq!(json[1]["type"], "bili-video");
+        assert_eq!(json[2]["type"], "new-embed");
+    }
+
+    #[test]
+    fn test_model_emoji() {
+        let emoji: Emoji = "😘".parse().unwrap();
+        assert_eq!(emoji, Emoji::unicode("😘"));
+        assert!(!emoji.is_guild());
+        assert_eq!(emoji.to_kmarkdown(), "😘");
+
+        let emoji: Emoji = "1234/abcd".parse().unwrap();
+        assert_eq!(emoji.guild_id(), Some("1234"));
+        assert_eq!(emoji.name, "");
+
+        let emoji: Emoji = "(emj)party(emj)[1234/abcd]".parse().unwrap();
+        assert_eq!(emoji, Emoji::guild("1234/abcd", "party"));
+        assert_eq!(emoji.to_kmarkdown(), "(emj)party(emj)[1234/abcd]");
+
+        let emoji = Emoji::guild("1234/ab_cd-e", "heart_eyes");
+        assert_eq!(emoji.to_kmarkdown(), "(emj)heart_eyes(emj)[1234/ab_cd-e]");
+        assert_eq!(emoji.to_kmarkdown().parse::<Emoji>().unwrap(), emoji);
+
+        let emoji: Emoji = "1234/abcd".parse().unwrap();
+        assert_eq!(emoji.to_kmarkdown().parse::<Emoji>().unwrap(), emoji);
+
+        assert_eq!("".parse::<Emoji>(), Err(ParseEmojiError::InvalidEmoji));
+        assert_eq!(
+            "/abcd".parse::<Emoji>(),
+            Err(ParseEmojiError::InvalidGuildEmojiId)
+        );
+        assert_eq!(
+            "(emj)party(emj)[abcd]".parse::<Emoji>(),
+            Err(ParseEmojiError::InvalidKMarkdown)
+        );
+        assert_eq!(Emoji::from("[#128561;]").id, "[#128561;]");
+
+        let emoji: Emoji = serde_json::from_str(r#"{"id": "1234/abcd", "name": "party"}"#).unwrap();
+        assert!(emoji.is_guild());
+    }
+
+    #[test]
+    fn test_model_round_trip() {
+        let channel: Channel = serde_json::from_str(include_str!("fixtures/channel.json")).unwrap();
+        let json = serde_json::to_string(&channel).unwrap();
+
+        assert_eq!(serde_json::from_str::<Channel>(&json).unwrap(), channel);
+    }
+}
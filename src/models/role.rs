@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Guild role object
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Role {
+    /// role id
+    pub role_id: u64,
+    /// role name
+    pub name: String,
+    /// role color
+    pub color: u32,
+    /// sort position
+    pub position: i64,
+    /// 1 if members of this role are displayed separately
+    pub hoist: i64,
+    /// 1 if this role can be mentioned
+    pub mentionable: i64,
+    /// permission bits, see <https://developer.kaiheila.cn/doc/http/guild-role>
+    pub permissions: u64,
+}
@@ -0,0 +1,54 @@
+//! Milliseconds timestamp used by api and events.
+
+use serde::{Deserialize, Serialize};
+
+/// Milliseconds unix timestamp, convert to `DateTime<Utc>` by [to_datetime](Self::to_datetime)
+/// if `chrono` feature is enabled.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    /// Create from milliseconds since unix epoch
+    pub fn from_millis(millis: i64) -> Self {
+        Self(millis)
+    }
+
+    /// Milliseconds since unix epoch
+    pub fn as_millis(&self) -> i64 {
+        self.0
+    }
+
+    /// Convert to `DateTime<Utc>`, `None` if out of range
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp_millis(self.0)
+    }
+
+    /// Create from `DateTime<Utc>`
+    #[cfg(feature = "chrono")]
+    pub fn from_datetime(datetime: chrono::DateTime<chrono::Utc>) -> Self {
+        Self(datetime.timestamp_millis())
+    }
+}
+
+impl From<i64> for Timestamp {
+    fn from(millis: i64) -> Self {
+        Self(millis)
+    }
+}
+
+impl From<Timestamp> for i64 {
+    fn from(t: Timestamp) -> Self {
+        t.0
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(datetime: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::from_datetime(datetime)
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use super::Timestamp;
+
+/// Common user object
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct User {
+    /// user id
+    pub id: String,
+    /// username
+    pub username: String,
+    /// identify number, `username#identify_num` is the full user name
+    pub identify_num: String,
+    /// nickname in guild
+    pub nickname: String,
+    /// avatar url
+    pub avatar: String,
+    /// avatar url for vip user, may be a gif
+    pub vip_avatar: String,
+    /// is online
+    pub online: bool,
+    /// is a bot
+    pub bot: bool,
+    /// user status, 0 and 1 for normal, 10 for banned
+    pub status: i64,
+    /// role ids in guild
+    pub roles: Vec<u64>,
+    /// is vip user
+    pub is_vip: bool,
+    /// is mobile phone verified
+    pub mobile_verified: bool,
+    /// time the user joined the guild, only in guild member data
+    pub joined_at: Timestamp,
+    /// last active time in the guild, only in guild member data
+    pub active_time: Timestamp,
+}
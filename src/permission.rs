@@ -0,0 +1,196 @@
+//! Permission bitflags and calculator.
+//!
+//! See: <https://developer.kaiheila.cn/doc/http/guild-role>
+
+use crate::models::{Channel, Guild, Role, User};
+
+bitflags::bitflags! {
+    /// Permission bitflags of roles and channel overwrites
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct Permissions: u64 {
+        /// administrator, has all permissions
+        const ADMIN = 1 << 0;
+        /// manage guild
+        const MANAGE_GUILD = 1 << 1;
+        /// view audit log
+        const VIEW_AUDIT_LOG = 1 << 2;
+        /// create invite
+        const CREATE_INVITE = 1 << 3;
+        /// manage invite
+        const MANAGE_INVITE = 1 << 4;
+        /// manage channel
+        const MANAGE_CHANNEL = 1 << 5;
+        /// kick user
+        const KICK_USER = 1 << 6;
+        /// ban user
+        const BAN_USER = 1 << 7;
+        /// manage custom emoji
+        const MANAGE_CUSTOM_EMOJI = 1 << 8;
+        /// change own nickname
+        const CHANGE_NICKNAME = 1 << 9;
+        /// manage role
+        const MANAGE_ROLE = 1 << 10;
+        /// view text and voice channel
+        const VIEW_CHANNEL = 1 << 11;
+        /// send message
+        const SEND_MESSAGE = 1 << 12;
+        /// manage message
+        const MANAGE_MESSAGE = 1 << 13;
+        /// upload file
+        const UPLOAD_FILE = 1 << 14;
+        /// connect voice channel
+        const VOICE_CONNECT = 1 << 15;
+        /// manage voice channel
+        const VOICE_MANAGE = 1 << 16;
+        /// mention @everyone, @here and all roles
+        const MENTION_EVERYONE = 1 << 17;
+        /// add reaction
+        const ADD_REACTION = 1 << 18;
+        /// follow existing reaction
+        const FOLLOW_REACTION = 1 << 19;
+        /// be moved into voice channel passively
+        const PASSIVE_VOICE_CONNECT = 1 << 20;
+        /// only use push to talk
+        const ONLY_PUSH_TO_TALK = 1 << 21;
+        /// use free voice
+        const FREE_VOICE = 1 << 22;
+        /// speak in voice channel
+        const SPEAK = 1 << 23;
+        /// deafen user in guild
+        const DEAFEN_USER = 1 << 24;
+        /// mute user in guild
+        const MUTE_USER = 1 << 25;
+        /// manage nickname of others
+        const MANAGE_NICKNAME = 1 << 26;
+        /// play music with accompaniment
+        const PLAY_MUSIC = 1 << 27;
+    }
+}
+
+/// role id of @everyone role
+const EVERYONE_ROLE_ID: u64 = 0;
+
+/// Compute effective permissions of a guild member in a channel.
+///
+/// Guild owner and administrators have all permissions. Otherwise, start with permissions of
+/// @everyone and member's roles, then apply overwrites of @everyone, member's roles and the
+/// member in the channel, in that order.
+pub fn permissions_for(
+    guild: &Guild,
+    roles: &[Role],
+    member: &User,
+    channel: &Channel,
+) -> Permissions {
+    if guild.user_id == member.id {
+        return Permissions::all();
+    }
+
+    let has_role = |role_id: u64| role_id == EVERYONE_ROLE_ID || member.roles.contains(&role_id);
+
+    let base = roles
+        .iter()
+        .filter(|r| has_role(r.role_id))
+        .fold(Permissions::empty(), |acc, r| {
+            acc | Permissions::from_bits_truncate(r.permissions)
+        });
+
+    if base.contains(Permissions::ADMIN) {
+        return Permissions::all();
+    }
+
+    let apply = |perms: Permissions, allow: u64, deny: u64| {
+        (perms - Permissions::from_bits_truncate(deny)) | Permissions::from_bits_truncate(allow)
+    };
+
+    let overwrites = &channel.permission_overwrites;
+
+    let mut perms = overwrites
+        .iter()
+        .filter(|o| o.role_id == EVERYONE_ROLE_ID)
+        .fold(base, |acc, o| apply(acc, o.allow, o.deny));
+
+    let (allow, deny) = overwrites
+        .iter()
+        .filter(|o| o.role_id != EVERYONE_ROLE_ID && has_role(o.role_id))
+        .fold((0, 0), |(allow, deny), o| (allow | o.allow, deny | o.deny));
+    perms = apply(perms, allow, deny);
+
+    channel
+        .permission_users
+        .iter()
+        .filter(|o| o.user.id == member.id)
+        .fold(perms, |acc, o| apply(acc, o.allow, o.deny))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{PermissionOverwrite, PermissionUser};
+
+    fn role(role_id: u64, permissions: Permissions) -> Role {
+        Role {
+            role_id,
+            permissions: permissions.bits(),
+            ..Default::default()
+        }
+    }
+
+    fn user(id: &str, roles: Vec<u64>) -> User {
+        User {
+            id: id.to_string(),
+            roles,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_permissions_for() {
+        let guild = Guild {
+            user_id: "owner".to_string(),
+            ..Default::default()
+        };
+        let roles = vec![
+            role(0, Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGE),
+            role(1, Permissions::MANAGE_MESSAGE),
+            role(2, Permissions::ADMIN),
+        ];
+        let channel = Channel {
+            permission_overwrites: vec![
+                PermissionOverwrite {
+                    role_id: 0,
+                    allow: 0,
+                    deny: Permissions::SEND_MESSAGE.bits(),
+                },
+                PermissionOverwrite {
+                    role_id: 1,
+                    allow: Permissions::SEND_MESSAGE.bits(),
+                    deny: 0,
+                },
+            ],
+            permission_users: vec![PermissionUser {
+                user: user("muted", vec![1]),
+                allow: 0,
+                deny: Permissions::SEND_MESSAGE.bits(),
+            }],
+            ..Default::default()
+        };
+
+        let member = permissions_for(&guild, &roles, &user("member", vec![]), &channel);
+        assert_eq!(member, Permissions::VIEW_CHANNEL);
+
+        let moderator = permissions_for(&guild, &roles, &user("moderator", vec![1]), &channel);
+        assert_eq!(
+            moderator,
+            Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGE | Permissions::MANAGE_MESSAGE
+        );
+
+        let muted = permissions_for(&guild, &roles, &user("muted", vec![1]), &channel);
+        assert!(!muted.contains(Permissions::SEND_MESSAGE));
+
+        let admin = permissions_for(&guild, &roles, &user("admin", vec![2]), &channel);
+        assert_eq!(admin, Permissions::all());
+
+        let owner = permissions_for(&guild, &roles, &user("owner", vec![]), &channel);
+        assert_eq!(owner, Permissions::all());
+    }
+}
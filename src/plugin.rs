@@ -0,0 +1,43 @@
+//! Bot plugins.
+
+use std::borrow::Cow;
+
+use crate::{context::BotContext, Bot};
+
+/// Plugin bundles subscribers, commands, shared data and setup/teardown logic,
+/// can be added to bot by [Bot::plugin].
+///
+/// ```no_run
+/// use std::borrow::Cow;
+///
+/// use burz::{filter, Bot, EventContext, Plugin};
+///
+/// struct Ping;
+///
+/// impl Plugin for Ping {
+///     fn name(&self) -> Cow<'static, str> {
+///         "ping".into()
+///     }
+///
+///     fn register(&mut self, bot: &mut Bot) {
+///         bot.subscribe(filter::command("!ping"), |ctx: EventContext| async move {
+///             ctx.reply("pong").await.map(|_| ())
+///         });
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait Plugin: Send + Sync {
+    /// plugin name
+    fn name(&self) -> Cow<'static, str>;
+
+    /// register subscribers, commands and shared data of this plugin into bot,
+    /// will be execute when the plugin is added
+    fn register(&mut self, bot: &mut Bot);
+
+    /// callback will be execute when bot is loaded, after all subscribers loaded
+    async fn setup(&self, _ctx: BotContext) {}
+
+    /// callback will be execute when bot stops running
+    async fn teardown(&self, _ctx: BotContext) {}
+}
@@ -0,0 +1,162 @@
+//! Scheduled tasks.
+
+use std::{fmt::Debug, future::Future, sync::Arc, time::Duration};
+
+use tokio::{task::JoinHandle, time::Instant};
+
+use crate::context::BotContext;
+
+/// Schedule decides when a task should run.
+pub trait Schedule: Send + Sync + Debug {
+    /// Get next run time after `now`, return None to stop the task.
+    fn next_after(&mut self, now: Instant) -> Option<Instant>;
+}
+
+/// Task can be scheduled by [Bot::schedule](crate::Bot::schedule).
+#[async_trait::async_trait]
+pub trait Task: Send + Sync {
+    /// callback will be execute every time the schedule fires,
+    /// with api client, bot user info and shared data in the context
+    async fn run(&self, ctx: BotContext);
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> Task for F
+where
+    F: Fn(BotContext) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn run(&self, ctx: BotContext) {
+        self(ctx).await
+    }
+}
+
+/// Schedule that fires at a fixed rate.
+#[derive(Debug, Clone)]
+pub struct Every {
+    period: Duration,
+    next: Option<Instant>,
+}
+
+impl Schedule for Every {
+    fn next_after(&mut self, now: Instant) -> Option<Instant> {
+        let mut next = self.next.unwrap_or(now) + self.period;
+        // skip missed runs if last run takes too long
+        if next <= now {
+            next = now + self.period;
+        }
+        self.next.replace(next);
+        Some(next)
+    }
+}
+
+/// Create a schedule that fires every `period`, the first run is one period after bot loaded.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn every(period: Duration) -> Every {
+    assert!(!period.is_zero(), "schedule period must not be zero");
+    Every { period, next: None }
+}
+
+/// Schedule that fires by a cron expression, in UTC.
+#[cfg(feature = "cron")]
+#[derive(Debug, Clone)]
+pub struct Cron {
+    schedule: cron::Schedule,
+}
+
+#[cfg(feature = "cron")]
+impl Schedule for Cron {
+    fn next_after(&mut self, now: Instant) -> Option<Instant> {
+        let next = self.schedule.upcoming(chrono::Utc).next()?;
+        let delay = (next - chrono::Utc::now()).to_std().unwrap_or_default();
+        Some(now + delay)
+    }
+}
+
+#[cfg(feature = "cron")]
+impl From<cron::Schedule> for Cron {
+    fn from(schedule: cron::Schedule) -> Self {
+        Self { schedule }
+    }
+}
+
+/// Create a schedule by cron expression, like `0 */5 * * * *` for every 5 minutes.
+///
+/// The expression starts with a seconds field, see [cron] crate for the syntax.
+#[cfg(feature = "cron")]
+pub fn cron(expr: &str) -> Result<Cron, cron::error::Error> {
+    expr.parse::<cron::Schedule>().map(Cron::from)
+}
+
+pub(crate) fn spawn(
+    mut schedule: Box<dyn Schedule>,
+    task: Arc<dyn Task>,
+    ctx: BotContext,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(next) = schedule.next_after(Instant::now()) {
+            tokio::time::sleep_until(next).await;
+            task.run(ctx.clone()).await;
+        }
+        debug!("Schedule {:?} finished", schedule);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_every() {
+        let start = Instant::now();
+        let period = Duration::from_secs(10);
+        let mut schedule = every(period);
+
+        assert_eq!(schedule.next_after(start), Some(start + period));
+        assert_eq!(
+            schedule.next_after(start + Duration::from_secs(11)),
+            Some(start + period * 2)
+        );
+        assert_eq!(
+            schedule.next_after(start + Duration::from_secs(35)),
+            Some(start + Duration::from_secs(45))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_every_zero_period() {
+        every(Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_schedule() {
+        #[derive(Debug)]
+        struct Times(usize);
+
+        impl Schedule for Times {
+            fn next_after(&mut self, now: Instant) -> Option<Instant> {
+                self.0 = self.0.checked_sub(1)?;
+                Some(now)
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        let task = move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async {}
+        };
+
+        spawn(Box::new(Times(3)), Arc::new(task), BotContext::mock())
+            .await
+            .unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+}
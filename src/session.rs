@@ -0,0 +1,205 @@
+//! Persistent websocket session.
+
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{api::types::GatewayResumeArguments, dedup::Dedup, subscriber::BoxError};
+
+/// Min interval between two saves of a changing session
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Storage of websocket session, used by bot to resume the session after process restart.
+///
+/// Set it by [BotBuilder::session_store](crate::BotBuilder::session_store).
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync + Debug {
+    /// load last saved session, `None` if no session saved
+    async fn load(&self) -> Result<Option<GatewayResumeArguments>, BoxError>;
+
+    /// save current session, will be execute when session id or sn changed, at most once per
+    /// [SAVE_INTERVAL]
+    async fn save(&self, resume: &GatewayResumeArguments) -> Result<(), BoxError>;
+
+    /// load recently received msg ids, used to drop events delivered again after restart
+    ///
+    /// See [BotBuilder::dedup_capacity](crate::BotBuilder::dedup_capacity).
+    async fn load_msg_ids(&self) -> Result<Vec<String>, BoxError> {
+        Ok(vec![])
+    }
+
+    /// save recently received msg ids, from oldest to newest
+    async fn save_msg_ids(&self, _ids: &[String]) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+/// Session store which saves session as a json file.
+///
+/// Recent msg ids are saved in another file, with `.msg_ids` appended to the path.
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    path: PathBuf,
+}
+
+impl FileSessionStore {
+    /// Create a store which saves session to the file
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn msg_ids_path(&self) -> PathBuf {
+        with_suffix(&self.path, ".msg_ids")
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+async fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>, BoxError> {
+    match tokio::fs::read(path).await {
+        Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn write_json<T: serde::Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), BoxError> {
+    // write to a temp file then rename, so a crash never leaves a broken file
+    let temp = with_suffix(path, ".tmp");
+    tokio::fs::write(&temp, serde_json::to_vec(value)?).await?;
+    tokio::fs::rename(&temp, path).await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load(&self) -> Result<Option<GatewayResumeArguments>, BoxError> {
+        read_json(&self.path).await
+    }
+
+    async fn save(&self, resume: &GatewayResumeArguments) -> Result<(), BoxError> {
+        write_json(&self.path, resume).await
+    }
+
+    async fn load_msg_ids(&self) -> Result<Vec<String>, BoxError> {
+        Ok(read_json(&self.msg_ids_path()).await?.unwrap_or_default())
+    }
+
+    async fn save_msg_ids(&self, ids: &[String]) -> Result<(), BoxError> {
+        write_json(&self.msg_ids_path(), ids).await
+    }
+}
+
+/// Save session, and recent msg ids if deduplication is enabled.
+pub(crate) async fn save(
+    store: &dyn SessionStore,
+    resume: &GatewayResumeArguments,
+    dedup: Option<&Mutex<Dedup>>,
+) {
+    if let Err(err) = store.save(resume).await {
+        warn!("Save session failed: {}", err);
+    }
+
+    if let Some(dedup) = dedup {
+        let ids: Vec<_> = dedup.lock().unwrap().ids().map(ToOwned::to_owned).collect();
+        if let Err(err) = store.save_msg_ids(&ids).await {
+            warn!("Save recent msg ids failed: {}", err);
+        }
+    }
+}
+
+/// Spawn a task which saves session to the store when it changes, at most once per
+/// [SAVE_INTERVAL]. Bot saves the latest session again when shutdown.
+pub(crate) fn spawn_saver(
+    store: Arc<dyn SessionStore + 'static>,
+    mut session: watch::Receiver<GatewayResumeArguments>,
+    dedup: Option<Arc<Mutex<Dedup>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let resume = session.borrow_and_update().clone();
+            save(store.as_ref(), &resume, dedup.as_deref()).await;
+            // changes during the interval are saved together after it
+            tokio::time::sleep(SAVE_INTERVAL).await;
+            if session.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct MemoryStore(Mutex<Vec<u64>>);
+
+    #[async_trait::async_trait]
+    impl SessionStore for MemoryStore {
+        async fn load(&self) -> Result<Option<GatewayResumeArguments>, BoxError> {
+            Ok(None)
+        }
+
+        async fn save(&self, resume: &GatewayResumeArguments) -> Result<(), BoxError> {
+            self.0.lock().unwrap().push(resume.sn);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_saver_debounce() {
+        let store = Arc::new(MemoryStore::default());
+        let (sender, receiver) = watch::channel(GatewayResumeArguments::default());
+        let saver = spawn_saver(Arc::clone(&store) as Arc<dyn SessionStore>, receiver, None);
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        for sn in 1..=5 {
+            sender.send_modify(|resume| resume.sn = sn);
+        }
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        assert_eq!(*store.0.lock().unwrap(), [0, 5]);
+
+        sender.send_modify(|resume| resume.sn = 6);
+        drop(sender);
+        saver.await.unwrap();
+        assert_eq!(*store.0.lock().unwrap(), [0, 5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_file_session_store() {
+        let path = std::env::temp_dir().join(format!("burz-session-{}.json", std::process::id()));
+        let store = FileSessionStore::new(&path);
+
+        assert_eq!(store.load().await.unwrap(), None);
+
+        let resume = GatewayResumeArguments {
+            sn: 42,
+            session_id: "session".to_string(),
+        };
+        store.save(&resume).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(resume));
+
+        assert!(store.load_msg_ids().await.unwrap().is_empty());
+        let ids = vec!["a".to_string(), "b".to_string()];
+        store.save_msg_ids(&ids).await.unwrap();
+        assert_eq!(store.load_msg_ids().await.unwrap(), ids);
+
+        tokio::fs::remove_file(&path).await.unwrap();
+        tokio::fs::remove_file(store.msg_ids_path()).await.unwrap();
+    }
+}
@@ -1,36 +1,298 @@
 //! Event subscribers.
 
-use std::{borrow::Cow, future::Future, sync::Arc};
+use std::{borrow::Cow, future::Future, marker::PhantomData, sync::Arc};
 
 use crate::{
-    api::{self, Client},
-    ws::Event,
+    context::{BotContext, EventContext},
+    filter::FilterMap,
 };
 
+/// Error type returned by subscribers.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Output type of subscriber closures, `()` or `Result<(), E>`.
+pub trait IntoSubscriberResult {
+    /// convert into result of [Subscriber::on_event]
+    fn into_result(self) -> Result<(), BoxError>;
+}
+
+impl IntoSubscriberResult for () {
+    fn into_result(self) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+impl<E> IntoSubscriberResult for Result<(), E>
+where
+    E: Into<BoxError>,
+{
+    fn into_result(self) -> Result<(), BoxError> {
+        self.map_err(Into::into)
+    }
+}
+
 /// Subscriber can be register to bot and process event.
 #[async_trait::async_trait]
-pub trait Subscriber {
+pub trait Subscriber: Send + Sync {
     /// subscriber name
     fn name(&self) -> Cow<'static, str>;
-    /// callback will be execute when a bot load this subscriber
-    async fn on_loaded(&mut self, client: Client);
-    /// callback will be execute when a bot load this subscriber
-    async fn on_event(self: Arc<Self>, event: Arc<Event>);
+    /// callback will be execute when a bot load this subscriber,
+    /// with api client, bot user info and shared data in the context
+    async fn on_loaded(&mut self, ctx: BotContext);
+    /// callback will be execute when a event passed the filter of this subscriber,
+    /// returned error will be passed to the error handler of bot
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError>;
+    /// callback will be execute when bot joined a guild, regardless of the filter
+    async fn on_self_joined_guild(self: Arc<Self>, _guild_id: String) {}
+    /// callback will be execute when bot exited a guild, regardless of the filter
+    async fn on_self_exited_guild(self: Arc<Self>, _guild_id: String) {}
+    /// callback will be execute when bot stops running,
+    /// can be used to flush caches, close connections, or send farewell messages
+    async fn on_shutdown(self: Arc<Self>) {}
 }
 
 #[async_trait::async_trait]
 impl<F, Fut> Subscriber for F
 where
-    F: Fn(Arc<Event>) -> Fut + Send + Sync,
-    Fut: Future<Output = ()> + Send,
+    F: Fn(EventContext) -> Fut + Send + Sync,
+    Fut: Future + Send,
+    Fut::Output: IntoSubscriberResult,
 {
     fn name(&self) -> Cow<'static, str> {
         "Anonymous FnMut Subscriber".into()
     }
 
-    async fn on_loaded(&mut self, _client: api::Client) {}
+    async fn on_loaded(&mut self, _ctx: BotContext) {}
+
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError> {
+        self(ctx).await.into_result()
+    }
+}
+
+/// Options of a subscriber, used by [Bot::subscribe_with](crate::Bot::subscribe_with).
+#[derive(Debug, Clone, Default)]
+pub struct SubscribeOptions {
+    consume: bool,
+    ordered: bool,
+    priority: i32,
+    concurrency: Option<Concurrency>,
+}
+
+impl SubscribeOptions {
+    /// Create default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consume events accepted by this subscriber, so subscribers after it will not receive them
+    pub fn consume(mut self) -> Self {
+        self.consume = true;
+        self
+    }
+
+    /// true if this subscriber consumes accepted events
+    pub fn is_consume(&self) -> bool {
+        self.consume
+    }
+
+    /// Run this subscriber to finish before starting subscribers after it, instead of spawning
+    /// a task for it.
+    ///
+    /// Ignored if [concurrency](Self::concurrency) is limited, events are queued to its workers.
+    pub fn ordered(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
+
+    /// true if this subscriber finishes before subscribers after it are started
+    pub fn is_ordered(&self) -> bool {
+        self.ordered
+    }
+
+    /// Set priority, default is 0.
+    ///
+    /// Subscribers with higher priority receive events and are started first,
+    /// subscribers with same priority are ordered by registration order.
+    ///
+    /// Started subscribers run concurrently, so a subscriber may finish after subscribers with
+    /// lower priority, unless it's [ordered](Self::ordered).
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Get priority
+    pub fn get_priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Limit concurrency of this subscriber, default is unlimited(spawn a task per event).
+    pub fn concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency.replace(concurrency);
+        self
+    }
+
+    /// Get concurrency limit
+    pub fn get_concurrency(&self) -> Option<&Concurrency> {
+        self.concurrency.as_ref()
+    }
+}
+
+/// What to do when the event queue of a subscriber is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the new event.
+    #[default]
+    DropNewest,
+    /// Wait until queue has space, this will delay subscribers with lower priority.
+    Wait,
+}
+
+/// Concurrency limit of a subscriber.
+///
+/// Accepted events are put into a queue, and processed by a fixed number of workers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Concurrency {
+    max_in_flight: usize,
+    queue_size: usize,
+    overflow: Overflow,
+}
+
+impl Concurrency {
+    /// At most `max_in_flight` events are processed at the same time, queue size is 64 by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_in_flight` is zero.
+    pub fn new(max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "max_in_flight must be positive");
+        Self {
+            max_in_flight,
+            queue_size: 64,
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Process events one by one, in the order they are received.
+    pub fn serialize() -> Self {
+        Self::new(1)
+    }
+
+    /// Set max number of events waiting to be processed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queue_size` is zero.
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        assert!(queue_size > 0, "queue_size must be positive");
+        self.queue_size = queue_size;
+        self
+    }
+
+    /// Set what to do when queue is full, default is [Overflow::DropNewest].
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Get max number of events processed at the same time.
+    pub fn get_max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Get max number of events waiting to be processed.
+    pub fn get_queue_size(&self) -> usize {
+        self.queue_size
+    }
+
+    /// Get overflow policy.
+    pub fn get_overflow(&self) -> Overflow {
+        self.overflow
+    }
+}
+
+/// Subscriber which extract data by a [FilterMap] and hand it to the handler.
+///
+/// The data is extracted once by the subscription, and taken from [EventContext].
+pub(crate) struct Mapped<M, H> {
+    handler: H,
+    _map: PhantomData<fn() -> M>,
+}
+
+impl<M, H> Mapped<M, H> {
+    pub(crate) fn new(handler: H) -> Self {
+        Self {
+            handler,
+            _map: PhantomData,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<M, H, Fut> Subscriber for Mapped<M, H>
+where
+    M: FilterMap,
+    H: Fn(EventContext, M::Output) -> Fut + Send + Sync,
+    Fut: Future + Send,
+    Fut::Output: IntoSubscriberResult,
+{
+    fn name(&self) -> Cow<'static, str> {
+        std::any::type_name::<H>().into()
+    }
+
+    async fn on_loaded(&mut self, _ctx: BotContext) {}
+
+    async fn on_event(self: Arc<Self>, ctx: EventContext) -> Result<(), BoxError> {
+        match ctx.take_extracted::<M::Output>() {
+            Some(output) => (self.handler)(ctx, output).await.into_result(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Handler of errors returned by subscribers.
+#[async_trait::async_trait]
+pub trait SubscriberErrorHandler: Send + Sync {
+    /// callback will be execute when a subscriber returns a error
+    async fn on_error(
+        self: Arc<Self>,
+        subscriber: Cow<'static, str>,
+        ctx: EventContext,
+        error: BoxError,
+    );
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> SubscriberErrorHandler for F
+where
+    F: Fn(Cow<'static, str>, EventContext, BoxError) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn on_error(
+        self: Arc<Self>,
+        subscriber: Cow<'static, str>,
+        ctx: EventContext,
+        error: BoxError,
+    ) {
+        self(subscriber, ctx, error).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::Event;
+
+    #[tokio::test]
+    async fn test_closure_subscriber_result() {
+        let ok = Arc::new(|_: EventContext| async {});
+        let failed = Arc::new(|_: EventContext| async { Err::<(), _>("failed") });
+        let ctx = EventContext::mock(Event::ChannelMessage(Default::default()));
 
-    async fn on_event(self: Arc<Self>, event: Arc<Event>) {
-        self(event).await
+        assert!(ok.on_event(ctx.clone()).await.is_ok());
+        assert_eq!(
+            failed.on_event(ctx).await.unwrap_err().to_string(),
+            "failed"
+        );
     }
 }
@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde_json::{json, Value};
+
+/// A api call recorded by [MockApi].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiCall {
+    /// http method
+    pub method: reqwest::Method,
+    /// api path, like `/message/create`, or full url of a downloaded asset
+    pub path: String,
+    /// query string
+    pub query: Option<String>,
+    /// json body
+    pub body: Option<Value>,
+}
+
+/// Response data, or error code and message of a api path.
+type MockResponse = Result<Value, (i64, String)>;
+
+/// Fake api server which records calls instead of sending them, for testing.
+///
+/// Create a api client using it by [Client::new_mock](crate::api::Client::new_mock),
+/// or use it in bot by [BotBuilder::mock_api](crate::BotBuilder::mock_api).
+#[derive(Debug)]
+pub struct MockApi {
+    calls: Mutex<Vec<ApiCall>>,
+    responses: Mutex<HashMap<String, MockResponse>>,
+    assets: Mutex<HashMap<String, bytes::Bytes>>,
+}
+
+impl Default for MockApi {
+    fn default() -> Self {
+        let message = json!({ "msg_id": "mock-msg-id", "msg_timestamp": 0 });
+        let responses = HashMap::from([
+            (
+                "/user/me".to_string(),
+                Ok(json!({ "id": "bot-user-id", "username": "bot", "bot": true })),
+            ),
+            ("/message/create".to_string(), Ok(message.clone())),
+            ("/direct-message/create".to_string(), Ok(message)),
+        ]);
+
+        Self {
+            calls: Mutex::default(),
+            responses: Mutex::new(responses),
+            assets: Mutex::default(),
+        }
+    }
+}
+
+impl MockApi {
+    /// Create a mock api, which returns a bot user for `/user/me`, a fake message for
+    /// message create apis, and empty object for others.
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// Set data returned by the api path, like `/guild/view`
+    pub fn respond<P: Into<String>>(&self, path: P, data: Value) -> &Self {
+        self.responses.lock().unwrap().insert(path.into(), Ok(data));
+        self
+    }
+
+    /// Make the api path return a non-zero error code, until [respond](Self::respond) is called
+    pub fn fail<P, M>(&self, path: P, code: i64, message: M) -> &Self
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(path.into(), Err((code, message.into())));
+        self
+    }
+
+    /// Set content of the asset url, downloading other urls gets a 404 error
+    pub fn respond_asset<U, B>(&self, url: U, content: B) -> &Self
+    where
+        U: Into<String>,
+        B: Into<bytes::Bytes>,
+    {
+        self.assets
+            .lock()
+            .unwrap()
+            .insert(url.into(), content.into());
+        self
+    }
+
+    /// All recorded calls, in order
+    pub fn calls(&self) -> Vec<ApiCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Recorded calls to the api path, in order
+    pub fn calls_to(&self, path: &str) -> Vec<ApiCall> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.path == path)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget all recorded calls
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    /// Record the request and return the response data, or error code and message.
+    pub(crate) fn call(&self, base_path: &str, req: &reqwest::Request) -> MockResponse {
+        let url = req.url();
+        let path = url
+            .path()
+            .strip_prefix(base_path)
+            .unwrap_or(url.path())
+            .to_string();
+
+        debug!("Mock api called: {} {}", req.method(), path);
+
+        let data = self
+            .responses
+            .lock()
+            .unwrap()
+            .get(&path)
+            .cloned()
+            .unwrap_or_else(|| Ok(json!({})));
+
+        self.calls.lock().unwrap().push(ApiCall {
+            method: req.method().clone(),
+            path,
+            query: url.query().map(ToOwned::to_owned),
+            body: req
+                .body()
+                .and_then(|body| body.as_bytes())
+                .and_then(|bytes| serde_json::from_slice(bytes).ok()),
+        });
+
+        data
+    }
+
+    /// Record the asset download and return its content if set.
+    pub(crate) fn asset(&self, req: &reqwest::Request) -> Option<bytes::Bytes> {
+        let url = req.url().to_string();
+
+        debug!("Mock asset downloaded: {}", url);
+
+        let content = self.assets.lock().unwrap().get(&url).cloned();
+        self.calls.lock().unwrap().push(ApiCall {
+            method: req.method().clone(),
+            path: url,
+            query: None,
+            body: None,
+        });
+
+        content
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        filter,
+        ws::{event::EventBody, Event},
+        Bot, EventContext,
+    };
+
+    #[tokio::test]
+    async fn test_mock_api_with_event_source() {
+        let api = MockApi::new();
+        let event = Event::ChannelMessage(EventBody {
+            target_id: "channel".to_string(),
+            content: "ping".to_string(),
+            ..Default::default()
+        });
+
+        let mut bot = Bot::builder("token")
+            .mock_api(Arc::clone(&api))
+            .event_source(futures_util::stream::iter([event]))
+            .build()
+            .unwrap();
+        bot.subscribe(filter::all(), |ctx: EventContext| async move {
+            ctx.reply("pong").await.unwrap();
+        });
+
+        bot.run().await.unwrap();
+
+        let calls = api.calls_to("/message/create");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].method, reqwest::Method::POST);
+        let body = calls[0].body.as_ref().unwrap();
+        assert_eq!(body["target_id"], "channel");
+        assert_eq!(body["content"], "pong");
+        assert_eq!(api.calls()[0].path, "/user/me");
+    }
+
+    #[tokio::test]
+    async fn test_gateway_url_with_compress() {
+        let api = MockApi::new();
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": "wss://gateway" }),
+        );
+        let client = crate::api::Client::new_mock(Arc::clone(&api));
+
+        assert_eq!(client.gateway_url().await.unwrap(), "wss://gateway");
+        client.gateway_url_with_compress(false).await.unwrap();
+
+        let calls = api.calls_to("/gateway/index");
+        assert_eq!(calls[0].query.as_deref(), Some("compress=1"));
+        assert_eq!(calls[1].query.as_deref(), Some("compress=0"));
+    }
+
+    #[tokio::test]
+    async fn test_message_update() {
+        let api = MockApi::new();
+        let client = crate::api::Client::new_mock(Arc::clone(&api));
+        let data = json!({ "score": 1 });
+
+        client
+            .message_update(&crate::api::types::MessageUpdate::template(
+                "msg-id", "tpl-id", &data,
+            ))
+            .await
+            .unwrap();
+        client
+            .direct_message_update(&crate::api::types::MessageUpdate::new("dm-id", "new"))
+            .await
+            .unwrap();
+
+        let body = api.calls_to("/message/update")[0].body.clone().unwrap();
+        assert_eq!(body["template_id"], "tpl-id");
+        assert_eq!(body["content"], r#"{"score":1}"#);
+        let body = api.calls_to("/direct-message/update")[0]
+            .body
+            .clone()
+            .unwrap();
+        assert_eq!(body["msg_id"], "dm-id");
+        assert!(body.get("template_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_add_reaction() {
+        let api = MockApi::new();
+        let client = crate::api::Client::new_mock(Arc::clone(&api));
+
+        client
+            .message_add_reaction("msg-id", &"(emj)party(emj)[1234/abcd]".into())
+            .await
+            .unwrap();
+
+        let body = api.calls_to("/message/add-reaction")[0]
+            .body
+            .clone()
+            .unwrap();
+        assert_eq!(body, json!({ "msg_id": "msg-id", "emoji": "1234/abcd" }));
+    }
+
+    #[tokio::test]
+    async fn test_download_asset() {
+        let api = MockApi::new();
+        let url = "https://img.kaiheila.cn/attachments/a.txt";
+        api.respond_asset(url, &b"hello"[..]);
+        let client = crate::api::Client::new_mock(Arc::clone(&api));
+
+        let attachment = crate::models::Attachment {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(attachment.download(&client).await.unwrap(), &b"hello"[..]);
+
+        let mut file = vec![];
+        let written = client.download_asset_to(url, &mut file).await.unwrap();
+        assert_eq!((written, file.as_slice()), (5, &b"hello"[..]));
+        assert_eq!(api.calls_to(url).len(), 2);
+
+        assert!(matches!(
+            client.download_asset("https://img.kaiheila.cn/missing").await,
+            Err(crate::api::Error::HTTPStatusNotOK { status_code, .. })
+                if status_code == reqwest::StatusCode::NOT_FOUND
+        ));
+    }
+}
@@ -0,0 +1,955 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        self as websocket,
+        handshake::server::{Callback, ErrorResponse, Request, Response},
+    },
+    WebSocketStream,
+};
+
+use crate::{
+    api::types::GatewayURLInfo,
+    ws::{
+        event::EventData,
+        message::{Hello, OnlyData, Reconnect, ResumeACK},
+        Event, Message,
+    },
+};
+
+type Sink = Arc<tokio::sync::Mutex<SplitSink<WebSocketStream<TcpStream>, websocket::Message>>>;
+
+/// One step of a [Scenario].
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Send a message to client
+    Send(Message),
+    /// Send a websocket frame as is, without compress
+    Raw(websocket::Message),
+    /// Wait for a while
+    Sleep(Duration),
+    /// Stop answering pings of client with pong
+    WithholdPong,
+    /// Answer pings of client with pong, this is the default
+    AnswerPong,
+    /// Close the connection
+    Close,
+}
+
+/// Scripted behavior of a connection to [MockGateway].
+///
+/// After all steps are executed, the connection is kept and pings are still handled.
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Create a empty scenario
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a step
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Send a message
+    pub fn send(self, message: Message) -> Self {
+        self.step(Step::Send(message))
+    }
+
+    /// Send a websocket frame as is, like a text frame or a message with unknown type
+    pub fn raw(self, frame: websocket::Message) -> Self {
+        self.step(Step::Raw(frame))
+    }
+
+    /// Send a success hello message with the session id
+    pub fn hello<S: Into<String>>(self, session_id: S) -> Self {
+        self.send(Message::Hello(OnlyData {
+            data: Hello {
+                code: 0,
+                session_id: Some(session_id.into()),
+            },
+        }))
+    }
+
+    /// Send a event with the sn
+    pub fn event(self, sn: u64, event: Event) -> Self {
+        self.send(Message::Event(EventData {
+            sn,
+            event: Box::new(event),
+        }))
+    }
+
+    /// Send a reconnect message
+    pub fn reconnect<S: Into<String>>(self, code: i64, err: S) -> Self {
+        self.send(Message::Reconnect(OnlyData {
+            data: Reconnect {
+                code,
+                err: err.into(),
+            },
+        }))
+    }
+
+    /// Send a resume ack message
+    pub fn resume_ack<S: Into<String>>(self, session_id: S) -> Self {
+        self.send(Message::ResumeACK(OnlyData {
+            data: ResumeACK {
+                session_id: session_id.into(),
+            },
+        }))
+    }
+
+    /// Wait for a while
+    pub fn sleep(self, duration: Duration) -> Self {
+        self.step(Step::Sleep(duration))
+    }
+
+    /// Stop answering pings
+    pub fn withhold_pong(self) -> Self {
+        self.step(Step::WithholdPong)
+    }
+
+    /// Answer pings again
+    pub fn answer_pong(self) -> Self {
+        self.step(Step::AnswerPong)
+    }
+
+    /// Close the connection
+    pub fn close(self) -> Self {
+        self.step(Step::Close)
+    }
+}
+
+/// A local websocket gateway which follows scripted [Scenario]s, for testing [ws::Client](crate::ws::Client).
+///
+/// The nth connection uses the nth scenario, the last scenario is used by all remaining connections.
+/// Messages are compressed if client asks for it.
+#[derive(Debug)]
+pub struct MockGateway {
+    addr: SocketAddr,
+    records: Arc<Records>,
+    task: JoinHandle<()>,
+}
+
+/// What the gateway received from all connections
+#[derive(Debug, Default)]
+struct Records {
+    requests: Mutex<Vec<String>>,
+    pongs: Mutex<Vec<Vec<u8>>>,
+    closes: AtomicUsize,
+}
+
+impl Drop for MockGateway {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl MockGateway {
+    /// Listen on a random local port and start serving
+    pub async fn start(scenarios: Vec<Scenario>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let records = Arc::<Records>::default();
+
+        let task = tokio::spawn(serve(listener, scenarios, Arc::clone(&records)));
+
+        Ok(Self {
+            addr,
+            records,
+            task,
+        })
+    }
+
+    /// Local address of the gateway
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Gateway url info to connect to this gateway
+    pub fn url(&self, compress: bool) -> GatewayURLInfo {
+        GatewayURLInfo {
+            schema: "ws".to_string(),
+            host: self.addr.ip().to_string(),
+            port: Some(self.addr.port()),
+            path: "/gateway".to_string(),
+            compress,
+            token: "mock-token".to_string(),
+            resume: None,
+        }
+    }
+
+    /// Request uri of all connections, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.records.requests.lock().unwrap().clone()
+    }
+
+    /// Payload of websocket pong frames received from all connections, in order
+    pub fn pong_frames(&self) -> Vec<Vec<u8>> {
+        self.records.pongs.lock().unwrap().clone()
+    }
+
+    /// How many websocket close frames are received from all connections
+    pub fn close_frames(&self) -> usize {
+        self.records.closes.load(Ordering::SeqCst)
+    }
+}
+
+async fn serve(listener: TcpListener, scenarios: Vec<Scenario>, records: Arc<Records>) {
+    let mut index = 0;
+
+    loop {
+        let conn = match listener.accept().await {
+            Ok((conn, _)) => conn,
+            Err(err) => {
+                warn!("Mock gateway accept failed: {}", err);
+                continue;
+            }
+        };
+
+        let scenario = scenarios
+            .get(index)
+            .or_else(|| scenarios.last())
+            .cloned()
+            .unwrap_or_default();
+        index += 1;
+
+        let records = Arc::clone(&records);
+        tokio::spawn(async move {
+            let mut uri = String::new();
+            let ws = tokio_tungstenite::accept_hdr_async(conn, RecordUri(&mut uri)).await;
+
+            match ws {
+                Ok(ws) => {
+                    let compress = uri.contains("compress=1");
+                    records.requests.lock().unwrap().push(uri);
+                    run(ws, scenario, compress, records).await;
+                }
+                Err(err) => warn!("Mock gateway handshake failed: {}", err),
+            }
+        });
+    }
+}
+
+struct RecordUri<'a>(&'a mut String);
+
+impl Callback for RecordUri<'_> {
+    #[allow(clippy::result_large_err)] // signature is defined by tungstenite
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        *self.0 = request.uri().to_string();
+        Ok(response)
+    }
+}
+
+async fn send(sink: &Sink, message: &Message, compress: bool) -> bool {
+    let data = if compress {
+        message.encode_compressed(6)
+    } else {
+        message.encode()
+    };
+    sink.lock()
+        .await
+        .send(websocket::Message::Binary(data))
+        .await
+        .is_ok()
+}
+
+async fn run(
+    ws: WebSocketStream<TcpStream>,
+    scenario: Scenario,
+    compress: bool,
+    records: Arc<Records>,
+) {
+    let (sink, mut stream) = ws.split();
+    let sink: Sink = Arc::new(tokio::sync::Mutex::new(sink));
+    let answer_pong = Arc::new(AtomicBool::new(true));
+
+    let pong_task = {
+        let sink = Arc::clone(&sink);
+        let answer_pong = Arc::clone(&answer_pong);
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = stream.next().await {
+                let data = match msg {
+                    websocket::Message::Binary(data) => data,
+                    websocket::Message::Text(text) => text.into_bytes(),
+                    websocket::Message::Close(_) => {
+                        records.closes.fetch_add(1, Ordering::SeqCst);
+                        break;
+                    }
+                    websocket::Message::Pong(data) => {
+                        records.pongs.lock().unwrap().push(data);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                // client may compress its messages too
+                let data = bytes::Bytes::from(data);
+                let message =
+                    Message::decode(data.clone(), false).or_else(|_| Message::decode(data, true));
+                let is_ping = matches!(message, Ok(Message::Ping(_)));
+                if is_ping && answer_pong.load(Ordering::SeqCst) {
+                    send(&sink, &Message::Pong, compress).await;
+                }
+            }
+        })
+    };
+
+    for step in scenario.steps {
+        match step {
+            Step::Send(message) => {
+                if !send(&sink, &message, compress).await {
+                    break;
+                }
+            }
+            Step::Raw(frame) => {
+                if sink.lock().await.send(frame).await.is_err() {
+                    break;
+                }
+            }
+            Step::Sleep(duration) => tokio::time::sleep(duration).await,
+            Step::WithholdPong => answer_pong.store(false, Ordering::SeqCst),
+            Step::AnswerPong => answer_pong.store(true, Ordering::SeqCst),
+            Step::Close => {
+                pong_task.abort();
+                let _ = sink.lock().await.close().await;
+                return;
+            }
+        }
+    }
+
+    let _ = pong_task.await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ws::{
+        self,
+        client::EventStreamErrorKind,
+        event::EventBody,
+        message::{IgnoredFrame, SN},
+    };
+
+    fn message(id: &str) -> Event {
+        Event::ChannelMessage(EventBody {
+            msg_id: id.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_mock_gateway_misordered_and_duplicated() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(2, message("2"))
+            .event(1, message("1"))
+            .event(1, message("1"))
+            .event(3, message("3"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(true)).await.unwrap();
+
+        for id in ["1", "2", "3"] {
+            let event = stream.next().await.unwrap().unwrap();
+            assert_eq!(event.msg_id(), id);
+        }
+        assert_eq!(stream.resume_arguments().session_id, "session");
+        assert!(gateway.requests()[0].contains("compress=1"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_gateway_resume_ack_updates_session() {
+        let scenario = Scenario::new()
+            .hello("old")
+            .resume_ack("new")
+            .event(1, message("1"))
+            .reconnect(40106, "resume failed");
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(false)).await.unwrap();
+        let mut watcher = stream.resume_watcher();
+        assert_eq!(watcher.borrow_and_update().sn, 0);
+
+        assert!(stream.next().await.unwrap().is_ok());
+        assert_eq!(stream.resume_arguments().session_id, "new");
+        assert_eq!(watcher.borrow_and_update().sn, 1);
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(err.resume.session_id, "new");
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_graceful_close() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .event(3, message("3"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(false)).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), "1");
+        // wait the out of order event buffered
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.close();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), "3");
+        let end = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+        assert!(matches!(end, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn test_event_gap_skip() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .event(3, message("3"))
+            .sleep(Duration::from_millis(300))
+            .event(5, message("5"))
+            .event(6, message("6"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let config = ws::ClientConfig::new()
+            .max_gap_wait(Duration::from_millis(100))
+            .max_buffered_events(1);
+        let mut stream = ws::Client::new()
+            .config(config)
+            .run(gateway.url(false))
+            .await
+            .unwrap();
+
+        let mut gaps = stream.gap_watcher();
+
+        // 2 is skipped after waiting timeout, 4 is skipped when buffer overflows
+        for id in ["1", "3", "5", "6"] {
+            assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), id);
+        }
+        assert_eq!(stream.resume_arguments().sn, 6);
+        assert!(gaps.has_changed().unwrap());
+        assert_eq!(
+            *gaps.borrow_and_update(),
+            Some(ws::client::EventGap {
+                from: 4,
+                to: 4,
+                buffered: 2,
+                policy: ws::GapPolicy::Skip,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_gap_resume() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .event(3, message("3"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let config = ws::ClientConfig::new()
+            .max_gap_wait(Duration::from_millis(100))
+            .gap_policy(ws::GapPolicy::Resume);
+        let mut stream = ws::Client::new()
+            .config(config)
+            .run(gateway.url(false))
+            .await
+            .unwrap();
+
+        assert!(stream.next().await.unwrap().is_ok());
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err.source,
+            EventStreamErrorKind::EventGap { from: 2, to: 2 }
+        ));
+        assert_eq!(err.resume.sn, 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_run_raw() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .sleep(Duration::from_millis(100))
+            .reconnect(40106, "resume failed");
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run_raw(gateway.url(true)).await.unwrap();
+
+        assert!(matches!(stream.next().await, Some(Ok(Message::Hello(_)))));
+        stream.send(Message::Ping(SN { sn: 0 })).await.unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(Message::Pong))));
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(Message::Reconnect(OnlyData {
+                data: Reconnect { code: 40106, .. }
+            })))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_compress_outgoing() {
+        let gateway = MockGateway::start(vec![Scenario::new().hello("session")])
+            .await
+            .unwrap();
+
+        let config = ws::ClientConfig::new().compress_outgoing(Some(6));
+        let mut stream = ws::Client::new()
+            .config(config)
+            .run_raw(gateway.url(false))
+            .await
+            .unwrap();
+
+        assert!(matches!(stream.next().await, Some(Ok(Message::Hello(_)))));
+        stream.send(Message::Ping(SN { sn: 0 })).await.unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(Message::Pong))));
+    }
+
+    #[tokio::test]
+    async fn test_client_max_message_size() {
+        let gateway = MockGateway::start(vec![Scenario::new().hello("session")])
+            .await
+            .unwrap();
+
+        let config = ws::ClientConfig::new().max_message_size(Some(8));
+        let result = ws::Client::new()
+            .config(config)
+            .run(gateway.url(false))
+            .await;
+        assert!(matches!(
+            result,
+            Err(ws::client::RunError::WaitHelloFailed { .. })
+        ));
+
+        let config = ws::ClientConfig::new().max_message_size(None);
+        assert_eq!(config.get_max_message_size(), None);
+        assert!(ws::Client::new()
+            .config(config)
+            .run(gateway.url(false))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mock_gateway_reconnect() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .reconnect(40106, "resume failed");
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(false)).await.unwrap();
+
+        assert!(stream.next().await.unwrap().is_ok());
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err.source,
+            EventStreamErrorKind::Reconnect { code: 40106, .. }
+        ));
+        assert_eq!(err.resume.sn, 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_state_watcher() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .sleep(Duration::from_millis(200))
+            .reconnect(40106, "resume failed");
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let client = ws::Client::new();
+        let mut state = client.state_watcher();
+        assert_eq!(*state.borrow_and_update(), ws::ConnectionState::Stopped);
+
+        let mut stream = client.run(gateway.url(false)).await.unwrap();
+        assert!(state.has_changed().unwrap());
+
+        let mut state = stream.state_watcher();
+        let streaming = state.wait_for(|s| *s == ws::ConnectionState::Streaming);
+        let result = tokio::time::timeout(Duration::from_secs(1), streaming).await;
+        assert!(result.is_ok_and(|r| r.is_ok()));
+
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.unwrap().is_err());
+
+        // channel is closed when stopped, but the last state is kept
+        let stopped = state.wait_for(|s| *s == ws::ConnectionState::Stopped);
+        let _ = tokio::time::timeout(Duration::from_secs(1), stopped).await;
+        assert_eq!(*state.borrow(), ws::ConnectionState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_bot_event_gap_lifecycle() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .event(3, message("3"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": gateway.url(false).url().to_string() }),
+        );
+
+        let config = ws::ClientConfig::new().max_gap_wait(Duration::from_millis(100));
+        let mut bot = crate::Bot::builder("token")
+            .mock_api(api)
+            .ws_config(config)
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.on_lifecycle(move |_, lifecycle| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(lifecycle);
+            }
+        });
+        let handle = bot.start();
+
+        let gap = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(lifecycle @ crate::Lifecycle::EventGap { .. }) = rx.recv().await {
+                    return lifecycle;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            gap,
+            crate::Lifecycle::EventGap {
+                from: 2,
+                to: 2,
+                resync: false,
+            }
+        );
+
+        handle.stop();
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_ignored_frames() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .raw(websocket::Message::Ping(b"ping".to_vec()))
+            .raw(websocket::Message::Binary(br#"{"s":9,"d":{}}"#.to_vec()))
+            .send(Message::Ping(SN { sn: 0 }))
+            .event(1, message("1"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        let config = ws::ClientConfig::new().ignored_frames(tx);
+        let mut stream = ws::Client::new()
+            .config(config)
+            .run(gateway.url(false))
+            .await
+            .unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), "1");
+
+        let frame = rx.recv().await.unwrap();
+        assert!(
+            matches!(frame, IgnoredFrame::NotBinary(websocket::Message::Ping(data)) if data == b"ping")
+        );
+        let frame = rx.recv().await.unwrap();
+        assert!(
+            matches!(&frame, IgnoredFrame::Unparsable { data, .. } if data.starts_with(b"{\"s\":9"))
+        );
+        let frame = rx.recv().await.unwrap();
+        assert!(matches!(frame, IgnoredFrame::Unhandled(Message::Ping(_))));
+    }
+
+    #[tokio::test]
+    async fn test_bot_gateway_override() {
+        let scenario = Scenario::new().hello("session").event(1, message("1"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        let mut bot = crate::Bot::builder("token")
+            .mock_api(Arc::clone(&api))
+            .gateway(gateway.url(false))
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.subscribe(crate::filter::all(), move |ctx: crate::EventContext| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(ctx.event().msg_id().to_string());
+            }
+        });
+        let handle = bot.start();
+
+        let received = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+        assert_eq!(received.unwrap().as_deref(), Some("1"));
+        assert!(api.calls_to("/gateway/index").is_empty());
+        assert!(gateway.requests()[0].contains("compress=0"));
+
+        handle.stop();
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_text_frames() {
+        let event = Message::Event(EventData {
+            sn: 1,
+            event: Box::new(message("1")),
+        });
+        let text = String::from_utf8(event.encode()).unwrap();
+        let scenario = Scenario::new()
+            .hello("session")
+            .raw(websocket::Message::Text(text));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        // text frames are parsed without decompress, even if compress is enabled
+        let mut stream = ws::Client::new().run(gateway.url(true)).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_client_answers_ping_frames() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .raw(websocket::Message::Ping(b"ping".to_vec()))
+            .event(1, message("1"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(false)).await.unwrap();
+
+        // ping frames are not errors, the stream continues
+        assert_eq!(stream.next().await.unwrap().unwrap().msg_id(), "1");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(gateway.pong_frames(), vec![b"ping".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_client_close_frame() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .close();
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let mut stream = ws::Client::new().run(gateway.url(false)).await.unwrap();
+
+        assert!(stream.next().await.unwrap().is_ok());
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err.source, EventStreamErrorKind::Closed { .. }));
+        assert_eq!(err.resume.sn, 1);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bot_reconnects_after_connection_closed() {
+        let first = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .close();
+        let second = Scenario::new().hello("session").event(2, message("2"));
+        let gateway = MockGateway::start(vec![first, second]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": gateway.url(false).url().to_string() }),
+        );
+
+        let mut bot = crate::Bot::builder("token").mock_api(api).build().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.subscribe(crate::filter::all(), move |ctx: crate::EventContext| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(ctx.event().msg_id().to_string());
+            }
+        });
+        let handle = bot.start();
+
+        for id in ["1", "2"] {
+            let received = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+            assert_eq!(received.unwrap().as_deref(), Some(id));
+        }
+        assert_eq!(gateway.requests().len(), 2);
+        assert!(gateway.requests()[1].contains("sn=1"));
+
+        handle.stop();
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bot_gives_up_after_max_reconnects() {
+        let scenario = Scenario::new().hello("session").close();
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": gateway.url(false).url().to_string() }),
+        );
+
+        let mut bot = crate::Bot::builder("token")
+            .mock_api(api)
+            .retry(crate::RetryPolicy::new().max_reconnects(2))
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.on_lifecycle(move |_, lifecycle| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(lifecycle);
+            }
+        });
+        let handle = bot.start();
+
+        let result = tokio::time::timeout(Duration::from_secs(2), handle.join()).await;
+        assert!(matches!(
+            *result.unwrap().unwrap_err(),
+            crate::Error::TooManyReconnects { count: 3, .. }
+        ));
+        assert_eq!(gateway.requests().len(), 3);
+
+        let gave_up = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(crate::Lifecycle::GaveUp { reason }) = rx.recv().await {
+                    return reason;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(gave_up.contains("closed"));
+    }
+
+    #[tokio::test]
+    async fn test_bot_retries_fetch_gateway_url() {
+        let scenario = Scenario::new().hello("session").event(1, message("1"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        api.fail("/gateway/index", 500, "internal error");
+
+        let retry = crate::RetryPolicy::new()
+            .initial_delay(Duration::from_millis(10))
+            .max_delay(Duration::from_millis(10));
+        let mut bot = crate::Bot::builder("token")
+            .mock_api(Arc::clone(&api))
+            .retry(retry)
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.subscribe(crate::filter::all(), move |ctx: crate::EventContext| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(ctx.event().msg_id().to_string());
+            }
+        });
+        let handle = bot.start();
+
+        while api.calls_to("/gateway/index").len() < 2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": gateway.url(false).url().to_string() }),
+        );
+
+        let received = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+        assert_eq!(received.unwrap().as_deref(), Some("1"));
+
+        handle.stop();
+        handle.join().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bot_gives_up_fetch_gateway_url_after_max_reconnects() {
+        let api = crate::testing::MockApi::new();
+        api.fail("/gateway/index", 500, "internal error");
+
+        let retry = crate::RetryPolicy::new()
+            .initial_delay(Duration::from_millis(10))
+            .max_reconnects(2);
+        let mut bot = crate::Bot::builder("token")
+            .mock_api(Arc::clone(&api))
+            .retry(retry)
+            .build()
+            .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.on_lifecycle(move |_, lifecycle| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(lifecycle);
+            }
+        });
+        let handle = bot.start();
+
+        let result = tokio::time::timeout(Duration::from_secs(2), handle.join()).await;
+        assert!(matches!(
+            *result.unwrap().unwrap_err(),
+            crate::Error::CallAPIFailed { .. }
+        ));
+        assert_eq!(api.calls_to("/gateway/index").len(), 3);
+
+        let gave_up = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let Some(crate::Lifecycle::GaveUp { reason }) = rx.recv().await {
+                    return reason;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(gave_up.contains("internal error"));
+    }
+
+    #[tokio::test]
+    async fn test_bot_stop_closes_event_stream() {
+        let scenario = Scenario::new()
+            .hello("session")
+            .event(1, message("1"))
+            .event(3, message("3"));
+        let gateway = MockGateway::start(vec![scenario]).await.unwrap();
+
+        let api = crate::testing::MockApi::new();
+        api.respond(
+            "/gateway/index",
+            serde_json::json!({ "url": gateway.url(false).url().to_string() }),
+        );
+
+        let mut bot = crate::Bot::builder("token").mock_api(api).build().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        bot.subscribe(crate::filter::all(), move |ctx: crate::EventContext| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(ctx.event().msg_id().to_string());
+            }
+        });
+        let handle = bot.start();
+
+        let received = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await;
+        assert_eq!(received.unwrap().as_deref(), Some("1"));
+        // wait the out of order event buffered
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        handle.stop();
+        handle.join().await.unwrap();
+
+        // buffered event is still handled when closing
+        assert_eq!(rx.recv().await.as_deref(), Some("3"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(gateway.close_frames(), 1);
+    }
+}
@@ -0,0 +1,11 @@
+//! Utilities for testing bots offline.
+
+mod api;
+mod gateway;
+mod recorder;
+mod replayer;
+
+pub use api::{ApiCall, MockApi};
+pub use gateway::{MockGateway, Scenario, Step};
+pub use recorder::{RecordedEvent, Recorder};
+pub use replayer::Replayer;
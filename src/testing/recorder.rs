@@ -0,0 +1,68 @@
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ws::event::EventData;
+
+/// A line of recorded events file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// milliseconds since unix epoch when the event is received
+    pub time: u64,
+    /// the event with its sn
+    pub event: EventData,
+}
+
+/// Record every event received by bot as a json line, can be replayed by [Replayer](super::Replayer).
+///
+/// Set it by [BotBuilder::recorder](crate::BotBuilder::recorder).
+pub struct Recorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    /// Create a recorder writes to the writer
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Create a recorder writes to the file, the file is truncated if exists
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub(crate) fn record(&self, data: &EventData) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let line = RecordedEvent {
+            time,
+            event: data.clone(),
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        let result = serde_json::to_writer(&mut *writer, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| writer.flush());
+
+        if let Err(err) = result {
+            warn!("Record event {} failed: {}", data.sn, err);
+        }
+    }
+}
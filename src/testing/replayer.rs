@@ -0,0 +1,139 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    time::Duration,
+};
+
+use tokio::sync::mpsc;
+
+use super::RecordedEvent;
+use crate::ws::event::EventData;
+
+/// Feed recorded events to bot, instead of receiving them from websocket.
+///
+/// Set it by [BotBuilder::replay](crate::BotBuilder::replay), bot stops after all events replayed.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    events: Vec<RecordedEvent>,
+    speed: f64,
+}
+
+impl Replayer {
+    /// Create a replayer of events
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Self { events, speed: 1.0 }
+    }
+
+    /// Read events from a file written by [Recorder](super::Recorder)
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read events from json lines
+    pub fn from_reader<R: BufRead>(reader: R) -> std::io::Result<Self> {
+        let mut events = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            events.push(serde_json::from_str(&line)?);
+        }
+        Ok(Self::new(events))
+    }
+
+    /// Set replay speed, 1.0 means real speed, 2.0 means twice as fast, default is 1.0
+    ///
+    /// Use [f64::INFINITY] to replay without waiting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is not positive.
+    pub fn speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0.0, "speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    /// Get events to be replayed
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /// Delay before the event recorded at `time`, after the event recorded at `last`
+    fn delay(&self, last: u64, time: u64) -> Duration {
+        Duration::from_millis(time.saturating_sub(last)).div_f64(self.speed)
+    }
+
+    /// Send all events to `sender` with recorded intervals
+    pub(crate) async fn replay(self, sender: mpsc::Sender<EventData>) {
+        let mut last = self.events.first().map_or(0, |e| e.time);
+
+        for recorded in self.events.iter() {
+            let delay = self.delay(last, recorded.time);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            last = recorded.time;
+
+            if sender.send(recorded.event.clone()).await.is_err() {
+                debug!("Event receiver dropped, stop replay");
+                return;
+            }
+        }
+
+        info!("All {} recorded events replayed", self.events.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{testing::Recorder, ws::Event};
+
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let buffer = Shared::default();
+        let recorder = Recorder::new(buffer.clone());
+        for sn in 1..=3 {
+            recorder.record(&EventData {
+                sn,
+                event: Box::new(Event::ChannelMessage(Default::default())),
+            });
+        }
+
+        let content = buffer.0.lock().unwrap().clone();
+        let replayer = Replayer::from_reader(content.as_slice())
+            .unwrap()
+            .speed(f64::INFINITY);
+        assert_eq!(replayer.events().len(), 3);
+        assert_eq!(replayer.delay(0, 1000), Duration::ZERO);
+        assert_eq!(
+            replayer.clone().speed(2.0).delay(1000, 3000),
+            Duration::from_secs(1)
+        );
+
+        let (sender, mut receiver) = mpsc::channel(1);
+        tokio::spawn(replayer.replay(sender));
+
+        let mut sns = vec![];
+        while let Some(data) = receiver.recv().await {
+            sns.push(data.sn);
+        }
+        assert_eq!(sns, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Push audio to a joined voice channel by rtp.
+//!
+//! [AudioSender] sends opus frames at real-time pace. burz does not include a opus encoder, use
+//! [PcmSender] with a [OpusEncoder] implementation (e.g. by the `opus` or `audiopus` crate) to
+//! send raw pcm.
+
+use std::{fmt::Debug, io, net::SocketAddr, time::Duration};
+
+use futures_util::{Stream, StreamExt};
+use snafu::prelude::*;
+use tokio::{net::UdpSocket, time::Instant};
+
+use crate::api::types::VoiceJoinData;
+
+/// Sample rate of opus audio
+pub const SAMPLE_RATE: u32 = 48000;
+/// Audio channel count, stereo
+pub const CHANNELS: usize = 2;
+/// Duration of one opus frame
+pub const FRAME_DURATION: Duration = Duration::from_millis(20);
+/// Samples per channel in one frame
+pub const FRAME_SAMPLES: usize = 960;
+
+const RTP_VERSION: u8 = 2;
+const RTP_HEADER_LEN: usize = 12;
+
+/// Audio push error
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)), module(error), context(suffix(false)))]
+pub enum AudioError {
+    /// rtp server address from join data is invalid
+    #[snafu(display("invalid rtp server address {ip}:{port}: {source}"))]
+    InvalidAddress {
+        /// server ip
+        ip: String,
+        /// server port
+        port: u16,
+        /// source error
+        source: std::net::AddrParseError,
+    },
+
+    /// bind or connect udp socket failed
+    #[snafu(display("connect rtp server {addr} failed: {source}"))]
+    ConnectFailed {
+        /// rtp server address
+        addr: SocketAddr,
+        /// source io error
+        source: io::Error,
+    },
+
+    /// send rtp packet failed
+    #[snafu(display("send rtp packet failed: {source}"))]
+    SendFailed {
+        /// source io error
+        source: io::Error,
+    },
+
+    /// encode pcm as opus failed
+    #[snafu(display("encode opus frame failed: {source}"))]
+    EncodeFailed {
+        /// source io error
+        source: io::Error,
+    },
+}
+
+/// Send opus frames to the rtp server of a joined voice channel
+#[derive(Debug)]
+pub struct AudioSender {
+    socket: UdpSocket,
+    payload_type: u8,
+    ssrc: u32,
+    sequence: u16,
+    timestamp: u32,
+    next: Option<Instant>,
+    packet: Vec<u8>,
+}
+
+impl AudioSender {
+    /// Connect to the rtp server in join data
+    pub async fn connect(info: &VoiceJoinData) -> Result<Self, AudioError> {
+        let addr = SocketAddr::new(
+            info.ip.parse().context(error::InvalidAddress {
+                ip: &info.ip,
+                port: info.port,
+            })?,
+            info.port,
+        );
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local)
+            .await
+            .context(error::ConnectFailed { addr })?;
+        socket
+            .connect(addr)
+            .await
+            .context(error::ConnectFailed { addr })?;
+        debug!("Rtp socket {:?} connected to {}", socket.local_addr(), addr);
+
+        Ok(Self {
+            socket,
+            payload_type: info.audio_pt & 0x7f,
+            ssrc: info.audio_ssrc,
+            sequence: 0,
+            timestamp: 0,
+            next: None,
+            packet: Vec::with_capacity(RTP_HEADER_LEN + 1500),
+        })
+    }
+
+    /// Send a opus frame of [FRAME_DURATION], waits until the previous frame is played
+    ///
+    /// If sending is paused longer than a frame, the gap is treated as silence.
+    pub async fn send_frame(&mut self, opus: &[u8]) -> Result<(), AudioError> {
+        let now = Instant::now();
+        let mut marker = false;
+
+        match self.next {
+            None => marker = true,
+            Some(next) if next + FRAME_DURATION < now => {
+                let gap = (now - next).as_micros() * u128::from(SAMPLE_RATE) / 1_000_000;
+                trace!("Rtp stream paused for {} samples", gap);
+                self.timestamp = self.timestamp.wrapping_add(gap as u32);
+                self.next.replace(now);
+                marker = true;
+            }
+            Some(next) => tokio::time::sleep_until(next).await,
+        }
+
+        self.packet.clear();
+        self.packet.push(RTP_VERSION << 6);
+        self.packet.push(u8::from(marker) << 7 | self.payload_type);
+        self.packet.extend(self.sequence.to_be_bytes());
+        self.packet.extend(self.timestamp.to_be_bytes());
+        self.packet.extend(self.ssrc.to_be_bytes());
+        self.packet.extend_from_slice(opus);
+
+        self.socket
+            .send(&self.packet)
+            .await
+            .context(error::SendFailed)?;
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(FRAME_SAMPLES as u32);
+        self.next = Some(self.next.unwrap_or(now) + FRAME_DURATION);
+
+        Ok(())
+    }
+
+    /// Send all opus frames in the stream
+    pub async fn play<S, B>(&mut self, frames: S) -> Result<(), AudioError>
+    where
+        S: Stream<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        futures_util::pin_mut!(frames);
+        while let Some(frame) = frames.next().await {
+            self.send_frame(frame.as_ref()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Encode pcm to opus, for [PcmSender]
+pub trait OpusEncoder: Send + Debug {
+    /// Encode one frame of interleaved stereo samples ([FRAME_SAMPLES] * [CHANNELS] items),
+    /// returns the length of opus data written to `output`
+    fn encode(&mut self, pcm: &[i16], output: &mut [u8]) -> io::Result<usize>;
+}
+
+/// Send raw pcm to the rtp server, encoded by a [OpusEncoder]
+#[derive(Debug)]
+pub struct PcmSender<E> {
+    sender: AudioSender,
+    encoder: E,
+    pcm: Vec<i16>,
+    opus: Vec<u8>,
+}
+
+impl<E: OpusEncoder> PcmSender<E> {
+    /// Send pcm by the sender
+    pub fn new(sender: AudioSender, encoder: E) -> Self {
+        Self {
+            sender,
+            encoder,
+            pcm: Vec::with_capacity(FRAME_SAMPLES * CHANNELS),
+            opus: vec![0; 4000],
+        }
+    }
+
+    /// Send interleaved stereo 48kHz samples, can be any length, samples less than a frame are
+    /// buffered until next call or [flush](Self::flush)
+    pub async fn send(&mut self, mut pcm: &[i16]) -> Result<(), AudioError> {
+        const FRAME_LEN: usize = FRAME_SAMPLES * CHANNELS;
+
+        while !pcm.is_empty() {
+            let take = pcm.len().min(FRAME_LEN - self.pcm.len());
+            self.pcm.extend_from_slice(&pcm[..take]);
+            pcm = &pcm[take..];
+
+            if self.pcm.len() == FRAME_LEN {
+                self.send_buffered().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send buffered samples, padded with silence to a full frame
+    pub async fn flush(&mut self) -> Result<(), AudioError> {
+        if self.pcm.is_empty() {
+            return Ok(());
+        }
+        self.pcm.resize(FRAME_SAMPLES * CHANNELS, 0);
+        self.send_buffered().await
+    }
+
+    async fn send_buffered(&mut self) -> Result<(), AudioError> {
+        let len = self
+            .encoder
+            .encode(&self.pcm, &mut self.opus)
+            .context(error::EncodeFailed)?;
+        self.pcm.clear();
+        self.sender.send_frame(&self.opus[..len]).await
+    }
+
+    /// Get the inner opus sender back
+    pub fn into_inner(self) -> AudioSender {
+        self.sender
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn receiver() -> (UdpSocket, VoiceJoinData) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let info = VoiceJoinData {
+            ip: "127.0.0.1".to_string(),
+            port: socket.local_addr().unwrap().port(),
+            audio_ssrc: 1111,
+            audio_pt: 111,
+            ..Default::default()
+        };
+        (socket, info)
+    }
+
+    async fn recv(socket: &UdpSocket) -> Vec<u8> {
+        let mut buffer = [0; 1500];
+        let len = socket.recv(&mut buffer).await.unwrap();
+        buffer[..len].to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_audio_sender_rtp_packets() {
+        let (socket, info) = receiver().await;
+        let mut sender = AudioSender::connect(&info).await.unwrap();
+
+        let start = Instant::now();
+        sender
+            .play(futures_util::stream::iter([b"a", b"b", b"c"]))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= FRAME_DURATION * 2);
+
+        let first = recv(&socket).await;
+        assert_eq!(first[0], 0x80);
+        assert_eq!(first[1], 0x80 | 111);
+        assert_eq!(&first[2..4], &[0, 0]);
+        assert_eq!(&first[4..8], &[0, 0, 0, 0]);
+        assert_eq!(&first[8..12], &1111u32.to_be_bytes());
+        assert_eq!(&first[12..], b"a");
+
+        let second = recv(&socket).await;
+        assert_eq!(second[1], 111);
+        assert_eq!(&second[2..4], &[0, 1]);
+        assert_eq!(&second[4..8], &960u32.to_be_bytes());
+        assert_eq!(&second[12..], b"b");
+        assert_eq!(&recv(&socket).await[4..8], &1920u32.to_be_bytes());
+    }
+
+    #[derive(Debug)]
+    struct SumEncoder;
+
+    impl OpusEncoder for SumEncoder {
+        fn encode(&mut self, pcm: &[i16], output: &mut [u8]) -> io::Result<usize> {
+            assert_eq!(pcm.len(), FRAME_SAMPLES * CHANNELS);
+            let sum: i64 = pcm.iter().map(|&s| i64::from(s)).sum();
+            output[..8].copy_from_slice(&sum.to_be_bytes());
+            Ok(8)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pcm_sender_frames() {
+        let (socket, info) = receiver().await;
+        let mut sender = PcmSender::new(AudioSender::connect(&info).await.unwrap(), SumEncoder);
+
+        sender.send(&vec![1; 1000]).await.unwrap();
+        sender.send(&vec![1; 1000]).await.unwrap();
+        sender.flush().await.unwrap();
+
+        assert_eq!(&recv(&socket).await[12..], &1920i64.to_be_bytes());
+        assert_eq!(&recv(&socket).await[12..], &80i64.to_be_bytes());
+    }
+}
@@ -0,0 +1,288 @@
+//! Voice channel connections.
+//!
+//! [VoiceConnection::join] joins a voice channel and calls the keep-alive api in background, so
+//! the bot stays in the channel even if it sends nothing. The returned [VoiceJoinData] tells
+//! where to push rtp audio stream, by the `audio` module if `voice-audio` feature is enabled.
+//!
+//! ```no_run
+//! # async fn run(client: burz::api::Client) -> burz::api::Result<()> {
+//! use burz::voice::{VoiceConnection, VoiceState};
+//!
+//! let voice = VoiceConnection::join(&client, "channel-id").await?;
+//! let mut state = voice.watch_state();
+//! while state.changed().await.is_ok() {
+//!     if *state.borrow() == VoiceState::Lost {
+//!         break;
+//!     }
+//! }
+//! voice.leave().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "voice-audio")]
+pub mod audio;
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
+
+use crate::api::{
+    self,
+    types::{VoiceJoin, VoiceJoinData},
+};
+
+/// Interval of keep-alive api calls
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(45);
+/// Delay before retrying a failed keep-alive
+pub const KEEP_ALIVE_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Server removes the bot from the channel if it's not kept alive in this duration
+pub const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// State of a [VoiceConnection]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoiceState {
+    /// In the channel, keep-alive works
+    Connected,
+    /// Keep-alive failed, retrying
+    KeepAliveFailed {
+        /// error message of last failure
+        reason: String,
+        /// failures since last success
+        failures: usize,
+    },
+    /// Keep-alive failed for [KEEP_ALIVE_TIMEOUT], the bot is probably not in the channel
+    /// anymore, should join again
+    Lost,
+    /// Left the channel
+    Left,
+}
+
+/// A joined voice channel, kept alive until [left](Self::leave) or dropped
+///
+/// Dropping it stops the keep-alive, the server removes the bot from the channel after
+/// [KEEP_ALIVE_TIMEOUT].
+#[derive(Debug)]
+pub struct VoiceConnection {
+    client: api::Client,
+    channel_id: String,
+    info: VoiceJoinData,
+    state: Arc<watch::Sender<VoiceState>>,
+    keep_alive: JoinHandle<()>,
+}
+
+impl Drop for VoiceConnection {
+    fn drop(&mut self) {
+        self.keep_alive.abort();
+    }
+}
+
+impl VoiceConnection {
+    /// Join the voice channel with server default audio parameters
+    pub async fn join(client: &api::Client, channel_id: &str) -> api::Result<Self> {
+        Self::join_with(client, &VoiceJoin::new(channel_id)).await
+    }
+
+    /// Join the voice channel with custom audio parameters or password
+    pub async fn join_with(client: &api::Client, join: &VoiceJoin) -> api::Result<Self> {
+        let info = client.voice_join(join).await?;
+        info!(
+            "Joined voice channel {}, rtp target {}:{}",
+            join.channel_id, info.ip, info.port
+        );
+
+        let state = Arc::new(watch::Sender::new(VoiceState::Connected));
+        let keep_alive = tokio::spawn(keep_alive(
+            client.clone(),
+            join.channel_id.clone(),
+            Arc::clone(&state),
+        ));
+
+        Ok(Self {
+            client: client.clone(),
+            channel_id: join.channel_id.clone(),
+            info,
+            state,
+            keep_alive,
+        })
+    }
+
+    /// Id of the joined channel
+    pub fn channel_id(&self) -> &str {
+        &self.channel_id
+    }
+
+    /// Where to push rtp audio stream
+    pub fn info(&self) -> &VoiceJoinData {
+        &self.info
+    }
+
+    /// Connect to the rtp server to push audio
+    #[cfg(feature = "voice-audio")]
+    pub async fn audio(&self) -> Result<audio::AudioSender, audio::AudioError> {
+        audio::AudioSender::connect(&self.info).await
+    }
+
+    /// Current state
+    pub fn state(&self) -> VoiceState {
+        self.state.borrow().clone()
+    }
+
+    /// Watch state changes
+    pub fn watch_state(&self) -> watch::Receiver<VoiceState> {
+        self.state.subscribe()
+    }
+
+    /// Stop keep-alive and leave the channel
+    pub async fn leave(self) -> api::Result<()> {
+        self.keep_alive.abort();
+        self.state.send_replace(VoiceState::Left);
+        info!("Leaving voice channel {}", self.channel_id);
+        self.client.voice_leave(&self.channel_id).await
+    }
+}
+
+async fn keep_alive(
+    client: api::Client,
+    channel_id: String,
+    state: Arc<watch::Sender<VoiceState>>,
+) {
+    let mut alive_at = Instant::now();
+    let mut failures = 0;
+
+    loop {
+        let delay = if failures == 0 {
+            KEEP_ALIVE_INTERVAL
+        } else {
+            KEEP_ALIVE_RETRY_DELAY
+        };
+        tokio::time::sleep(delay).await;
+
+        match client.voice_keep_alive(&channel_id).await {
+            Ok(()) => {
+                trace!("Voice channel {} kept alive", channel_id);
+                alive_at = Instant::now();
+                failures = 0;
+                state.send_if_modified(|state| {
+                    let changed = *state != VoiceState::Connected;
+                    *state = VoiceState::Connected;
+                    changed
+                });
+            }
+            Err(e) if alive_at.elapsed() + KEEP_ALIVE_RETRY_DELAY >= KEEP_ALIVE_TIMEOUT => {
+                error!(
+                    "Keep alive voice channel {} failed, lost: {}",
+                    channel_id, e
+                );
+                state.send_replace(VoiceState::Lost);
+                return;
+            }
+            Err(e) => {
+                failures += 1;
+                warn!(
+                    "Keep alive voice channel {} failed {} times: {}",
+                    channel_id, failures, e
+                );
+                state.send_replace(VoiceState::KeepAliveFailed {
+                    reason: e.to_string(),
+                    failures,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::MockApi;
+
+    async fn join(api: &Arc<MockApi>) -> VoiceConnection {
+        api.respond(
+            "/voice/join",
+            json!({ "ip": "1.2.3.4", "port": "30000", "rtcp_mux": true, "audio_ssrc": "1111" }),
+        );
+        let client = api::Client::new_mock(Arc::clone(api));
+        VoiceConnection::join(&client, "voice-channel")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keep_alive() {
+        let api = MockApi::new();
+        api.respond("/voice/keep-alive", json!({}));
+
+        let voice = join(&api).await;
+        assert_eq!(voice.info().port, 30000);
+        assert_eq!(voice.state(), VoiceState::Connected);
+
+        tokio::time::sleep(KEEP_ALIVE_INTERVAL * 3 + KEEP_ALIVE_RETRY_DELAY).await;
+        assert_eq!(voice.state(), VoiceState::Connected);
+
+        let keep_alive = api.calls_to("/voice/keep-alive");
+        assert_eq!(keep_alive.len(), 3);
+        assert_eq!(
+            keep_alive[0].body,
+            Some(json!({ "channel_id": "voice-channel" }))
+        );
+
+        let mut state = voice.watch_state();
+        voice.leave().await.unwrap();
+        assert_eq!(*state.borrow_and_update(), VoiceState::Left);
+        assert_eq!(api.calls_to("/voice/leave").len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keep_alive_recover() {
+        let api = MockApi::new();
+        api.fail("/voice/keep-alive", 40000, "not in channel");
+
+        let start = Instant::now();
+        let voice = join(&api).await;
+        let mut state = voice.watch_state();
+
+        state.changed().await.unwrap();
+        assert_eq!(
+            *state.borrow(),
+            VoiceState::KeepAliveFailed {
+                reason: "api return error code 40000, not in channel".to_string(),
+                failures: 1
+            }
+        );
+        assert_eq!(start.elapsed(), KEEP_ALIVE_INTERVAL);
+
+        api.respond("/voice/keep-alive", json!({}));
+        state.changed().await.unwrap();
+        assert_eq!(*state.borrow(), VoiceState::Connected);
+        assert_eq!(
+            start.elapsed(),
+            KEEP_ALIVE_INTERVAL + KEEP_ALIVE_RETRY_DELAY
+        );
+        assert_eq!(api.calls_to("/voice/keep-alive").len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keep_alive_lost() {
+        let api = MockApi::new();
+        api.fail("/voice/keep-alive", 40000, "not in channel");
+
+        let start = Instant::now();
+        let voice = join(&api).await;
+        let lost_at = voice
+            .watch_state()
+            .wait_for(|s| *s == VoiceState::Lost)
+            .await
+            .map(|_| start.elapsed());
+
+        // join is the last success, fails at 45s and 50s, then at 55s next retry would be too late
+        assert_eq!(lost_at.unwrap(), Duration::from_secs(55));
+        assert_eq!(api.calls_to("/voice/keep-alive").len(), 3);
+
+        // keep-alive stops after lost
+        tokio::time::sleep(KEEP_ALIVE_INTERVAL).await;
+        assert_eq!(api.calls_to("/voice/keep-alive").len(), 3);
+    }
+}
@@ -0,0 +1,65 @@
+//! Wait for follow-up events.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{context::BotContext, filter::AsyncFilter, ws::Event};
+
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Broadcast tap of the bot event loop, for waiting follow-up events.
+#[derive(Debug, Clone)]
+pub(crate) struct Waiter {
+    sender: broadcast::Sender<Arc<Event>>,
+}
+
+impl Default for Waiter {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl Waiter {
+    pub(crate) fn publish(&self, event: Arc<Event>) {
+        // error only means no one is waiting
+        let _ = self.sender.send(event);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn waiting(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    pub(crate) async fn wait_for<F>(
+        &self,
+        ctx: &BotContext,
+        filter: F,
+        timeout: Duration,
+    ) -> Option<Arc<Event>>
+    where
+        F: AsyncFilter,
+    {
+        let mut receiver = self.sender.subscribe();
+
+        let wait = async {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if filter.check(ctx, Arc::clone(&event)).await {
+                            return Some(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("Waiter lagged, {} events skipped", n);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, wait).await.ok().flatten()
+    }
+}
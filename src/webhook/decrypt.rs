@@ -0,0 +1,96 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use snafu::prelude::*;
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+const KEY_SIZE: usize = 32;
+const IV_SIZE: usize = 16;
+
+/// Error when decrypt encrypted webhook body
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)), module(error), context(suffix(false)))]
+pub enum DecryptError {
+    /// Encrypt key is longer than 32 bytes
+    #[snafu(display("encrypt key length {len} is longer than {KEY_SIZE}"))]
+    InvalidKey {
+        /// key length
+        len: usize,
+    },
+
+    /// Encrypted data is not valid base64
+    #[snafu(display("encrypted data is not valid base64: {source}"))]
+    InvalidBase64 {
+        /// source error
+        source: base64::DecodeError,
+    },
+
+    /// Encrypted data is too short to contain iv
+    #[snafu(display("encrypted data is too short, length {len}"))]
+    TooShort {
+        /// data length
+        len: usize,
+    },
+
+    /// Decrypt failed, usually caused by wrong key
+    #[snafu(display("decrypt failed, maybe the encrypt key is wrong"))]
+    DecryptFailed,
+}
+
+/// Decrypt `encrypt` field of webhook body.
+///
+/// The field is base64 of `iv + base64(ciphertext)`,
+/// and ciphertext is encrypted by AES-256-CBC with the key padded by `\0` to 32 bytes.
+pub(crate) fn decrypt(key: &str, encrypted: &str) -> Result<Vec<u8>, DecryptError> {
+    ensure!(key.len() <= KEY_SIZE, error::InvalidKey { len: key.len() });
+    let mut padded_key = [0u8; KEY_SIZE];
+    padded_key[..key.len()].copy_from_slice(key.as_bytes());
+
+    let data = STANDARD.decode(encrypted).context(error::InvalidBase64)?;
+    ensure!(data.len() > IV_SIZE, error::TooShort { len: data.len() });
+    let (iv, ciphertext) = data.split_at(IV_SIZE);
+
+    let ciphertext = STANDARD.decode(ciphertext).context(error::InvalidBase64)?;
+
+    Aes256CbcDec::new(&padded_key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+        .ok()
+        .context(error::DecryptFailed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // generated by `openssl enc -aes-256-cbc` with key "encrypt-key" and iv "0123456789abcdef"
+    const SAMPLE: &str = "MDEyMzQ1Njc4OWFiY2RlZnJjcyttNG5BOFB3M2R6RFl0WGpDWlNsc0xsN0xpOTJQYk5kR0l5U2ZpdUxPQ0JaK29ONWhrOXd6RnlaWTBDbFJPTTRtTy9Yczk5SmNYMnpEbEczZlBMQ2E4YVNCRUs0R1JvSGNpN1JyVEY0RVpOcm8wWkptRmdaSE1UWFpZZnJlTnp2b09SZ1labTJkVXlRRWlySU5Kdz09";
+
+    #[test]
+    fn test_decrypt() {
+        let plain = decrypt("encrypt-key", SAMPLE).unwrap();
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            r#"{"s":0,"d":{"type":255,"channel_type":"WEBHOOK_CHALLENGE","challenge":"bkfwucbn3c","verify_token":"token"}}"#
+        );
+    }
+
+    #[test]
+    fn test_decrypt_error() {
+        assert!(matches!(
+            decrypt("wrong-key", SAMPLE),
+            Err(DecryptError::DecryptFailed)
+        ));
+        assert!(matches!(
+            decrypt(&"k".repeat(33), SAMPLE),
+            Err(DecryptError::InvalidKey { len: 33 })
+        ));
+        assert!(matches!(
+            decrypt("encrypt-key", "not base64!"),
+            Err(DecryptError::InvalidBase64 { .. })
+        ));
+        assert!(matches!(
+            decrypt("encrypt-key", "MDEy"),
+            Err(DecryptError::TooShort { len: 3 })
+        ));
+    }
+}
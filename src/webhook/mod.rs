@@ -0,0 +1,348 @@
+//! Receive Kaiheila events by webhook, as an alternative to websocket.
+//!
+//! See: <https://developer.kaiheila.cn/doc/webhook>
+
+mod decrypt;
+mod server;
+
+pub use decrypt::DecryptError;
+pub(crate) use server::serve;
+
+use std::{net::SocketAddr, time::Duration};
+
+use bytes::Bytes;
+use miniz_oxide::inflate::{self, TINFLStatus};
+use serde_json::Value;
+use snafu::prelude::*;
+
+use crate::ws::event::EventData;
+
+static CHALLENGE_CHANNEL_TYPE: &str = "WEBHOOK_CHALLENGE";
+
+const DEDUP_CAPACITY: usize = 1024;
+const DEDUP_TTL: Duration = Duration::from_secs(600);
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Error when parse webhook request body
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)), module(error), context(suffix(false)))]
+pub enum ParsePayloadError {
+    /// Body or decompressed body is larger than the limit
+    #[snafu(display("body is larger than {limit} bytes"))]
+    BodyTooLarge {
+        /// max body size
+        limit: usize,
+    },
+
+    /// Decompress body failed
+    #[snafu(display("decompress body failed: {status:?}"))]
+    DecompressFailed {
+        /// decompress error status code
+        status: TINFLStatus,
+    },
+
+    /// Body is not valid json
+    #[snafu(display("parse body as json failed: {source}"))]
+    ParseJSONFailed {
+        /// source error
+        source: serde_json::Error,
+    },
+
+    /// Body is encrypted but no encrypt key configured
+    #[snafu(display("body is encrypted but no encrypt key configured"))]
+    NoEncryptKey,
+
+    /// Decrypt body failed
+    #[snafu(display("decrypt body failed: {source}"))]
+    DecryptFailed {
+        /// source error
+        source: DecryptError,
+    },
+
+    /// Body has no data field
+    #[snafu(display("body has no data field"))]
+    NoData,
+
+    /// Verify token in body mismatch
+    #[snafu(display("verify token {token} mismatch"))]
+    VerifyTokenMismatch {
+        /// received token
+        token: String,
+    },
+
+    /// Challenge request has no challenge field
+    #[snafu(display("challenge request has no challenge field"))]
+    NoChallenge,
+
+    /// Body is not a valid event
+    #[snafu(display("parse body as event failed: {source}"))]
+    ParseEventFailed {
+        /// source error
+        source: serde_json::Error,
+    },
+}
+
+/// Parsed webhook request body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload {
+    /// Challenge request, should be answered with the challenge string
+    Challenge(String),
+    /// Event
+    Event(EventData),
+}
+
+/// Config of webhook server
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    addr: SocketAddr,
+    path: String,
+    verify_token: String,
+    encrypt_key: Option<String>,
+    compress: bool,
+    dedup_capacity: usize,
+    dedup_ttl: Duration,
+    max_body_size: usize,
+}
+
+impl WebhookConfig {
+    /// Create a config, listening on `addr` and verifying requests by `verify_token`
+    pub fn new<S: Into<String>>(addr: SocketAddr, verify_token: S) -> Self {
+        Self {
+            addr,
+            path: "/".to_string(),
+            verify_token: verify_token.into(),
+            encrypt_key: None,
+            compress: true,
+            dedup_capacity: DEDUP_CAPACITY,
+            dedup_ttl: DEDUP_TTL,
+            max_body_size: MAX_BODY_SIZE,
+        }
+    }
+
+    /// Set request path of callback url, default is `/`
+    pub fn path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Set if request body is compressed, default is true.
+    ///
+    /// It should be false if callback url contains `compress=0`.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Set encrypt key, needed if message encryption is enabled in bot settings
+    pub fn encrypt_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.encrypt_key.replace(key.into());
+        self
+    }
+
+    /// Set how many recent msg ids are remembered to drop re-delivered events,
+    /// default is 1024, 0 to disable deduplication
+    pub fn dedup_capacity(mut self, capacity: usize) -> Self {
+        self.dedup_capacity = capacity;
+        self
+    }
+
+    /// Set how long a msg id is remembered to drop re-delivered events, default is 10 minutes
+    pub fn dedup_ttl(mut self, ttl: Duration) -> Self {
+        self.dedup_ttl = ttl;
+        self
+    }
+
+    /// Set max size of request body, before and after decompression, default is 1 MiB.
+    ///
+    /// Larger requests are rejected with status 413 before verifying the token.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Get max size of request body
+    pub fn get_max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Get listening address
+    pub fn get_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Get request path
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// Parse a webhook request body
+    pub fn parse(&self, mut body: Bytes) -> Result<Payload, ParsePayloadError> {
+        let limit = self.max_body_size;
+        ensure!(body.len() <= limit, error::BodyTooLarge { limit });
+
+        if self.compress {
+            body = inflate::decompress_to_vec_zlib_with_limit(&body, limit)
+                .map_err(|status| match status {
+                    TINFLStatus::HasMoreOutput => ParsePayloadError::BodyTooLarge { limit },
+                    status => ParsePayloadError::DecompressFailed { status },
+                })?
+                .into();
+        }
+
+        let mut value: Value = serde_json::from_slice(&body).context(error::ParseJSONFailed)?;
+
+        if let Some(encrypted) = value.get("encrypt").and_then(Value::as_str) {
+            let key = self.encrypt_key.as_deref().context(error::NoEncryptKey)?;
+            let body = decrypt::decrypt(key, encrypted).context(error::DecryptFailed)?;
+            value = serde_json::from_slice(&body).context(error::ParseJSONFailed)?;
+        }
+
+        let data = value
+            .get_mut("d")
+            .and_then(Value::as_object_mut)
+            .context(error::NoData)?;
+
+        let token = data
+            .remove("verify_token")
+            .and_then(|v| v.as_str().map(ToOwned::to_owned))
+            .unwrap_or_default();
+        ensure!(
+            token == self.verify_token,
+            error::VerifyTokenMismatch { token }
+        );
+
+        if data.get("channel_type").and_then(Value::as_str) == Some(CHALLENGE_CHANNEL_TYPE) {
+            let challenge = data
+                .get("challenge")
+                .and_then(Value::as_str)
+                .context(error::NoChallenge)?;
+            return Ok(Payload::Challenge(challenge.to_string()));
+        }
+
+        serde_json::from_value(value)
+            .context(error::ParseEventFailed)
+            .map(Payload::Event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> WebhookConfig {
+        WebhookConfig::new(([127, 0, 0, 1], 8080).into(), "token").compress(false)
+    }
+
+    fn body(value: Value) -> Bytes {
+        serde_json::to_vec(&value).unwrap().into()
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let payload = config().parse(body(json!({
+            "s": 0,
+            "d": {
+                "type": 255,
+                "channel_type": "WEBHOOK_CHALLENGE",
+                "challenge": "some-challenge",
+                "verify_token": "token",
+            }
+        })));
+
+        assert_eq!(
+            payload.unwrap(),
+            Payload::Challenge("some-challenge".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_encrypted() {
+        let encrypted = body(json!({
+            "encrypt": "MDEyMzQ1Njc4OWFiY2RlZnJjcyttNG5BOFB3M2R6RFl0WGpDWlNsc0xsN0xpOTJQYk5kR0l5U2ZpdUxPQ0JaK29ONWhrOXd6RnlaWTBDbFJPTTRtTy9Yczk5SmNYMnpEbEczZlBMQ2E4YVNCRUs0R1JvSGNpN1JyVEY0RVpOcm8wWkptRmdaSE1UWFpZZnJlTnp2b09SZ1labTJkVXlRRWlySU5Kdz09",
+        }));
+
+        assert!(matches!(
+            config().parse(encrypted.clone()),
+            Err(ParsePayloadError::NoEncryptKey)
+        ));
+
+        let payload = config().encrypt_key("encrypt-key").parse(encrypted);
+        assert_eq!(
+            payload.unwrap(),
+            Payload::Challenge("bkfwucbn3c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let event = json!({
+            "s": 0,
+            "sn": 7,
+            "d": {
+                "channel_type": "GROUP",
+                "type": 9,
+                "target_id": "some-channel-id",
+                "author_id": "some-user-id",
+                "content": "hello",
+                "msg_id": "some-msg-id",
+                "msg_timestamp": 1612703779612_i64,
+                "nonce": "",
+                "verify_token": "token",
+                "extra": {
+                    "type": 9,
+                    "guild_id": "some-guild-id",
+                    "channel_name": "general",
+                    "mention": [],
+                    "mention_all": false,
+                    "mention_roles": [],
+                    "mention_here": false,
+                    "author": {
+                        "id": "some-user-id",
+                        "username": "user",
+                        "identify_num": "0001",
+                        "online": true,
+                        "avatar": "",
+                        "bot": false,
+                    },
+                },
+            }
+        });
+
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&body(event.clone()), 6);
+        let payload = config().compress(true).parse(compressed.into()).unwrap();
+
+        match payload {
+            Payload::Event(data) => {
+                assert_eq!(data.sn, 7);
+                assert_eq!(data.event.content(), "hello");
+            }
+            _ => panic!("not a event"),
+        }
+
+        let mut wrong = event;
+        wrong["d"]["verify_token"] = json!("other");
+        assert!(matches!(
+            config().parse(body(wrong)),
+            Err(ParsePayloadError::VerifyTokenMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_body_limit() {
+        let config = config().max_body_size(1024);
+
+        assert!(matches!(
+            config.parse(vec![b' '; 1025].into()),
+            Err(ParsePayloadError::BodyTooLarge { limit: 1024 })
+        ));
+
+        let bomb = miniz_oxide::deflate::compress_to_vec_zlib(&[b' '; 512 * 1024], 10);
+        assert!(bomb.len() < 1024);
+        assert!(matches!(
+            config.compress(true).parse(bomb.into()),
+            Err(ParsePayloadError::BodyTooLarge { limit: 1024 })
+        ));
+    }
+}
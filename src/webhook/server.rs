@@ -0,0 +1,226 @@
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use bytes::{Bytes, BytesMut};
+use hyper::{
+    body::HttpBody,
+    header::CONTENT_LENGTH,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use tokio::sync::mpsc;
+
+use super::{ParsePayloadError, Payload, WebhookConfig};
+use crate::{dedup::Dedup, ws::event::EventData};
+
+#[derive(Debug)]
+struct State {
+    config: WebhookConfig,
+    dedup: Mutex<Dedup>,
+    sender: mpsc::Sender<EventData>,
+}
+
+async fn handle(state: Arc<State>, req: Request<Body>) -> Response<Body> {
+    let config = &state.config;
+
+    if req.method() != Method::POST || req.uri().path() != config.path {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    let limit = config.max_body_size;
+    let content_length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > limit as u64) {
+        warn!("Webhook request body is larger than {} bytes", limit);
+        return status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let body = match read_body(req.into_body(), limit).await {
+        Ok(Some(body)) => body,
+        Ok(None) => {
+            warn!("Webhook request body is larger than {} bytes", limit);
+            return status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        Err(err) => {
+            warn!("Read webhook request body failed: {}", err);
+            return status(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    match config.parse(body) {
+        Ok(Payload::Challenge(challenge)) => {
+            info!("Answer webhook challenge");
+            let body = serde_json::json!({ "challenge": challenge }).to_string();
+            Response::new(Body::from(body))
+        }
+        Ok(Payload::Event(data)) => {
+            trace!("Received webhook event sn = {}", data.sn);
+
+            let msg_id = data.event.msg_id().to_string();
+            if !state.dedup.lock().unwrap().check(&msg_id, Instant::now()) {
+                debug!("Drop re-delivered webhook event {}", msg_id);
+                return status(StatusCode::OK);
+            }
+
+            if state.sender.send(data).await.is_err() {
+                debug!("Event receiver dropped");
+                // not delivered, so the re-delivered one should be accepted
+                state.dedup.lock().unwrap().forget(&msg_id);
+                return status(StatusCode::SERVICE_UNAVAILABLE);
+            }
+            status(StatusCode::OK)
+        }
+        Err(err @ ParsePayloadError::BodyTooLarge { .. }) => {
+            warn!("Invalid webhook request: {}", err);
+            status(StatusCode::PAYLOAD_TOO_LARGE)
+        }
+        Err(err) => {
+            warn!("Invalid webhook request: {}", err);
+            status(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Read body up to `limit` bytes, None if it's larger
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<Bytes>, hyper::Error> {
+    let mut buffer = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buffer.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Some(buffer.freeze()))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = code;
+    resp
+}
+
+/// Run webhook server, send received events to `sender`
+pub(crate) async fn serve(
+    config: WebhookConfig,
+    sender: mpsc::Sender<EventData>,
+) -> Result<(), hyper::Error> {
+    let addr = config.addr;
+    let state = Arc::new(State {
+        dedup: Mutex::new(Dedup::new(config.dedup_capacity, config.dedup_ttl)),
+        config,
+        sender,
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let response = handle(Arc::clone(&state), req);
+                async move { Ok::<_, Infallible>(response.await) }
+            }))
+        }
+    });
+
+    info!("Webhook server listening on {}", addr);
+
+    Server::try_bind(&addr)?.serve(make_service).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(max_body_size: usize) -> (Arc<State>, mpsc::Receiver<EventData>) {
+        let config = WebhookConfig::new(([127, 0, 0, 1], 0).into(), "token")
+            .compress(false)
+            .max_body_size(max_body_size);
+        let (sender, receiver) = mpsc::channel(1);
+        let state = State {
+            dedup: Mutex::new(Dedup::new(config.dedup_capacity, config.dedup_ttl)),
+            config,
+            sender,
+        };
+        (Arc::new(state), receiver)
+    }
+
+    fn post(body: Body) -> Request<Body> {
+        Request::post("/").body(body).unwrap()
+    }
+
+    fn event() -> Body {
+        let event = serde_json::json!({
+            "s": 0,
+            "sn": 1,
+            "d": {
+                "channel_type": "GROUP",
+                "type": 9,
+                "target_id": "some-channel-id",
+                "author_id": "some-user-id",
+                "content": "hello",
+                "msg_id": "some-msg-id",
+                "msg_timestamp": 1612703779612_i64,
+                "nonce": "",
+                "verify_token": "token",
+                "extra": {
+                    "type": 9,
+                    "guild_id": "some-guild-id",
+                    "channel_name": "general",
+                    "mention": [],
+                    "mention_all": false,
+                    "mention_roles": [],
+                    "mention_here": false,
+                    "author": {
+                        "id": "some-user-id",
+                        "username": "user",
+                        "identify_num": "0001",
+                        "online": true,
+                        "avatar": "",
+                        "bot": false,
+                    },
+                },
+            }
+        });
+        Body::from(event.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_handle_dedup() {
+        let (open, mut receiver) = state(4096);
+        for _ in 0..2 {
+            let resp = handle(Arc::clone(&open), post(event())).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        assert!(receiver.recv().await.is_some());
+        assert!(receiver.try_recv().is_err());
+
+        let (closed, receiver) = state(4096);
+        drop(receiver);
+        let resp = handle(Arc::clone(&closed), post(event())).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(closed.dedup.lock().unwrap().ids().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_body_limit() {
+        let (state, _receiver) = state(16);
+
+        let resp = handle(Arc::clone(&state), post(Body::from(vec![b' '; 17]))).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // chunked body without content length
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move { while sender.send_data(vec![b' '; 10].into()).await.is_ok() {} });
+        let resp = handle(Arc::clone(&state), post(body)).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = handle(Arc::clone(&state), post(Body::from("{}"))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
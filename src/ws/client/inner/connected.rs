@@ -1,15 +1,19 @@
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc};
 
-use futures_util::{future, Sink, Stream, StreamExt};
+use futures_util::{Sink, Stream, StreamExt};
 use snafu::prelude::*;
 use tokio::time::Instant;
 
-use super::{streaming::ClientStateStreaming, ClientInner, EventStream};
+use super::{
+    streaming::ClientStateStreaming, ClientInner, ConnectionState, EventStream, StateNotifier,
+    HELLO_TIMEOUT,
+};
 use crate::{
     api::types::GatewayURLInfo,
+    logging,
     ws::{
-        client::{inner::streaming::EventStreamSender, WebsocketClient},
-        message::{Message, MessageStreamSink, MessageStreamSinkError},
+        client::{inner::streaming::EventStreamSender, ClientConfig, WebsocketClient},
+        message::{FilteredMessageStreamSink, Message, MessageStreamSinkError},
     },
 };
 
@@ -26,7 +30,7 @@ pub enum WaitHelloError {
     MessageStream {
         /// source error
         #[snafu(source(from(MessageStreamSinkError, Box::new)))]
-        source: Box<dyn std::error::Error + Send>,
+        source: Box<dyn std::error::Error + Send + Sync>,
     },
 
     /// received first message is not hello type
@@ -50,12 +54,57 @@ pub enum WaitHelloError {
 pub(crate) struct ClientStateConnected {
     pub gateway: GatewayURLInfo,
     pub ws: WebsocketClient,
+    pub config: ClientConfig,
+    pub conn_state: Arc<StateNotifier>,
+}
+
+/// Wait the hello message from gateway, return the session id in it
+pub(super) async fn wait_hello_message<S>(stream: &mut S) -> Result<String, WaitHelloError>
+where
+    S: Stream<Item = Result<Message, MessageStreamSinkError>> + Unpin,
+{
+    let deadline = Instant::now() + HELLO_TIMEOUT;
+
+    debug!("Waiting hello message, timeout tick: {:?}", deadline);
+
+    let message = tokio::select! {
+        _ = tokio::time::sleep_until(deadline) => {
+            warn!("Wait hello timeout");
+            return error::Timeout.fail();
+        }
+        result = stream.next() => {
+            result
+                .unwrap_or(Err(MessageStreamSinkError::Closed { frame: None }))
+                .context(error::MessageStream)?
+        }
+    };
+
+    debug!("Wait hello get a {} message", message.type_name());
+
+    ensure!(matches!(message, Message::Hello(_)), error::MessageNotHello,);
+
+    let hello = message.into_hello().unwrap(); // checked in last line
+
+    debug!("Hello message data: {:?}", hello);
+
+    ensure!(
+        hello.data.code == 0,
+        error::HelloMessageCodeNotZero {
+            code: hello.data.code
+        }
+    );
+
+    hello
+        .data
+        .session_id
+        .ok_or_else(|| error::HelloMessageNoSessionId.build())
 }
 
 impl ClientInner<ClientStateConnected> {
     async fn real_wait_hello(
         ws: WebsocketClient,
         compress: bool,
+        config: &ClientConfig,
     ) -> Result<
         (
             impl Stream<Item = Result<Message, MessageStreamSinkError>>
@@ -65,67 +114,43 @@ impl ClientInner<ClientStateConnected> {
         ),
         WaitHelloError,
     > {
-        let mut message_stream = MessageStreamSink::new(ws, compress).filter(|result| {
-            let skip = matches!(result, Err(e) if !e.is_fatal());
-            if skip {
-                log::warn!(
-                    "Message stream error happened but ignored: {}",
-                    result.as_ref().unwrap_err()
-                );
-            }
-            future::ready(!skip)
-        });
-
-        let deadline = Instant::now() + Duration::from_secs(6);
-
-        log::debug!("Waiting hello message, timeout tick: {:?}", deadline);
+        let mut message_stream = FilteredMessageStreamSink::new(ws, compress)
+            .compress_outgoing(config.compress_outgoing)
+            .ignored_frames(config.ignored_frames.clone());
 
-        let message = tokio::select! {
-            _ = tokio::time::sleep_until(deadline) => {
-                log::warn!("Wait hello timeout");
-                return error::Timeout.fail();
-            }
-            result = message_stream.next() => {
-                result.unwrap().context(error::MessageStream)?
-            }
-        };
-
-        log::debug!("Wait hello get a {} message", message.type_name());
-
-        ensure!(matches!(message, Message::Hello(_)), error::MessageNotHello,);
-
-        let hello = message.into_hello().unwrap(); // checked in last line
-
-        log::debug!("Hello message data: {:?}", hello);
-
-        ensure!(
-            hello.data.code == 0,
-            error::HelloMessageCodeNotZero {
-                code: hello.data.code
-            }
-        );
-
-        let session_id = hello
-            .data
-            .session_id
-            .ok_or_else(|| error::HelloMessageNoSessionId.build())?;
+        let session_id = wait_hello_message(&mut message_stream).await?;
 
         Ok((message_stream, session_id))
     }
 
+    /// Give up the state machine, the connection is used directly
+    pub fn into_message_stream(self) -> FilteredMessageStreamSink {
+        FilteredMessageStreamSink::new(self.state.ws, self.state.gateway.compress)
+            .compress_outgoing(self.state.config.compress_outgoing)
+            .ignored_frames(self.state.config.ignored_frames)
+    }
+
     pub async fn wait_hello(mut self) -> Result<EventStream, WaitHelloError> {
-        let (message_stream, session_id) =
-            Self::real_wait_hello(self.state.ws, self.state.gateway.compress).await?;
+        self.state.conn_state.set(ConnectionState::WaitingHello);
+
+        let (message_stream, session_id) = Self::real_wait_hello(
+            self.state.ws,
+            self.state.gateway.compress,
+            &self.state.config,
+        )
+        .await?;
 
         let mut resume = self.state.gateway.resume.take().unwrap_or_default();
         resume.session_id = session_id;
+        logging::record_session_id(&resume.session_id);
 
-        log::debug!("New resume argument: {:?}", resume);
+        debug!("New resume argument: {:?}", resume);
 
         let (sink, stream) = message_stream.split();
-        let (sender, event_stream) = EventStreamSender::new(resume);
+        let (sender, event_stream) =
+            EventStreamSender::new(resume, self.state.config, self.state.conn_state);
 
-        log::debug!("Move to streaming state");
+        debug!("Move to streaming state");
 
         ClientInner {
             state: ClientStateStreaming {
@@ -140,32 +165,39 @@ impl ClientInner<ClientStateConnected> {
         Ok(event_stream)
     }
 
-    pub async fn re_wait_hello(mut self, sender: EventStreamSender) {
-        let (message_stream, session_id) =
-            match Self::real_wait_hello(self.state.ws, self.state.gateway.compress)
-                .await
-                .context(super::streaming::error::ReWaitHelloFailed)
-            {
-                Ok((m, s)) => (m, s),
-                Err(err) => {
-                    log::warn!(
-                        "Reconnect state wait hello failed: {}, send event stream error and stop",
-                        err
-                    );
-
-                    sender.send_err(err).await;
-                    return;
-                }
-            };
+    pub async fn re_wait_hello(mut self, mut sender: EventStreamSender) {
+        self.state.conn_state.set(ConnectionState::WaitingHello);
+
+        let (message_stream, session_id) = match Self::real_wait_hello(
+            self.state.ws,
+            self.state.gateway.compress,
+            &self.state.config,
+        )
+        .await
+        .context(super::streaming::error::ReWaitHelloFailed)
+        {
+            Ok((m, s)) => (m, s),
+            Err(err) => {
+                warn!(
+                    "Reconnect state wait hello failed: {}, send event stream error and stop",
+                    err
+                );
+
+                sender.send_err(err).await;
+                return;
+            }
+        };
 
         let mut resume = self.state.gateway.resume.take().unwrap_or_default();
         resume.session_id = session_id;
 
-        log::debug!("New resume argument: {:?}", resume);
+        debug!("New resume argument: {:?}", resume);
+
+        sender.set_session_id(resume.session_id);
 
         let (sink, stream) = message_stream.split();
 
-        log::debug!("Move to streaming state");
+        debug!("Move to streaming state");
 
         ClientInner {
             state: ClientStateStreaming {
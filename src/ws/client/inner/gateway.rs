@@ -1,8 +1,13 @@
+use std::sync::Arc;
+
 use snafu::*;
 use tokio_tungstenite as websocket;
 
-use super::{connected::ClientStateConnected, ClientInner};
-use crate::api::types::GatewayURLInfo;
+use super::{connected::ClientStateConnected, ClientInner, StateNotifier};
+use crate::{
+    api::types::GatewayURLInfo,
+    ws::client::{proxy, ClientConfig, WebsocketClient},
+};
 
 /// Error when connect to websocket gateway
 #[derive(Debug, Snafu)]
@@ -22,30 +27,44 @@ pub struct ConnectGatewayError {
 #[derive(Debug)]
 pub(crate) struct ClientStateGateway {
     pub gateway: GatewayURLInfo,
+    pub config: ClientConfig,
+    pub conn_state: Arc<StateNotifier>,
 }
 
 impl ClientInner<ClientStateGateway> {
+    async fn try_connect(
+        &self,
+        url: &str,
+    ) -> Result<WebsocketClient, websocket::tungstenite::Error> {
+        let config = Some(self.state.config.websocket);
+        let result = match &self.state.config.proxy {
+            Some(proxy) => proxy::connect_async(proxy, url, config).await,
+            None => websocket::connect_async_with_config(url, config).await,
+        };
+        result.map(|(client, _)| client)
+    }
+
     pub async fn connect(self) -> Result<ClientInner<ClientStateConnected>, ConnectGatewayError> {
         let u = self.state.gateway.url();
 
-        log::debug!("Connecting gateway: {}", u);
+        debug!("Connecting gateway: {}", u);
 
-        let mut conn_result = websocket::connect_async(&u).await;
+        let mut conn_result = self.try_connect(u.as_str()).await;
         if conn_result.is_err() {
-            log::warn!("First try to connect gateway failed, start second try");
-            conn_result = websocket::connect_async(&u).await
+            warn!("First try to connect gateway failed, start second try");
+            conn_result = self.try_connect(u.as_str()).await
         }
 
-        let ws = conn_result
-            .map(|(client, _)| client)
-            .with_context(|_| error::ConnectGateway { url: u })?;
+        let ws = conn_result.with_context(|_| error::ConnectGateway { url: u })?;
 
-        log::debug!("Move to connected state");
+        debug!("Move to connected state");
 
         Ok(ClientInner {
             state: ClientStateConnected {
                 gateway: self.state.gateway,
                 ws,
+                config: self.state.config,
+                conn_state: self.state.conn_state,
             },
         })
     }
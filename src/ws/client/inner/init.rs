@@ -1,9 +1,16 @@
+use std::sync::Arc;
+
 use snafu::prelude::*;
 
 use super::{
-    gateway::ClientStateGateway, ClientInner, ConnectGatewayError, EventStream, WaitHelloError,
+    gateway::ClientStateGateway, ClientInner, ConnectGatewayError, ConnectionState, EventStream,
+    StateNotifier, WaitHelloError,
+};
+use crate::{
+    api::types::{GatewayResumeArguments, GatewayURLInfo},
+    logging::{self, Instrument},
+    ws::{client::ClientConfig, message::FilteredMessageStreamSink},
 };
-use crate::api::types::{GatewayResumeArguments, GatewayURLInfo};
 
 /// Error when run websocket client
 #[derive(Debug, Snafu)]
@@ -27,33 +34,57 @@ pub enum RunError {
 #[derive(Debug)]
 pub(crate) struct ClientStateInit {
     pub resume: Option<GatewayResumeArguments>,
+    pub config: ClientConfig,
+    pub conn_state: Arc<StateNotifier>,
 }
 
 impl ClientInner<ClientStateInit> {
+    /// Connect and wait hello in a new connection span, which background tasks are attached to
     pub async fn run(self, gateway: GatewayURLInfo) -> Result<EventStream, RunError> {
-        self.into_gateway(gateway)
+        async move {
+            self.state.conn_state.set(ConnectionState::Connecting);
+            self.into_gateway(gateway)
+                .connect()
+                .await
+                .context(error::ConnectGatewayFailed)?
+                .wait_hello()
+                .await
+                .context(error::WaitHelloFailed)
+        }
+        .instrument(logging::connection_span())
+        .await
+    }
+
+    pub async fn run_raw(
+        self,
+        gateway: GatewayURLInfo,
+    ) -> Result<FilteredMessageStreamSink, ConnectGatewayError> {
+        Ok(self
+            .into_gateway(gateway)
             .connect()
-            .await
-            .context(error::ConnectGatewayFailed)?
-            .wait_hello()
-            .await
-            .context(error::WaitHelloFailed)
+            .instrument(logging::connection_span())
+            .await?
+            .into_message_stream())
     }
 
     pub(crate) fn into_gateway(
         mut self,
         mut gateway: GatewayURLInfo,
     ) -> ClientInner<ClientStateGateway> {
-        log::debug!("Try resume from {:?}", self.state.resume);
+        debug!("Try resume from {:?}", self.state.resume);
 
         std::mem::swap(&mut gateway.resume, &mut self.state.resume);
 
-        log::debug!("Updated gateway url {}", gateway.url());
+        debug!("Updated gateway url {}", gateway.url());
 
-        log::debug!("Move to gateway state");
+        debug!("Move to gateway state");
 
         ClientInner {
-            state: ClientStateGateway { gateway },
+            state: ClientStateGateway {
+                gateway,
+                config: self.state.config,
+                conn_state: self.state.conn_state,
+            },
         }
     }
 }
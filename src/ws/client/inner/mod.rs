@@ -1,25 +1,215 @@
 mod connected;
 mod gateway;
 mod init;
+mod state;
 mod streaming;
 mod timeout;
 
 pub(super) use init::ClientStateInit;
+pub(crate) use state::StateNotifier;
+pub(crate) use streaming::Heartbeat;
 
 pub use connected::WaitHelloError;
 pub use gateway::ConnectGatewayError;
 pub use init::RunError;
-pub use streaming::{EventStream, EventStreamError, EventStreamErrorKind};
+pub use state::ConnectionState;
+pub use streaming::{EventStream, EventStreamError, EventStreamErrorKind, Latency};
 
-pub(crate) const PONG_TIMEOUT: u64 = 6;
+use std::time::Duration;
 
-pub(crate) const STREAMING_STATE_PING_INTERVAL: u64 = 30;
+pub(crate) const HELLO_TIMEOUT: Duration = Duration::from_secs(6);
+
+pub(crate) const PONG_TIMEOUT: Duration = Duration::from_secs(6);
+
+pub(crate) const STREAMING_STATE_PING_INTERVAL: Duration = Duration::from_secs(30);
 pub(crate) const STREAMING_STATE_PONG_TIMEOUT_MAX_COUNT: usize = 2;
 
-pub(crate) const TIMEOUT_STATE_SEND_PING_INTERVAL_START: u64 = 2;
-pub(crate) const TIMEOUT_STATE_SEND_PING_INTERVAL_MAX: u64 = PONG_TIMEOUT;
+pub(crate) const TIMEOUT_STATE_SEND_PING_INTERVAL_START: Duration = Duration::from_secs(2);
+
+pub(crate) const EVENT_STREAM_CAPACITY: usize = 32;
+
+pub(crate) const EVENT_BUFFER_MAX_SIZE: usize = 1024;
+pub(crate) const EVENT_GAP_MAX_WAIT: Duration = Duration::from_secs(30);
 
 #[derive(Debug)]
 pub(crate) struct ClientInner<S> {
     pub state: S,
 }
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, task::Poll};
+
+    use futures_util::{Sink, Stream, StreamExt};
+    use tokio::{sync::mpsc, time::Instant};
+
+    use super::*;
+    use crate::{
+        api::types::{GatewayResumeArguments, GatewayURLInfo},
+        ws::{
+            client::{
+                inner::streaming::{ClientStateStreaming, EventStreamSender},
+                ClientConfig,
+            },
+            message::MessageStreamSinkError,
+            Message,
+        },
+    };
+
+    /// In memory connection, so state machine only waits on tokio timers and virtual time
+    /// advances by itself
+    #[derive(Debug)]
+    struct MockConnection {
+        incoming: mpsc::UnboundedReceiver<Message>,
+        outgoing: mpsc::UnboundedSender<Message>,
+    }
+
+    /// The gateway side of [MockConnection]
+    struct MockPeer {
+        incoming: mpsc::UnboundedSender<Message>,
+        outgoing: mpsc::UnboundedReceiver<Message>,
+    }
+
+    fn connection() -> (MockConnection, MockPeer) {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+        (
+            MockConnection {
+                incoming: incoming_rx,
+                outgoing: outgoing_tx,
+            },
+            MockPeer {
+                incoming: incoming_tx,
+                outgoing: outgoing_rx,
+            },
+        )
+    }
+
+    impl Stream for MockConnection {
+        type Item = Result<Message, MessageStreamSinkError>;
+
+        fn poll_next(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> Poll<Option<Self::Item>> {
+            self.incoming.poll_recv(cx).map(|message| message.map(Ok))
+        }
+    }
+
+    impl Sink<Message> for MockConnection {
+        type Error = MessageStreamSinkError;
+
+        fn poll_ready(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: std::pin::Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+            self.outgoing
+                .send(item)
+                .map_err(|_| MessageStreamSinkError::Closed { frame: None })
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_streaming(config: ClientConfig) -> (MockPeer, EventStream) {
+        let (conn, peer) = connection();
+        let (sink, stream) = conn.split();
+        let resume = GatewayResumeArguments {
+            session_id: "session".to_string(),
+            sn: 0,
+        };
+        let (sender, event_stream) =
+            EventStreamSender::new(resume, config, Arc::new(StateNotifier::new()));
+
+        ClientInner {
+            state: ClientStateStreaming {
+                gateway: GatewayURLInfo {
+                    schema: "ws".to_string(),
+                    host: "127.0.0.1".to_string(),
+                    port: Some(1),
+                    path: "/gateway".to_string(),
+                    compress: false,
+                    token: "token".to_string(),
+                    resume: None,
+                },
+                sender,
+                sink: Some(sink),
+                stream,
+            },
+        }
+        .streaming_start();
+
+        (peer, event_stream)
+    }
+
+    async fn expect_ping(peer: &mut MockPeer, start: Instant, at: Duration) {
+        let message = peer.outgoing.recv().await.unwrap();
+        assert!(matches!(message, Message::Ping(_)));
+        assert_eq!(start.elapsed().as_secs(), at.as_secs());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_streaming_ping_interval() {
+        let start = Instant::now();
+        let (mut peer, _stream) = start_streaming(ClientConfig::new());
+
+        for n in 0..3 {
+            expect_ping(&mut peer, start, STREAMING_STATE_PING_INTERVAL * n).await;
+            peer.incoming.send(Message::Pong).unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_streaming_pong_timeout_and_recovery() {
+        let start = Instant::now();
+        let (mut peer, stream) = start_streaming(ClientConfig::new());
+        let mut state = stream.state_watcher();
+
+        // pongs are withheld, the second timeout moves client to timeout state
+        expect_ping(&mut peer, start, Duration::ZERO).await;
+        expect_ping(&mut peer, start, STREAMING_STATE_PING_INTERVAL).await;
+
+        let timeout_at = STREAMING_STATE_PING_INTERVAL + PONG_TIMEOUT;
+        expect_ping(&mut peer, start, timeout_at).await;
+        assert_eq!(*state.borrow_and_update(), ConnectionState::Timeout);
+
+        // timeout state pings more frequently, any message brings client back
+        let retry_at = timeout_at + TIMEOUT_STATE_SEND_PING_INTERVAL_START;
+        expect_ping(&mut peer, start, retry_at).await;
+        peer.incoming.send(Message::Pong).unwrap();
+
+        state
+            .wait_for(|state| *state == ConnectionState::Streaming)
+            .await
+            .unwrap();
+        expect_ping(&mut peer, start, retry_at).await;
+        assert!(stream.latency().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_hello_timeout() {
+        let start = Instant::now();
+        let (mut conn, _peer) = connection();
+
+        let result = connected::wait_hello_message(&mut conn).await;
+
+        assert!(matches!(result, Err(WaitHelloError::Timeout)));
+        assert_eq!(start.elapsed().as_secs(), HELLO_TIMEOUT.as_secs());
+    }
+}
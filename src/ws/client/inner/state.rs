@@ -0,0 +1,51 @@
+use tokio::sync::watch;
+
+/// State of websocket client state machine, see [Client::state_watcher](crate::ws::Client::state_watcher)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connecting to gateway
+    Connecting,
+    /// Connected, waiting hello message from gateway
+    WaitingHello,
+    /// Receiving events
+    Streaming,
+    /// Pong not received in time, sending pings to check if connection is alive
+    Timeout,
+    /// Connection is dead, connecting to gateway again
+    Reconnecting,
+    /// Client is not running, or stopped by error or close
+    Stopped,
+}
+
+/// Publish state changes, shared by all parts of one client.
+///
+/// State becomes [ConnectionState::Stopped] when the last part is dropped.
+#[derive(Debug)]
+pub(crate) struct StateNotifier(watch::Sender<ConnectionState>);
+
+impl StateNotifier {
+    pub fn new() -> Self {
+        Self(watch::Sender::new(ConnectionState::Stopped))
+    }
+
+    pub fn set(&self, state: ConnectionState) {
+        self.0.send_if_modified(|current| {
+            let changed = *current != state;
+            if changed {
+                debug!("Connection state {:?} -> {:?}", current, state);
+                *current = state;
+            }
+            changed
+        });
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.0.subscribe()
+    }
+}
+
+impl Drop for StateNotifier {
+    fn drop(&mut self) {
+        self.set(ConnectionState::Stopped);
+    }
+}
@@ -3,12 +3,16 @@ use std::{
     collections::{BinaryHeap, HashSet},
 };
 
+use tokio::time::Instant;
+
 use crate::ws::event::EventData;
 
 #[derive(Debug, Default)]
 pub(crate) struct EventBuffer {
     exist: HashSet<u64>,
     buffer: BinaryHeap<Reverse<EventData>>,
+    /// sn before the gap, and when we start waiting for it
+    gap: Option<(u64, Instant)>,
 }
 
 #[derive(Debug)]
@@ -33,7 +37,7 @@ impl Iterator for EventsCanBeSend<'_> {
 impl EventBuffer {
     pub fn put(&mut self, sn: u64, item: EventData) {
         if item.sn <= sn || self.exist.contains(&item.sn) {
-            log::trace!("Duplicated event {} received, drop it", item.sn);
+            trace!("Duplicated event {} received, drop it", item.sn);
             return;
         }
         self.exist.insert(item.sn);
@@ -50,7 +54,25 @@ impl EventBuffer {
         Some(item.0)
     }
 
-    pub fn events_can_be_sent(&mut self, sn: u64) -> EventsCanBeSend {
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Start waiting a new gap after sn, or stop waiting if buffer is empty
+    pub fn update_gap(&mut self, sn: u64) {
+        if self.buffer.is_empty() {
+            self.gap = None;
+        } else if self.gap.is_none_or(|(gap_sn, _)| gap_sn != sn) {
+            self.gap = Some((sn, Instant::now()));
+        }
+    }
+
+    /// When we start waiting for the current gap
+    pub fn gap_since(&self) -> Option<Instant> {
+        self.gap.map(|(_, since)| since)
+    }
+
+    pub fn events_can_be_sent(&mut self, sn: u64) -> EventsCanBeSend<'_> {
         EventsCanBeSend { sn, buffer: self }
     }
 }
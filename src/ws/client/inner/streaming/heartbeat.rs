@@ -0,0 +1,94 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+const LATENCY_SAMPLES: usize = 10;
+
+/// Gateway heartbeat latency, time between a ping and the following pong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latency {
+    /// latency of the last ping
+    pub latest: Duration,
+    /// average latency of recent pings
+    pub average: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    ping_at: Option<Instant>,
+    recent: VecDeque<Duration>,
+}
+
+/// Latency records shared by ping worker, streaming state and event stream.
+#[derive(Debug, Default)]
+pub(crate) struct Heartbeat {
+    samples: Mutex<Samples>,
+}
+
+impl Heartbeat {
+    pub fn ping_sent(&self, at: Instant) {
+        self.samples.lock().unwrap().ping_at.replace(at);
+    }
+
+    /// Record a pong, returns its latency if there is a ping waiting for it
+    pub fn pong_received(&self, at: Instant) -> Option<Duration> {
+        let mut samples = self.samples.lock().unwrap();
+        let latency = at.saturating_duration_since(samples.ping_at.take()?);
+
+        if samples.recent.len() >= LATENCY_SAMPLES {
+            samples.recent.pop_front();
+        }
+        samples.recent.push_back(latency);
+
+        Some(latency)
+    }
+
+    pub fn latency(&self) -> Option<Latency> {
+        let samples = self.samples.lock().unwrap();
+        let latest = *samples.recent.back()?;
+        let average = samples.recent.iter().sum::<Duration>() / samples.recent.len() as u32;
+
+        Some(Latency { latest, average })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_latency() {
+        let heartbeat = Heartbeat::default();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        assert_eq!(heartbeat.pong_received(at(10)), None);
+        assert_eq!(heartbeat.latency(), None);
+
+        heartbeat.ping_sent(at(0));
+        assert_eq!(
+            heartbeat.pong_received(at(100)),
+            Some(Duration::from_millis(100))
+        );
+        heartbeat.ping_sent(at(1000));
+        heartbeat.pong_received(at(1300));
+        assert_eq!(heartbeat.pong_received(at(1400)), None);
+
+        assert_eq!(
+            heartbeat.latency(),
+            Some(Latency {
+                latest: Duration::from_millis(300),
+                average: Duration::from_millis(200),
+            })
+        );
+
+        for i in 0..LATENCY_SAMPLES as u64 {
+            heartbeat.ping_sent(at(2000 + i * 100));
+            heartbeat.pong_received(at(2000 + i * 100 + 50));
+        }
+        assert_eq!(
+            heartbeat.latency().map(|l| l.average),
+            Some(Duration::from_millis(50))
+        );
+    }
+}
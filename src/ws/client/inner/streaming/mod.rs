@@ -1,14 +1,17 @@
 mod buffer;
+mod heartbeat;
 mod ping;
 mod sender;
 mod state;
 mod stream;
 
 pub(crate) use buffer::EventBuffer;
-pub(crate) use sender::EventStreamSender;
+pub(crate) use heartbeat::Heartbeat;
+pub(crate) use sender::{shutdown_requested, EventStreamSender};
 pub(crate) use state::ClientStateStreaming;
 pub(crate) use stream::error;
 
+pub use heartbeat::Latency;
 pub use stream::{EventStream, EventStreamError, EventStreamErrorKind};
 
 // =====
@@ -18,7 +21,10 @@ use std::fmt::Debug;
 use futures_util::{Sink, Stream};
 
 use super::ClientInner;
-use crate::ws::{message::MessageStreamSinkError, Message};
+use crate::{
+    logging::Instrument,
+    ws::{message::MessageStreamSinkError, Message},
+};
 
 impl<S> ClientInner<ClientStateStreaming<S>>
 where
@@ -30,6 +36,6 @@ where
         + 'static,
 {
     pub fn streaming_start(self) {
-        tokio::spawn(self.state.streaming());
+        tokio::spawn(self.state.streaming().in_current_span());
     }
 }
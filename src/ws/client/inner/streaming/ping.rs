@@ -1,14 +1,11 @@
-use std::{fmt::Debug, time::Duration};
+use std::fmt::Debug;
 
 use futures_util::{stream::SplitSink, Sink, SinkExt};
 use snafu::prelude::*;
 use tokio::{sync::watch, time::Instant};
 
 use super::{error, EventStreamSender};
-use crate::ws::{
-    client::inner::{PONG_TIMEOUT, STREAMING_STATE_PING_INTERVAL},
-    message::{Message, MessageStreamSinkError},
-};
+use crate::ws::message::{Message, MessageStreamSinkError};
 
 #[derive(Debug)]
 pub(crate) struct PingWorker<S> {
@@ -34,7 +31,7 @@ where
     }
 
     pub async fn run(mut self) -> SplitSink<S, Message> {
-        log::debug!("Ping worker start");
+        debug!("Ping worker start");
 
         let mut send_ping_tick = Instant::now();
 
@@ -46,30 +43,31 @@ where
 
                 notifier_alive = self.sender.wait_sn_change() => {
                     if !notifier_alive {
-                        log::debug!("Find sn notifier dead when wait sn update");
-                        log::debug!("Stop");
+                        debug!("Find sn notifier dead when wait sn update");
+                        debug!("Stop");
                         break
                     }
-                    log::trace!("Ping worker sn update to {}", self.sender.sn());
+                    trace!("Ping worker sn update to {}", self.sender.sn());
                 }
 
                 _ = send_ping_clock => {
-                    log::trace!("Send ping message with sn {}", self.sender.sn());
-                    if let Err(err) = self.sink.feed(self.sender.ping()).await.context(error::MessageStream) {
-                        log::debug!("Find message stream broken when send ping message: {}", err);
-                        log::trace!("Send error to event stream");
+                    trace!("Send ping message with sn {}", self.sender.sn());
+                    if let Err(err) = self.sink.send(self.sender.ping()).await.context(error::MessageStream) {
+                        debug!("Find message stream broken when send ping message: {}", err);
+                        trace!("Send error to event stream");
                         self.sender.send_err(err).await;
-                        log::debug!("Stop");
+                        debug!("Stop");
                         break
                     }
 
-                    send_ping_tick = Instant::now() + Duration::from_secs(STREAMING_STATE_PING_INTERVAL);
+                    self.sender.heartbeat().ping_sent(Instant::now());
+                    send_ping_tick = Instant::now() + self.sender.config().ping_interval;
 
-                    log::trace!("Send pong timeout tick to streaming background task");
-                    let pong_timeout_tick = Instant::now() + Duration::from_secs(PONG_TIMEOUT);
+                    trace!("Send pong timeout tick to streaming background task");
+                    let pong_timeout_tick = Instant::now() + self.sender.config().pong_timeout;
                     if let Err(err) = self.pong_timeout_tick_notifier.send(Some(pong_timeout_tick)) {
-                        log::debug!("Find streaming background task stopped due to pong timeout tick notifier returning error: {}", err);
-                        log::debug!("Stop");
+                        debug!("Find streaming background task stopped due to pong timeout tick notifier returning error: {}", err);
+                        debug!("Stop");
                         break
                     }
                 }
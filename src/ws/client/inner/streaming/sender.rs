@@ -1,12 +1,22 @@
-use tokio::sync::{mpsc, watch};
+use std::sync::Arc;
 
-use super::{EventBuffer, EventStream, EventStreamError, EventStreamErrorKind};
+use tokio::{
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+use super::{
+    super::{ConnectionState, StateNotifier},
+    EventBuffer, EventStream, EventStreamError, EventStreamErrorKind, Heartbeat,
+};
 use crate::{
     api::types::GatewayResumeArguments,
+    logging,
     ws::{
+        client::{ClientConfig, EventGap, GapPolicy},
         event::EventData,
-        message::{MessageStreamSinkError, Reconnect},
-        Event, Message,
+        message::{tap_ignored, IgnoredFrame, MessageStreamSinkError, Reconnect},
+        Message,
     },
 };
 
@@ -55,11 +65,24 @@ impl SnRecorder {
     }
 }
 
+/// Resolves when [EventStream::close] is called, never resolves if the event stream is dropped
+pub(crate) async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|closing| *closing).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct EventStreamSender {
     buffer: EventBuffer,
-    event_tx: mpsc::Sender<Result<Box<Event>, EventStreamError>>,
+    event_tx: mpsc::Sender<Result<EventData, EventStreamError>>,
     recorder: SnRecorder,
+    config: ClientConfig,
+    status: Arc<watch::Sender<GatewayResumeArguments>>,
+    gaps: Arc<watch::Sender<Option<EventGap>>>,
+    shutdown: watch::Receiver<bool>,
+    heartbeat: Arc<Heartbeat>,
+    conn_state: Arc<StateNotifier>,
 }
 
 impl Clone for EventStreamSender {
@@ -68,13 +91,27 @@ impl Clone for EventStreamSender {
             buffer: EventBuffer::default(),
             event_tx: self.event_tx.clone(),
             recorder: self.recorder.clone(),
+            config: self.config.clone(),
+            status: Arc::clone(&self.status),
+            gaps: Arc::clone(&self.gaps),
+            shutdown: self.shutdown.clone(),
+            heartbeat: Arc::clone(&self.heartbeat),
+            conn_state: Arc::clone(&self.conn_state),
         }
     }
 }
 
 impl EventStreamSender {
-    pub fn new(resume: GatewayResumeArguments) -> (Self, EventStream) {
-        let (event_tx, event_rx) = tokio::sync::mpsc::channel(32);
+    pub fn new(
+        resume: GatewayResumeArguments,
+        config: ClientConfig,
+        conn_state: Arc<StateNotifier>,
+    ) -> (Self, EventStream) {
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(config.event_capacity);
+        let (status, status_rx) = watch::channel(resume.clone());
+        let (gaps, gaps_rx) = watch::channel(None);
+        let (shutdown_tx, shutdown) = watch::channel(false);
+        let heartbeat = Arc::<Heartbeat>::default();
 
         (
             Self {
@@ -85,8 +122,21 @@ impl EventStreamSender {
                     sn_watcher: None,
                     sn_notifier: None,
                 },
+                config,
+                status: Arc::new(status),
+                gaps: Arc::new(gaps),
+                shutdown,
+                heartbeat: Arc::clone(&heartbeat),
+                conn_state: Arc::clone(&conn_state),
+            },
+            EventStream {
+                rx: event_rx,
+                status: status_rx,
+                gaps: gaps_rx,
+                state: conn_state.subscribe(),
+                shutdown: shutdown_tx,
+                heartbeat,
             },
-            EventStream { rx: event_rx },
         )
     }
 
@@ -108,6 +158,14 @@ impl EventStreamSender {
         &self.recorder.resume
     }
 
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+
     pub fn sn(&self) -> u64 {
         self.recorder.resume.sn
     }
@@ -122,32 +180,143 @@ impl EventStreamSender {
 
     pub async fn flush(&mut self) -> bool {
         for data in self.buffer.events_can_be_sent(self.sn()) {
-            if self.event_tx.send(Ok(data.event)).await.is_ok() {
-                log::trace!("Send event {} to event stream success", data.sn);
+            let sn = data.sn;
+
+            if self.event_tx.send(Ok(data)).await.is_ok() {
+                trace!("Send event {} to event stream success", sn);
             } else {
-                log::debug!(
+                debug!(
                     "Send event {} to event stream failed, means receive side dropped, stop",
-                    data.sn
+                    sn
                 );
                 // event receive side dropped, stop produce
                 return false;
             }
 
-            if !self.recorder.update_sn(data.sn) {
+            if !self.recorder.update_sn(sn) {
                 return false;
             }
+
+            self.status.send_if_modified(|status| {
+                let changed = status.sn < sn;
+                if changed {
+                    status.sn = sn;
+                }
+                changed
+            });
         }
 
+        self.buffer.update_gap(self.sn());
+
         true
     }
 
+    /// When the current gap should be resolved by [EventStreamSender::resolve_gap]
+    pub fn gap_deadline(&self) -> Option<Instant> {
+        Some(self.buffer.gap_since()? + self.config.max_gap_wait)
+    }
+
+    /// Skip the missing events or ask for resume by [GapPolicy], returns false if stream stopped
+    pub async fn resolve_gap(&mut self) -> bool {
+        let Some(next) = self.buffer.peek().map(|data| data.sn) else {
+            return true;
+        };
+        let (from, to) = (self.sn() + 1, next - 1);
+        let gap = EventGap {
+            from,
+            to,
+            buffered: self.buffer.len(),
+            policy: self.config.gap_policy,
+        };
+        let waited = self.buffer.gap_since().map(|since| since.elapsed());
+        self.gaps.send_replace(Some(gap));
+
+        match self.config.gap_policy {
+            GapPolicy::Skip => {
+                warn!(
+                    "Events {} to {} missing, {} later events waited {:?}, skip them",
+                    from, to, gap.buffered, waited
+                );
+                self.recorder.update_sn(to) && self.flush().await
+            }
+            GapPolicy::Resume => {
+                warn!(
+                    "Events {} to {} missing, {} later events waited {:?}, stop for resume",
+                    from, to, gap.buffered, waited
+                );
+                self.send_err(EventStreamErrorKind::EventGap { from, to })
+                    .await;
+                false
+            }
+        }
+    }
+
+    /// Update session id used by later resumes and reported by [EventStream::resume_arguments]
+    pub fn set_session_id(&mut self, session_id: String) {
+        logging::record_session_id(&session_id);
+        self.recorder.resume.session_id.clone_from(&session_id);
+        self.status.send_if_modified(|status| {
+            let changed = status.session_id != session_id;
+            if changed {
+                status.session_id = session_id;
+            }
+            changed
+        });
+    }
+
+    /// Send all buffered events in order, even if some events before them are missing
+    pub async fn flush_all(&mut self) {
+        while let Some(data) = self.buffer.pop() {
+            let sn = data.sn;
+            if self.event_tx.send(Ok(data)).await.is_err() {
+                return;
+            }
+            self.recorder.update_sn(sn);
+        }
+    }
+
+    /// Report new state of the client state machine
+    pub fn set_state(&self, state: ConnectionState) {
+        self.conn_state.set(state);
+    }
+
+    /// State notifier shared with new states created when reconnecting
+    pub fn conn_state(&self) -> Arc<StateNotifier> {
+        Arc::clone(&self.conn_state)
+    }
+
+    /// Send message not handled in current state to ignored frames tap
+    pub fn ignore(&self, message: Message) {
+        let tap = self.config.ignored_frames.as_ref();
+        tap_ignored(tap, IgnoredFrame::Unhandled(message));
+    }
+
+    /// Watcher of graceful close request from [EventStream::close]
+    pub fn shutdown_watcher(&self) -> watch::Receiver<bool> {
+        self.shutdown.clone()
+    }
+
     pub fn put(&mut self, event: EventData) {
         self.buffer.put(self.sn(), event);
     }
 
     pub async fn send_event(&mut self, event: EventData) -> bool {
         self.put(event);
-        self.flush().await
+        if !self.flush().await {
+            return false;
+        }
+
+        if self.buffer.len() > self.config.max_buffered_events {
+            warn!("Too many events buffered waiting for missing events");
+            return self.resolve_gap().await;
+        }
+
+        true
+    }
+
+    /// Wait until the event stream is dropped
+    pub async fn closed(&self) {
+        self.event_tx.closed().await
     }
 
     pub async fn send_err(&self, err: EventStreamErrorKind) -> bool {
@@ -161,7 +330,7 @@ impl EventStreamSender {
     }
 
     pub async fn send_reconnect(&self, data: Reconnect) {
-        log::trace!("Send reconnect error to event stream");
+        trace!("Send reconnect error to event stream");
         self.send_err(EventStreamErrorKind::Reconnect {
             code: data.code,
             message: data.err,
@@ -170,10 +339,18 @@ impl EventStreamSender {
     }
 
     pub async fn send_message_stream_broken(&self, err: MessageStreamSinkError) {
-        log::trace!("Send message stream broken error to event stream");
-        self.send_err(EventStreamErrorKind::MessageStream {
-            source: Box::new(err),
-        })
-        .await;
+        let err = match err {
+            MessageStreamSinkError::Closed { frame } => {
+                trace!("Send closed error to event stream");
+                EventStreamErrorKind::Closed { frame }
+            }
+            err => {
+                trace!("Send message stream broken error to event stream");
+                EventStreamErrorKind::MessageStream {
+                    source: Box::new(err),
+                }
+            }
+        };
+        self.send_err(err).await;
     }
 }
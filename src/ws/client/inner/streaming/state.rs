@@ -3,17 +3,16 @@ use std::fmt::Debug;
 use futures_util::{
     future,
     stream::{SplitSink, SplitStream},
-    FutureExt, Sink, Stream, StreamExt,
+    FutureExt, Sink, SinkExt, Stream, StreamExt,
 };
 use tokio::{sync::watch, task::JoinHandle, time::Instant};
 
-use super::{ping::PingWorker, EventStreamSender};
+use super::{ping::PingWorker, shutdown_requested, EventStreamSender};
 use crate::{
     api::types::GatewayURLInfo,
+    logging::Instrument,
     ws::{
-        client::inner::{
-            timeout::ClientStateTimeout, ClientInner, STREAMING_STATE_PONG_TIMEOUT_MAX_COUNT,
-        },
+        client::inner::{timeout::ClientStateTimeout, ClientInner, ConnectionState},
         message::{Message, MessageStreamSinkError},
     },
 };
@@ -55,18 +54,33 @@ where
             pong_timeout_notifier,
         );
 
-        let pw_handler = tokio::spawn(pw.run());
+        let pw_handler = tokio::spawn(pw.run().in_current_span());
 
         (pw_handler, pong_timeout_watcher)
     }
 
+    /// Stop ping worker, send close frame and flush all buffered events
+    async fn close(mut self, pw_handler: JoinHandle<SplitSink<S, Message>>) {
+        self.sender.remove_sn_notifier();
+
+        trace!("Waiting ping worker to stop");
+        let mut sink = pw_handler.await.unwrap();
+
+        if let Err(err) = sink.close().await {
+            debug!("Send close frame failed: {}", err);
+        }
+
+        self.sender.flush_all().await;
+        debug!("Connection closed");
+    }
+
     async fn into_timeout(
         mut self,
         pw_handler: JoinHandle<SplitSink<S, Message>>,
     ) -> ClientStateTimeout<S> {
         self.sender.remove_sn_notifier();
 
-        log::trace!("Waiting ping worker to stop");
+        trace!("Waiting ping worker to stop");
         let sink = pw_handler.await.unwrap();
 
         ClientStateTimeout::<S> {
@@ -78,39 +92,51 @@ where
     }
 
     async fn on_message(&mut self, data: Option<Result<Message, MessageStreamSinkError>>) -> bool {
-        match data.unwrap() {
+        match data.unwrap_or(Err(MessageStreamSinkError::Closed { frame: None })) {
             Ok(message) => {
-                log::trace!("Received new message type: {}", message.type_name());
+                trace!("Received new message type: {}", message.type_name());
 
                 match message {
                     Message::Event(data) => {
-                        log::trace!("Received event sn = {}", data.sn);
+                        trace!("Received event sn = {}", data.sn);
                         self.sender.send_event(data).await
                     }
                     Message::Reconnect(data) => {
                         self.sender.send_reconnect(data.data).await;
-                        log::debug!("Stop");
+                        debug!("Stop");
                         false
                     }
-                    Message::ResumeACK(_) => {
-                        // TODO: do we need update session id?
+                    Message::Pong => {
+                        if let Some(latency) = self.sender.heartbeat().pong_received(Instant::now())
+                        {
+                            trace!("Heartbeat latency {:?}", latency);
+                        }
+                        true
+                    }
+                    Message::ResumeACK(data) => {
+                        debug!("Resume ack with session id {}", data.data.session_id);
+                        self.sender.set_session_id(data.data.session_id);
                         true
                     }
                     // Ignore other message
-                    _ => true,
+                    message => {
+                        self.sender.ignore(message);
+                        true
+                    }
                 }
             }
             Err(err) => {
-                log::warn!("Find message stream broken when receive message: {}", err);
+                warn!("Find message stream broken when receive message: {}", err);
                 self.sender.send_message_stream_broken(err).await;
-                log::debug!("Stop");
+                debug!("Stop");
                 false
             }
         }
     }
 
     pub(crate) async fn streaming(mut self) {
-        log::debug!("Streaming background task start");
+        debug!("Streaming background task start");
+        self.sender.set_state(ConnectionState::Streaming);
 
         let (pw_handler, mut pong_timeout_watcher) = self.create_ping_worker();
 
@@ -119,6 +145,7 @@ where
             return;
         }
 
+        let mut shutdown = self.sender.shutdown_watcher();
         let mut pong_timeout_tick: Option<Instant> = None;
         let mut pong_timeout_count = 0;
 
@@ -129,23 +156,29 @@ where
                 future::pending().boxed()
             };
 
+            let gap_clock = if let Some(deadline) = self.sender.gap_deadline() {
+                tokio::time::sleep_until(deadline).boxed()
+            } else {
+                future::pending().boxed()
+            };
+
             tokio::select! {
                 biased;
 
                 // pong timeout
                 _ = pong_timeout_clock => {
                     pong_timeout_count += 1;
-                    log::warn!("Pong timeout, counts {}", pong_timeout_count);
+                    warn!("Pong timeout, counts {}", pong_timeout_count);
 
-                    log::trace!("Reset pong timeout tick to inf");
+                    trace!("Reset pong timeout tick to inf");
                     pong_timeout_tick = None;
 
-                    if pong_timeout_count >= STREAMING_STATE_PONG_TIMEOUT_MAX_COUNT {
-                        log::warn!("Reached pong time out count limit, move to timeout state");
+                    if pong_timeout_count >= self.sender.config().max_pong_timeouts {
+                        warn!("Reached pong time out count limit, move to timeout state");
 
                         let client = ClientInner { state: self.into_timeout(pw_handler).await };
 
-                        log::debug!("Move to timeout state");
+                        debug!("Move to timeout state");
 
                         client.timeout_start();
                         break;
@@ -155,18 +188,40 @@ where
                 // new ping message sent, update ping pong timeout clock
                 watch_result = pong_timeout_watcher.changed() => {
                     if let Err(err) = watch_result {
-                        log::debug!("Find ping worker stopped due to pong timeout watcher returning error: {}", err);
+                        debug!("Find ping worker stopped due to pong timeout watcher returning error: {}", err);
                         break
                     }
 
                     pong_timeout_tick = *pong_timeout_watcher.borrow();
 
-                    log::trace!("Next pong timeout tick: {:?}", pong_timeout_tick);
+                    trace!("Next pong timeout tick: {:?}", pong_timeout_tick);
+                }
+
+                // graceful close requested by user
+                _ = shutdown_requested(&mut shutdown) => {
+                    debug!("Close requested, stop");
+                    self.close(pw_handler).await;
+                    break;
+                }
+
+                // event stream dropped by user
+                _ = self.sender.closed() => {
+                    debug!("Event stream dropped, stop");
+                    self.close(pw_handler).await;
+                    break;
+                }
+
+                // missing events not received in time
+                _ = gap_clock => {
+                    warn!("Missing events not received in {:?}", self.sender.config().max_gap_wait);
+                    if !self.sender.resolve_gap().await {
+                        break;
+                    }
                 }
 
                 // new message received
                 result = self.stream.next() => {
-                    log::trace!("New Message received, reset pong timeout tick to inf and clean timeout count");
+                    trace!("New Message received, reset pong timeout tick to inf and clean timeout count");
                     pong_timeout_tick = None;
                     pong_timeout_count = 0;
 
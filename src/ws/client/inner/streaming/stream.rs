@@ -1,13 +1,22 @@
-use std::task::Poll;
+use std::{sync::Arc, task::Poll};
 
 use futures_util::Stream;
 use snafu::prelude::*;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
-use super::super::ConnectGatewayError;
+use super::{
+    super::{ConnectGatewayError, ConnectionState},
+    Heartbeat, Latency,
+};
 use crate::{
     api::types::GatewayResumeArguments,
-    ws::{client::WaitHelloError, message::MessageStreamSinkError, Event},
+    ws::{
+        client::{EventGap, WaitHelloError},
+        event::EventData,
+        message::{describe_close, MessageStreamSinkError},
+        Event,
+    },
 };
 
 /// Error for event stream
@@ -29,7 +38,14 @@ pub enum EventStreamErrorKind {
     MessageStream {
         /// source error
         #[snafu(source(from(MessageStreamSinkError, Box::new)))]
-        source: Box<dyn std::error::Error + Send>,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// gateway closed the connection, `frame` is `None` if the connection ends without a close frame
+    #[snafu(display("connection closed by gateway: {}", describe_close(frame)))]
+    Closed {
+        /// close frame sent by gateway
+        frame: Option<CloseFrame<'static>>,
     },
 
     /// received server reconnect message
@@ -42,6 +58,15 @@ pub enum EventStreamErrorKind {
         message: String,
     },
 
+    /// missing events are not received in time, with [GapPolicy::Resume](crate::ws::GapPolicy::Resume)
+    #[snafu(display("events {from} to {to} missing"))]
+    EventGap {
+        /// sn of first missing event
+        from: u64,
+        /// sn of last missing event
+        to: u64,
+    },
+
     /// reconnect to websocket gateway failed
     #[snafu(display("(re)connect ws gateway failed: {source}"))]
     ReConnectGatewayFailed {
@@ -60,7 +85,58 @@ pub enum EventStreamErrorKind {
 /// Kaiheila websocket event stream
 #[derive(Debug)]
 pub struct EventStream {
-    pub(crate) rx: mpsc::Receiver<Result<Box<Event>, EventStreamError>>,
+    pub(crate) rx: mpsc::Receiver<Result<EventData, EventStreamError>>,
+    pub(crate) status: watch::Receiver<GatewayResumeArguments>,
+    pub(crate) gaps: watch::Receiver<Option<EventGap>>,
+    pub(crate) state: watch::Receiver<ConnectionState>,
+    pub(crate) shutdown: watch::Sender<bool>,
+    pub(crate) heartbeat: Arc<Heartbeat>,
+}
+
+impl EventStream {
+    /// Current session id and sn of last received event, can be used to resume the session
+    pub fn resume_arguments(&self) -> GatewayResumeArguments {
+        self.status.borrow().clone()
+    }
+
+    /// Heartbeat latency to gateway, `None` if no pong received yet
+    pub fn latency(&self) -> Option<Latency> {
+        self.heartbeat.latency()
+    }
+
+    /// Watch the latest missing events which are skipped or resumed by [GapPolicy]
+    ///
+    /// [GapPolicy]: crate::ws::GapPolicy
+    pub fn gap_watcher(&self) -> watch::Receiver<Option<EventGap>> {
+        self.gaps.clone()
+    }
+
+    /// Watch state of the client, notified whenever it changes
+    pub fn state_watcher(&self) -> watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
+
+    pub(crate) fn heartbeat(&self) -> Arc<Heartbeat> {
+        Arc::clone(&self.heartbeat)
+    }
+
+    /// Close the connection gracefully.
+    ///
+    /// Client sends a close frame to gateway and stops sending pings, events already received
+    /// are still yielded, even if some events before them are missing, then the stream ends.
+    pub fn close(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Receive next event with its sn
+    pub(crate) async fn next_data(&mut self) -> Option<Result<EventData, EventStreamError>> {
+        self.rx.recv().await
+    }
+
+    /// Watch session id and sn of last received event, notified whenever any of them changes
+    pub fn resume_watcher(&self) -> watch::Receiver<GatewayResumeArguments> {
+        self.status.clone()
+    }
 }
 
 impl Stream for EventStream {
@@ -70,6 +146,11 @@ impl Stream for EventStream {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        self.rx.poll_recv(cx)
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(Ok(data))) => Poll::Ready(Some(Ok(data.event))),
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
     }
 }
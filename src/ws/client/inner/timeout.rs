@@ -1,4 +1,4 @@
-use std::{fmt::Debug, time::Duration};
+use std::fmt::Debug;
 
 use futures_util::{
     stream::{SplitSink, SplitStream},
@@ -11,17 +11,12 @@ use super::{
     connected::ClientStateConnected,
     streaming::error,
     streaming::{self, ClientStateStreaming, EventStreamSender},
-    ClientInner, ClientStateInit,
+    ClientInner, ClientStateInit, ConnectionState,
 };
 use crate::{
     api::types::GatewayURLInfo,
-    ws::{
-        client::inner::{
-            PONG_TIMEOUT, TIMEOUT_STATE_SEND_PING_INTERVAL_MAX,
-            TIMEOUT_STATE_SEND_PING_INTERVAL_START,
-        },
-        message::{Message, MessageStreamSinkError},
-    },
+    logging::Instrument,
+    ws::message::{Message, MessageStreamSinkError},
 };
 
 pub(crate) struct ClientStateTimeout<S> {
@@ -50,9 +45,13 @@ where
     }
 
     async fn reconnect(&mut self) -> Option<ClientStateConnected> {
+        self.sender.set_state(ConnectionState::Reconnecting);
+
         let client = ClientInner {
             state: ClientStateInit {
                 resume: Some(self.sender.resume().clone()),
+                config: self.sender.config().clone(),
+                conn_state: self.sender.conn_state(),
             },
         };
 
@@ -73,54 +72,69 @@ where
     async fn on_message(mut self, message: Result<Message, MessageStreamSinkError>) {
         match message {
             Ok(message) => {
-                log::trace!("Received new message type: {}", message.type_name());
+                trace!("Received new message type: {}", message.type_name());
 
                 match message {
                     Message::Reconnect(data) => {
                         self.sender.send_reconnect(data.data).await;
-                        log::debug!("Stop");
+                        debug!("Stop");
                     }
                     _ => {
+                        if matches!(message, Message::Pong) {
+                            self.sender.heartbeat().pong_received(Instant::now());
+                        }
+
                         if let Ok(data) = message.into_event() {
                             self.sender.put(data);
                         }
 
-                        log::info!("Recovery from timeout state");
+                        info!("Recovery from timeout state");
 
                         let streaming = self.into_streaming();
                         let client = ClientInner { state: streaming };
 
-                        log::debug!("Move to streaming state");
+                        debug!("Move to streaming state");
                         client.streaming_start();
                     }
                 }
             }
             Err(err) => {
-                log::warn!("Find message stream broken when receive message: {}", err);
+                warn!("Find message stream broken when receive message: {}", err);
                 self.sender.send_message_stream_broken(err).await;
-                log::debug!("Stop");
+                debug!("Stop");
             }
         };
     }
 
     pub async fn waiting(mut self) {
-        log::debug!("Timeout background task start");
+        debug!("Timeout background task start");
+        self.sender.set_state(ConnectionState::Timeout);
 
-        let pong_timeout_clock = tokio::time::sleep(Duration::from_secs(PONG_TIMEOUT));
+        let pong_timeout_clock = tokio::time::sleep(self.sender.config().pong_timeout);
         tokio::pin!(pong_timeout_clock);
 
-        let mut send_ping_delay = 0;
+        let mut send_ping_count = 0;
         let mut send_ping_tick = Instant::now();
+        let mut shutdown = self.sender.shutdown_watcher();
 
         loop {
             tokio::select! {
                 biased;
 
+                _ = streaming::shutdown_requested(&mut shutdown) => {
+                    debug!("Close requested, stop");
+                    if let Err(err) = self.sink.close().await {
+                        debug!("Send close frame failed: {}", err);
+                    }
+                    self.sender.flush_all().await;
+                    return;
+                }
+
                 _ = &mut pong_timeout_clock => {
-                    log::warn!("Pong still timeout, reconnect to gateway");
+                    warn!("Pong still timeout, reconnect to gateway");
 
                     if let Some(connected) = self.reconnect().await {
-                        log::debug!("Reconnect success");
+                        debug!("Reconnect success");
                         let client = ClientInner { state: connected};
                         client.re_wait_hello(self.sender).await;
                     }
@@ -129,31 +143,35 @@ where
                 }
 
                 _ = tokio::time::sleep_until(send_ping_tick) => {
-                    log::trace!("Send ping with sn {}", self.sender.sn());
+                    trace!("Send ping with sn {}", self.sender.sn());
 
                     if let Err(err) = self
                     .sink
-                    .feed(self.sender.ping())
+                    .send(self.sender.ping())
                     .await
                     .context(streaming::error::MessageStream)
                     {
-                        log::debug!("Find message stream broken when send ping message: {}", err);
-                        log::trace!("Send error to event stream");
+                        debug!("Find message stream broken when send ping message: {}", err);
+                        trace!("Send error to event stream");
                         self.sender.send_err(err).await;
-                        log::trace!("Stop");
+                        trace!("Stop");
                         return;
                     }
+                    self.sender.heartbeat().ping_sent(Instant::now());
 
-                    send_ping_delay *= 2;
-                    send_ping_delay = send_ping_delay.clamp(TIMEOUT_STATE_SEND_PING_INTERVAL_START, TIMEOUT_STATE_SEND_PING_INTERVAL_MAX);
+                    let send_ping_delay = self.sender.config().get_retry_ping_delay(send_ping_count);
+                    send_ping_count += 1;
 
-                    log::trace!("Next ping in {} seconds", send_ping_delay);
+                    trace!("Next ping in {:?}", send_ping_delay);
 
-                    send_ping_tick = Instant::now() + Duration::from_secs(send_ping_delay);
+                    send_ping_tick = Instant::now() + send_ping_delay;
                 }
 
                 result = self.stream.next() => {
-                    self.on_message(result.unwrap()).await;
+                    self.on_message(
+                        result.unwrap_or(Err(MessageStreamSinkError::Closed { frame: None })),
+                    )
+                    .await;
                     return;
                 }
             }
@@ -171,6 +189,6 @@ where
         + 'static,
 {
     pub fn timeout_start(self) {
-        tokio::spawn(self.state.waiting());
+        tokio::spawn(self.state.waiting().in_current_span());
     }
 }
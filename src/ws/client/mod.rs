@@ -1,20 +1,345 @@
 //! Kaiheila websocket client
 
 mod inner;
+mod proxy;
 
 pub use inner::{
-    ConnectGatewayError, EventStream, EventStreamError, EventStreamErrorKind, RunError,
-    WaitHelloError,
+    ConnectGatewayError, ConnectionState, EventStream, EventStreamError, EventStreamErrorKind,
+    Latency, RunError, WaitHelloError,
 };
 
-use tokio_tungstenite as websocket;
+use std::{ops::RangeInclusive, sync::Arc, time::Duration};
 
-use crate::api::types::{GatewayResumeArguments, GatewayURLInfo};
-use inner::{ClientInner, ClientStateInit};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{self as websocket, tungstenite::protocol::WebSocketConfig};
+
+use crate::{
+    api::types::{GatewayResumeArguments, GatewayURLInfo},
+    backoff::{BackoffStrategy, ExponentialBackoff},
+    ws::message::{FilteredMessageStreamSink, IgnoredFrame},
+};
+pub(crate) use inner::Heartbeat;
+pub use proxy::{ParseProxyError, Proxy, ProxyKind};
+
+use inner::{
+    ClientInner, ClientStateInit, StateNotifier, EVENT_BUFFER_MAX_SIZE, EVENT_GAP_MAX_WAIT,
+    EVENT_STREAM_CAPACITY, PONG_TIMEOUT, STREAMING_STATE_PING_INTERVAL,
+    STREAMING_STATE_PONG_TIMEOUT_MAX_COUNT, TIMEOUT_STATE_SEND_PING_INTERVAL_START,
+};
+
+const PING_INTERVAL_RANGE: RangeInclusive<Duration> =
+    Duration::from_secs(1)..=Duration::from_secs(600);
+const PONG_TIMEOUT_RANGE: RangeInclusive<Duration> =
+    Duration::from_secs(1)..=Duration::from_secs(60);
 
 pub(crate) type WebsocketClient =
     websocket::WebSocketStream<websocket::MaybeTlsStream<tokio::net::TcpStream>>;
 
+/// What to do when a missing event is not received in time, or too many events are buffered
+/// waiting for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GapPolicy {
+    /// Skip the missing events with a warning log, this is the default.
+    #[default]
+    Skip,
+    /// Stop the event stream with [EventStreamErrorKind::EventGap], so it can be resumed
+    /// from the last continuous event.
+    Resume,
+}
+
+/// Missing events detected by the client, reported by [EventStream::gap_watcher].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventGap {
+    /// sn of first missing event
+    pub from: u64,
+    /// sn of last missing event
+    pub to: u64,
+    /// how many events after the gap are buffered
+    pub buffered: usize,
+    /// what the client does about it
+    pub policy: GapPolicy,
+}
+
+/// Config of websocket client
+///
+/// When pong is not received in time for `max_pong_timeouts` pings in a row, client enters
+/// timeout state, and sends pings with increasing interval starting from `retry_ping_interval`,
+/// until the last one also timeout, then it reconnects to gateway. The interval can be replaced
+/// by a [BackoffStrategy] with [ClientConfig::ping_backoff].
+///
+/// Events received out of order are buffered until missing ones arrive. When a gap is not
+/// filled in `max_gap_wait`, or buffered events exceed `max_buffered_events`, [GapPolicy]
+/// decides what to do, and the gap is reported by [EventStream::gap_watcher], which bot
+/// forwards as [Lifecycle::EventGap](crate::Lifecycle::EventGap).
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub(crate) ping_interval: Duration,
+    pub(crate) pong_timeout: Duration,
+    pub(crate) max_pong_timeouts: usize,
+    pub(crate) retry_ping_interval: Duration,
+    pub(crate) ping_backoff: Option<Arc<dyn BackoffStrategy>>,
+    pub(crate) event_capacity: usize,
+    pub(crate) max_buffered_events: usize,
+    pub(crate) max_gap_wait: Duration,
+    pub(crate) gap_policy: GapPolicy,
+    pub(crate) proxy: Option<Proxy>,
+    pub(crate) websocket: WebSocketConfig,
+    pub(crate) compress_outgoing: Option<u8>,
+    pub(crate) ignored_frames: Option<mpsc::Sender<IgnoredFrame>>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: STREAMING_STATE_PING_INTERVAL,
+            pong_timeout: PONG_TIMEOUT,
+            max_pong_timeouts: STREAMING_STATE_PONG_TIMEOUT_MAX_COUNT,
+            retry_ping_interval: TIMEOUT_STATE_SEND_PING_INTERVAL_START,
+            ping_backoff: None,
+            event_capacity: EVENT_STREAM_CAPACITY,
+            max_buffered_events: EVENT_BUFFER_MAX_SIZE,
+            max_gap_wait: EVENT_GAP_MAX_WAIT,
+            gap_policy: GapPolicy::default(),
+            proxy: None,
+            websocket: WebSocketConfig::default(),
+            compress_outgoing: None,
+            ignored_frames: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Create default config
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set interval of sending ping message, default is 30 seconds
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is not in 1 second to 10 minutes.
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        assert!(
+            PING_INTERVAL_RANGE.contains(&interval),
+            "ping interval must be in {PING_INTERVAL_RANGE:?}"
+        );
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Set max time to wait pong message, default is 6 seconds
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is not in 1 to 60 seconds.
+    pub fn pong_timeout(mut self, timeout: Duration) -> Self {
+        assert!(
+            PONG_TIMEOUT_RANGE.contains(&timeout),
+            "pong timeout must be in {PONG_TIMEOUT_RANGE:?}"
+        );
+        self.pong_timeout = timeout;
+        self
+    }
+
+    /// Set how many pong timeouts in a row make client enter timeout state, default is 2
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero.
+    pub fn max_pong_timeouts(mut self, count: usize) -> Self {
+        assert!(count > 0, "max pong timeouts must be positive");
+        self.max_pong_timeouts = count;
+        self
+    }
+
+    /// Set first interval of sending ping in timeout state, it doubles after each ping,
+    /// up to pong timeout. Default is 2 seconds
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is not in 1 to 60 seconds.
+    pub fn retry_ping_interval(mut self, interval: Duration) -> Self {
+        assert!(
+            PONG_TIMEOUT_RANGE.contains(&interval),
+            "retry ping interval must be in {PONG_TIMEOUT_RANGE:?}"
+        );
+        self.retry_ping_interval = interval;
+        self
+    }
+
+    /// Set strategy of ping interval in timeout state, retry ping interval is ignored when it's set
+    pub fn ping_backoff<B: BackoffStrategy + 'static>(mut self, backoff: B) -> Self {
+        self.ping_backoff.replace(Arc::new(backoff));
+        self
+    }
+
+    /// Set capacity of event stream channel, default is 32
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    /// Set max count of events buffered waiting for a missing event, default is 1024
+    pub fn max_buffered_events(mut self, count: usize) -> Self {
+        self.max_buffered_events = count.max(1);
+        self
+    }
+
+    /// Set max time to wait a missing event, default is 30 seconds
+    ///
+    /// # Panics
+    ///
+    /// Panics if `wait` is zero.
+    pub fn max_gap_wait(mut self, wait: Duration) -> Self {
+        assert!(!wait.is_zero(), "max gap wait must be positive");
+        self.max_gap_wait = wait;
+        self
+    }
+
+    /// Set what to do when a missing event is not received in time, default is [GapPolicy::Skip]
+    pub fn gap_policy(mut self, policy: GapPolicy) -> Self {
+        self.gap_policy = policy;
+        self
+    }
+
+    /// Set proxy to connect to gateway, default is connecting directly
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy.replace(proxy);
+        self
+    }
+
+    /// Set max size of a websocket message, `None` means no limit, default is 64 MiB
+    pub fn max_message_size(mut self, size: Option<usize>) -> Self {
+        self.websocket.max_message_size = size;
+        self
+    }
+
+    /// Set max payload size of a websocket frame, `None` means no limit, default is 16 MiB
+    pub fn max_frame_size(mut self, size: Option<usize>) -> Self {
+        self.websocket.max_frame_size = size;
+        self
+    }
+
+    /// Set max count of messages waiting to be written, `None` means no limit, this is the default
+    pub fn max_send_queue(mut self, size: Option<usize>) -> Self {
+        self.websocket.max_send_queue = size;
+        self
+    }
+
+    /// Set zlib level(0 to 10) to compress messages sent to gateway, `None` means no compress,
+    /// this is the default.
+    ///
+    /// Kaiheila gateway only documents compress of server->client messages, which is set by
+    /// [BotBuilder::compress](crate::BotBuilder::compress) or [GatewayURLInfo::compress],
+    /// only enable this if the gateway accepts compressed messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than 10.
+    pub fn compress_outgoing(mut self, level: Option<u8>) -> Self {
+        assert!(
+            level.is_none_or(|l| l <= 10),
+            "compress level must be in 0..=10"
+        );
+        self.compress_outgoing = level;
+        self
+    }
+
+    /// Send frames ignored by client to the channel, so new protocol features can be detected,
+    /// default is dropping them with a log.
+    ///
+    /// These includes non-data frames like ping, frames can not be parsed as message (like ones with
+    /// unknown `s` value), and messages not handled in current state. Frames are dropped if the
+    /// channel is full.
+    pub fn ignored_frames(mut self, tap: mpsc::Sender<IgnoredFrame>) -> Self {
+        self.ignored_frames.replace(tap);
+        self
+    }
+
+    /// Get ping interval
+    pub fn get_ping_interval(&self) -> Duration {
+        self.ping_interval
+    }
+
+    /// Get pong timeout
+    pub fn get_pong_timeout(&self) -> Duration {
+        self.pong_timeout
+    }
+
+    /// Get max pong timeouts in a row before entering timeout state
+    pub fn get_max_pong_timeouts(&self) -> usize {
+        self.max_pong_timeouts
+    }
+
+    /// Get first ping interval in timeout state
+    pub fn get_retry_ping_interval(&self) -> Duration {
+        self.retry_ping_interval
+    }
+
+    /// Get interval after the nth(0-based) ping in timeout state
+    pub fn get_retry_ping_delay(&self, attempt: usize) -> Duration {
+        match &self.ping_backoff {
+            Some(backoff) => backoff.delay(attempt),
+            None => ExponentialBackoff::new(
+                self.retry_ping_interval,
+                self.pong_timeout.max(self.retry_ping_interval),
+            )
+            .delay(attempt),
+        }
+    }
+
+    /// Get event stream channel capacity
+    pub fn get_event_capacity(&self) -> usize {
+        self.event_capacity
+    }
+
+    /// Get max count of buffered events
+    pub fn get_max_buffered_events(&self) -> usize {
+        self.max_buffered_events
+    }
+
+    /// Get max time to wait a missing event
+    pub fn get_max_gap_wait(&self) -> Duration {
+        self.max_gap_wait
+    }
+
+    /// Get gap policy
+    pub fn get_gap_policy(&self) -> GapPolicy {
+        self.gap_policy
+    }
+
+    /// Get proxy to connect to gateway
+    pub fn get_proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// Get max size of a websocket message
+    pub fn get_max_message_size(&self) -> Option<usize> {
+        self.websocket.max_message_size
+    }
+
+    /// Get max payload size of a websocket frame
+    pub fn get_max_frame_size(&self) -> Option<usize> {
+        self.websocket.max_frame_size
+    }
+
+    /// Get max count of messages waiting to be written
+    pub fn get_max_send_queue(&self) -> Option<usize> {
+        self.websocket.max_send_queue
+    }
+
+    /// Get zlib level to compress messages sent to gateway
+    pub fn get_compress_outgoing(&self) -> Option<u8> {
+        self.compress_outgoing
+    }
+
+    /// Get channel of ignored frames
+    pub fn get_ignored_frames(&self) -> Option<&mpsc::Sender<IgnoredFrame>> {
+        self.ignored_frames.as_ref()
+    }
+}
+
 /// Kaiheila websocket protocol client, it will follow the official state machine at:
 /// <https://developer.kaiheila.cn/doc/websocket#Gateway>
 #[derive(Debug)]
@@ -33,7 +358,11 @@ impl Client {
     pub fn new() -> Self {
         Self {
             inner: ClientInner {
-                state: ClientStateInit { resume: None },
+                state: ClientStateInit {
+                    resume: None,
+                    config: ClientConfig::default(),
+                    conn_state: Arc::new(StateNotifier::new()),
+                },
             },
         }
     }
@@ -42,13 +371,78 @@ impl Client {
     pub fn resume(args: GatewayResumeArguments) -> Self {
         Self {
             inner: ClientInner {
-                state: ClientStateInit { resume: Some(args) },
+                state: ClientStateInit {
+                    resume: Some(args),
+                    config: ClientConfig::default(),
+                    conn_state: Arc::new(StateNotifier::new()),
+                },
             },
         }
     }
 
+    /// Set config of the client
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.inner.state.config = config;
+        self
+    }
+
+    /// Watch state of the client, notified whenever it changes.
+    ///
+    /// State is [ConnectionState::Stopped] before running, and after the event stream stopped.
+    /// Can be called before [Client::run], or by [EventStream::state_watcher] after it.
+    pub fn state_watcher(&self) -> tokio::sync::watch::Receiver<ConnectionState> {
+        self.inner.state.conn_state.subscribe()
+    }
+
     /// start running the client in given gateway, returning a stream for kaiheila event
     pub async fn run(self, gateway: GatewayURLInfo) -> Result<EventStream, RunError> {
         self.inner.run(gateway).await
     }
+
+    /// Low-level escape hatch: connect to given gateway, returning the raw message stream/sink
+    /// without the event state machine.
+    ///
+    /// All messages are yielded as is, including hello, pong and reconnect, non-fatal errors
+    /// are skipped. Nothing is done automatically: user should check the hello message, send
+    /// pings, reorder events and reconnect by itself. Resume arguments set by
+    /// [Client::resume] are still used to build the gateway url, only connection related config
+    /// is used: proxy, size limits, outgoing compress and ignored frames.
+    pub async fn run_raw(
+        self,
+        gateway: GatewayURLInfo,
+    ) -> Result<FilteredMessageStreamSink, ConnectGatewayError> {
+        self.inner.run_raw(gateway).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_client_config_ranges() {
+        let config = ClientConfig::new()
+            .ping_interval(Duration::from_secs(60))
+            .pong_timeout(Duration::from_secs(20))
+            .max_pong_timeouts(3)
+            .retry_ping_interval(Duration::from_secs(5));
+
+        assert_eq!(config.get_ping_interval(), Duration::from_secs(60));
+        assert_eq!(config.get_pong_timeout(), Duration::from_secs(20));
+        assert_eq!(config.get_max_pong_timeouts(), 3);
+        assert_eq!(config.get_retry_ping_interval(), Duration::from_secs(5));
+        let delays: Vec<_> = (0..4)
+            .map(|n| config.get_retry_ping_delay(n).as_secs())
+            .collect();
+        assert_eq!(delays, [5, 10, 20, 20]);
+
+        let config = config.ping_backoff(crate::backoff::FixedBackoff(Duration::from_secs(1)));
+        assert_eq!(config.get_retry_ping_delay(3), Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "pong timeout must be in")]
+    fn test_client_config_invalid_pong_timeout() {
+        let _ = ClientConfig::new().pong_timeout(Duration::from_millis(100));
+    }
 }
@@ -1,10 +1,16 @@
 //! Kaiheila websocket events in [Event](super::message::Message::Event) message type.
 
-mod types;
+mod system;
 
-pub use types::*;
+pub use system::*;
 
-use serde::{Deserialize, Serialize};
+use enum_as_inner::EnumAsInner;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    card::CardMessage,
+    models::{Attachment, ChannelType, Embed, MessageType, Quote, Timestamp, User},
+};
 
 /// Event data
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -19,7 +25,7 @@ pub struct EventData {
 
 impl PartialOrd for EventData {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.sn.partial_cmp(&other.sn)
+        Some(self.cmp(other))
     }
 }
 
@@ -29,13 +35,27 @@ impl Ord for EventData {
     }
 }
 
-/// Event type
+/// Event type, dispatched by message type and channel type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, EnumAsInner)]
+#[serde(untagged)]
+pub enum Event {
+    /// message sent in guild channel
+    ChannelMessage(EventBody<MessageExtra>),
+    /// message sent in private chat
+    PrivateMessage(EventBody<MessageExtra>),
+    /// system event, message type is 255
+    SystemEvent(EventBody<SystemEvent>),
+    /// event can't be recognized, the extra field is kept as raw json
+    Unknown(EventBody<serde_json::Value>),
+}
+
+/// Common structure of all events, the extra field differs by event kind
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Event {
+pub struct EventBody<E> {
     /// 消息通道类型, GROUP 为组播消息, PERSON 为单播消息, BROADCAST 为广播消息
-    pub channel_type: String,
+    pub channel_type: ChannelType,
     /// 1:文字消息, 2:图片消息，3:视频消息，4:文件消息， 8:音频消息，9:KMarkdown，10:card 消息，255:系统消息, 其它的暂未开放
-    pub r#type: i64,
+    pub r#type: MessageType,
     /// 发送目的, 频道消息类时, 代表的是频道 channel_id，如果 channel_type 为 GROUP 组播且 type 为 255 系统消息时，则代表服务器 guild_id
     pub target_id: String,
     /// 发送者 id, 1 代表系统
@@ -45,36 +65,42 @@ pub struct Event {
     /// 消息的 id
     pub msg_id: String,
     /// 消息发送时间的毫秒时间戳
-    pub msg_timestamp: i64,
+    pub msg_timestamp: Timestamp,
     /// 随机串，与用户消息发送 api 中传的 nonce 保持一致
     pub nonce: String,
     /// 不同的消息类型，结构不一致
-    pub extra: EventExtra,
-}
-
-/// Extra info for an event
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum EventExtra {
-    /// type = 1, text message
-    TextMessage(TextMessageExtra),
+    pub extra: E,
 }
 
-impl Default for EventExtra {
-    fn default() -> Self {
-        Self::TextMessage(TextMessageExtra::default())
+impl<E> EventBody<E> {
+    /// Replace extra field, keep others unchanged
+    pub fn with_extra<T>(self, extra: T) -> EventBody<T> {
+        EventBody {
+            channel_type: self.channel_type,
+            r#type: self.r#type,
+            target_id: self.target_id,
+            author_id: self.author_id,
+            content: self.content,
+            msg_id: self.msg_id,
+            msg_timestamp: self.msg_timestamp,
+            nonce: self.nonce,
+            extra,
+        }
     }
 }
 
-/// Extra info for text message
+/// Extra info for channel and private message
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct TextMessageExtra {
-    /// const 1
-    pub r#type: i64,
-    /// 服务器 id
+#[serde(default)]
+pub struct MessageExtra {
+    /// same as event type
+    pub r#type: MessageType,
+    /// 服务器 id, 仅频道消息
     pub guild_id: String,
-    /// 频道名
+    /// 频道名, 仅频道消息
     pub channel_name: String,
+    /// 私信会话 code, 仅私信消息
+    pub code: String,
     /// 提及到的用户 id 的列表
     pub mention: Vec<String>,
     /// 是否 mention 所有用户
@@ -86,5 +112,434 @@ pub struct TextMessageExtra {
     /// 发消息用户信息
     pub author: User,
     /// 引用消息
-    pub quote: Option<Quote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<Box<Quote>>,
+    /// 图片, 视频, 文件, 音频消息的附件
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<Attachment>,
+    /// 链接预览等嵌入内容
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<Embed>,
+}
+
+macro_rules! with_body {
+    ($event:expr, $body:ident => $e:expr) => {
+        match $event {
+            Event::ChannelMessage($body) => $e,
+            Event::PrivateMessage($body) => $e,
+            Event::SystemEvent($body) => $e,
+            Event::Unknown($body) => $e,
+        }
+    };
+}
+
+impl Event {
+    /// Dispatch a raw event to typed event by message type and channel type.
+    ///
+    /// If the extra field can't be parsed as expected, a [Event::Unknown] will be returned.
+    pub fn from_raw(raw: EventBody<serde_json::Value>) -> Self {
+        match (raw.r#type, &raw.channel_type) {
+            (MessageType::System, _) => match SystemEvent::deserialize(&raw.extra) {
+                Ok(extra) => Self::SystemEvent(raw.with_extra(extra)),
+                Err(err) => Self::unknown(raw, err),
+            },
+            (_, ChannelType::Group) => match MessageExtra::deserialize(&raw.extra) {
+                Ok(extra) => Self::ChannelMessage(raw.with_extra(extra)),
+                Err(err) => Self::unknown(raw, err),
+            },
+            (_, ChannelType::Person) => match MessageExtra::deserialize(&raw.extra) {
+                Ok(extra) => Self::PrivateMessage(raw.with_extra(extra)),
+                Err(err) => Self::unknown(raw, err),
+            },
+            _ => Self::Unknown(raw),
+        }
+    }
+
+    fn unknown(raw: EventBody<serde_json::Value>, err: serde_json::Error) -> Self {
+        debug!("Parse event extra failed, treat as unknown event: {}", err);
+        Self::Unknown(raw)
+    }
+
+    /// channel type
+    pub fn channel_type(&self) -> &ChannelType {
+        with_body!(self, b => &b.channel_type)
+    }
+
+    /// message type
+    pub fn message_type(&self) -> MessageType {
+        with_body!(self, b => b.r#type)
+    }
+
+    /// target id, channel id for channel message, user id for private message, guild id for system event
+    pub fn target_id(&self) -> &str {
+        with_body!(self, b => &b.target_id)
+    }
+
+    /// author id, "1" for system
+    pub fn author_id(&self) -> &str {
+        with_body!(self, b => &b.author_id)
+    }
+
+    /// content
+    pub fn content(&self) -> &str {
+        with_body!(self, b => &b.content)
+    }
+
+    /// message id
+    pub fn msg_id(&self) -> &str {
+        with_body!(self, b => &b.msg_id)
+    }
+
+    /// message timestamp
+    pub fn msg_timestamp(&self) -> &Timestamp {
+        with_body!(self, b => &b.msg_timestamp)
+    }
+
+    /// nonce
+    pub fn nonce(&self) -> &str {
+        with_body!(self, b => &b.nonce)
+    }
+
+    /// body of channel or private message
+    pub fn as_message(&self) -> Option<&EventBody<MessageExtra>> {
+        match self {
+            Self::ChannelMessage(b) | Self::PrivateMessage(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// extra of system event
+    pub fn as_system(&self) -> Option<&SystemEvent> {
+        self.as_system_event().map(|b| &b.extra)
+    }
+
+    /// guild id of channel message or guild system event, None for private message/event
+    pub fn guild_id(&self) -> Option<&str> {
+        match self {
+            Self::ChannelMessage(b) => Some(&b.extra.guild_id),
+            Self::SystemEvent(b) if b.channel_type == ChannelType::Group => Some(&b.target_id),
+            _ => None,
+        }
+    }
+
+    /// channel id of channel message or channel related system event, None for others
+    pub fn channel_id(&self) -> Option<&str> {
+        match self {
+            Self::ChannelMessage(b) => Some(&b.target_id),
+            Self::SystemEvent(b) => match b.extra {
+                SystemEvent::AddedReaction(ref e) | SystemEvent::DeletedReaction(ref e) => {
+                    Some(&e.channel_id)
+                }
+                SystemEvent::UpdatedMessage(ref e) => Some(&e.channel_id),
+                SystemEvent::DeletedMessage(ref e) => Some(&e.channel_id),
+                SystemEvent::PinnedMessage(ref e) | SystemEvent::UnpinnedMessage(ref e) => {
+                    Some(&e.channel_id)
+                }
+                SystemEvent::JoinedChannel(ref e) => Some(&e.channel_id),
+                SystemEvent::ExitedChannel(ref e) => Some(&e.channel_id),
+                SystemEvent::AddedChannel(ref c) | SystemEvent::UpdatedChannel(ref c) => {
+                    Some(&c.id)
+                }
+                SystemEvent::DeletedChannel(ref e) => Some(&e.id),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// author info of channel or private message
+    pub fn author(&self) -> Option<&User> {
+        self.as_message().map(|b| &b.extra.author)
+    }
+
+    /// user id list mentioned by the message, also works for updated message event
+    pub fn mentioned_users(&self) -> &[String] {
+        match self {
+            Self::ChannelMessage(b) | Self::PrivateMessage(b) => &b.extra.mention,
+            Self::SystemEvent(b) => match b.extra {
+                SystemEvent::UpdatedMessage(ref u) => &u.mention,
+                _ => &[],
+            },
+            Self::Unknown(_) => &[],
+        }
+    }
+
+    /// role id list mentioned by the message, also works for updated message event
+    pub fn mentioned_roles(&self) -> &[u64] {
+        match self {
+            Self::ChannelMessage(b) | Self::PrivateMessage(b) => &b.extra.mention_roles,
+            Self::SystemEvent(b) => match b.extra {
+                SystemEvent::UpdatedMessage(ref u) => &u.mention_roles,
+                _ => &[],
+            },
+            Self::Unknown(_) => &[],
+        }
+    }
+
+    /// check if the message mentions all users
+    pub fn is_mention_all(&self) -> bool {
+        self.as_message().is_some_and(|b| b.extra.mention_all)
+    }
+
+    /// check if the message mentions online users
+    pub fn is_mention_here(&self) -> bool {
+        self.as_message().is_some_and(|b| b.extra.mention_here)
+    }
+
+    /// check if the given user is mentioned by the message directly
+    pub fn is_mentioned(&self, user_id: &str) -> bool {
+        self.mentioned_users().iter().any(|id| id == user_id)
+    }
+
+    /// check if the bot itself is mentioned, `me` is the bot user id
+    pub fn is_mention_me(&self, me: &str) -> bool {
+        self.is_mentioned(me)
+    }
+
+    /// quoted message
+    pub fn quote(&self) -> Option<&Quote> {
+        self.as_message()?.extra.quote.as_deref()
+    }
+
+    /// attachment of image/video/file/audio message
+    pub fn attachments(&self) -> Option<&Attachment> {
+        self.as_message()?.extra.attachments.as_ref()
+    }
+
+    /// embed contents of the message
+    pub fn embeds(&self) -> &[Embed] {
+        self.as_message().map_or(&[], |b| &b.extra.embeds)
+    }
+
+    /// cards of card message, None if it's not a card message or content is not valid
+    pub fn cards(&self) -> Option<CardMessage> {
+        if self.message_type() != MessageType::Card {
+            return None;
+        }
+        self.content().parse().ok()
+    }
+
+    /// Convert to raw json, the escape hatch for fields not covered by typed structure
+    pub fn to_raw(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        EventBody::<serde_json::Value>::deserialize(deserializer).map(Self::from_raw)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_event_text_message_with_quote() {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "GROUP",
+            "type": 1,
+            "target_id": "some-channel-id",
+            "author_id": "some-user-id",
+            "content": "reply",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": 1,
+                "guild_id": "some-guild-id",
+                "channel_name": "general",
+                "mention": [],
+                "mention_all": false,
+                "mention_roles": [],
+                "mention_here": false,
+                "author": {
+                    "id": "some-user-id",
+                    "username": "someone",
+                },
+                "quote": {
+                    "id": "quoted-msg-id",
+                    "rong_id": "quoted-rong-id",
+                    "type": 1,
+                    "content": "origin",
+                    "create_at": 1612703779000_i64,
+                    "author": {
+                        "id": "other-user-id",
+                        "username": "other",
+                    },
+                },
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(event.guild_id(), Some("some-guild-id"));
+        assert_eq!(event.quote().unwrap().id, "quoted-msg-id");
+
+        let Event::ChannelMessage(body) = event else {
+            panic!("parsed event is not channel message")
+        };
+
+        let quote = body.extra.quote.unwrap();
+        assert_eq!(quote.id, "quoted-msg-id");
+        assert_eq!(quote.content, "origin");
+        assert_eq!(quote.author.username, "other");
+    }
+
+    #[test]
+    fn test_event_image_message_attachment() {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "GROUP",
+            "type": 2,
+            "target_id": "some-channel-id",
+            "author_id": "some-user-id",
+            "content": "https://img.kaiheila.cn/assets/some.png",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": 2,
+                "guild_id": "some-guild-id",
+                "channel_name": "general",
+                "mention": [],
+                "mention_all": false,
+                "mention_roles": [],
+                "mention_here": false,
+                "author": {},
+                "attachments": {
+                    "type": "image",
+                    "url": "https://img.kaiheila.cn/assets/some.png",
+                    "name": "some.png",
+                    "size": 10240,
+                    "width": 640,
+                    "height": 480,
+                    "file_type": "image/png",
+                },
+            },
+        }))
+        .unwrap();
+
+        let Event::ChannelMessage(body) = event else {
+            panic!("parsed event is not channel message")
+        };
+
+        let attachment = body.extra.attachments.unwrap();
+        assert_eq!(attachment.r#type, "image");
+        assert_eq!(attachment.size, 10240);
+        assert_eq!(attachment.width, Some(640));
+        assert!(attachment.duration.is_none());
+    }
+
+    #[test]
+    fn test_event_private_message() {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "PERSON",
+            "type": 9,
+            "target_id": "bot-user-id",
+            "author_id": "some-user-id",
+            "content": "**hello**",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": 9,
+                "code": "some-chat-code",
+                "author": {
+                    "id": "some-user-id",
+                },
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(event.guild_id(), None);
+
+        let body = event.as_private_message().unwrap();
+        assert_eq!(body.extra.code, "some-chat-code");
+        assert_eq!(event.message_type(), MessageType::KMarkdown);
+        assert_eq!(event.content(), "**hello**");
+        assert!(event.cards().is_none());
+    }
+
+    #[test]
+    fn test_event_card_message() {
+        let content = json!([{
+            "type": "card",
+            "modules": [{ "type": "header", "text": { "type": "plain-text", "content": "hi" } }],
+        }]);
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "PERSON",
+            "type": 10,
+            "target_id": "bot-user-id",
+            "author_id": "some-user-id",
+            "content": content.to_string(),
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": 10,
+                "code": "some-chat-code",
+                "author": {
+                    "id": "some-user-id",
+                },
+            },
+        }))
+        .unwrap();
+
+        let cards = event.cards().unwrap();
+        assert_eq!(cards.0[0].modules, vec![crate::card::Module::header("hi")]);
+    }
+
+    #[test]
+    fn test_event_mentions() {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "GROUP",
+            "type": 9,
+            "target_id": "some-channel-id",
+            "author_id": "some-user-id",
+            "content": "(met)bot-user-id(met) hi",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": 9,
+                "guild_id": "some-guild-id",
+                "mention": ["bot-user-id"],
+                "mention_roles": [123],
+                "mention_all": false,
+                "mention_here": true,
+            },
+        }))
+        .unwrap();
+
+        assert!(event.is_mention_me("bot-user-id"));
+        assert!(!event.is_mentioned("other-user-id"));
+        assert_eq!(event.mentioned_roles(), &[123]);
+        assert!(!event.is_mention_all());
+        assert!(event.is_mention_here());
+        assert!(event.attachments().is_none());
+    }
+
+    #[test]
+    fn test_event_unknown_keeps_raw() {
+        let raw = json!({
+            "channel_type": "BROADCAST",
+            "type": 1,
+            "target_id": "some-target-id",
+            "author_id": "some-user-id",
+            "content": "hello",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "some_new_field": 1,
+            },
+        });
+
+        let event: Event = serde_json::from_value(raw.clone()).unwrap();
+
+        assert_eq!(event.as_unknown().unwrap().extra["some_new_field"], 1);
+        assert_eq!(event.to_raw(), raw);
+    }
 }
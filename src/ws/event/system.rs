@@ -0,0 +1,592 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Channel, Emoji, Guild, Role, Timestamp, User};
+
+/// System event, extra info of event with type 255.
+///
+/// see <https://developer.kaiheila.cn/doc/event/channel> and other pages in event section for details.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "body", rename_all = "snake_case")]
+pub enum SystemEvent {
+    /// user add a reaction to a channel message
+    AddedReaction(ReactionBody),
+    /// user delete a reaction from a channel message
+    DeletedReaction(ReactionBody),
+    /// user add a reaction to a private message
+    PrivateAddedReaction(PrivateReactionBody),
+    /// user delete a reaction from a private message
+    PrivateDeletedReaction(PrivateReactionBody),
+    /// channel message is updated
+    UpdatedMessage(UpdatedMessageBody),
+    /// channel message is deleted
+    DeletedMessage(DeletedMessageBody),
+    /// channel message is pinned
+    PinnedMessage(PinnedMessageBody),
+    /// channel message is unpinned
+    UnpinnedMessage(PinnedMessageBody),
+    /// private message is updated
+    UpdatedPrivateMessage(UpdatedPrivateMessageBody),
+    /// private message is deleted
+    DeletedPrivateMessage(DeletedPrivateMessageBody),
+    /// user joined guild
+    JoinedGuild(JoinedGuildBody),
+    /// user exited guild
+    ExitedGuild(ExitedGuildBody),
+    /// guild member info is updated
+    UpdatedGuildMember(UpdatedGuildMemberBody),
+    /// guild member is online
+    GuildMemberOnline(GuildMemberPresenceBody),
+    /// guild member is offline
+    GuildMemberOffline(GuildMemberPresenceBody),
+    /// guild role is added
+    AddedRole(Role),
+    /// guild role is deleted
+    DeletedRole(Role),
+    /// guild role is updated
+    UpdatedRole(Role),
+    /// guild info is updated
+    UpdatedGuild(Guild),
+    /// guild is deleted
+    DeletedGuild(Guild),
+    /// users are added to guild block list
+    AddedBlockList(BlockListBody),
+    /// users are removed from guild block list
+    DeletedBlockList(BlockListBody),
+    /// channel is added
+    AddedChannel(Channel),
+    /// channel is updated
+    UpdatedChannel(Channel),
+    /// channel is deleted
+    DeletedChannel(DeletedChannelBody),
+    /// user joined a voice channel
+    JoinedChannel(JoinedChannelBody),
+    /// user exited a voice channel
+    ExitedChannel(ExitedChannelBody),
+    /// user profile is updated
+    UserUpdated(UserUpdatedBody),
+    /// bot joined a guild
+    SelfJoinedGuild(SelfGuildBody),
+    /// bot exited a guild
+    SelfExitedGuild(SelfGuildBody),
+    /// user clicked a button in card message
+    MessageBtnClick(ButtonClickBody),
+}
+
+/// Body of channel reaction events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReactionBody {
+    /// message id
+    pub msg_id: String,
+    /// user who add/delete the reaction
+    pub user_id: String,
+    /// channel id the message belongs to
+    pub channel_id: String,
+    /// reaction emoji
+    pub emoji: Emoji,
+}
+
+/// Body of private reaction events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateReactionBody {
+    /// message id
+    pub msg_id: String,
+    /// user who add/delete the reaction
+    pub user_id: String,
+    /// private chat code
+    pub chat_code: String,
+    /// reaction emoji
+    pub emoji: Emoji,
+}
+
+/// Body of updated channel message event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdatedMessageBody {
+    /// message id
+    pub msg_id: String,
+    /// channel id the message belongs to
+    pub channel_id: String,
+    /// new message content
+    pub content: String,
+    /// mentioned user id list
+    pub mention: Vec<String>,
+    /// is mention all users
+    pub mention_all: bool,
+    /// is mention online users
+    pub mention_here: bool,
+    /// mentioned role id list
+    pub mention_roles: Vec<u64>,
+    /// update time in milliseconds timestamp
+    pub updated_at: Timestamp,
+}
+
+/// Body of deleted channel message event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedMessageBody {
+    /// message id
+    pub msg_id: String,
+    /// channel id the message belongs to
+    pub channel_id: String,
+}
+
+/// Body of pinned/unpinned channel message events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedMessageBody {
+    /// message id
+    pub msg_id: String,
+    /// channel id the message belongs to
+    pub channel_id: String,
+    /// user who pin/unpin the message
+    pub operator_id: String,
+}
+
+/// Body of updated private message event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdatedPrivateMessageBody {
+    /// message id
+    pub msg_id: String,
+    /// message author id
+    pub author_id: String,
+    /// message receiver id
+    pub target_id: String,
+    /// new message content
+    pub content: String,
+    /// private chat code
+    pub chat_code: String,
+    /// update time in milliseconds timestamp
+    pub updated_at: Timestamp,
+}
+
+/// Body of deleted private message event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedPrivateMessageBody {
+    /// message id
+    pub msg_id: String,
+    /// message author id
+    pub author_id: String,
+    /// message receiver id
+    pub target_id: String,
+    /// private chat code
+    pub chat_code: String,
+    /// delete time in milliseconds timestamp
+    pub deleted_at: Timestamp,
+}
+
+/// Body of joined guild event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinedGuildBody {
+    /// user who joined the guild
+    pub user_id: String,
+    /// join time in milliseconds timestamp
+    pub joined_at: Timestamp,
+}
+
+/// Body of exited guild event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitedGuildBody {
+    /// user who exited the guild
+    pub user_id: String,
+    /// exit time in milliseconds timestamp
+    pub exited_at: Timestamp,
+}
+
+/// Body of updated guild member event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdatedGuildMemberBody {
+    /// updated user id
+    pub user_id: String,
+    /// new nickname in guild
+    pub nickname: String,
+}
+
+/// Body of guild member online/offline events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildMemberPresenceBody {
+    /// user id
+    pub user_id: String,
+    /// event time in milliseconds timestamp
+    pub event_time: Timestamp,
+    /// guilds which the user and bot both joined
+    pub guilds: Vec<String>,
+}
+
+/// Body of guild block list add/remove events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockListBody {
+    /// user who changed the block list
+    pub operator_id: String,
+    /// block reason, only exists in add event
+    #[serde(default)]
+    pub remark: String,
+    /// affected user id list
+    #[serde(rename = "user_id")]
+    pub user_ids: Vec<String>,
+}
+
+/// Body of deleted channel event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletedChannelBody {
+    /// deleted channel id
+    pub id: String,
+    /// delete time in milliseconds timestamp
+    pub deleted_at: Timestamp,
+}
+
+/// Body of joined voice channel event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinedChannelBody {
+    /// user who joined the channel
+    pub user_id: String,
+    /// voice channel id
+    pub channel_id: String,
+    /// join time in milliseconds timestamp
+    pub joined_at: Timestamp,
+}
+
+/// Body of exited voice channel event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitedChannelBody {
+    /// user who exited the channel
+    pub user_id: String,
+    /// voice channel id
+    pub channel_id: String,
+    /// exit time in milliseconds timestamp
+    pub exited_at: Timestamp,
+}
+
+/// Body of user updated event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserUpdatedBody {
+    /// updated user id
+    pub user_id: String,
+    /// new username
+    pub username: String,
+    /// new avatar url
+    pub avatar: String,
+}
+
+/// Body of bot self joined/exited guild events
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfGuildBody {
+    /// guild id
+    pub guild_id: String,
+}
+
+/// Body of card message button click event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonClickBody {
+    /// value of the clicked button
+    pub value: String,
+    /// message id of the card message
+    pub msg_id: String,
+    /// user who clicked the button
+    pub user_id: String,
+    /// channel id, or user id if card is in private chat
+    pub target_id: String,
+    /// info of user who clicked the button
+    pub user_info: User,
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::super::Event;
+    use super::*;
+    use crate::models::Timestamp;
+
+    fn system_event(extra: serde_json::Value) -> SystemEvent {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "GROUP",
+            "type": 255,
+            "target_id": "some-guild-id",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": extra,
+        }))
+        .unwrap();
+
+        match event {
+            Event::SystemEvent(body) => body.extra,
+            event => panic!("parsed event is not system event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn test_system_event_added_reaction() {
+        let event = system_event(json!({
+            "type": "added_reaction",
+            "body": {
+                "channel_id": "some-channel-id",
+                "emoji": {
+                    "id": "😘",
+                    "name": "😘",
+                },
+                "user_id": "some-user-id",
+                "msg_id": "some-msg-id",
+            },
+        }));
+
+        if let SystemEvent::AddedReaction(body) = event {
+            assert_eq!(body.channel_id, "some-channel-id");
+            assert_eq!(body.emoji.name, "😘");
+            assert_eq!(body.user_id, "some-user-id");
+            assert_eq!(body.msg_id, "some-msg-id");
+        } else {
+            panic!("parsed event is not added reaction")
+        }
+    }
+
+    #[test]
+    fn test_system_event_private_deleted_reaction() {
+        let event = system_event(json!({
+            "type": "private_deleted_reaction",
+            "body": {
+                "emoji": {
+                    "id": "[#128561;]",
+                    "name": "[#128561;]",
+                },
+                "user_id": "some-user-id",
+                "chat_code": "some-chat-code",
+                "msg_id": "some-msg-id",
+            },
+        }));
+
+        if let SystemEvent::PrivateDeletedReaction(body) = event {
+            assert_eq!(body.chat_code, "some-chat-code");
+            assert_eq!(body.emoji.id, "[#128561;]");
+        } else {
+            panic!("parsed event is not private deleted reaction")
+        }
+    }
+
+    #[test]
+    fn test_system_event_updated_message() {
+        let event = system_event(json!({
+            "type": "updated_message",
+            "body": {
+                "channel_id": "some-channel-id",
+                "content": "new content",
+                "mention": ["some-user-id"],
+                "mention_all": false,
+                "mention_here": false,
+                "mention_roles": [],
+                "updated_at": 1612703779612_i64,
+                "msg_id": "some-msg-id",
+            },
+        }));
+
+        if let SystemEvent::UpdatedMessage(body) = event {
+            assert_eq!(body.content, "new content");
+            assert_eq!(body.mention, vec!["some-user-id"]);
+            assert_eq!(body.updated_at, Timestamp::from_millis(1612703779612));
+        } else {
+            panic!("parsed event is not updated message")
+        }
+    }
+
+    #[test]
+    fn test_system_event_unpinned_message() {
+        let event = system_event(json!({
+            "type": "unpinned_message",
+            "body": {
+                "channel_id": "some-channel-id",
+                "operator_id": "some-user-id",
+                "msg_id": "some-msg-id",
+            },
+        }));
+
+        if let SystemEvent::UnpinnedMessage(body) = event {
+            assert_eq!(body.operator_id, "some-user-id");
+        } else {
+            panic!("parsed event is not unpinned message")
+        }
+    }
+
+    #[test]
+    fn test_system_event_guild_member_online() {
+        let event = system_event(json!({
+            "type": "guild_member_online",
+            "body": {
+                "user_id": "some-user-id",
+                "event_time": 1612703779612_i64,
+                "guilds": ["some-guild-id"],
+            },
+        }));
+
+        if let SystemEvent::GuildMemberOnline(body) = event {
+            assert_eq!(body.user_id, "some-user-id");
+            assert_eq!(body.guilds, vec!["some-guild-id"]);
+        } else {
+            panic!("parsed event is not guild member online")
+        }
+    }
+
+    #[test]
+    fn test_system_event_updated_role() {
+        let event = system_event(json!({
+            "type": "updated_role",
+            "body": {
+                "role_id": 11111,
+                "name": "Admin",
+                "color": 33023,
+                "position": 5,
+                "hoist": 1,
+                "mentionable": 0,
+                "permissions": 142924296,
+            },
+        }));
+
+        if let SystemEvent::UpdatedRole(role) = event {
+            assert_eq!(role.role_id, 11111);
+            assert_eq!(role.name, "Admin");
+            assert_eq!(role.permissions, 142924296);
+        } else {
+            panic!("parsed event is not updated role")
+        }
+    }
+
+    #[test]
+    fn test_system_event_added_block_list() {
+        let event = system_event(json!({
+            "type": "added_block_list",
+            "body": {
+                "operator_id": "some-operator-id",
+                "remark": "spam",
+                "user_id": ["user-a", "user-b"],
+            },
+        }));
+
+        if let SystemEvent::AddedBlockList(body) = event {
+            assert_eq!(body.operator_id, "some-operator-id");
+            assert_eq!(body.remark, "spam");
+            assert_eq!(body.user_ids, vec!["user-a", "user-b"]);
+        } else {
+            panic!("parsed event is not added block list")
+        }
+    }
+
+    #[test]
+    fn test_system_event_added_channel() {
+        let event = system_event(json!({
+            "type": "added_channel",
+            "body": {
+                "id": "some-channel-id",
+                "name": "new channel",
+                "user_id": "some-user-id",
+                "guild_id": "some-guild-id",
+                "is_category": 0,
+                "parent_id": "some-category-id",
+                "level": 100,
+                "slow_mode": 0,
+                "topic": "",
+                "type": 1,
+                "permission_overwrites": [
+                    {
+                        "role_id": 0,
+                        "allow": 0,
+                        "deny": 0,
+                    },
+                ],
+                "permission_users": [],
+                "permission_sync": 1,
+            },
+        }));
+
+        if let SystemEvent::AddedChannel(channel) = event {
+            assert_eq!(channel.id, "some-channel-id");
+            assert!(!channel.is_category);
+            assert_eq!(channel.permission_overwrites.len(), 1);
+        } else {
+            panic!("parsed event is not added channel")
+        }
+    }
+
+    #[test]
+    fn test_system_event_joined_channel() {
+        let event = system_event(json!({
+            "type": "joined_channel",
+            "body": {
+                "user_id": "some-user-id",
+                "channel_id": "some-channel-id",
+                "joined_at": 1612703779612_i64,
+            },
+        }));
+
+        if let SystemEvent::JoinedChannel(body) = event {
+            assert_eq!(body.channel_id, "some-channel-id");
+            assert_eq!(body.joined_at, Timestamp::from_millis(1612703779612));
+        } else {
+            panic!("parsed event is not joined channel")
+        }
+    }
+
+    #[test]
+    fn test_system_event_self_joined_guild() {
+        let event = system_event(json!({
+            "type": "self_joined_guild",
+            "body": {
+                "guild_id": "some-guild-id",
+            },
+        }));
+
+        if let SystemEvent::SelfJoinedGuild(body) = event {
+            assert_eq!(body.guild_id, "some-guild-id");
+        } else {
+            panic!("parsed event is not self joined guild")
+        }
+    }
+
+    #[test]
+    fn test_system_event_message_btn_click() {
+        let event = system_event(json!({
+            "type": "message_btn_click",
+            "body": {
+                "value": "vote:yes",
+                "msg_id": "some-msg-id",
+                "user_id": "some-user-id",
+                "target_id": "some-channel-id",
+                "user_info": {
+                    "id": "some-user-id",
+                    "username": "someone",
+                    "identify_num": "1234",
+                    "online": true,
+                    "avatar": "https://img.kaiheila.cn/avatars/some.jpg",
+                    "bot": false,
+                },
+            },
+        }));
+
+        if let SystemEvent::MessageBtnClick(body) = event {
+            assert_eq!(body.value, "vote:yes");
+            assert_eq!(body.target_id, "some-channel-id");
+            assert_eq!(body.user_info.identify_num, "1234");
+            assert!(body.user_info.online);
+        } else {
+            panic!("parsed event is not message button click")
+        }
+    }
+
+    #[test]
+    fn test_system_event_unknown_fallback() {
+        let event: Event = serde_json::from_value(json!({
+            "channel_type": "GROUP",
+            "type": 255,
+            "target_id": "some-guild-id",
+            "author_id": "1",
+            "content": "[系统消息]",
+            "msg_id": "some-msg-id",
+            "msg_timestamp": 1612703779612_i64,
+            "nonce": "",
+            "extra": {
+                "type": "some_new_event",
+                "body": {},
+            },
+        }))
+        .unwrap();
+
+        assert!(matches!(event, Event::Unknown(_)));
+    }
+}
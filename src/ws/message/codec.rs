@@ -0,0 +1,134 @@
+//! Serde implement of [Message] in the wire format, type in the `s` field.
+
+use std::fmt;
+
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::value::RawValue;
+
+use super::{Hello, Message, OnlyData, Reconnect, ResumeACK, SN};
+use crate::ws::{event::EventData, Event};
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("s", &self.type_number())?;
+        match self {
+            Self::Event(data) => {
+                map.serialize_entry("d", &data.event)?;
+                map.serialize_entry("sn", &data.sn)?;
+            }
+            Self::Hello(data) => map.serialize_entry("d", &data.data)?,
+            Self::Ping(sn) | Self::Resume(sn) => map.serialize_entry("sn", &sn.sn)?,
+            Self::Pong => {}
+            Self::Reconnect(data) => map.serialize_entry("d", &data.data)?,
+            Self::ResumeACK(data) => map.serialize_entry("d", &data.data)?,
+        }
+        map.end()
+    }
+}
+
+/// Typed `d` field of a message
+enum Body {
+    Event(Box<Event>),
+    Hello(Hello),
+    Reconnect(Reconnect),
+    ResumeACK(ResumeACK),
+    None,
+}
+
+/// Deserialize `d` field by message type
+struct BodySeed(i64);
+
+impl<'de> DeserializeSeed<'de> for BodySeed {
+    type Value = Body;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Ok(match self.0 {
+            0 => Body::Event(Deserialize::deserialize(deserializer)?),
+            1 => Body::Hello(Deserialize::deserialize(deserializer)?),
+            5 => Body::Reconnect(Deserialize::deserialize(deserializer)?),
+            6 => Body::ResumeACK(Deserialize::deserialize(deserializer)?),
+            _ => {
+                IgnoredAny::deserialize(deserializer)?;
+                Body::None
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    S,
+    Sn,
+    D,
+    #[serde(other)]
+    Other,
+}
+
+struct MessageVisitor;
+
+impl<'de> Visitor<'de> for MessageVisitor {
+    type Value = Message;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a kaiheila websocket message")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut s = None;
+        let mut sn = None;
+        let mut body = None;
+        // `d` before `s` can not be typed yet, keep a raw copy, rare since gateway sends `s` first
+        let mut raw: Option<Box<RawValue>> = None;
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::S => s = Some(map.next_value::<i64>()?),
+                Field::Sn => sn = Some(map.next_value::<u64>()?),
+                Field::D => match s {
+                    Some(s) => body = Some(map.next_value_seed(BodySeed(s))?),
+                    None => raw = Some(map.next_value()?),
+                },
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let s = s.ok_or_else(|| de::Error::missing_field("s"))?;
+        if let Some(raw) = raw {
+            let mut deserializer = serde_json::Deserializer::from_str(raw.get());
+            body = Some(
+                BodySeed(s)
+                    .deserialize(&mut deserializer)
+                    .map_err(de::Error::custom)?,
+            );
+        }
+
+        let sn = || sn.ok_or_else(|| de::Error::missing_field("sn"));
+        let body = body.ok_or_else(|| de::Error::missing_field("d"));
+
+        Ok(match (s, body) {
+            (0, Ok(Body::Event(event))) => Message::Event(EventData { sn: sn()?, event }),
+            (1, Ok(Body::Hello(data))) => Message::Hello(OnlyData { data }),
+            (2, _) => Message::Ping(SN { sn: sn()? }),
+            (3, _) => Message::Pong,
+            (4, _) => Message::Resume(SN { sn: sn()? }),
+            (5, Ok(Body::Reconnect(data))) => Message::Reconnect(OnlyData { data }),
+            (6, Ok(Body::ResumeACK(data))) => Message::ResumeACK(OnlyData { data }),
+            (0 | 1 | 5 | 6, Err(err)) => return Err(err),
+            (s, _) => return Err(de::Error::custom(format_args!("unknown message type {s}"))),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MessageVisitor)
+    }
+}
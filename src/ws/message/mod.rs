@@ -1,15 +1,21 @@
 //! Kaiheila websocket message types.
 
+mod codec;
 mod stream;
 mod types;
 
-pub use stream::{MessageStreamSink, MessageStreamSinkError};
+pub(crate) use stream::{describe_close, tap_ignored};
+pub use stream::{
+    FilteredMessageStreamSink, IgnoredFrame, MessageStreamSink, MessageStreamSinkError,
+};
 pub use types::{Hello, OnlyData, Reconnect, ResumeACK, SN};
 
 use bytes::Bytes;
 use enum_as_inner::EnumAsInner;
-use miniz_oxide::inflate::{self, TINFLStatus};
-use serde::{Deserialize, Serialize};
+use miniz_oxide::{
+    deflate,
+    inflate::{self, TINFLStatus},
+};
 use snafu::prelude::*;
 
 use super::event::EventData;
@@ -74,12 +80,10 @@ pub enum ParseMessageError {
     },
 }
 
-static MESSAGE_INTERNAL_TYPE_TAG: &str = "__internal_type_tag__";
-
 /// Kaiheila websocket protocol message type
-#[derive(Debug, Clone, Serialize, Deserialize, EnumAsInner)]
-// serde does not support number tag for now, see: https://github.com/serde-rs/serde/issues/745
-#[serde(tag = "__internal_type_tag__")]
+///
+/// Serialized in the wire format, like `{"s": 2, "sn": 6}`.
+#[derive(Debug, Clone, EnumAsInner)]
 pub enum Message {
     /// Event, server -> client
     Event(EventData),
@@ -109,48 +113,45 @@ impl Message {
                 .into();
         }
 
-        let mut value: serde_json::Value =
-            serde_json::from_slice(&buff).context(error::ParseJSONFailed { data: buff.clone() })?;
-
-        let obj = value
-            .as_object_mut()
-            .with_context(|| error::MessageNotObject {
-                json: String::from_utf8_lossy(&buff),
-            })?;
-
-        let s = obj
-            .get("s")
-            .with_context(|| error::NoMessageType {
-                json: String::from_utf8_lossy(&buff),
-            })?
-            .as_i64()
-            .with_context(|| error::MessageTypeNotNumber {
-                json: String::from_utf8_lossy(&buff),
-            })?;
-
-        let type_name = Self::type_number_to_type_name(s)
-            .with_context(|| error::UnknownMessageType { t: s })?;
-
-        obj.insert(
-            MESSAGE_INTERNAL_TYPE_TAG.to_string(),
-            serde_json::Value::String(type_name.to_string()),
-        );
-
-        serde_json::from_value(value).with_context(|_| error::ParseJSONToTypedMessageFailed {
-            type_name: type_name.to_string(),
-        })
+        serde_json::from_slice(&buff).map_err(|err| Self::diagnose(buff, err))
+    }
+
+    /// Find out why the data can't be decoded, only called when decode failed.
+    fn diagnose(buff: Bytes, err: serde_json::Error) -> ParseMessageError {
+        let value: serde_json::Value = match serde_json::from_slice(&buff) {
+            Ok(value) => value,
+            Err(source) => return ParseMessageError::ParseJSONFailed { data: buff, source },
+        };
+
+        let json = || String::from_utf8_lossy(&buff).into_owned();
+
+        let Some(obj) = value.as_object() else {
+            return ParseMessageError::MessageNotObject { json: json() };
+        };
+        let Some(s) = obj.get("s") else {
+            return ParseMessageError::NoMessageType { json: json() };
+        };
+        let Some(s) = s.as_i64() else {
+            return ParseMessageError::MessageTypeNotNumber { json: json() };
+        };
+
+        match Self::type_number_to_type_name(s) {
+            Some(type_name) => ParseMessageError::ParseJSONToTypedMessageFailed {
+                type_name: type_name.to_string(),
+                source: err,
+            },
+            None => ParseMessageError::UnknownMessageType { t: s },
+        }
     }
 
     /// encode data to binary message(without compress)
     pub fn encode(&self) -> Vec<u8> {
-        let mut value = serde_json::to_value(&self).unwrap();
-        let obj = value.as_object_mut().unwrap();
-        obj.remove(MESSAGE_INTERNAL_TYPE_TAG);
-        obj.insert(
-            "s".to_string(),
-            serde_json::Value::Number(self.type_number().into()),
-        );
-        serde_json::to_vec(&value).unwrap()
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// encode data to zlib compressed binary message, `level` is from 0 to 10
+    pub fn encode_compressed(&self, level: u8) -> Vec<u8> {
+        deflate::compress_to_vec_zlib(&self.encode(), level)
     }
 
     fn type_number_to_type_name(s: i64) -> Option<&'static str> {
@@ -305,6 +306,80 @@ mod test {
         }
     }
 
+    mod codec {
+        use super::super::*;
+        use crate::ws::{event::EventBody, Event};
+        use serde_json::json;
+
+        #[test]
+        fn test_message_decode_data_before_type() {
+            let data = br#"{"d":{"code":41008,"err":"Missing params"},"s":5}"#;
+            let msg = Message::decode(Bytes::from_static(data), false).unwrap();
+            assert_eq!(msg.into_reconnect().unwrap().data.code, 41008);
+        }
+
+        #[test]
+        fn test_message_decode_errors() {
+            let decode = |value: serde_json::Value| {
+                Message::decode(serde_json::to_vec(&value).unwrap().into(), false).unwrap_err()
+            };
+
+            assert!(matches!(
+                Message::decode(Bytes::from_static(b"{"), false),
+                Err(ParseMessageError::ParseJSONFailed { .. })
+            ));
+            assert!(matches!(
+                decode(json!([])),
+                ParseMessageError::MessageNotObject { .. }
+            ));
+            assert!(matches!(
+                decode(json!({"sn": 1})),
+                ParseMessageError::NoMessageType { .. }
+            ));
+            assert!(matches!(
+                decode(json!({"s": "1"})),
+                ParseMessageError::MessageTypeNotNumber { .. }
+            ));
+            assert!(matches!(
+                decode(json!({"s": 42})),
+                ParseMessageError::UnknownMessageType { t: 42 }
+            ));
+            assert!(matches!(
+                decode(json!({"s": 2})),
+                ParseMessageError::ParseJSONToTypedMessageFailed { .. }
+            ));
+        }
+
+        #[test]
+        fn test_message_encode_decode_roundtrip() {
+            let event = Message::Event(EventData {
+                sn: 7,
+                event: Box::new(Event::ChannelMessage(EventBody {
+                    msg_id: "id".to_string(),
+                    ..Default::default()
+                })),
+            });
+
+            let value: serde_json::Value = serde_json::from_slice(&event.encode()).unwrap();
+            assert_eq!(value["s"], 0);
+            assert_eq!(value["sn"], 7);
+            assert_eq!(value["d"]["msg_id"], "id");
+
+            let decoded = Message::decode(event.encode().into(), false).unwrap();
+            assert_eq!(decoded.into_event().unwrap().event.msg_id(), "id");
+
+            let ping = Message::Ping(SN { sn: 3 }).encode();
+            assert_eq!(ping, br#"{"s":2,"sn":3}"#);
+        }
+
+        #[test]
+        fn test_message_encode_compressed() {
+            let data = Message::Ping(SN { sn: 3 }).encode_compressed(6);
+            let decoded = Message::decode(data.into(), true).unwrap();
+            assert_eq!(decoded.into_ping().unwrap().sn, 3);
+        }
+    }
+
     mod encode {
         use super::super::*;
 
@@ -1,9 +1,11 @@
-use std::task::Poll;
+use std::task::{ready, Poll};
 
 use bytes::Bytes;
 use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use miniz_oxide::inflate;
 use snafu::prelude::*;
-use tokio_tungstenite::tungstenite as websocket;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::{self as websocket, protocol::CloseFrame};
 
 use super::{Message, ParseMessageError};
 use crate::ws::client::WebsocketClient;
@@ -19,10 +21,17 @@ pub enum MessageStreamSinkError {
         source: websocket::Error,
     },
 
-    /// received an non-binary frame
-    #[snafu(display("received a non-binary type frame"))]
+    /// received a frame which is neither binary, text nor control frame
+    #[snafu(display("received a non-data type frame"))]
     NotBinaryFrame,
 
+    /// gateway closed the connection, `frame` is `None` if the connection ends without a close frame
+    #[snafu(display("connection closed by gateway: {}", describe_close(frame)))]
+    Closed {
+        /// close frame sent by gateway
+        frame: Option<CloseFrame<'static>>,
+    },
+
     /// parse binary message data failed
     #[snafu(display("parse frame to message failed: {source}"))]
     ParseMessageFailed {
@@ -37,6 +46,7 @@ impl MessageStreamSinkError {
         match self {
             Self::Websocket { .. } => true,
             Self::NotBinaryFrame => false,
+            Self::Closed { .. } => true,
             Self::ParseMessageFailed { source } => {
                 !matches!(source, ParseMessageError::UnknownMessageType { .. })
             }
@@ -44,11 +54,48 @@ impl MessageStreamSinkError {
     }
 }
 
+/// Human readable close reason, for display of close errors
+pub(crate) fn describe_close(frame: &Option<CloseFrame<'_>>) -> String {
+    match frame {
+        Some(frame) => frame.to_string(),
+        None => "without close frame".to_string(),
+    }
+}
+
+/// A frame received from gateway but not used, see [ClientConfig::ignored_frames]
+///
+/// [ClientConfig::ignored_frames]: crate::ws::ClientConfig::ignored_frames
+#[derive(Debug, Clone)]
+pub enum IgnoredFrame {
+    /// Websocket frame which is neither binary nor text, like ping. Pings are still answered
+    NotBinary(websocket::Message),
+    /// Binary frame which can not be parsed as a message, like one with unknown `s` value
+    Unparsable {
+        /// frame data, decompressed if possible
+        data: Bytes,
+        /// why it can not be parsed
+        reason: String,
+    },
+    /// Message which is not handled in current state of client
+    Unhandled(Message),
+}
+
+/// Send the frame to tap if any, drop it if the tap is full or closed
+pub(crate) fn tap_ignored(tap: Option<&mpsc::Sender<IgnoredFrame>>, frame: IgnoredFrame) {
+    if let Some(tap) = tap {
+        if tap.try_send(frame).is_err() {
+            trace!("Ignored frame tap is full or closed, frame dropped");
+        }
+    }
+}
+
 /// Kaiheila websocket message stream/sink
 #[derive(Debug)]
 pub struct MessageStreamSink {
     ws: WebsocketClient,
     compress: bool,
+    compress_outgoing: Option<u8>,
+    ignored_frames: Option<mpsc::Sender<IgnoredFrame>>,
 }
 
 impl MessageStreamSink {
@@ -57,7 +104,53 @@ impl MessageStreamSink {
     /// the `compress` argument controls if the stream will decompress binary data
     /// before parse it to message.
     pub fn new(ws: WebsocketClient, compress: bool) -> Self {
-        Self { ws, compress }
+        Self {
+            ws,
+            compress,
+            compress_outgoing: None,
+            ignored_frames: None,
+        }
+    }
+
+    /// Send non-data and unparsable frames to the channel, `None` means drop them,
+    /// this is the default
+    pub fn ignored_frames(mut self, tap: Option<mpsc::Sender<IgnoredFrame>>) -> Self {
+        self.ignored_frames = tap;
+        self
+    }
+
+    fn decode(&self, buffer: Bytes, compressed: bool) -> Result<Message, ParseMessageError> {
+        Message::decode(buffer.clone(), compressed).inspect_err(|e| {
+            trace!(
+                "Parse failed message data: {}",
+                std::str::from_utf8(&buffer).unwrap_or("<not-utf8-binary>")
+            );
+            self.tap_unparsable(buffer, compressed, e);
+        })
+    }
+
+    fn tap_unparsable(&self, buffer: Bytes, compressed: bool, error: &ParseMessageError) {
+        if self.ignored_frames.is_none() {
+            return;
+        }
+
+        let data = if compressed {
+            inflate::decompress_to_vec_zlib(&buffer).map_or(buffer, Bytes::from)
+        } else {
+            buffer
+        };
+        let frame = IgnoredFrame::Unparsable {
+            data,
+            reason: error.to_string(),
+        };
+        tap_ignored(self.ignored_frames.as_ref(), frame);
+    }
+
+    /// Compress sent messages with the zlib level(0 to 10), `None` means no compress,
+    /// this is the default
+    pub fn compress_outgoing(mut self, level: Option<u8>) -> Self {
+        self.compress_outgoing = level;
+        self
     }
 }
 
@@ -68,28 +161,51 @@ impl Stream for MessageStreamSink {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        match self.ws.poll_next_unpin(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(frame) => {
-                let frame = frame.unwrap().context(error::Websocket)?;
-                let result = match frame {
-                    websocket::Message::Binary(data) => {
-                        let buffer: Bytes = data.into();
-                        match Message::decode(buffer.clone(), self.compress) {
-                            Ok(msg) => Ok(msg),
-                            Err(e) => {
-                                log::trace!(
-                                    "Parse failed message data: {}",
-                                    std::str::from_utf8(&buffer).unwrap_or("<not-utf8-binary>")
-                                );
-                                Err(MessageStreamSinkError::ParseMessageFailed { source: e })
-                            }
-                        }
+        loop {
+            let frame = match ready!(self.ws.poll_next_unpin(cx)) {
+                Some(frame) => frame.context(error::Websocket)?,
+                None => return Poll::Ready(None),
+            };
+
+            let result = match frame {
+                websocket::Message::Binary(data) => self
+                    .decode(data.into(), self.compress)
+                    .context(error::ParseMessageFailed),
+                // text frames are never compressed
+                websocket::Message::Text(text) => self
+                    .decode(text.into(), false)
+                    .context(error::ParseMessageFailed),
+                websocket::Message::Ping(data) => {
+                    trace!("Received ping frame, reply pong");
+                    tap_ignored(
+                        self.ignored_frames.as_ref(),
+                        IgnoredFrame::NotBinary(websocket::Message::Ping(data)),
+                    );
+                    // pong is queued by tungstenite, flush to send it now instead of on next write
+                    if let Poll::Ready(Err(e)) = self.ws.poll_flush_unpin(cx) {
+                        return Poll::Ready(Some(Err(e).context(error::Websocket)));
                     }
-                    _ => Err(MessageStreamSinkError::NotBinaryFrame),
-                };
-                Poll::Ready(Some(result))
-            }
+                    continue;
+                }
+                websocket::Message::Pong(data) => {
+                    trace!("Received pong frame");
+                    tap_ignored(
+                        self.ignored_frames.as_ref(),
+                        IgnoredFrame::NotBinary(websocket::Message::Pong(data)),
+                    );
+                    continue;
+                }
+                websocket::Message::Close(frame) => {
+                    debug!("Gateway closed the connection: {}", describe_close(&frame));
+                    error::Closed { frame }.fail()
+                }
+                frame => {
+                    tap_ignored(self.ignored_frames.as_ref(), IgnoredFrame::NotBinary(frame));
+                    Err(MessageStreamSinkError::NotBinaryFrame)
+                }
+            };
+
+            return Poll::Ready(Some(result));
         }
     }
 }
@@ -107,8 +223,12 @@ impl Sink<Message> for MessageStreamSink {
     }
 
     fn start_send(mut self: std::pin::Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        let data = match self.compress_outgoing {
+            Some(level) => item.encode_compressed(level),
+            None => item.encode(),
+        };
         self.ws
-            .start_send_unpin(websocket::Message::Binary(item.encode()))
+            .start_send_unpin(websocket::Message::Binary(data))
             .map_err(|e| Self::Error::Websocket { source: e })
     }
 
@@ -130,3 +250,79 @@ impl Sink<Message> for MessageStreamSink {
             .map_err(|e| Self::Error::Websocket { source: e })
     }
 }
+
+/// [MessageStreamSink] which skips non-fatal errors with a warning log,
+/// see [MessageStreamSinkError::is_fatal].
+#[derive(Debug)]
+pub struct FilteredMessageStreamSink {
+    inner: MessageStreamSink,
+}
+
+impl FilteredMessageStreamSink {
+    /// Construct a new stream with underlying websocket connection,
+    /// see [MessageStreamSink::new].
+    pub fn new(ws: WebsocketClient, compress: bool) -> Self {
+        Self {
+            inner: MessageStreamSink::new(ws, compress),
+        }
+    }
+
+    /// Compress sent messages, see [MessageStreamSink::compress_outgoing]
+    pub fn compress_outgoing(mut self, level: Option<u8>) -> Self {
+        self.inner = self.inner.compress_outgoing(level);
+        self
+    }
+
+    /// Send ignored frames to the channel, see [MessageStreamSink::ignored_frames]
+    pub fn ignored_frames(mut self, tap: Option<mpsc::Sender<IgnoredFrame>>) -> Self {
+        self.inner = self.inner.ignored_frames(tap);
+        self
+    }
+}
+
+impl Stream for FilteredMessageStreamSink {
+    type Item = Result<Message, MessageStreamSinkError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Err(err))) if !err.is_fatal() => {
+                    warn!("Message stream error happened but ignored: {}", err);
+                }
+                poll => return poll,
+            }
+        }
+    }
+}
+
+impl Sink<Message> for FilteredMessageStreamSink {
+    type Error = MessageStreamSinkError;
+
+    fn poll_ready(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: std::pin::Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        self.inner.start_send_unpin(item)
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
@@ -4,6 +4,6 @@ pub mod client;
 pub mod event;
 pub mod message;
 
-pub use client::Client;
+pub use client::{Client, ClientConfig, ConnectionState, EventGap, GapPolicy, Proxy};
 pub use event::Event;
 pub use message::Message;